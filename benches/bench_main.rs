@@ -2,6 +2,7 @@ use criterion::BenchmarkId;
 use criterion::{Criterion, criterion_group, criterion_main};
 
 use std::fs::File;
+use std::io::Cursor;
 
 fn read_mp4(filename: &str) -> u64 {
     let f = File::open(filename).unwrap();
@@ -10,6 +11,58 @@ fn read_mp4(filename: &str) -> u64 {
     m.size()
 }
 
+/// Build a video track with varying sample durations and rendering offsets,
+/// so that the stts and ctts tables have an entry per sample.
+fn write_mp4(sample_count: u32) -> Vec<u8> {
+    let config = mp4::Mp4Config {
+        major_brand: str::parse("isom").unwrap(),
+        minor_version: 512,
+        compatible_brands: vec![str::parse("isom").unwrap()],
+        timescale: 1000,
+    };
+    let mut writer = mp4::Mp4Writer::write_start(Cursor::new(Vec::new()), &config).unwrap();
+    writer
+        .add_track(&mp4::TrackConfig::from(mp4::AvcConfig {
+            width: 320,
+            height: 240,
+            seq_param_set: vec![0x67, 0x64, 0x00, 0x0d],
+            pic_param_set: vec![0x68, 0xee, 0x3c, 0x80],
+        }))
+        .unwrap();
+    let mut start_time = 0;
+    for i in 0..sample_count {
+        let duration = 40 + i % 2;
+        let sample = mp4::Mp4Sample {
+            start_time,
+            duration,
+            rendering_offset: (i % 3) as i32 * 40,
+            is_sync: i % 50 == 0,
+            flags: None,
+            dependency: None,
+            subsamples: Vec::new(),
+            bytes: vec![0u8; 16 + (i % 7) as usize],
+        };
+        writer.write_sample(1, &sample).unwrap();
+        start_time += duration as u64;
+    }
+    writer.write_end().unwrap();
+    writer.into_writer().into_inner()
+}
+
+fn read_samples(data: &[u8], index: bool) -> usize {
+    let mut mp4 = mp4::Mp4Reader::read_header(Cursor::new(data), data.len() as u64).unwrap();
+    if index {
+        mp4.build_sample_index().unwrap();
+    }
+    let sample_count = mp4.sample_count(1).unwrap();
+    let mut total = 0;
+    for sample_id in 1..=sample_count {
+        let sample = mp4.read_sample(1, sample_id).unwrap().unwrap();
+        total += sample.bytes.len();
+    }
+    total
+}
+
 fn criterion_benchmark(c: &mut Criterion) {
     let filename = "tests/samples/minimal.mp4";
 
@@ -20,6 +73,22 @@ fn criterion_benchmark(c: &mut Criterion) {
             b.iter(|| read_mp4(s));
         },
     );
+
+    let data = write_mp4(10_000);
+    c.bench_with_input(
+        BenchmarkId::new("read_samples", "no_index"),
+        &data,
+        |b, data| {
+            b.iter(|| read_samples(data, false));
+        },
+    );
+    c.bench_with_input(
+        BenchmarkId::new("read_samples", "index"),
+        &data,
+        |b, data| {
+            b.iter(|| read_samples(data, true));
+        },
+    );
 }
 
 criterion_group!(benches, criterion_benchmark);
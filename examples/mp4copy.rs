@@ -1,3 +1,4 @@
+use std::collections::HashMap;
 use std::env;
 use std::fs::File;
 use std::io::prelude::*;
@@ -5,7 +6,8 @@ use std::io::{self, BufReader, BufWriter};
 use std::path::Path;
 
 use mp4::{
-    AacConfig, AvcConfig, HevcConfig, MediaConfig, MediaType, Mp4Config, Result, TrackConfig,
+    AacConfig, Ac3Config, Av1Config, AvcConfig, BoxType, Ec3Config, FlacConfig, HevcConfig,
+    HevcSampleEntry, MediaConfig, MediaType, Mp4Config, OpusConfig, Result, TrackConfig,
     TtxtConfig, Vp9Config,
 };
 
@@ -41,8 +43,12 @@ fn copy<P: AsRef<Path>>(src_filename: &P, dst_filename: &P) -> Result<()> {
         },
     )?;
 
-    // TODO interleaving
-    for track in mp4_reader.tracks().values() {
+    let mut track_ids: Vec<u32> = mp4_reader.tracks().keys().copied().collect();
+    track_ids.sort_unstable();
+
+    let mut dst_track_ids = HashMap::new();
+    for (i, track_id) in track_ids.iter().enumerate() {
+        let track = &mp4_reader.tracks()[track_id];
         let media_conf = match track.media_type()? {
             MediaType::H264 => MediaConfig::AvcConfig(AvcConfig {
                 width: track.width(),
@@ -53,17 +59,67 @@ fn copy<P: AsRef<Path>>(src_filename: &P, dst_filename: &P) -> Result<()> {
             MediaType::H265 => MediaConfig::HevcConfig(HevcConfig {
                 width: track.width(),
                 height: track.height(),
+                sample_entry: if track.box_type()? == BoxType::Hvc1Box.into() {
+                    HevcSampleEntry::Hvc1
+                } else {
+                    HevcSampleEntry::Hev1
+                },
+                video_param_set: track.video_parameter_set()?.to_vec(),
+                seq_param_set: track.sequence_parameter_set()?.to_vec(),
+                pic_param_set: track.picture_parameter_set()?.to_vec(),
             }),
             MediaType::VP9 => MediaConfig::Vp9Config(Vp9Config {
                 width: track.width(),
                 height: track.height(),
             }),
+            MediaType::AV1 => MediaConfig::Av1Config(Av1Config {
+                width: track.width(),
+                height: track.height(),
+                sequence_header: track.sequence_header()?.to_vec(),
+            }),
             MediaType::AAC => MediaConfig::AacConfig(AacConfig {
                 bitrate: track.bitrate(),
                 profile: track.audio_profile()?,
                 freq_index: track.sample_freq_index()?,
                 chan_conf: track.channel_config()?,
             }),
+            MediaType::OPUS => {
+                let dops = track.opus_header()?;
+                MediaConfig::OpusConfig(OpusConfig {
+                    channel_count: dops.output_channel_count,
+                    pre_skip: dops.pre_skip,
+                    input_sample_rate: dops.input_sample_rate,
+                    output_gain: dops.output_gain,
+                    channel_mapping_family: dops.channel_mapping_family,
+                    channel_mapping: dops.channel_mapping.clone(),
+                })
+            }
+            MediaType::FLAC => {
+                let dfla = track.flac_metadata()?;
+                MediaConfig::FlacConfig(FlacConfig {
+                    stream_info: dfla.stream_info.clone(),
+                    metadata_blocks: dfla.metadata_blocks.clone(),
+                })
+            }
+            MediaType::AC3 => {
+                let dac3 = track.ac3_config()?;
+                MediaConfig::Ac3Config(Ac3Config {
+                    fscod: dac3.fscod,
+                    bsid: dac3.bsid,
+                    bsmod: dac3.bsmod,
+                    acmod: dac3.acmod,
+                    lfeon: dac3.lfeon,
+                    bit_rate_code: dac3.bit_rate_code,
+                })
+            }
+            MediaType::EAC3 => {
+                let dec3 = track.ec3_config()?;
+                MediaConfig::Ec3Config(Ec3Config {
+                    data_rate: dec3.data_rate,
+                    substreams: dec3.substreams.clone(),
+                    joc_complexity_index: dec3.joc_complexity_index,
+                })
+            }
             MediaType::TTXT => MediaConfig::TtxtConfig(TtxtConfig {}),
         };
 
@@ -72,19 +128,16 @@ fn copy<P: AsRef<Path>>(src_filename: &P, dst_filename: &P) -> Result<()> {
             timescale: track.timescale(),
             language: track.language().to_string(),
             media_conf,
+            encryption: None,
         };
 
         mp4_writer.add_track(&track_conf)?;
+        dst_track_ids.insert(*track_id, i as u32 + 1);
     }
 
-    for track_id in mp4_reader.tracks().keys().copied().collect::<Vec<u32>>() {
-        let sample_count = mp4_reader.sample_count(track_id)?;
-        for sample_idx in 0..sample_count {
-            let sample_id = sample_idx + 1;
-            let sample = mp4_reader.read_sample(track_id, sample_id)?.unwrap();
-            mp4_writer.write_sample(track_id, &sample)?;
-            // println!("copy {}:({})", sample_id, sample);
-        }
+    for result in mp4_reader.samples() {
+        let (track_id, sample) = result?;
+        mp4_writer.write_sample(dst_track_ids[&track_id], &sample)?;
     }
 
     mp4_writer.write_end()?;
@@ -0,0 +1,98 @@
+//! Reading MP4 files from asynchronous sources.
+//!
+//! Only the IO is asynchronous: boxes are read into memory as a whole and
+//! parsed with the same code as [Mp4Reader::read_header].
+
+use std::io::{Cursor, SeekFrom};
+
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncSeek, AsyncSeekExt};
+
+use crate::reader::TopLevelBoxes;
+use crate::*;
+
+impl<R: AsyncRead + AsyncSeek + Unpin> Mp4Reader<R> {
+    /// Asynchronous version of [Mp4Reader::read_header].
+    ///
+    /// Every top-level box except `mdat` and `free` is buffered in memory
+    /// while it is read.
+    pub async fn read_header_async(mut reader: R, size: u64) -> Result<Self> {
+        let start = reader.stream_position().await?;
+
+        let mut boxes = TopLevelBoxes::default();
+
+        let mut current = start;
+        while current < size {
+            // Get box header.
+            let (header, header_size) = read_box_header(&mut reader).await?;
+            let BoxHeader { name, size: s } = header;
+            if s > size {
+                return Err(Error::InvalidData(
+                    "file contains a box with a larger size than it",
+                ));
+            }
+
+            // Break if size zero BoxHeader, which can result in dead-loop.
+            if s == 0 {
+                break;
+            }
+
+            // The box size excludes the largesize field, see BoxHeader::read.
+            if s < HEADER_SIZE {
+                return Err(Error::InvalidData("box size is smaller than its header"));
+            }
+            if TopLevelBoxes::is_parsed(name) {
+                // Leave room for the header, box parsers expect to be positioned
+                // right after it.
+                let mut buf = vec![0u8; s as usize];
+                reader.read_exact(&mut buf[HEADER_SIZE as usize..]).await?;
+                let mut cursor = Cursor::new(buf);
+                cursor.set_position(HEADER_SIZE);
+                let box_size = header_size - HEADER_SIZE + s;
+                boxes.read_box(&mut cursor, name, s, current, box_size)?;
+            } else {
+                let end = current + header_size - HEADER_SIZE + s;
+                reader.seek(SeekFrom::Start(end)).await?;
+                if name == BoxType::MdatBox {
+                    boxes.mdats.push((current, end - current));
+                }
+            }
+            current = reader.stream_position().await?;
+        }
+
+        Self::from_boxes(reader, boxes, current - start)
+    }
+
+    /// Asynchronous version of [Mp4Reader::read_sample].
+    pub async fn read_sample_async(
+        &mut self,
+        track_id: u32,
+        sample_id: u32,
+    ) -> Result<Option<Mp4Sample>> {
+        let track = self
+            .tracks
+            .get(&track_id)
+            .ok_or(Error::TrakNotFound(track_id))?;
+        let (sample_offset, sample_size) = match track.sample_location(sample_id)? {
+            Some(location) => location,
+            None => return Ok(None),
+        };
+
+        let mut buffer = vec![0x0u8; sample_size as usize];
+        self.reader.seek(SeekFrom::Start(sample_offset)).await?;
+        self.reader.read_exact(&mut buffer).await?;
+
+        Ok(Some(track.sample_with_bytes(sample_id, buffer)))
+    }
+}
+
+/// Read a box header, returning it with the number of bytes it occupies.
+async fn read_box_header<R: AsyncRead + Unpin>(reader: &mut R) -> Result<(BoxHeader, u64)> {
+    let mut buf = [0u8; 16];
+    reader.read_exact(&mut buf[..8]).await?;
+    let header_size = if buf[0..4] == [0, 0, 0, 1] { 16 } else { 8 };
+    if header_size == 16 {
+        reader.read_exact(&mut buf[8..]).await?;
+    }
+    let header = BoxHeader::read(&mut &buf[..header_size])?;
+    Ok((header, header_size as u64))
+}
@@ -0,0 +1,151 @@
+//! Reading of the bit fields of codec configuration data, such as parameter
+//! set NAL units.
+
+use crate::*;
+
+/// Reads bit fields from a byte slice, most significant bit first.
+#[derive(Debug)]
+pub(crate) struct BitReader<'a> {
+    data: &'a [u8],
+
+    // Position in bits.
+    pos: usize,
+}
+
+impl<'a> BitReader<'a> {
+    pub(crate) fn new(data: &'a [u8]) -> Self {
+        BitReader { data, pos: 0 }
+    }
+
+    /// Read an unsigned integer of up to 64 bits.
+    pub(crate) fn read_bits(&mut self, count: u32) -> Result<u64> {
+        debug_assert!(count <= 64);
+        if self.pos + count as usize > self.data.len() * 8 {
+            return Err(Error::InvalidData("bitstream is truncated"));
+        }
+        let mut value = 0;
+        for _ in 0..count {
+            let bit = self.data[self.pos / 8] >> (7 - self.pos % 8) & 1;
+            value = value << 1 | bit as u64;
+            self.pos += 1;
+        }
+        Ok(value)
+    }
+
+    pub(crate) fn read_bit(&mut self) -> Result<bool> {
+        Ok(self.read_bits(1)? == 1)
+    }
+
+    /// Number of bits not yet read.
+    pub(crate) fn bits_left(&self) -> usize {
+        self.data.len() * 8 - self.pos
+    }
+
+    pub(crate) fn skip_bits(&mut self, count: usize) -> Result<()> {
+        if self.pos + count > self.data.len() * 8 {
+            return Err(Error::InvalidData("bitstream is truncated"));
+        }
+        self.pos += count;
+        Ok(())
+    }
+
+    /// Read an unsigned Exp-Golomb code, `ue(v)` in the H.264 and H.265
+    /// syntax.
+    pub(crate) fn read_ue(&mut self) -> Result<u32> {
+        let mut leading_zeros = 0;
+        while !self.read_bit()? {
+            leading_zeros += 1;
+            if leading_zeros > 31 {
+                return Err(Error::InvalidData("Exp-Golomb code is too long"));
+            }
+        }
+        let value = (1u64 << leading_zeros) - 1 + self.read_bits(leading_zeros)?;
+        Ok(value as u32)
+    }
+
+    /// Read a signed Exp-Golomb code, `se(v)` in the H.264 and H.265
+    /// syntax.
+    pub(crate) fn read_se(&mut self) -> Result<i32> {
+        let value = self.read_ue()? as i64;
+        let value = if value % 2 == 1 {
+            (value + 1) / 2
+        } else {
+            -value / 2
+        };
+        Ok(value as i32)
+    }
+}
+
+/// The payload of a NAL unit with its emulation prevention bytes removed.
+pub(crate) fn nal_unit_rbsp(data: &[u8]) -> Vec<u8> {
+    let mut rbsp = Vec::with_capacity(data.len());
+    let mut zeros = 0;
+    for byte in data.iter() {
+        if zeros >= 2 && *byte == 3 {
+            zeros = 0;
+            continue;
+        }
+        zeros = if *byte == 0 { zeros + 1 } else { 0 };
+        rbsp.push(*byte);
+    }
+    rbsp
+}
+
+/// Number of bytes of a NAL unit payload that hold the first `rbsp_size`
+/// bytes of its RBSP, counting the emulation prevention bytes among them.
+pub(crate) fn nal_unit_size(data: &[u8], rbsp_size: usize) -> usize {
+    let mut size = 0;
+    let mut zeros = 0;
+    for (offset, byte) in data.iter().enumerate() {
+        if size == rbsp_size {
+            return offset;
+        }
+        if zeros >= 2 && *byte == 3 {
+            zeros = 0;
+            continue;
+        }
+        zeros = if *byte == 0 { zeros + 1 } else { 0 };
+        size += 1;
+    }
+    data.len()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_read_bits() {
+        let mut reader = BitReader::new(&[0b1010_0110, 0b0010_0000]);
+        assert_eq!(reader.read_bits(3).unwrap(), 0b101);
+        assert!(!reader.read_bit().unwrap());
+        // 0b011 is 2 and 0b00100 is 3.
+        assert_eq!(reader.read_ue().unwrap(), 2);
+        reader.skip_bits(1).unwrap();
+        assert_eq!(reader.read_ue().unwrap(), 3);
+        assert!(reader.read_bits(5).is_err());
+
+        // 0b011, 0b010 and 0b1 are -1, 1 and 0.
+        let mut reader = BitReader::new(&[0b0110_1010]);
+        assert_eq!(reader.read_se().unwrap(), -1);
+        assert_eq!(reader.read_se().unwrap(), 1);
+        assert_eq!(reader.read_se().unwrap(), 0);
+    }
+
+    #[test]
+    fn test_nal_unit_rbsp() {
+        assert_eq!(
+            nal_unit_rbsp(&[0x42, 0, 0, 3, 1, 0, 0, 3, 3, 0]),
+            [0x42, 0, 0, 1, 0, 0, 3, 0]
+        );
+    }
+
+    #[test]
+    fn test_nal_unit_size() {
+        let data = [0x42, 0, 0, 3, 1, 0, 0, 3, 3, 0];
+        assert_eq!(nal_unit_size(&data, 3), 3);
+        assert_eq!(nal_unit_size(&data, 4), 5);
+        assert_eq!(nal_unit_size(&data, 7), 9);
+        assert_eq!(nal_unit_size(&data, 8), 10);
+    }
+}
@@ -0,0 +1,328 @@
+use std::io::{Cursor, Read, Seek, SeekFrom};
+
+use crate::*;
+
+macro_rules! box_values {
+    ($( $name:ident ),* $(,)?) => {
+        /// The parsed value of a box that this crate knows about.
+        #[derive(Debug, Clone, PartialEq, Eq)]
+        pub enum BoxValue {
+            $( $name(Box<$name>), )*
+        }
+
+        impl BoxValue {
+            /// Parse a box of the given type, returning `None` if the type is
+            /// not known.
+            fn read<R: Read + Seek>(
+                reader: &mut R,
+                name: BoxType,
+                size: u64,
+            ) -> Option<Result<Self>> {
+                match name {
+                    $( BoxType::$name => Some($name::read_box(reader, size).map(|b| BoxValue::$name(Box::new(b)))), )*
+                    _ => None,
+                }
+            }
+
+            pub fn box_type(&self) -> BoxType {
+                match self {
+                    $( BoxValue::$name(b) => Mp4Box::box_type(&**b), )*
+                }
+            }
+
+            pub fn box_size(&self) -> u64 {
+                match self {
+                    $( BoxValue::$name(b) => Mp4Box::box_size(&**b), )*
+                }
+            }
+
+            pub fn summary(&self) -> Result<String> {
+                match self {
+                    $( BoxValue::$name(b) => Mp4Box::summary(&**b), )*
+                }
+            }
+        }
+    };
+}
+
+box_values!(
+    FtypBox, MoovBox, MvhdBox, MvexBox, MehdBox, TrexBox, EmsgBox, SidxBox, MoofBox, MfhdBox,
+    TrakBox, TkhdBox, EdtsBox, ElstBox, MdiaBox, MdhdBox, HdlrBox, MinfBox, VmhdBox, SmhdBox,
+    DinfBox, DrefBox, UrlBox, StblBox, StsdBox, Avc1Box, AvcCBox, Hev1Box, HvcCBox, Mp4aBox,
+    EsdsBox, Tx3gBox, Vp09Box, VpccBox, SttsBox, CttsBox, StssBox, StscBox, StszBox, StcoBox,
+    Co64Box, SgpdBox, SbgpBox, SdtpBox, SubsBox, TrafBox, TfhdBox, TfdtBox, TrunBox, MfraBox,
+    TfraBox, MfroBox, UdtaBox, MetaBox, IlstBox, DataBox, SinfBox, FrmaBox, SchmBox, SchiBox,
+    TencBox, PsshBox, SencBox, SaizBox, SaioBox, Av01Box, Av1CBox, OpusBox, DopsBox, FlacBox,
+    DflaBox, Ac3Box, Dac3Box, Ec3Box, Dec3Box,
+);
+
+/// The contents of a box in a [BoxTree].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum BoxContent {
+    /// A box parsed into its typed value.
+    Known(BoxValue),
+
+    /// A box that is not parsed, with its payload.
+    Unknown(Vec<u8>),
+
+    /// A known box that failed to parse, with the error and its payload.
+    Invalid { error: String, data: Vec<u8> },
+
+    /// Media data, which is not read.
+    Skipped,
+}
+
+/// A box in a [BoxTree], with its position in the file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BoxNode {
+    pub box_type: BoxType,
+
+    /// File offset of the start of the box header.
+    pub offset: u64,
+
+    /// Size of the box header, 16 if it uses a largesize.
+    pub header_size: u64,
+
+    /// Total size of the box, including its header.
+    pub size: u64,
+
+    pub content: BoxContent,
+    pub children: Vec<BoxNode>,
+}
+
+impl BoxNode {
+    /// File offset of the first byte after the box header.
+    pub fn payload_offset(&self) -> u64 {
+        self.offset + self.header_size
+    }
+
+    /// The typed value of the box, if it is a known box.
+    pub fn value(&self) -> Option<&BoxValue> {
+        match &self.content {
+            BoxContent::Known(value) => Some(value),
+            _ => None,
+        }
+    }
+
+    /// The payload of the box, if it was not parsed.
+    pub fn raw_data(&self) -> Option<&[u8]> {
+        match &self.content {
+            BoxContent::Unknown(data) | BoxContent::Invalid { data, .. } => Some(data),
+            _ => None,
+        }
+    }
+
+    /// Visit this box and all of its descendants depth first, together with
+    /// their depth relative to this box.
+    pub fn visit<F: FnMut(&BoxNode, usize)>(&self, visitor: &mut F) {
+        self.visit_at(0, visitor);
+    }
+
+    fn visit_at<F: FnMut(&BoxNode, usize)>(&self, depth: usize, visitor: &mut F) {
+        visitor(self, depth);
+        for child in &self.children {
+            child.visit_at(depth + 1, visitor);
+        }
+    }
+}
+
+/// Every box of an ISO-BMFF file, known to this crate or not.
+///
+/// Unlike [Mp4Reader], this keeps the layout of the file: the offset and
+/// header size of each box and the order of the boxes in their parent.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BoxTree {
+    pub boxes: Vec<BoxNode>,
+}
+
+impl BoxTree {
+    /// Read the boxes of a file of `size` bytes, starting at the current
+    /// position of `reader`.
+    ///
+    /// The payload of `mdat` boxes is not read.
+    pub fn read<R: Read + Seek>(mut reader: R, size: u64) -> Result<Self> {
+        let start = reader.stream_position()?;
+        let end = start + size;
+
+        let mut boxes = Vec::new();
+        let mut current = start;
+        while current < end {
+            let header = BoxHeader::read(&mut reader)?;
+            let header_size = reader.stream_position()? - current;
+
+            // A size of zero means the box extends to the end of the file.
+            let box_size = if header.size == 0 {
+                end - current
+            } else if header.size < HEADER_SIZE {
+                return Err(Error::InvalidData("box size is smaller than its header"));
+            } else {
+                header.size - HEADER_SIZE + header_size
+            };
+            if box_size > end - current {
+                return Err(Error::InvalidData("box is truncated"));
+            }
+
+            if header.name == BoxType::MdatBox {
+                boxes.push(BoxNode {
+                    box_type: header.name,
+                    offset: current,
+                    header_size,
+                    size: box_size,
+                    content: BoxContent::Skipped,
+                    children: Vec::new(),
+                });
+            } else {
+                // Buffer the whole box, so that it can be parsed both into
+                // its typed value and into its children.
+                let mut data = Vec::new();
+                reader.seek(SeekFrom::Start(current))?;
+                (&mut reader).take(box_size).read_to_end(&mut data)?;
+                if data.len() as u64 != box_size {
+                    return Err(Error::InvalidData("box is truncated"));
+                }
+                boxes.push(read_node(&data, 0, current, None)?);
+            }
+
+            current += box_size;
+            reader.seek(SeekFrom::Start(current))?;
+        }
+
+        Ok(BoxTree { boxes })
+    }
+
+    /// Visit every box depth first, together with its depth in the tree.
+    pub fn visit<F: FnMut(&BoxNode, usize)>(&self, mut visitor: F) {
+        for node in &self.boxes {
+            node.visit(&mut visitor);
+        }
+    }
+}
+
+/// Read the box at `pos` in `data`, which starts at file offset `base`.
+fn read_node(data: &[u8], pos: usize, base: u64, parent: Option<BoxType>) -> Result<BoxNode> {
+    let mut cursor = Cursor::new(data);
+    cursor.set_position(pos as u64);
+    let header = BoxHeader::read(&mut cursor)?;
+    let header_size = cursor.position() - pos as u64;
+
+    let size = if header.size == 0 && parent.is_none() {
+        data.len() as u64
+    } else if header.size < HEADER_SIZE {
+        return Err(Error::InvalidData("box size is smaller than its header"));
+    } else {
+        header.size - HEADER_SIZE + header_size
+    };
+    if size > (data.len() - pos) as u64 {
+        return Err(Error::InvalidData("box is larger than its parent"));
+    }
+    let end = pos as u64 + size;
+    let payload = &data[(pos as u64 + header_size) as usize..end as usize];
+
+    // Parsers expect the box size to exclude the largesize field.
+    let content = match BoxValue::read(&mut cursor, header.name, size - header_size + HEADER_SIZE) {
+        Some(Ok(value)) => BoxContent::Known(value),
+        Some(Err(err)) => BoxContent::Invalid {
+            error: err.to_string(),
+            data: payload.to_vec(),
+        },
+        None => BoxContent::Unknown(payload.to_vec()),
+    };
+
+    let mut children = Vec::new();
+    if let Some(children_offset) = children_offset(header.name, parent, payload) {
+        let mut child = pos + header_size as usize + children_offset;
+        while child + HEADER_SIZE as usize <= end as usize {
+            let node = match read_node(&data[..end as usize], child, base, Some(header.name)) {
+                Ok(node) => node,
+                Err(err) => invalid_node(&data[..end as usize], child, base, err),
+            };
+            child += node.size as usize;
+            children.push(node);
+        }
+    }
+
+    Ok(BoxNode {
+        box_type: header.name,
+        offset: base + pos as u64,
+        header_size,
+        size,
+        content,
+        children,
+    })
+}
+
+/// A node for a box at `pos` in `data` whose header is broken, such as one
+/// smaller than its header or larger than its parent. As its real size is
+/// not known, it covers the rest of `data`.
+fn invalid_node(data: &[u8], pos: usize, base: u64, err: Error) -> BoxNode {
+    let header_size = HEADER_SIZE as usize;
+    let box_type = BoxType::from(u32::from_be_bytes(
+        data[pos + 4..pos + header_size].try_into().unwrap(),
+    ));
+    BoxNode {
+        box_type,
+        offset: base + pos as u64,
+        header_size: header_size as u64,
+        size: (data.len() - pos) as u64,
+        content: BoxContent::Invalid {
+            error: err.to_string(),
+            data: data[pos + header_size..].to_vec(),
+        },
+        children: Vec::new(),
+    }
+}
+
+/// Offset of the first child box in the payload of a box, or `None` if the
+/// box does not contain other boxes.
+fn children_offset(name: BoxType, parent: Option<BoxType>, payload: &[u8]) -> Option<usize> {
+    let offset = match name {
+        BoxType::MoovBox
+        | BoxType::TrakBox
+        | BoxType::EdtsBox
+        | BoxType::MdiaBox
+        | BoxType::MinfBox
+        | BoxType::DinfBox
+        | BoxType::StblBox
+        | BoxType::MvexBox
+        | BoxType::MoofBox
+        | BoxType::TrafBox
+        | BoxType::MfraBox
+        | BoxType::UdtaBox
+        | BoxType::IlstBox
+        | BoxType::WaveBox
+        | BoxType::SinfBox
+        | BoxType::SchiBox => 0,
+        BoxType::StsdBox | BoxType::DrefBox => 8,
+        BoxType::MetaBox => {
+            // Some files skip the version and flags and directly start the
+            // hdlr box, see MetaBox::read_box.
+            if payload.get(4..8) == Some(&u32::from(BoxType::HdlrBox).to_be_bytes()) {
+                0
+            } else {
+                4
+            }
+        }
+        BoxType::Avc1Box
+        | BoxType::Hev1Box
+        | BoxType::Hvc1Box
+        | BoxType::Vp09Box
+        | BoxType::Av01Box
+        | BoxType::EncvBox => 78,
+        BoxType::Tx3gBox => 38,
+        BoxType::Mp4aBox
+        | BoxType::OpusBox
+        | BoxType::FlacBox
+        | BoxType::Ac3Box
+        | BoxType::Ec3Box
+        | BoxType::EncaBox => match payload.get(8..10) {
+            // QuickTime sound sample description versions 1 and 2.
+            Some([0, 1]) => 44,
+            Some([0, 2]) => 64,
+            _ => 28,
+        },
+        // Metadata items hold their data boxes.
+        _ if parent == Some(BoxType::IlstBox) => 0,
+        _ => return None,
+    };
+    (offset <= payload.len()).then_some(offset)
+}
@@ -0,0 +1,431 @@
+//! Encryption and decryption of samples with common encryption
+//! (ISO/IEC 23001-7).
+//!
+//! Supports the `cenc` and `cens` (AES-CTR) and the `cbc1` and `cbcs`
+//! (AES-CBC) protection schemes, with subsample maps and encryption patterns.
+
+use std::collections::HashMap;
+use std::ops::Range;
+
+use aes::Aes128;
+use aes::cipher::{BlockDecrypt, BlockEncrypt, KeyInit};
+
+use crate::*;
+
+const BLOCK_SIZE: usize = 16;
+
+/// Decrypt the data of a sample in place, with the key of
+/// `encryption.kid`.
+pub fn decrypt_sample(
+    key: &[u8; 16],
+    encryption: &SampleEncryption,
+    data: &mut [u8],
+) -> Result<()> {
+    crypt_sample(key, encryption, data, false)
+}
+
+/// Encrypt the data of a sample in place, with the key of
+/// `encryption.kid`. The inverse of [decrypt_sample].
+pub fn encrypt_sample(
+    key: &[u8; 16],
+    encryption: &SampleEncryption,
+    data: &mut [u8],
+) -> Result<()> {
+    crypt_sample(key, encryption, data, true)
+}
+
+fn crypt_sample(
+    key: &[u8; 16],
+    encryption: &SampleEncryption,
+    data: &mut [u8],
+    encrypt: bool,
+) -> Result<()> {
+    let cipher = Aes128::new(key.into());
+    let ranges = protected_ranges(encryption, data.len())?;
+    let pattern = Pattern::new(encryption);
+    match encryption.scheme_type {
+        SchmBox::CENC => {
+            let mut ctr = Ctr::new(&cipher, &encryption.iv)?;
+            for range in ranges {
+                ctr.apply(&mut data[range]);
+            }
+        }
+        SchmBox::CENS => {
+            let mut ctr = Ctr::new(&cipher, &encryption.iv)?;
+            for range in ranges {
+                for block in pattern.encrypted_blocks(&mut data[range]) {
+                    ctr.apply(block);
+                }
+            }
+        }
+        SchmBox::CBC1 => {
+            let mut cbc = Cbc::new(&cipher, &encryption.iv, encrypt)?;
+            for range in ranges {
+                for block in data[range].as_chunks_mut::<BLOCK_SIZE>().0 {
+                    cbc.apply(block);
+                }
+            }
+        }
+        SchmBox::CBCS => {
+            // Every subsample restarts from the IV.
+            for range in ranges {
+                let mut cbc = Cbc::new(&cipher, &encryption.iv, encrypt)?;
+                for block in pattern.encrypted_blocks(&mut data[range]) {
+                    cbc.apply(block);
+                }
+            }
+        }
+        _ => return Err(Error::InvalidData("unsupported protection scheme")),
+    }
+    Ok(())
+}
+
+/// The protected ranges of a sample of `size` bytes.
+fn protected_ranges(encryption: &SampleEncryption, size: usize) -> Result<Vec<Range<usize>>> {
+    let mut ranges = Vec::with_capacity(encryption.subsamples.len().max(1));
+    if encryption.subsamples.is_empty() {
+        ranges.push(0..size);
+        return Ok(ranges);
+    }
+    let mut start = 0;
+    for subsample in encryption.subsamples.iter() {
+        start += subsample.bytes_of_clear_data as usize;
+        let end = start + subsample.bytes_of_protected_data as usize;
+        if end > size {
+            return Err(Error::InvalidData("subsamples are larger than the sample"));
+        }
+        ranges.push(start..end);
+        start = end;
+    }
+    Ok(ranges)
+}
+
+/// Expand an 8 byte IV to a 16 byte block, padding it with zeros.
+fn iv_block(iv: &[u8]) -> Result<[u8; BLOCK_SIZE]> {
+    let mut block = [0; BLOCK_SIZE];
+    match iv.len() {
+        8 | 16 => block[..iv.len()].copy_from_slice(iv),
+        _ => return Err(Error::InvalidData("IV must be 8 or 16 bytes")),
+    }
+    Ok(block)
+}
+
+/// The encryption pattern of `cens` and `cbcs`: `crypt` encrypted blocks
+/// followed by `skip` clear blocks, repeated over each protected range.
+struct Pattern {
+    crypt: usize,
+    skip: usize,
+}
+
+impl Pattern {
+    fn new(encryption: &SampleEncryption) -> Self {
+        Pattern {
+            crypt: encryption.crypt_byte_block as usize,
+            skip: encryption.skip_byte_block as usize,
+        }
+    }
+
+    /// The encrypted blocks of a protected range. A trailing partial block
+    /// is never encrypted.
+    fn encrypted_blocks<'a>(
+        &self,
+        data: &'a mut [u8],
+    ) -> impl Iterator<Item = &'a mut [u8; BLOCK_SIZE]> {
+        let (crypt, period) = match self.crypt + self.skip {
+            // No pattern, every block is encrypted.
+            0 => (1, 1),
+            period => (self.crypt, period),
+        };
+        data.as_chunks_mut::<BLOCK_SIZE>()
+            .0
+            .iter_mut()
+            .enumerate()
+            .filter(move |(i, _)| i % period < crypt)
+            .map(|(_, block)| block)
+    }
+}
+
+/// AES-CTR with a 64-bit block counter, continuing over all the data it is
+/// applied to.
+struct Ctr<'a> {
+    cipher: &'a Aes128,
+    counter: [u8; BLOCK_SIZE],
+    keystream: [u8; BLOCK_SIZE],
+    used: usize,
+}
+
+impl<'a> Ctr<'a> {
+    fn new(cipher: &'a Aes128, iv: &[u8]) -> Result<Self> {
+        Ok(Ctr {
+            cipher,
+            counter: iv_block(iv)?,
+            keystream: [0; BLOCK_SIZE],
+            used: BLOCK_SIZE,
+        })
+    }
+
+    fn apply(&mut self, data: &mut [u8]) {
+        for byte in data.iter_mut() {
+            if self.used == BLOCK_SIZE {
+                self.keystream = self.counter;
+                self.cipher.encrypt_block((&mut self.keystream).into());
+                let count = u64::from_be_bytes(self.counter[8..].try_into().unwrap());
+                self.counter[8..].copy_from_slice(&count.wrapping_add(1).to_be_bytes());
+                self.used = 0;
+            }
+            *byte ^= self.keystream[self.used];
+            self.used += 1;
+        }
+    }
+}
+
+/// AES-CBC over whole blocks, chaining from block to block.
+struct Cbc<'a> {
+    cipher: &'a Aes128,
+    chain: [u8; BLOCK_SIZE],
+    encrypt: bool,
+}
+
+impl<'a> Cbc<'a> {
+    fn new(cipher: &'a Aes128, iv: &[u8], encrypt: bool) -> Result<Self> {
+        Ok(Cbc {
+            cipher,
+            chain: iv_block(iv)?,
+            encrypt,
+        })
+    }
+
+    fn apply(&mut self, block: &mut [u8; BLOCK_SIZE]) {
+        if self.encrypt {
+            for (byte, chain) in block.iter_mut().zip(self.chain.iter()) {
+                *byte ^= chain;
+            }
+            self.cipher.encrypt_block(block.into());
+            self.chain = *block;
+        } else {
+            let ciphertext = *block;
+            self.cipher.decrypt_block(block.into());
+            for (byte, chain) in block.iter_mut().zip(self.chain.iter()) {
+                *byte ^= chain;
+            }
+            self.chain = ciphertext;
+        }
+    }
+}
+
+/// A copy of `moov` with the protection of its tracks removed: sample
+/// entries get back their original format and the `pssh`, `seig` sample
+/// groups and auxiliary information offsets are dropped.
+///
+/// Returns the protection scheme of each encrypted track by track ID.
+pub(crate) fn remove_moov_protection(moov: &MoovBox) -> (MoovBox, HashMap<u32, Option<FourCC>>) {
+    let mut moov = moov.clone();
+    let mut schemes = HashMap::new();
+    moov.psshs.clear();
+    for trak in moov.traks.iter_mut() {
+        let stsd = &mut trak.mdia.minf.stbl.stsd;
+        let sinf = if let Some(ref mut avc1) = stsd.avc1 {
+            avc1.sinf.take()
+        } else if let Some(ref mut hev1) = stsd.hev1 {
+            hev1.sinf.take()
+        } else if let Some(ref mut vp09) = stsd.vp09 {
+            vp09.sinf.take()
+        } else if let Some(ref mut av01) = stsd.av01 {
+            av01.sinf.take()
+        } else if let Some(ref mut mp4a) = stsd.mp4a {
+            mp4a.sinf.take()
+        } else if let Some(ref mut opus) = stsd.opus {
+            opus.sinf.take()
+        } else if let Some(ref mut flac) = stsd.flac {
+            flac.sinf.take()
+        } else if let Some(ref mut ac3) = stsd.ac3 {
+            ac3.sinf.take()
+        } else if let Some(ref mut ec3) = stsd.ec3 {
+            ec3.sinf.take()
+        } else {
+            None
+        };
+        let Some(sinf) = sinf else {
+            continue;
+        };
+
+        let scheme = sinf.schm.map(|schm| schm.scheme_type);
+        let stbl = &mut trak.mdia.minf.stbl;
+        stbl.saizs
+            .retain(|saiz| !scheme.is_some_and(|scheme| saiz.has_aux_info_type(scheme)));
+        stbl.saios
+            .retain(|saio| !scheme.is_some_and(|scheme| saio.has_aux_info_type(scheme)));
+        stbl.sgpds
+            .retain(|sgpd| sgpd.grouping_type != SampleGroupEntry::SEIG);
+        stbl.sbgps
+            .retain(|sbgp| sbgp.grouping_type != SampleGroupEntry::SEIG);
+        schemes.insert(trak.tkhd.track_id, scheme);
+    }
+    (moov, schemes)
+}
+
+/// A copy of `moof` with the protection of the given tracks removed, see
+/// [remove_moov_protection].
+pub(crate) fn remove_moof_protection(
+    moof: &MoofBox,
+    schemes: &HashMap<u32, Option<FourCC>>,
+) -> MoofBox {
+    let mut moof = moof.clone();
+    moof.psshs.clear();
+    for traf in moof.trafs.iter_mut() {
+        let Some(scheme) = schemes.get(&traf.tfhd.track_id) else {
+            continue;
+        };
+        traf.senc = None;
+        traf.saizs
+            .retain(|saiz| !scheme.is_some_and(|scheme| saiz.has_aux_info_type(scheme)));
+        traf.saios
+            .retain(|saio| !scheme.is_some_and(|scheme| saio.has_aux_info_type(scheme)));
+        traf.sgpds
+            .retain(|sgpd| sgpd.grouping_type != SampleGroupEntry::SEIG);
+        traf.sbgps
+            .retain(|sbgp| sbgp.grouping_type != SampleGroupEntry::SEIG);
+    }
+    moof
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const KEY: [u8; 16] = [
+        0x2b, 0x7e, 0x15, 0x16, 0x28, 0xae, 0xd2, 0xa6, 0xab, 0xf7, 0x15, 0x88, 0x09, 0xcf, 0x4f,
+        0x3c,
+    ];
+
+    const PLAINTEXT: [u8; 32] = [
+        0x6b, 0xc1, 0xbe, 0xe2, 0x2e, 0x40, 0x9f, 0x96, 0xe9, 0x3d, 0x7e, 0x11, 0x73, 0x93, 0x17,
+        0x2a, 0xae, 0x2d, 0x8a, 0x57, 0x1e, 0x03, 0xac, 0x9c, 0x9e, 0xb7, 0x6f, 0xac, 0x45, 0xaf,
+        0x8e, 0x51,
+    ];
+
+    fn encryption(scheme_type: FourCC, iv: &[u8]) -> SampleEncryption {
+        SampleEncryption {
+            scheme_type,
+            iv: iv.to_vec(),
+            ..SampleEncryption::default()
+        }
+    }
+
+    #[test]
+    fn test_decrypt_ctr() {
+        // NIST SP 800-38A F.5.1, CTR-AES128.
+        let iv = [
+            0xf0, 0xf1, 0xf2, 0xf3, 0xf4, 0xf5, 0xf6, 0xf7, 0xf8, 0xf9, 0xfa, 0xfb, 0xfc, 0xfd,
+            0xfe, 0xff,
+        ];
+        let mut data = [
+            0x87, 0x4d, 0x61, 0x91, 0xb6, 0x20, 0xe3, 0x26, 0x1b, 0xef, 0x68, 0x64, 0x99, 0x0d,
+            0xb6, 0xce, 0x98, 0x06, 0xf6, 0x6b, 0x79, 0x70, 0xfd, 0xff, 0x86, 0x17, 0x18, 0x7b,
+            0xb9, 0xff, 0xfd, 0xff,
+        ];
+        decrypt_sample(&KEY, &encryption(SchmBox::CENC, &iv), &mut data).unwrap();
+        assert_eq!(data, PLAINTEXT);
+    }
+
+    #[test]
+    fn test_decrypt_cbc() {
+        // NIST SP 800-38A F.2.2, CBC-AES128.
+        let iv: Vec<u8> = (0..16).collect();
+        let ciphertext = [
+            0x76, 0x49, 0xab, 0xac, 0x81, 0x19, 0xb2, 0x46, 0xce, 0xe9, 0x8e, 0x9b, 0x12, 0xe9,
+            0x19, 0x7d, 0x50, 0x86, 0xcb, 0x9b, 0x50, 0x72, 0x19, 0xee, 0x95, 0xdb, 0x11, 0x3a,
+            0x91, 0x76, 0x78, 0xb2,
+        ];
+
+        let mut data = ciphertext;
+        decrypt_sample(&KEY, &encryption(SchmBox::CBC1, &iv), &mut data).unwrap();
+        assert_eq!(data, PLAINTEXT);
+
+        // With a 1:1 pattern only the first block is encrypted, and a
+        // trailing partial block is clear.
+        let mut data = ciphertext[..16].to_vec();
+        data.extend_from_slice(&PLAINTEXT[16..]);
+        data.extend_from_slice(&[1, 2, 3]);
+        let cbcs = SampleEncryption {
+            crypt_byte_block: 1,
+            skip_byte_block: 1,
+            ..encryption(SchmBox::CBCS, &iv)
+        };
+        decrypt_sample(&KEY, &cbcs, &mut data).unwrap();
+        assert_eq!(data[..32], PLAINTEXT);
+        assert_eq!(data[32..], [1, 2, 3]);
+    }
+
+    #[test]
+    fn test_encrypt() {
+        let subsamples = vec![
+            SencSubsample {
+                bytes_of_clear_data: 3,
+                bytes_of_protected_data: 45,
+            },
+            SencSubsample {
+                bytes_of_clear_data: 7,
+                bytes_of_protected_data: 64,
+            },
+        ];
+        let data: Vec<u8> = (0..130).map(|i| i as u8).collect();
+        for scheme_type in [SchmBox::CENC, SchmBox::CENS, SchmBox::CBC1, SchmBox::CBCS] {
+            let pattern = if scheme_type == SchmBox::CENS || scheme_type == SchmBox::CBCS {
+                (1, 2)
+            } else {
+                (0, 0)
+            };
+            let encryption = SampleEncryption {
+                crypt_byte_block: pattern.0,
+                skip_byte_block: pattern.1,
+                subsamples: subsamples.clone(),
+                ..encryption(scheme_type, &[5; 16])
+            };
+            let mut encrypted = data.clone();
+            encrypt_sample(&KEY, &encryption, &mut encrypted).unwrap();
+            assert_ne!(encrypted, data);
+            assert_eq!(encrypted[..3], data[..3]);
+            assert_eq!(encrypted[48..55], data[48..55]);
+            assert_eq!(encrypted[119..], data[119..]);
+
+            let mut decrypted = encrypted;
+            decrypt_sample(&KEY, &encryption, &mut decrypted).unwrap();
+            assert_eq!(decrypted, data);
+        }
+    }
+
+    #[test]
+    fn test_decrypt_subsamples() {
+        // The counter continues from one protected range to the next.
+        let iv = [0xf0, 0xf1, 0xf2, 0xf3, 0xf4, 0xf5, 0xf6, 0xf7];
+        let mut whole = PLAINTEXT;
+        decrypt_sample(&KEY, &encryption(SchmBox::CENC, &iv), &mut whole).unwrap();
+
+        let mut data = vec![9; 5];
+        data.extend_from_slice(&PLAINTEXT[..10]);
+        data.extend_from_slice(&[9; 3]);
+        data.extend_from_slice(&PLAINTEXT[10..]);
+        let cenc = SampleEncryption {
+            subsamples: vec![
+                SencSubsample {
+                    bytes_of_clear_data: 5,
+                    bytes_of_protected_data: 10,
+                },
+                SencSubsample {
+                    bytes_of_clear_data: 3,
+                    bytes_of_protected_data: 22,
+                },
+            ],
+            ..encryption(SchmBox::CENC, &iv)
+        };
+        decrypt_sample(&KEY, &cenc, &mut data).unwrap();
+        assert_eq!(data[..5], [9; 5]);
+        assert_eq!(data[5..15], whole[..10]);
+        assert_eq!(data[15..18], [9; 3]);
+        assert_eq!(data[18..], whole[10..]);
+
+        let mut data = vec![0; 10];
+        assert!(decrypt_sample(&KEY, &cenc, &mut data).is_err());
+    }
+}
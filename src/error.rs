@@ -13,6 +13,7 @@ pub enum Error {
     EntryInStblNotFound(u32, BoxType, u32),
     EntryInTrunNotFound(u32, BoxType, u32),
     UnsupportedBoxVersion(BoxType, u8),
+    KeyNotFound([u8; 16]),
 }
 
 impl std::error::Error for Error {}
@@ -49,6 +50,13 @@ impl std::fmt::Display for Error {
             Error::UnsupportedBoxVersion(box_type, version) => {
                 write!(f, "{} version {} is not supported", box_type, version)
             }
+            Error::KeyNotFound(kid) => {
+                write!(f, "key for KID ")?;
+                for byte in kid.iter() {
+                    write!(f, "{:02x}", byte)?;
+                }
+                write!(f, " not found")
+            }
         }
     }
 }
@@ -79,10 +79,27 @@ mod track;
 pub use track::{Mp4Track, TrackConfig};
 
 mod reader;
-pub use reader::Mp4Reader;
+pub use reader::{Mp4Reader, Mp4Samples};
+
+#[cfg(feature = "tokio")]
+mod async_reader;
+
+#[cfg(feature = "cenc")]
+mod cenc;
+#[cfg(feature = "cenc")]
+pub use cenc::{decrypt_sample, encrypt_sample};
+
+mod stream_reader;
+pub use stream_reader::Mp4StreamReader;
+
+mod bitreader;
+mod slice_header;
+
+mod box_tree;
+pub use box_tree::{BoxContent, BoxNode, BoxTree, BoxValue};
 
 mod writer;
-pub use writer::{Mp4Config, Mp4Writer};
+pub use writer::{FragmentConfig, Mp4Config, Mp4FragmentWriter, Mp4Writer};
 
 pub fn read_mp4(f: File) -> Result<Mp4Reader<BufReader<File>>> {
     let size = f.metadata()?.len();
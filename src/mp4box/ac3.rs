@@ -0,0 +1,338 @@
+use byteorder::{BigEndian, ReadBytesExt, WriteBytesExt};
+use std::io::{Read, Seek, Write};
+
+use crate::mp4box::*;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Ac3Box {
+    pub data_reference_index: u16,
+    pub channelcount: u16,
+    pub samplesize: u16,
+    pub samplerate: FixedPointU16,
+    pub dac3: Dac3Box,
+
+    /// Protection scheme information, if the sample entry is an encrypted
+    /// `enca` entry with this original format.
+    pub sinf: Option<SinfBox>,
+
+    pub unknown_boxes: Vec<(BoxType, Vec<u8>)>,
+}
+
+impl Default for Ac3Box {
+    fn default() -> Self {
+        Self {
+            data_reference_index: 0,
+            channelcount: 2,
+            samplesize: 16,
+            samplerate: FixedPointU16::new(48000),
+            dac3: Dac3Box::default(),
+            sinf: None,
+            unknown_boxes: Vec::new(),
+        }
+    }
+}
+
+impl Ac3Box {
+    pub fn new(config: &Ac3Config) -> Result<Self> {
+        let dac3 = Dac3Box {
+            fscod: config.fscod,
+            bsid: config.bsid,
+            bsmod: config.bsmod,
+            acmod: config.acmod,
+            lfeon: config.lfeon,
+            bit_rate_code: config.bit_rate_code,
+        };
+        if dac3.sample_rate().is_none() || dac3.bitrate().is_none() || dac3.acmod > 7 {
+            return Err(Error::InvalidData("AC-3 configuration is invalid"));
+        }
+        Ok(Self {
+            data_reference_index: 1,
+            channelcount: dac3.channel_layout().channel_count(),
+            samplerate: FixedPointU16::new(dac3.sample_rate().unwrap() as u16),
+            dac3,
+            ..Ac3Box::default()
+        })
+    }
+
+    pub fn get_type(&self) -> BoxType {
+        if self.sinf.is_some() {
+            return BoxType::EncaBox;
+        }
+        BoxType::Ac3Box
+    }
+
+    pub fn get_size(&self) -> u64 {
+        let mut size = HEADER_SIZE + 8 + 20 + self.dac3.box_size();
+        if let Some(ref sinf) = self.sinf {
+            size += sinf.box_size();
+        }
+        size + unknown_boxes_size(&self.unknown_boxes)
+    }
+}
+
+impl Mp4Box for Ac3Box {
+    fn box_type(&self) -> BoxType {
+        self.get_type()
+    }
+
+    fn box_size(&self) -> u64 {
+        self.get_size()
+    }
+
+    fn summary(&self) -> Result<String> {
+        let s = format!(
+            "channel_count={} sample_size={} sample_rate={}",
+            self.channelcount,
+            self.samplesize,
+            self.samplerate.value()
+        );
+        Ok(s)
+    }
+}
+
+impl<R: Read + Seek> ReadBox<&mut R> for Ac3Box {
+    fn read_box(reader: &mut R, size: u64) -> Result<Self> {
+        let start = box_start(reader)?;
+
+        reader.read_u32::<BigEndian>()?; // reserved
+        reader.read_u16::<BigEndian>()?; // reserved
+        let data_reference_index = reader.read_u16::<BigEndian>()?;
+        reader.read_u64::<BigEndian>()?; // reserved
+        let channelcount = reader.read_u16::<BigEndian>()?;
+        let samplesize = reader.read_u16::<BigEndian>()?;
+        reader.read_u32::<BigEndian>()?; // pre-defined, reserved
+        let samplerate = FixedPointU16::new_raw(reader.read_u32::<BigEndian>()?);
+
+        let mut dac3 = None;
+        let mut sinf = None;
+        let mut unknown_boxes = Vec::new();
+
+        let mut current = reader.stream_position()?;
+        let end = start + size;
+        while current < end {
+            let header = BoxHeader::read(reader)?;
+            let BoxHeader { name, size: s } = header;
+            if s > size {
+                return Err(Error::InvalidData(
+                    "ac-3 box contains a box with a larger size than it",
+                ));
+            }
+            if name == BoxType::Dac3Box && dac3.is_none() {
+                dac3 = Some(Dac3Box::read_box(reader, s)?);
+            } else if name == BoxType::SinfBox && sinf.is_none() {
+                sinf = Some(SinfBox::read_box(reader, s)?);
+            } else {
+                unknown_boxes.push(read_unknown_box(reader, name, s)?);
+            }
+            current = reader.stream_position()?;
+        }
+
+        let Some(dac3) = dac3 else {
+            return Err(Error::InvalidData("dac3 not found"));
+        };
+
+        skip_bytes_to(reader, start + size)?;
+
+        Ok(Ac3Box {
+            data_reference_index,
+            channelcount,
+            samplesize,
+            samplerate,
+            dac3,
+            sinf,
+            unknown_boxes,
+        })
+    }
+}
+
+impl<W: Write> WriteBox<&mut W> for Ac3Box {
+    fn write_box(&self, writer: &mut W) -> Result<u64> {
+        let size = self.box_size();
+        BoxHeader::new(self.box_type(), size).write(writer)?;
+
+        writer.write_u32::<BigEndian>(0)?; // reserved
+        writer.write_u16::<BigEndian>(0)?; // reserved
+        writer.write_u16::<BigEndian>(self.data_reference_index)?;
+
+        writer.write_u64::<BigEndian>(0)?; // reserved
+        writer.write_u16::<BigEndian>(self.channelcount)?;
+        writer.write_u16::<BigEndian>(self.samplesize)?;
+        writer.write_u32::<BigEndian>(0)?; // reserved
+        writer.write_u32::<BigEndian>(self.samplerate.raw_value())?;
+
+        self.dac3.write_box(writer)?;
+        if let Some(ref sinf) = self.sinf {
+            sinf.write_box(writer)?;
+        }
+        write_unknown_boxes(writer, &self.unknown_boxes)?;
+
+        Ok(size)
+    }
+}
+
+/// The AC-3 specific box, see ETSI TS 102 366 F.4.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Dac3Box {
+    /// Sample rate code: 48, 44.1 or 32 kHz.
+    pub fscod: u8,
+    pub bsid: u8,
+
+    /// Bit stream mode, such as complete main (0) or commentary (5).
+    pub bsmod: u8,
+
+    /// Audio coding mode, the arrangement of the full bandwidth channels.
+    pub acmod: u8,
+    pub lfeon: bool,
+
+    /// Index of the bit rate in kbit/s, see [Dac3Box::bitrate].
+    pub bit_rate_code: u8,
+}
+
+impl Default for Dac3Box {
+    fn default() -> Self {
+        // 5.1 at 48 kHz and 448 kbit/s.
+        Self {
+            fscod: 0,
+            bsid: 8,
+            bsmod: 0,
+            acmod: 7,
+            lfeon: true,
+            bit_rate_code: 15,
+        }
+    }
+}
+
+impl Dac3Box {
+    /// The sample rate in Hz.
+    pub fn sample_rate(&self) -> Option<u32> {
+        ac3_sample_rate(self.fscod)
+    }
+
+    /// The bit rate in bit/s.
+    pub fn bitrate(&self) -> Option<u32> {
+        const BITRATES: [u32; 19] = [
+            32, 40, 48, 56, 64, 80, 96, 112, 128, 160, 192, 224, 256, 320, 384, 448, 512, 576, 640,
+        ];
+        BITRATES
+            .get(self.bit_rate_code as usize)
+            .map(|kbps| kbps * 1000)
+    }
+
+    pub fn channel_layout(&self) -> DolbyChannelLayout {
+        DolbyChannelLayout::from_acmod(self.acmod, self.lfeon)
+    }
+}
+
+/// The sample rate in Hz of an AC-3 or E-AC-3 sample rate code.
+pub(crate) fn ac3_sample_rate(fscod: u8) -> Option<u32> {
+    match fscod {
+        0 => Some(48000),
+        1 => Some(44100),
+        2 => Some(32000),
+        _ => None,
+    }
+}
+
+impl Mp4Box for Dac3Box {
+    fn box_type(&self) -> BoxType {
+        BoxType::Dac3Box
+    }
+
+    fn box_size(&self) -> u64 {
+        HEADER_SIZE + 3
+    }
+
+    fn summary(&self) -> Result<String> {
+        let s = format!(
+            "fscod={} bsid={} bsmod={} acmod={} lfeon={} bit_rate_code={}",
+            self.fscod, self.bsid, self.bsmod, self.acmod, self.lfeon, self.bit_rate_code
+        );
+        Ok(s)
+    }
+}
+
+impl<R: Read + Seek> ReadBox<&mut R> for Dac3Box {
+    fn read_box(reader: &mut R, size: u64) -> Result<Self> {
+        let start = box_start(reader)?;
+
+        let params = reader.read_u24::<BigEndian>()?;
+        let fscod = (params >> 22) as u8;
+        let bsid = ((params >> 17) & 0b11111) as u8;
+        let bsmod = ((params >> 14) & 0b111) as u8;
+        let acmod = ((params >> 11) & 0b111) as u8;
+        let lfeon = (params >> 10) & 1 != 0;
+        let bit_rate_code = ((params >> 5) & 0b11111) as u8;
+
+        skip_bytes_to(reader, start + size)?;
+
+        Ok(Dac3Box {
+            fscod,
+            bsid,
+            bsmod,
+            acmod,
+            lfeon,
+            bit_rate_code,
+        })
+    }
+}
+
+impl<W: Write> WriteBox<&mut W> for Dac3Box {
+    fn write_box(&self, writer: &mut W) -> Result<u64> {
+        let size = self.box_size();
+        BoxHeader::new(self.box_type(), size).write(writer)?;
+
+        let params = (self.fscod as u32 & 0b11) << 22
+            | (self.bsid as u32 & 0b11111) << 17
+            | (self.bsmod as u32 & 0b111) << 14
+            | (self.acmod as u32 & 0b111) << 11
+            | (self.lfeon as u32) << 10
+            | (self.bit_rate_code as u32 & 0b11111) << 5;
+        writer.write_u24::<BigEndian>(params)?;
+
+        Ok(size)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mp4box::BoxHeader;
+    use std::io::Cursor;
+
+    #[test]
+    fn test_ac3() {
+        let src_box = Ac3Box::new(&Ac3Config::default()).unwrap();
+        assert_eq!(src_box.channelcount, 6);
+        let mut buf = Vec::new();
+        src_box.write_box(&mut buf).unwrap();
+        assert_eq!(buf.len(), src_box.box_size() as usize);
+
+        let mut reader = Cursor::new(&buf);
+        let header = BoxHeader::read(&mut reader).unwrap();
+        assert_eq!(header.name, BoxType::Ac3Box);
+        assert_eq!(src_box.box_size(), header.size);
+
+        let dst_box = Ac3Box::read_box(&mut reader, header.size).unwrap();
+        assert_eq!(src_box, dst_box);
+    }
+
+    #[test]
+    fn test_dac3() {
+        // 48 kHz, 2/0 without LFE at 192 kbit/s.
+        let data = [0, 0, 0, 11, b'd', b'a', b'c', b'3', 0x10, 0x11, 0x40];
+        let mut reader = Cursor::new(&data);
+        let header = BoxHeader::read(&mut reader).unwrap();
+        assert_eq!(header.name, BoxType::Dac3Box);
+        let dac3 = Dac3Box::read_box(&mut reader, header.size).unwrap();
+        assert_eq!(dac3.sample_rate(), Some(48000));
+        assert_eq!(dac3.bsid, 8);
+        assert_eq!(dac3.acmod, 2);
+        assert!(!dac3.lfeon);
+        assert_eq!(dac3.bitrate(), Some(192000));
+        assert_eq!(dac3.channel_layout(), DolbyChannelLayout(0xA000));
+
+        let mut buf = Vec::new();
+        dac3.write_box(&mut buf).unwrap();
+        assert_eq!(buf, data);
+    }
+}
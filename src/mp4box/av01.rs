@@ -0,0 +1,589 @@
+use byteorder::{ReadBytesExt, WriteBytesExt};
+use std::io::{Read, Seek, Write};
+
+use crate::bitreader::BitReader;
+use crate::mp4box::*;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Av01Box {
+    pub data_reference_index: u16,
+    pub width: u16,
+    pub height: u16,
+    pub horizresolution: FixedPointU16,
+    pub vertresolution: FixedPointU16,
+    pub frame_count: u16,
+    pub depth: u16,
+    pub av1c: Av1CBox,
+
+    /// Protection scheme information, if the sample entry is an encrypted
+    /// `encv` entry with this original format.
+    pub sinf: Option<SinfBox>,
+
+    pub unknown_boxes: Vec<(BoxType, Vec<u8>)>,
+}
+
+impl Default for Av01Box {
+    fn default() -> Self {
+        Av01Box {
+            data_reference_index: 0,
+            width: 0,
+            height: 0,
+            horizresolution: FixedPointU16::new(0x48),
+            vertresolution: FixedPointU16::new(0x48),
+            frame_count: 1,
+            depth: 0x0018,
+            av1c: Av1CBox::default(),
+            sinf: None,
+            unknown_boxes: Vec::new(),
+        }
+    }
+}
+
+impl Av01Box {
+    pub fn new(config: &Av1Config) -> Result<Self> {
+        Ok(Av01Box {
+            data_reference_index: 1,
+            width: config.width,
+            height: config.height,
+            av1c: Av1CBox::with_sequence_header(&config.sequence_header)?,
+            ..Av01Box::default()
+        })
+    }
+
+    pub fn get_type(&self) -> BoxType {
+        if self.sinf.is_some() {
+            return BoxType::EncvBox;
+        }
+        BoxType::Av01Box
+    }
+
+    pub fn get_size(&self) -> u64 {
+        let mut size = HEADER_SIZE + 8 + 70 + self.av1c.box_size();
+        if let Some(ref sinf) = self.sinf {
+            size += sinf.box_size();
+        }
+        size + unknown_boxes_size(&self.unknown_boxes)
+    }
+}
+
+impl Mp4Box for Av01Box {
+    fn box_type(&self) -> BoxType {
+        self.get_type()
+    }
+
+    fn box_size(&self) -> u64 {
+        self.get_size()
+    }
+
+    fn summary(&self) -> Result<String> {
+        let s = format!(
+            "data_reference_index={} width={} height={} frame_count={}",
+            self.data_reference_index, self.width, self.height, self.frame_count
+        );
+        Ok(s)
+    }
+}
+
+impl<R: Read + Seek> ReadBox<&mut R> for Av01Box {
+    fn read_box(reader: &mut R, size: u64) -> Result<Self> {
+        let start = box_start(reader)?;
+
+        reader.read_u32::<BigEndian>()?; // reserved
+        reader.read_u16::<BigEndian>()?; // reserved
+        let data_reference_index = reader.read_u16::<BigEndian>()?;
+
+        reader.read_u32::<BigEndian>()?; // pre-defined, reserved
+        reader.read_u64::<BigEndian>()?; // pre-defined
+        reader.read_u32::<BigEndian>()?; // pre-defined
+        let width = reader.read_u16::<BigEndian>()?;
+        let height = reader.read_u16::<BigEndian>()?;
+        let horizresolution = FixedPointU16::new_raw(reader.read_u32::<BigEndian>()?);
+        let vertresolution = FixedPointU16::new_raw(reader.read_u32::<BigEndian>()?);
+        reader.read_u32::<BigEndian>()?; // reserved
+        let frame_count = reader.read_u16::<BigEndian>()?;
+        skip_bytes(reader, 32)?; // compressorname
+        let depth = reader.read_u16::<BigEndian>()?;
+        reader.read_i16::<BigEndian>()?; // pre-defined
+
+        let mut av1c = None;
+        let mut sinf = None;
+        let mut unknown_boxes = Vec::new();
+
+        let mut current = reader.stream_position()?;
+        let end = start + size;
+        while current < end {
+            let header = BoxHeader::read(reader)?;
+            let BoxHeader { name, size: s } = header;
+            if s > size {
+                return Err(Error::InvalidData(
+                    "av01 box contains a box with a larger size than it",
+                ));
+            }
+            if name == BoxType::Av1CBox && av1c.is_none() {
+                av1c = Some(Av1CBox::read_box(reader, s)?);
+            } else if name == BoxType::SinfBox && sinf.is_none() {
+                sinf = Some(SinfBox::read_box(reader, s)?);
+            } else {
+                unknown_boxes.push(read_unknown_box(reader, name, s)?);
+            }
+            current = reader.stream_position()?;
+        }
+
+        let Some(av1c) = av1c else {
+            return Err(Error::InvalidData("av1c not found"));
+        };
+
+        skip_bytes_to(reader, start + size)?;
+
+        Ok(Av01Box {
+            data_reference_index,
+            width,
+            height,
+            horizresolution,
+            vertresolution,
+            frame_count,
+            depth,
+            av1c,
+            sinf,
+            unknown_boxes,
+        })
+    }
+}
+
+impl<W: Write> WriteBox<&mut W> for Av01Box {
+    fn write_box(&self, writer: &mut W) -> Result<u64> {
+        let size = self.box_size();
+        BoxHeader::new(self.box_type(), size).write(writer)?;
+
+        writer.write_u32::<BigEndian>(0)?; // reserved
+        writer.write_u16::<BigEndian>(0)?; // reserved
+        writer.write_u16::<BigEndian>(self.data_reference_index)?;
+
+        writer.write_u32::<BigEndian>(0)?; // pre-defined, reserved
+        writer.write_u64::<BigEndian>(0)?; // pre-defined
+        writer.write_u32::<BigEndian>(0)?; // pre-defined
+        writer.write_u16::<BigEndian>(self.width)?;
+        writer.write_u16::<BigEndian>(self.height)?;
+        writer.write_u32::<BigEndian>(self.horizresolution.raw_value())?;
+        writer.write_u32::<BigEndian>(self.vertresolution.raw_value())?;
+        writer.write_u32::<BigEndian>(0)?; // reserved
+        writer.write_u16::<BigEndian>(self.frame_count)?;
+        // skip compressorname
+        write_zeros(writer, 32)?;
+        writer.write_u16::<BigEndian>(self.depth)?;
+        writer.write_i16::<BigEndian>(-1)?; // pre-defined
+
+        self.av1c.write_box(writer)?;
+        if let Some(ref sinf) = self.sinf {
+            sinf.write_box(writer)?;
+        }
+        write_unknown_boxes(writer, &self.unknown_boxes)?;
+
+        Ok(size)
+    }
+}
+
+/// The AV1 codec configuration record, see the AV1 Codec ISO Media File
+/// Format Binding 2.3.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct Av1CBox {
+    pub seq_profile: u8,
+    pub seq_level_idx_0: u8,
+    pub seq_tier_0: bool,
+    pub high_bitdepth: bool,
+    pub twelve_bit: bool,
+    pub monochrome: bool,
+    pub chroma_subsampling_x: bool,
+    pub chroma_subsampling_y: bool,
+    pub chroma_sample_position: u8,
+    pub initial_presentation_delay_minus_one: Option<u8>,
+
+    /// Configuration OBUs, such as the sequence header, each with an OBU
+    /// size field.
+    pub config_obus: Vec<u8>,
+}
+
+impl Av1CBox {
+    /// The OBU type of a sequence header.
+    pub const OBU_SEQUENCE_HEADER: u8 = 1;
+
+    /// A configuration holding the given sequence header OBU, with the
+    /// profile, level, tier, bit depth and chroma format it signals.
+    pub fn with_sequence_header(obu: &[u8]) -> Result<Self> {
+        let Some((obu_type, payload, _)) = split_obu(obu) else {
+            return Err(Error::InvalidData("sequence header is truncated"));
+        };
+        if obu_type != Av1CBox::OBU_SEQUENCE_HEADER {
+            return Err(Error::InvalidData("OBU is not a sequence header"));
+        }
+        let mut reader = BitReader::new(payload);
+
+        let seq_profile = reader.read_bits(3)? as u8;
+        if seq_profile > 2 {
+            return Err(Error::InvalidData("sequence header has an invalid profile"));
+        }
+        reader.skip_bits(1)?; // still_picture
+        let reduced_still_picture_header = reader.read_bit()?;
+        let seq_level_idx_0;
+        let mut seq_tier_0 = false;
+        if reduced_still_picture_header {
+            seq_level_idx_0 = reader.read_bits(5)? as u8;
+        } else {
+            let mut buffer_delay_length = 0;
+            let decoder_model_info_present = if reader.read_bit()? {
+                // timing_info
+                reader.skip_bits(64)?;
+                if reader.read_bit()? {
+                    reader.read_ue()?; // num_ticks_per_picture_minus_1
+                }
+                let present = reader.read_bit()?;
+                if present {
+                    buffer_delay_length = reader.read_bits(5)? as usize + 1;
+                    reader.skip_bits(42)?;
+                }
+                present
+            } else {
+                false
+            };
+            let initial_display_delay_present = reader.read_bit()?;
+            let operating_points_cnt = reader.read_bits(5)? + 1;
+            let mut levels = Vec::with_capacity(operating_points_cnt as usize);
+            for _ in 0..operating_points_cnt {
+                reader.skip_bits(12)?; // operating_point_idc
+                let seq_level_idx = reader.read_bits(5)? as u8;
+                let seq_tier = seq_level_idx > 7 && reader.read_bit()?;
+                levels.push((seq_level_idx, seq_tier));
+                if decoder_model_info_present && reader.read_bit()? {
+                    // operating_parameters_info
+                    reader.skip_bits(2 * buffer_delay_length + 1)?;
+                }
+                if initial_display_delay_present && reader.read_bit()? {
+                    reader.skip_bits(4)?; // initial_display_delay_minus_1
+                }
+            }
+            (seq_level_idx_0, seq_tier_0) = levels[0];
+        }
+
+        let frame_width_bits = reader.read_bits(4)? as usize + 1;
+        let frame_height_bits = reader.read_bits(4)? as usize + 1;
+        reader.skip_bits(frame_width_bits + frame_height_bits)?;
+        if !reduced_still_picture_header && reader.read_bit()? {
+            // delta_frame_id_length_minus_2, additional_frame_id_length_minus_1
+            reader.skip_bits(7)?;
+        }
+        // use_128x128_superblock, enable_filter_intra, enable_intra_edge_filter
+        reader.skip_bits(3)?;
+        if !reduced_still_picture_header {
+            // enable_interintra_compound, enable_masked_compound,
+            // enable_warped_motion, enable_dual_filter
+            reader.skip_bits(4)?;
+            let enable_order_hint = reader.read_bit()?;
+            if enable_order_hint {
+                reader.skip_bits(2)?; // enable_jnt_comp, enable_ref_frame_mvs
+            }
+            let seq_force_screen_content_tools = if reader.read_bit()? {
+                2
+            } else {
+                reader.read_bits(1)?
+            };
+            if seq_force_screen_content_tools > 0 {
+                // seq_choose_integer_mv and seq_force_integer_mv
+                if !reader.read_bit()? {
+                    reader.skip_bits(1)?;
+                }
+            }
+            if enable_order_hint {
+                reader.skip_bits(3)?; // order_hint_bits_minus_1
+            }
+        }
+        // enable_superres, enable_cdef, enable_restoration
+        reader.skip_bits(3)?;
+
+        // color_config
+        let high_bitdepth = reader.read_bit()?;
+        let twelve_bit = seq_profile == 2 && high_bitdepth && reader.read_bit()?;
+        let monochrome = seq_profile != 1 && reader.read_bit()?;
+        let (mut color_primaries, mut transfer_characteristics, mut matrix_coefficients) =
+            (2, 2, 2);
+        if reader.read_bit()? {
+            color_primaries = reader.read_bits(8)?;
+            transfer_characteristics = reader.read_bits(8)?;
+            matrix_coefficients = reader.read_bits(8)?;
+        }
+        let mut chroma_subsampling_x = true;
+        let mut chroma_subsampling_y = true;
+        let mut chroma_sample_position = 0;
+        if monochrome {
+            // Only the color range follows.
+        } else if color_primaries == 1 && transfer_characteristics == 13 && matrix_coefficients == 0
+        {
+            // sRGB is always 4:4:4.
+            chroma_subsampling_x = false;
+            chroma_subsampling_y = false;
+        } else {
+            reader.skip_bits(1)?; // color_range
+            match seq_profile {
+                0 => {}
+                1 => {
+                    chroma_subsampling_x = false;
+                    chroma_subsampling_y = false;
+                }
+                _ if twelve_bit => {
+                    chroma_subsampling_x = reader.read_bit()?;
+                    chroma_subsampling_y = chroma_subsampling_x && reader.read_bit()?;
+                }
+                _ => chroma_subsampling_y = false,
+            }
+            if chroma_subsampling_x && chroma_subsampling_y {
+                chroma_sample_position = reader.read_bits(2)? as u8;
+            }
+        }
+
+        let mut config_obus = Vec::new();
+        write_obu_with_size(&mut config_obus, obu)?;
+
+        Ok(Av1CBox {
+            seq_profile,
+            seq_level_idx_0,
+            seq_tier_0,
+            high_bitdepth,
+            twelve_bit,
+            monochrome,
+            chroma_subsampling_x,
+            chroma_subsampling_y,
+            chroma_sample_position,
+            initial_presentation_delay_minus_one: None,
+            config_obus,
+        })
+    }
+
+    /// The bit depth of the samples, 8, 10 or 12.
+    pub fn bit_depth(&self) -> u8 {
+        match (self.high_bitdepth, self.twelve_bit) {
+            (false, _) => 8,
+            (true, false) => 10,
+            (true, true) => 12,
+        }
+    }
+
+    /// The sequence header OBU among the configuration OBUs.
+    pub fn sequence_header(&self) -> Option<&[u8]> {
+        let mut data = self.config_obus.as_slice();
+        while let Some((obu_type, _, len)) = split_obu(data) {
+            if obu_type == Av1CBox::OBU_SEQUENCE_HEADER {
+                return Some(&data[..len]);
+            }
+            data = &data[len..];
+        }
+        None
+    }
+}
+
+impl Mp4Box for Av1CBox {
+    fn box_type(&self) -> BoxType {
+        BoxType::Av1CBox
+    }
+
+    fn box_size(&self) -> u64 {
+        HEADER_SIZE + 4 + self.config_obus.len() as u64
+    }
+
+    fn summary(&self) -> Result<String> {
+        let s = format!(
+            "seq_profile={} seq_level_idx_0={} seq_tier_0={} bit_depth={}",
+            self.seq_profile,
+            self.seq_level_idx_0,
+            self.seq_tier_0,
+            self.bit_depth()
+        );
+        Ok(s)
+    }
+}
+
+impl<R: Read + Seek> ReadBox<&mut R> for Av1CBox {
+    fn read_box(reader: &mut R, size: u64) -> Result<Self> {
+        let start = box_start(reader)?;
+
+        let marker_version = reader.read_u8()?;
+        if marker_version != 0x81 {
+            return Err(Error::InvalidData("av1C has an unsupported version"));
+        }
+        let params = reader.read_u8()?;
+        let seq_profile = params >> 5;
+        let seq_level_idx_0 = params & 0b00011111;
+        let params = reader.read_u8()?;
+        let seq_tier_0 = params & 0b10000000 != 0;
+        let high_bitdepth = params & 0b01000000 != 0;
+        let twelve_bit = params & 0b00100000 != 0;
+        let monochrome = params & 0b00010000 != 0;
+        let chroma_subsampling_x = params & 0b00001000 != 0;
+        let chroma_subsampling_y = params & 0b00000100 != 0;
+        let chroma_sample_position = params & 0b00000011;
+        let params = reader.read_u8()?;
+        let initial_presentation_delay_minus_one =
+            (params & 0b00010000 != 0).then_some(params & 0b00001111);
+
+        let Some(obus_size) = (start + size).checked_sub(reader.stream_position()?) else {
+            return Err(Error::InvalidData("av1C box is truncated"));
+        };
+        let mut config_obus = vec![0; obus_size as usize];
+        reader.read_exact(&mut config_obus)?;
+
+        skip_bytes_to(reader, start + size)?;
+
+        Ok(Av1CBox {
+            seq_profile,
+            seq_level_idx_0,
+            seq_tier_0,
+            high_bitdepth,
+            twelve_bit,
+            monochrome,
+            chroma_subsampling_x,
+            chroma_subsampling_y,
+            chroma_sample_position,
+            initial_presentation_delay_minus_one,
+            config_obus,
+        })
+    }
+}
+
+impl<W: Write> WriteBox<&mut W> for Av1CBox {
+    fn write_box(&self, writer: &mut W) -> Result<u64> {
+        let size = self.box_size();
+        BoxHeader::new(self.box_type(), size).write(writer)?;
+
+        writer.write_u8(0x81)?; // marker, version
+        writer.write_u8(self.seq_profile << 5 | self.seq_level_idx_0 & 0b00011111)?;
+        writer.write_u8(
+            (self.seq_tier_0 as u8) << 7
+                | (self.high_bitdepth as u8) << 6
+                | (self.twelve_bit as u8) << 5
+                | (self.monochrome as u8) << 4
+                | (self.chroma_subsampling_x as u8) << 3
+                | (self.chroma_subsampling_y as u8) << 2
+                | self.chroma_sample_position & 0b00000011,
+        )?;
+        match self.initial_presentation_delay_minus_one {
+            Some(delay) => writer.write_u8(0b00010000 | delay & 0b00001111)?,
+            None => writer.write_u8(0)?,
+        }
+        writer.write_all(&self.config_obus)?;
+
+        Ok(size)
+    }
+}
+
+/// The type, payload and total size of the OBU at the start of `data`. An
+/// OBU without a size field extends to the end of `data`.
+fn split_obu(data: &[u8]) -> Option<(u8, &[u8], usize)> {
+    let header = *data.first()?;
+    let obu_type = (header >> 3) & 0b1111;
+    let has_extension = header & 0b100 != 0;
+    let has_size_field = header & 0b10 != 0;
+    let mut pos = 1 + has_extension as usize;
+    let payload_size = if has_size_field {
+        let (size, len) = read_leb128(data.get(pos..)?)?;
+        pos += len;
+        usize::try_from(size).ok()?
+    } else {
+        data.len().checked_sub(pos)?
+    };
+    let end = pos.checked_add(payload_size)?;
+    Some((obu_type, data.get(pos..end)?, end))
+}
+
+/// Write an OBU, adding a size field if it has none, as required in `av1C`.
+fn write_obu_with_size(buf: &mut Vec<u8>, obu: &[u8]) -> Result<()> {
+    let Some((_, payload, len)) = split_obu(obu) else {
+        return Err(Error::InvalidData("OBU is truncated"));
+    };
+    if obu[0] & 0b10 != 0 {
+        buf.extend_from_slice(&obu[..len]);
+        return Ok(());
+    }
+    let header_len = len - payload.len();
+    buf.push(obu[0] | 0b10);
+    buf.extend_from_slice(&obu[1..header_len]);
+    let mut size = payload.len();
+    loop {
+        let byte = (size & 0x7f) as u8;
+        size >>= 7;
+        if size == 0 {
+            buf.push(byte);
+            break;
+        }
+        buf.push(byte | 0x80);
+    }
+    buf.extend_from_slice(payload);
+    Ok(())
+}
+
+/// A `leb128()` value and its length in bytes.
+fn read_leb128(data: &[u8]) -> Option<(u64, usize)> {
+    let mut value = 0;
+    for (i, byte) in data.iter().take(8).enumerate() {
+        value |= u64::from(byte & 0x7f) << (7 * i);
+        if byte & 0x80 == 0 {
+            return Some((value, i + 1));
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mp4box::BoxHeader;
+    use std::io::Cursor;
+
+    // Main profile, level 4.0, 1920x1080 4:2:0 8-bit.
+    const SEQUENCE_HEADER: [u8; 16] = [
+        0x0a, 0x0e, 0x00, 0x00, 0x00, 0x42, 0xab, 0xbf, 0xc3, 0x73, 0xff, 0xe6, 0x40, 0x40, 0x40,
+        0x41,
+    ];
+
+    #[test]
+    fn test_av01() {
+        let src_box = Av01Box::new(&Av1Config {
+            width: 1920,
+            height: 1080,
+            sequence_header: SEQUENCE_HEADER.to_vec(),
+        })
+        .unwrap();
+        let mut buf = Vec::new();
+        src_box.write_box(&mut buf).unwrap();
+        assert_eq!(buf.len(), src_box.box_size() as usize);
+
+        let mut reader = Cursor::new(&buf);
+        let header = BoxHeader::read(&mut reader).unwrap();
+        assert_eq!(header.name, BoxType::Av01Box);
+        assert_eq!(src_box.box_size(), header.size);
+
+        let dst_box = Av01Box::read_box(&mut reader, header.size).unwrap();
+        assert_eq!(src_box, dst_box);
+    }
+
+    #[test]
+    fn test_av1c_sequence_header() {
+        let av1c = Av1CBox::with_sequence_header(&SEQUENCE_HEADER).unwrap();
+        assert_eq!(av1c.seq_profile, 0);
+        assert_eq!(av1c.seq_level_idx_0, 8);
+        assert!(!av1c.seq_tier_0);
+        assert_eq!(av1c.bit_depth(), 8);
+        assert!(!av1c.monochrome);
+        assert!(av1c.chroma_subsampling_x);
+        assert!(av1c.chroma_subsampling_y);
+        assert_eq!(av1c.chroma_sample_position, 0);
+        assert_eq!(av1c.sequence_header(), Some(SEQUENCE_HEADER.as_slice()));
+
+        // A size field is added to an OBU without one.
+        let mut obu = vec![0x08];
+        obu.extend_from_slice(&SEQUENCE_HEADER[2..]);
+        let av1c = Av1CBox::with_sequence_header(&obu).unwrap();
+        assert_eq!(av1c.config_obus, SEQUENCE_HEADER);
+
+        // A temporal delimiter is not a sequence header.
+        assert!(Av1CBox::with_sequence_header(&[0x12, 0x00]).is_err());
+        assert!(Av1CBox::with_sequence_header(&SEQUENCE_HEADER[..8]).is_err());
+    }
+}
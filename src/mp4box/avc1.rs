@@ -13,6 +13,12 @@ pub struct Avc1Box {
     pub frame_count: u16,
     pub depth: u16,
     pub avcc: AvcCBox,
+
+    /// Protection scheme information, if the sample entry is an encrypted
+    /// `encv` entry with this original format.
+    pub sinf: Option<SinfBox>,
+
+    pub unknown_boxes: Vec<(BoxType, Vec<u8>)>,
 }
 
 impl Default for Avc1Box {
@@ -26,6 +32,8 @@ impl Default for Avc1Box {
             frame_count: 1,
             depth: 0x0018,
             avcc: AvcCBox::default(),
+            sinf: None,
+            unknown_boxes: Vec::new(),
         }
     }
 }
@@ -41,15 +49,24 @@ impl Avc1Box {
             frame_count: 1,
             depth: 0x0018,
             avcc: AvcCBox::new(&config.seq_param_set, &config.pic_param_set),
+            sinf: None,
+            unknown_boxes: Vec::new(),
         }
     }
 
     pub fn get_type(&self) -> BoxType {
+        if self.sinf.is_some() {
+            return BoxType::EncvBox;
+        }
         BoxType::Avc1Box
     }
 
     pub fn get_size(&self) -> u64 {
-        HEADER_SIZE + 8 + 70 + self.avcc.box_size()
+        let mut size = HEADER_SIZE + 8 + 70 + self.avcc.box_size();
+        if let Some(ref sinf) = self.sinf {
+            size += sinf.box_size();
+        }
+        size + unknown_boxes_size(&self.unknown_boxes)
     }
 }
 
@@ -92,12 +109,13 @@ impl<R: Read + Seek> ReadBox<&mut R> for Avc1Box {
         let depth = reader.read_u16::<BigEndian>()?;
         reader.read_i16::<BigEndian>()?; // pre-defined
 
+        let mut avcc = None;
+        let mut sinf = None;
+        let mut unknown_boxes = Vec::new();
+
+        let mut current = reader.stream_position()?;
         let end = start + size;
-        loop {
-            let current = reader.stream_position()?;
-            if current >= end {
-                return Err(Error::InvalidData("avcc not found"));
-            }
+        while current < end {
             let header = BoxHeader::read(reader)?;
             let BoxHeader { name, size: s } = header;
             if s > size {
@@ -105,25 +123,34 @@ impl<R: Read + Seek> ReadBox<&mut R> for Avc1Box {
                     "avc1 box contains a box with a larger size than it",
                 ));
             }
-            if name == BoxType::AvcCBox {
-                let avcc = AvcCBox::read_box(reader, s)?;
-
-                skip_bytes_to(reader, start + size)?;
-
-                return Ok(Avc1Box {
-                    data_reference_index,
-                    width,
-                    height,
-                    horizresolution,
-                    vertresolution,
-                    frame_count,
-                    depth,
-                    avcc,
-                });
+            if name == BoxType::AvcCBox && avcc.is_none() {
+                avcc = Some(AvcCBox::read_box(reader, s)?);
+            } else if name == BoxType::SinfBox && sinf.is_none() {
+                sinf = Some(SinfBox::read_box(reader, s)?);
             } else {
-                skip_bytes_to(reader, current + s)?;
+                unknown_boxes.push(read_unknown_box(reader, name, s)?);
             }
+            current = reader.stream_position()?;
         }
+
+        let Some(avcc) = avcc else {
+            return Err(Error::InvalidData("avcc not found"));
+        };
+
+        skip_bytes_to(reader, start + size)?;
+
+        Ok(Avc1Box {
+            data_reference_index,
+            width,
+            height,
+            horizresolution,
+            vertresolution,
+            frame_count,
+            depth,
+            avcc,
+            sinf,
+            unknown_boxes,
+        })
     }
 }
 
@@ -151,6 +178,10 @@ impl<W: Write> WriteBox<&mut W> for Avc1Box {
         writer.write_i16::<BigEndian>(-1)?; // pre-defined
 
         self.avcc.write_box(writer)?;
+        if let Some(ref sinf) = self.sinf {
+            sinf.write_box(writer)?;
+        }
+        write_unknown_boxes(writer, &self.unknown_boxes)?;
 
         Ok(size)
     }
@@ -325,6 +356,12 @@ mod tests {
                     bytes: vec![0x68, 0xEB, 0xE3, 0xCB, 0x22, 0xC0],
                 }],
             },
+            // pasp
+            sinf: None,
+            unknown_boxes: vec![(
+                BoxType::UnknownBox(0x70617370),
+                vec![0, 0, 0, 1, 0, 0, 0, 1],
+            )],
         };
         let mut buf = Vec::new();
         src_box.write_box(&mut buf).unwrap();
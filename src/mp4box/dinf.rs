@@ -5,6 +5,7 @@ use crate::mp4box::*;
 #[derive(Debug, Clone, PartialEq, Eq, Default)]
 pub struct DinfBox {
     pub dref: DrefBox,
+    pub unknown_boxes: Vec<(BoxType, Vec<u8>)>,
 }
 
 impl DinfBox {
@@ -13,7 +14,7 @@ impl DinfBox {
     }
 
     pub fn get_size(&self) -> u64 {
-        HEADER_SIZE + self.dref.box_size()
+        HEADER_SIZE + self.dref.box_size() + unknown_boxes_size(&self.unknown_boxes)
     }
 }
 
@@ -37,6 +38,7 @@ impl<R: Read + Seek> ReadBox<&mut R> for DinfBox {
         let start = box_start(reader)?;
 
         let mut dref = None;
+        let mut unknown_boxes = Vec::new();
 
         let mut current = reader.stream_position()?;
         let end = start + size;
@@ -55,8 +57,7 @@ impl<R: Read + Seek> ReadBox<&mut R> for DinfBox {
                     dref = Some(DrefBox::read_box(reader, s)?);
                 }
                 _ => {
-                    // XXX warn!()
-                    skip_box(reader, s)?;
+                    unknown_boxes.push(read_unknown_box(reader, name, s)?);
                 }
             }
 
@@ -71,6 +72,7 @@ impl<R: Read + Seek> ReadBox<&mut R> for DinfBox {
 
         Ok(DinfBox {
             dref: dref.unwrap(),
+            unknown_boxes,
         })
     }
 }
@@ -80,6 +82,7 @@ impl<W: Write> WriteBox<&mut W> for DinfBox {
         let size = self.box_size();
         BoxHeader::new(self.box_type(), size).write(writer)?;
         self.dref.write_box(writer)?;
+        write_unknown_boxes(writer, &self.unknown_boxes)?;
         Ok(size)
     }
 }
@@ -89,6 +92,7 @@ pub struct DrefBox {
     pub version: u8,
     pub flags: u32,
     pub url: Option<UrlBox>,
+    pub unknown_boxes: Vec<(BoxType, Vec<u8>)>,
 }
 
 impl Default for DrefBox {
@@ -97,6 +101,7 @@ impl Default for DrefBox {
             version: 0,
             flags: 0,
             url: Some(UrlBox::default()),
+            unknown_boxes: Vec::new(),
         }
     }
 }
@@ -111,6 +116,7 @@ impl DrefBox {
         if let Some(ref url) = self.url {
             size += url.box_size();
         }
+        size += unknown_boxes_size(&self.unknown_boxes);
         size
     }
 }
@@ -140,6 +146,7 @@ impl<R: Read + Seek> ReadBox<&mut R> for DrefBox {
         let end = start + size;
 
         let mut url = None;
+        let mut unknown_boxes = Vec::new();
 
         let entry_count = reader.read_u32::<BigEndian>()?;
         for _i in 0..entry_count {
@@ -161,7 +168,7 @@ impl<R: Read + Seek> ReadBox<&mut R> for DrefBox {
                     url = Some(UrlBox::read_box(reader, s)?);
                 }
                 _ => {
-                    skip_box(reader, s)?;
+                    unknown_boxes.push(read_unknown_box(reader, name, s)?);
                 }
             }
 
@@ -174,6 +181,7 @@ impl<R: Read + Seek> ReadBox<&mut R> for DrefBox {
             version,
             flags,
             url,
+            unknown_boxes,
         })
     }
 }
@@ -185,11 +193,13 @@ impl<W: Write> WriteBox<&mut W> for DrefBox {
 
         write_box_header_ext(writer, self.version, self.flags)?;
 
-        writer.write_u32::<BigEndian>(1)?;
+        let entry_count = self.url.is_some() as usize + self.unknown_boxes.len();
+        writer.write_u32::<BigEndian>(entry_count as u32)?;
 
         if let Some(ref url) = self.url {
             url.write_box(writer)?;
         }
+        write_unknown_boxes(writer, &self.unknown_boxes)?;
 
         Ok(size)
     }
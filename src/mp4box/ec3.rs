@@ -0,0 +1,499 @@
+use byteorder::{BigEndian, ReadBytesExt, WriteBytesExt};
+use std::io::{Read, Seek, Write};
+
+use crate::bitreader::BitReader;
+use crate::mp4box::ac3::ac3_sample_rate;
+use crate::mp4box::*;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Ec3Box {
+    pub data_reference_index: u16,
+    pub channelcount: u16,
+    pub samplesize: u16,
+    pub samplerate: FixedPointU16,
+    pub dec3: Dec3Box,
+
+    /// Protection scheme information, if the sample entry is an encrypted
+    /// `enca` entry with this original format.
+    pub sinf: Option<SinfBox>,
+
+    pub unknown_boxes: Vec<(BoxType, Vec<u8>)>,
+}
+
+impl Default for Ec3Box {
+    fn default() -> Self {
+        Self {
+            data_reference_index: 0,
+            channelcount: 2,
+            samplesize: 16,
+            samplerate: FixedPointU16::new(48000),
+            dec3: Dec3Box::default(),
+            sinf: None,
+            unknown_boxes: Vec::new(),
+        }
+    }
+}
+
+impl Ec3Box {
+    pub fn new(config: &Ec3Config) -> Result<Self> {
+        let dec3 = Dec3Box {
+            data_rate: config.data_rate,
+            substreams: config.substreams.clone(),
+            joc_complexity_index: config.joc_complexity_index,
+        };
+        dec3.validate()?;
+        Ok(Self {
+            data_reference_index: 1,
+            channelcount: dec3.channel_layout().channel_count(),
+            samplerate: FixedPointU16::new(dec3.sample_rate().unwrap() as u16),
+            dec3,
+            ..Ec3Box::default()
+        })
+    }
+
+    pub fn get_type(&self) -> BoxType {
+        if self.sinf.is_some() {
+            return BoxType::EncaBox;
+        }
+        BoxType::Ec3Box
+    }
+
+    pub fn get_size(&self) -> u64 {
+        let mut size = HEADER_SIZE + 8 + 20 + self.dec3.box_size();
+        if let Some(ref sinf) = self.sinf {
+            size += sinf.box_size();
+        }
+        size + unknown_boxes_size(&self.unknown_boxes)
+    }
+}
+
+impl Mp4Box for Ec3Box {
+    fn box_type(&self) -> BoxType {
+        self.get_type()
+    }
+
+    fn box_size(&self) -> u64 {
+        self.get_size()
+    }
+
+    fn summary(&self) -> Result<String> {
+        let s = format!(
+            "channel_count={} sample_size={} sample_rate={}",
+            self.channelcount,
+            self.samplesize,
+            self.samplerate.value()
+        );
+        Ok(s)
+    }
+}
+
+impl<R: Read + Seek> ReadBox<&mut R> for Ec3Box {
+    fn read_box(reader: &mut R, size: u64) -> Result<Self> {
+        let start = box_start(reader)?;
+
+        reader.read_u32::<BigEndian>()?; // reserved
+        reader.read_u16::<BigEndian>()?; // reserved
+        let data_reference_index = reader.read_u16::<BigEndian>()?;
+        reader.read_u64::<BigEndian>()?; // reserved
+        let channelcount = reader.read_u16::<BigEndian>()?;
+        let samplesize = reader.read_u16::<BigEndian>()?;
+        reader.read_u32::<BigEndian>()?; // pre-defined, reserved
+        let samplerate = FixedPointU16::new_raw(reader.read_u32::<BigEndian>()?);
+
+        let mut dec3 = None;
+        let mut sinf = None;
+        let mut unknown_boxes = Vec::new();
+
+        let mut current = reader.stream_position()?;
+        let end = start + size;
+        while current < end {
+            let header = BoxHeader::read(reader)?;
+            let BoxHeader { name, size: s } = header;
+            if s > size {
+                return Err(Error::InvalidData(
+                    "ec-3 box contains a box with a larger size than it",
+                ));
+            }
+            if name == BoxType::Dec3Box && dec3.is_none() {
+                dec3 = Some(Dec3Box::read_box(reader, s)?);
+            } else if name == BoxType::SinfBox && sinf.is_none() {
+                sinf = Some(SinfBox::read_box(reader, s)?);
+            } else {
+                unknown_boxes.push(read_unknown_box(reader, name, s)?);
+            }
+            current = reader.stream_position()?;
+        }
+
+        let Some(dec3) = dec3 else {
+            return Err(Error::InvalidData("dec3 not found"));
+        };
+
+        skip_bytes_to(reader, start + size)?;
+
+        Ok(Ec3Box {
+            data_reference_index,
+            channelcount,
+            samplesize,
+            samplerate,
+            dec3,
+            sinf,
+            unknown_boxes,
+        })
+    }
+}
+
+impl<W: Write> WriteBox<&mut W> for Ec3Box {
+    fn write_box(&self, writer: &mut W) -> Result<u64> {
+        let size = self.box_size();
+        BoxHeader::new(self.box_type(), size).write(writer)?;
+
+        writer.write_u32::<BigEndian>(0)?; // reserved
+        writer.write_u16::<BigEndian>(0)?; // reserved
+        writer.write_u16::<BigEndian>(self.data_reference_index)?;
+
+        writer.write_u64::<BigEndian>(0)?; // reserved
+        writer.write_u16::<BigEndian>(self.channelcount)?;
+        writer.write_u16::<BigEndian>(self.samplesize)?;
+        writer.write_u32::<BigEndian>(0)?; // reserved
+        writer.write_u32::<BigEndian>(self.samplerate.raw_value())?;
+
+        self.dec3.write_box(writer)?;
+        if let Some(ref sinf) = self.sinf {
+            sinf.write_box(writer)?;
+        }
+        write_unknown_boxes(writer, &self.unknown_boxes)?;
+
+        Ok(size)
+    }
+}
+
+/// The E-AC-3 specific box, see ETSI TS 102 366 F.6, with the JOC extension
+/// of ETSI TS 103 420.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Dec3Box {
+    /// The data rate of the stream in kbit/s.
+    pub data_rate: u16,
+
+    /// The independent substreams, at least one and at most eight.
+    pub substreams: Vec<Ec3Substream>,
+
+    /// The object complexity of a Dolby Atmos stream using joint object
+    /// coding (JOC), if `flag_ec3_extension_type_a` is set.
+    pub joc_complexity_index: Option<u8>,
+}
+
+/// An independent substream of an E-AC-3 stream and its dependent
+/// substreams.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Ec3Substream {
+    pub fscod: u8,
+    pub bsid: u8,
+
+    /// Whether this substream is an associated service, rather than a main
+    /// service.
+    pub asvc: bool,
+    pub bsmod: u8,
+    pub acmod: u8,
+    pub lfeon: bool,
+
+    /// Number of dependent substreams associated with this substream.
+    pub num_dep_sub: u8,
+
+    /// The channels carried by the dependent substreams in addition to
+    /// those of `acmod`, as a 9 bit mask starting from the Lc/Rc pair in
+    /// the most significant bit. Only present if `num_dep_sub` is not 0.
+    pub chan_loc: u16,
+}
+
+impl Default for Ec3Substream {
+    fn default() -> Self {
+        // 5.1 at 48 kHz.
+        Self {
+            fscod: 0,
+            bsid: 16,
+            asvc: false,
+            bsmod: 0,
+            acmod: 7,
+            lfeon: true,
+            num_dep_sub: 0,
+            chan_loc: 0,
+        }
+    }
+}
+
+impl Default for Dec3Box {
+    fn default() -> Self {
+        Self {
+            data_rate: 640,
+            substreams: vec![Ec3Substream::default()],
+            joc_complexity_index: None,
+        }
+    }
+}
+
+impl Dec3Box {
+    /// The sample rate in Hz, from the first independent substream.
+    pub fn sample_rate(&self) -> Option<u32> {
+        ac3_sample_rate(self.substreams.first()?.fscod)
+    }
+
+    /// The bit rate in bit/s.
+    pub fn bitrate(&self) -> u32 {
+        self.data_rate as u32 * 1000
+    }
+
+    /// The channel layout of the first independent substream, including the
+    /// channels of its dependent substreams.
+    pub fn channel_layout(&self) -> DolbyChannelLayout {
+        // chanmap positions of the chan_loc bits, most significant first.
+        const CHAN_LOC_POSITIONS: [u16; 9] = [5, 6, 7, 8, 9, 10, 11, 12, 14];
+
+        let Some(substream) = self.substreams.first() else {
+            return DolbyChannelLayout(0);
+        };
+        let mut layout = DolbyChannelLayout::from_acmod(substream.acmod, substream.lfeon);
+        if substream.num_dep_sub > 0 {
+            for (i, position) in CHAN_LOC_POSITIONS.iter().enumerate() {
+                if substream.chan_loc & (0x100 >> i) != 0 {
+                    layout.0 |= 0x8000 >> position;
+                }
+            }
+        }
+        layout
+    }
+
+    /// Whether the stream carries Dolby Atmos using joint object coding.
+    pub fn is_atmos(&self) -> bool {
+        self.joc_complexity_index.is_some()
+    }
+
+    fn validate(&self) -> Result<()> {
+        if self.substreams.is_empty() || self.substreams.len() > 8 {
+            return Err(Error::InvalidData(
+                "E-AC-3 must have between one and eight independent substreams",
+            ));
+        }
+        for substream in &self.substreams {
+            if ac3_sample_rate(substream.fscod).is_none()
+                || substream.acmod > 7
+                || substream.num_dep_sub > 15
+                || substream.chan_loc > 0x1ff
+            {
+                return Err(Error::InvalidData("E-AC-3 substream is invalid"));
+            }
+        }
+        if self.data_rate > 0x1fff {
+            return Err(Error::InvalidData("E-AC-3 data rate is too large"));
+        }
+        Ok(())
+    }
+}
+
+impl Mp4Box for Dec3Box {
+    fn box_type(&self) -> BoxType {
+        BoxType::Dec3Box
+    }
+
+    fn box_size(&self) -> u64 {
+        let mut size = HEADER_SIZE + 2;
+        for substream in &self.substreams {
+            size += if substream.num_dep_sub > 0 { 4 } else { 3 };
+        }
+        if self.joc_complexity_index.is_some() {
+            size += 2;
+        }
+        size
+    }
+
+    fn summary(&self) -> Result<String> {
+        let mut s = format!(
+            "data_rate={} num_ind_sub={}",
+            self.data_rate,
+            self.substreams.len()
+        );
+        if let Some(index) = self.joc_complexity_index {
+            s.push_str(&format!(" joc_complexity_index={}", index));
+        }
+        Ok(s)
+    }
+}
+
+impl<R: Read + Seek> ReadBox<&mut R> for Dec3Box {
+    fn read_box(reader: &mut R, size: u64) -> Result<Self> {
+        let start = box_start(reader)?;
+
+        let mut data = vec![0; size.saturating_sub(HEADER_SIZE) as usize];
+        reader.read_exact(&mut data)?;
+        let mut bits = BitReader::new(&data);
+
+        let data_rate = bits.read_bits(13)? as u16;
+        let num_ind_sub = bits.read_bits(3)? as usize + 1;
+        let mut substreams = Vec::with_capacity(num_ind_sub);
+        for _ in 0..num_ind_sub {
+            let fscod = bits.read_bits(2)? as u8;
+            let bsid = bits.read_bits(5)? as u8;
+            bits.skip_bits(1)?; // reserved
+            let asvc = bits.read_bit()?;
+            let bsmod = bits.read_bits(3)? as u8;
+            let acmod = bits.read_bits(3)? as u8;
+            let lfeon = bits.read_bit()?;
+            bits.skip_bits(3)?; // reserved
+            let num_dep_sub = bits.read_bits(4)? as u8;
+            let chan_loc = if num_dep_sub > 0 {
+                bits.read_bits(9)? as u16
+            } else {
+                bits.skip_bits(1)?; // reserved
+                0
+            };
+            substreams.push(Ec3Substream {
+                fscod,
+                bsid,
+                asvc,
+                bsmod,
+                acmod,
+                lfeon,
+                num_dep_sub,
+                chan_loc,
+            });
+        }
+
+        // Older writers end the box here.
+        let mut joc_complexity_index = None;
+        if bits.bits_left() >= 16 {
+            bits.skip_bits(7)?; // reserved
+            if bits.read_bit()? {
+                joc_complexity_index = Some(bits.read_bits(8)? as u8);
+            }
+        }
+
+        skip_bytes_to(reader, start + size)?;
+
+        Ok(Dec3Box {
+            data_rate,
+            substreams,
+            joc_complexity_index,
+        })
+    }
+}
+
+impl<W: Write> WriteBox<&mut W> for Dec3Box {
+    fn write_box(&self, writer: &mut W) -> Result<u64> {
+        self.validate()?;
+
+        let size = self.box_size();
+        BoxHeader::new(self.box_type(), size).write(writer)?;
+
+        writer.write_u16::<BigEndian>(self.data_rate << 3 | (self.substreams.len() as u16 - 1))?;
+        for substream in &self.substreams {
+            let mut params = (substream.fscod as u32) << 22
+                | (substream.bsid as u32 & 0b11111) << 17
+                | (substream.asvc as u32) << 15
+                | (substream.bsmod as u32 & 0b111) << 12
+                | (substream.acmod as u32) << 9
+                | (substream.lfeon as u32) << 8
+                | (substream.num_dep_sub as u32) << 1;
+            if substream.num_dep_sub > 0 {
+                params |= substream.chan_loc as u32 >> 8;
+            }
+            writer.write_u24::<BigEndian>(params)?;
+            if substream.num_dep_sub > 0 {
+                writer.write_u8(substream.chan_loc as u8)?;
+            }
+        }
+        if let Some(index) = self.joc_complexity_index {
+            writer.write_u8(1)?; // flag_ec3_extension_type_a
+            writer.write_u8(index)?;
+        }
+
+        Ok(size)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mp4box::BoxHeader;
+    use std::io::Cursor;
+
+    #[test]
+    fn test_ec3() {
+        let src_box = Ec3Box::new(&Ec3Config::default()).unwrap();
+        assert_eq!(src_box.channelcount, 6);
+        let mut buf = Vec::new();
+        src_box.write_box(&mut buf).unwrap();
+        assert_eq!(buf.len(), src_box.box_size() as usize);
+
+        let mut reader = Cursor::new(&buf);
+        let header = BoxHeader::read(&mut reader).unwrap();
+        assert_eq!(header.name, BoxType::Ec3Box);
+        assert_eq!(src_box.box_size(), header.size);
+
+        let dst_box = Ec3Box::read_box(&mut reader, header.size).unwrap();
+        assert_eq!(src_box, dst_box);
+    }
+
+    #[test]
+    fn test_dec3_dependent_substreams() {
+        // 7.1 as 5.1 plus a dependent substream with the Lrs/Rrs pair.
+        let src_box = Dec3Box {
+            data_rate: 1024,
+            substreams: vec![
+                Ec3Substream {
+                    num_dep_sub: 1,
+                    chan_loc: 0x080,
+                    ..Ec3Substream::default()
+                },
+                Ec3Substream {
+                    asvc: true,
+                    bsmod: 2,
+                    acmod: 2,
+                    lfeon: false,
+                    ..Ec3Substream::default()
+                },
+            ],
+            joc_complexity_index: None,
+        };
+        let mut buf = Vec::new();
+        src_box.write_box(&mut buf).unwrap();
+        assert_eq!(buf.len(), src_box.box_size() as usize);
+
+        let mut reader = Cursor::new(&buf);
+        let header = BoxHeader::read(&mut reader).unwrap();
+        assert_eq!(header.name, BoxType::Dec3Box);
+        let dst_box = Dec3Box::read_box(&mut reader, header.size).unwrap();
+        assert_eq!(src_box, dst_box);
+        assert_eq!(dst_box.channel_layout(), DolbyChannelLayout(0xFA01));
+        assert_eq!(dst_box.channel_layout().channel_count(), 8);
+        assert!(!dst_box.is_atmos());
+    }
+
+    #[test]
+    fn test_dec3_joc() {
+        // 5.1 at 768 kbit/s with JOC complexity index 16.
+        let data = [
+            0, 0, 0, 15, b'd', b'e', b'c', b'3', 0x18, 0x00, 0x20, 0x0f, 0x00, 0x01, 0x10,
+        ];
+        let mut reader = Cursor::new(&data);
+        let header = BoxHeader::read(&mut reader).unwrap();
+        let dec3 = Dec3Box::read_box(&mut reader, header.size).unwrap();
+        assert_eq!(dec3.bitrate(), 768000);
+        assert_eq!(dec3.sample_rate(), Some(48000));
+        assert_eq!(dec3.substreams[0].bsid, 16);
+        assert_eq!(dec3.channel_layout(), DolbyChannelLayout(0xF801));
+        assert_eq!(dec3.joc_complexity_index, Some(16));
+        assert!(dec3.is_atmos());
+
+        let mut buf = Vec::new();
+        dec3.write_box(&mut buf).unwrap();
+        assert_eq!(buf, data);
+    }
+
+    #[test]
+    fn test_ec3_invalid_substreams() {
+        let config = Ec3Config {
+            substreams: Vec::new(),
+            ..Ec3Config::default()
+        };
+        assert!(Ec3Box::new(&config).is_err());
+    }
+}
@@ -19,6 +19,14 @@ pub struct ElstEntry {
     pub media_rate_fraction: u16,
 }
 
+impl ElstEntry {
+    /// Whether this is an empty edit, which presents no media for its
+    /// duration. Its `media_time` is -1.
+    pub fn is_empty_edit(&self) -> bool {
+        self.media_time as i64 == -1
+    }
+}
+
 impl ElstBox {
     pub fn get_type(&self) -> BoxType {
         BoxType::ElstBox
@@ -87,9 +95,11 @@ impl<R: Read + Seek> ReadBox<&mut R> for ElstBox {
                     reader.read_u64::<BigEndian>()?,
                 )
             } else {
+                // Sign extend media_time, so that empty edits read as -1
+                // regardless of the version.
                 (
                     reader.read_u32::<BigEndian>()? as u64,
-                    reader.read_u32::<BigEndian>()? as u64,
+                    reader.read_i32::<BigEndian>()? as u64,
                 )
             };
 
@@ -191,4 +201,36 @@ mod tests {
         let dst_box = ElstBox::read_box(&mut reader, header.size).unwrap();
         assert_eq!(src_box, dst_box);
     }
+
+    #[test]
+    fn test_elst32_empty_edit() {
+        let src_box = ElstBox {
+            version: 0,
+            flags: 0,
+            entries: vec![
+                ElstEntry {
+                    segment_duration: 1000,
+                    media_time: u64::MAX,
+                    media_rate: 1,
+                    media_rate_fraction: 0,
+                },
+                ElstEntry {
+                    segment_duration: 634634,
+                    media_time: 1024,
+                    media_rate: 1,
+                    media_rate_fraction: 0,
+                },
+            ],
+        };
+        let mut buf = Vec::new();
+        src_box.write_box(&mut buf).unwrap();
+        assert_eq!(buf.len(), src_box.box_size() as usize);
+
+        let mut reader = Cursor::new(&buf);
+        let header = BoxHeader::read(&mut reader).unwrap();
+        let dst_box = ElstBox::read_box(&mut reader, header.size).unwrap();
+        assert_eq!(src_box, dst_box);
+        assert!(dst_box.entries[0].is_empty_edit());
+        assert!(!dst_box.entries[1].is_empty_edit());
+    }
 }
@@ -0,0 +1,443 @@
+use byteorder::{BigEndian, ReadBytesExt, WriteBytesExt};
+use std::io::{Read, Seek, Write};
+
+use crate::mp4box::*;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FlacBox {
+    pub data_reference_index: u16,
+    pub channelcount: u16,
+    pub samplesize: u16,
+    pub samplerate: FixedPointU16,
+    pub dfla: DflaBox,
+
+    /// Protection scheme information, if the sample entry is an encrypted
+    /// `enca` entry with this original format.
+    pub sinf: Option<SinfBox>,
+
+    pub unknown_boxes: Vec<(BoxType, Vec<u8>)>,
+}
+
+impl Default for FlacBox {
+    fn default() -> Self {
+        Self {
+            data_reference_index: 0,
+            channelcount: 2,
+            samplesize: 16,
+            samplerate: FixedPointU16::new(48000),
+            dfla: DflaBox::default(),
+            sinf: None,
+            unknown_boxes: Vec::new(),
+        }
+    }
+}
+
+impl FlacBox {
+    pub fn new(config: &FlacConfig) -> Result<Self> {
+        let stream_info = &config.stream_info;
+        if !(1..=655350).contains(&stream_info.sample_rate)
+            || !(1..=8).contains(&stream_info.channels)
+            || !(4..=32).contains(&stream_info.bits_per_sample)
+        {
+            return Err(Error::InvalidData("FLAC STREAMINFO is invalid"));
+        }
+        if config
+            .metadata_blocks
+            .iter()
+            .any(|block| block.block_type == FlacMetadataBlock::STREAMINFO)
+        {
+            return Err(Error::InvalidData("FLAC STREAMINFO is given twice"));
+        }
+        Ok(Self {
+            data_reference_index: 1,
+            channelcount: stream_info.channels as u16,
+            samplesize: stream_info.bits_per_sample as u16,
+            // Sample rates that do not fit are only given in STREAMINFO.
+            samplerate: FixedPointU16::new(u16::try_from(stream_info.sample_rate).unwrap_or(0)),
+            dfla: DflaBox {
+                version: 0,
+                flags: 0,
+                stream_info: stream_info.clone(),
+                metadata_blocks: config.metadata_blocks.clone(),
+            },
+            ..FlacBox::default()
+        })
+    }
+
+    pub fn get_type(&self) -> BoxType {
+        if self.sinf.is_some() {
+            return BoxType::EncaBox;
+        }
+        BoxType::FlacBox
+    }
+
+    pub fn get_size(&self) -> u64 {
+        let mut size = HEADER_SIZE + 8 + 20 + self.dfla.box_size();
+        if let Some(ref sinf) = self.sinf {
+            size += sinf.box_size();
+        }
+        size + unknown_boxes_size(&self.unknown_boxes)
+    }
+}
+
+impl Mp4Box for FlacBox {
+    fn box_type(&self) -> BoxType {
+        self.get_type()
+    }
+
+    fn box_size(&self) -> u64 {
+        self.get_size()
+    }
+
+    fn summary(&self) -> Result<String> {
+        let s = format!(
+            "channel_count={} sample_size={} sample_rate={}",
+            self.channelcount,
+            self.samplesize,
+            self.samplerate.value()
+        );
+        Ok(s)
+    }
+}
+
+impl<R: Read + Seek> ReadBox<&mut R> for FlacBox {
+    fn read_box(reader: &mut R, size: u64) -> Result<Self> {
+        let start = box_start(reader)?;
+
+        reader.read_u32::<BigEndian>()?; // reserved
+        reader.read_u16::<BigEndian>()?; // reserved
+        let data_reference_index = reader.read_u16::<BigEndian>()?;
+        reader.read_u64::<BigEndian>()?; // reserved
+        let channelcount = reader.read_u16::<BigEndian>()?;
+        let samplesize = reader.read_u16::<BigEndian>()?;
+        reader.read_u32::<BigEndian>()?; // pre-defined, reserved
+        let samplerate = FixedPointU16::new_raw(reader.read_u32::<BigEndian>()?);
+
+        let mut dfla = None;
+        let mut sinf = None;
+        let mut unknown_boxes = Vec::new();
+
+        let mut current = reader.stream_position()?;
+        let end = start + size;
+        while current < end {
+            let header = BoxHeader::read(reader)?;
+            let BoxHeader { name, size: s } = header;
+            if s > size {
+                return Err(Error::InvalidData(
+                    "fLaC box contains a box with a larger size than it",
+                ));
+            }
+            if name == BoxType::DflaBox && dfla.is_none() {
+                dfla = Some(DflaBox::read_box(reader, s)?);
+            } else if name == BoxType::SinfBox && sinf.is_none() {
+                sinf = Some(SinfBox::read_box(reader, s)?);
+            } else {
+                unknown_boxes.push(read_unknown_box(reader, name, s)?);
+            }
+            current = reader.stream_position()?;
+        }
+
+        let Some(dfla) = dfla else {
+            return Err(Error::InvalidData("dfla not found"));
+        };
+
+        skip_bytes_to(reader, start + size)?;
+
+        Ok(FlacBox {
+            data_reference_index,
+            channelcount,
+            samplesize,
+            samplerate,
+            dfla,
+            sinf,
+            unknown_boxes,
+        })
+    }
+}
+
+impl<W: Write> WriteBox<&mut W> for FlacBox {
+    fn write_box(&self, writer: &mut W) -> Result<u64> {
+        let size = self.box_size();
+        BoxHeader::new(self.box_type(), size).write(writer)?;
+
+        writer.write_u32::<BigEndian>(0)?; // reserved
+        writer.write_u16::<BigEndian>(0)?; // reserved
+        writer.write_u16::<BigEndian>(self.data_reference_index)?;
+
+        writer.write_u64::<BigEndian>(0)?; // reserved
+        writer.write_u16::<BigEndian>(self.channelcount)?;
+        writer.write_u16::<BigEndian>(self.samplesize)?;
+        writer.write_u32::<BigEndian>(0)?; // reserved
+        writer.write_u32::<BigEndian>(self.samplerate.raw_value())?;
+
+        self.dfla.write_box(writer)?;
+        if let Some(ref sinf) = self.sinf {
+            sinf.write_box(writer)?;
+        }
+        write_unknown_boxes(writer, &self.unknown_boxes)?;
+
+        Ok(size)
+    }
+}
+
+/// The FLAC specific box, see Encapsulation of FLAC in ISO Base Media File
+/// Format 3.3.2.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct DflaBox {
+    pub version: u8,
+    pub flags: u32,
+    pub stream_info: FlacStreamInfo,
+
+    /// The metadata blocks following STREAMINFO, such as SEEKTABLE or
+    /// VORBIS_COMMENT.
+    pub metadata_blocks: Vec<FlacMetadataBlock>,
+}
+
+/// The STREAMINFO metadata block of a FLAC stream.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct FlacStreamInfo {
+    pub min_block_size: u16,
+    pub max_block_size: u16,
+    pub min_frame_size: u32,
+    pub max_frame_size: u32,
+    pub sample_rate: u32,
+    pub channels: u8,
+    pub bits_per_sample: u8,
+
+    /// Number of inter-channel samples, or 0 if unknown.
+    pub total_samples: u64,
+
+    /// MD5 of the unencoded audio data, or zeros if unknown.
+    pub md5: [u8; 16],
+}
+
+impl FlacStreamInfo {
+    const SIZE: usize = 34;
+
+    fn parse(data: &[u8]) -> Result<Self> {
+        let Ok(data) = <&[u8; FlacStreamInfo::SIZE]>::try_from(data) else {
+            return Err(Error::InvalidData("FLAC STREAMINFO has an invalid size"));
+        };
+        let u24 = |bytes: &[u8]| u32::from_be_bytes([0, bytes[0], bytes[1], bytes[2]]);
+        let params = u64::from_be_bytes(data[10..18].try_into().unwrap());
+        Ok(FlacStreamInfo {
+            min_block_size: u16::from_be_bytes([data[0], data[1]]),
+            max_block_size: u16::from_be_bytes([data[2], data[3]]),
+            min_frame_size: u24(&data[4..7]),
+            max_frame_size: u24(&data[7..10]),
+            sample_rate: (params >> 44) as u32,
+            channels: ((params >> 41) & 0b111) as u8 + 1,
+            bits_per_sample: ((params >> 36) & 0b11111) as u8 + 1,
+            total_samples: params & 0xF_FFFF_FFFF,
+            md5: data[18..34].try_into().unwrap(),
+        })
+    }
+
+    fn to_bytes(&self) -> Vec<u8> {
+        let mut data = Vec::with_capacity(FlacStreamInfo::SIZE);
+        data.extend_from_slice(&self.min_block_size.to_be_bytes());
+        data.extend_from_slice(&self.max_block_size.to_be_bytes());
+        data.extend_from_slice(&self.min_frame_size.to_be_bytes()[1..]);
+        data.extend_from_slice(&self.max_frame_size.to_be_bytes()[1..]);
+        let params = (self.sample_rate as u64 & 0xF_FFFF) << 44
+            | (self.channels.saturating_sub(1) as u64 & 0b111) << 41
+            | (self.bits_per_sample.saturating_sub(1) as u64 & 0b11111) << 36
+            | self.total_samples & 0xF_FFFF_FFFF;
+        data.extend_from_slice(&params.to_be_bytes());
+        data.extend_from_slice(&self.md5);
+        data
+    }
+}
+
+/// A FLAC metadata block other than STREAMINFO, kept as it is.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct FlacMetadataBlock {
+    pub block_type: u8,
+    pub data: Vec<u8>,
+}
+
+impl FlacMetadataBlock {
+    pub const STREAMINFO: u8 = 0;
+    pub const PADDING: u8 = 1;
+    pub const APPLICATION: u8 = 2;
+    pub const SEEKTABLE: u8 = 3;
+    pub const VORBIS_COMMENT: u8 = 4;
+    pub const CUESHEET: u8 = 5;
+    pub const PICTURE: u8 = 6;
+}
+
+impl Mp4Box for DflaBox {
+    fn box_type(&self) -> BoxType {
+        BoxType::DflaBox
+    }
+
+    fn box_size(&self) -> u64 {
+        HEADER_SIZE
+            + HEADER_EXT_SIZE
+            + 4
+            + FlacStreamInfo::SIZE as u64
+            + self
+                .metadata_blocks
+                .iter()
+                .map(|block| 4 + block.data.len() as u64)
+                .sum::<u64>()
+    }
+
+    fn summary(&self) -> Result<String> {
+        let s = format!(
+            "sample_rate={} channels={} bits_per_sample={} metadata_blocks={}",
+            self.stream_info.sample_rate,
+            self.stream_info.channels,
+            self.stream_info.bits_per_sample,
+            self.metadata_blocks.len()
+        );
+        Ok(s)
+    }
+}
+
+impl<R: Read + Seek> ReadBox<&mut R> for DflaBox {
+    fn read_box(reader: &mut R, size: u64) -> Result<Self> {
+        let start = box_start(reader)?;
+
+        let (version, flags) = read_box_header_ext(reader)?;
+
+        let mut stream_info = None;
+        let mut metadata_blocks = Vec::new();
+        let end = start + size;
+        while reader.stream_position()? + 4 <= end {
+            let header = reader.read_u32::<BigEndian>()?;
+            let is_last = header & 0x8000_0000 != 0;
+            let block_type = ((header >> 24) & 0x7f) as u8;
+            let length = header & 0xFF_FFFF;
+            if reader.stream_position()? + length as u64 > end {
+                return Err(Error::InvalidData(
+                    "dfLa box contains a metadata block larger than it",
+                ));
+            }
+            let mut data = vec![0; length as usize];
+            reader.read_exact(&mut data)?;
+
+            if stream_info.is_none() {
+                if block_type != FlacMetadataBlock::STREAMINFO {
+                    return Err(Error::InvalidData("dfLa does not start with STREAMINFO"));
+                }
+                stream_info = Some(FlacStreamInfo::parse(&data)?);
+            } else {
+                metadata_blocks.push(FlacMetadataBlock { block_type, data });
+            }
+            if is_last {
+                break;
+            }
+        }
+
+        let Some(stream_info) = stream_info else {
+            return Err(Error::InvalidData("dfLa does not start with STREAMINFO"));
+        };
+
+        skip_bytes_to(reader, start + size)?;
+
+        Ok(DflaBox {
+            version,
+            flags,
+            stream_info,
+            metadata_blocks,
+        })
+    }
+}
+
+impl<W: Write> WriteBox<&mut W> for DflaBox {
+    fn write_box(&self, writer: &mut W) -> Result<u64> {
+        let size = self.box_size();
+        BoxHeader::new(self.box_type(), size).write(writer)?;
+
+        write_box_header_ext(writer, self.version, self.flags)?;
+
+        let blocks = std::iter::once((FlacMetadataBlock::STREAMINFO, self.stream_info.to_bytes()))
+            .chain(
+                self.metadata_blocks
+                    .iter()
+                    .map(|block| (block.block_type, block.data.clone())),
+            );
+        let count = 1 + self.metadata_blocks.len();
+        for (i, (block_type, data)) in blocks.enumerate() {
+            if data.len() > 0xFF_FFFF {
+                return Err(Error::InvalidData("FLAC metadata block is too large"));
+            }
+            let is_last = (i + 1 == count) as u32;
+            writer.write_u32::<BigEndian>(
+                is_last << 31 | (block_type as u32 & 0x7f) << 24 | data.len() as u32,
+            )?;
+            writer.write_all(&data)?;
+        }
+
+        Ok(size)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mp4box::BoxHeader;
+    use std::io::Cursor;
+
+    #[test]
+    fn test_flac() {
+        let src_box = FlacBox::new(&FlacConfig {
+            stream_info: FlacStreamInfo {
+                min_block_size: 4096,
+                max_block_size: 4096,
+                min_frame_size: 14,
+                max_frame_size: 12637,
+                sample_rate: 96000,
+                channels: 2,
+                bits_per_sample: 24,
+                total_samples: 0x1_2345_6789,
+                md5: [0xab; 16],
+            },
+            metadata_blocks: vec![FlacMetadataBlock {
+                block_type: FlacMetadataBlock::VORBIS_COMMENT,
+                data: b"\x09\x00\x00\x00reference\x00\x00\x00\x00".to_vec(),
+            }],
+        })
+        .unwrap();
+        assert_eq!(src_box.samplerate.value(), 0);
+        let mut buf = Vec::new();
+        src_box.write_box(&mut buf).unwrap();
+        assert_eq!(buf.len(), src_box.box_size() as usize);
+
+        let mut reader = Cursor::new(&buf);
+        let header = BoxHeader::read(&mut reader).unwrap();
+        assert_eq!(header.name, BoxType::FlacBox);
+        assert_eq!(src_box.box_size(), header.size);
+
+        let dst_box = FlacBox::read_box(&mut reader, header.size).unwrap();
+        assert_eq!(src_box, dst_box);
+    }
+
+    #[test]
+    fn test_dfla_stream_info() {
+        // 44.1 kHz, stereo, 16 bits, 441000 samples.
+        #[rustfmt::skip]
+        let data = [
+            0x00, 0x00, 0x00, 0x32, b'd', b'f', b'L', b'a', 0, 0, 0, 0,
+            0x80, 0x00, 0x00, 0x22,
+            0x10, 0x00, 0x10, 0x00, 0x00, 0x00, 0x0e, 0x00, 0x31, 0x5d,
+            0x0a, 0xc4, 0x42, 0xf0, 0x00, 0x06, 0xba, 0xa8,
+            0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0,
+        ];
+        let mut reader = Cursor::new(&data);
+        let header = BoxHeader::read(&mut reader).unwrap();
+        assert_eq!(header.name, BoxType::DflaBox);
+        let dfla = DflaBox::read_box(&mut reader, header.size).unwrap();
+        assert_eq!(dfla.stream_info.sample_rate, 44100);
+        assert_eq!(dfla.stream_info.channels, 2);
+        assert_eq!(dfla.stream_info.bits_per_sample, 16);
+        assert_eq!(dfla.stream_info.total_samples, 441000);
+        assert_eq!(dfla.stream_info.max_frame_size, 0x315d);
+        assert!(dfla.metadata_blocks.is_empty());
+
+        let mut buf = Vec::new();
+        dfla.write_box(&mut buf).unwrap();
+        assert_eq!(buf, data);
+    }
+}
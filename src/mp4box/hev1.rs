@@ -1,6 +1,7 @@
 use byteorder::{BigEndian, ReadBytesExt, WriteBytesExt};
 use std::io::{Read, Seek, Write};
 
+use crate::bitreader::{BitReader, nal_unit_rbsp};
 use crate::mp4box::*;
 
 #[derive(Debug, Clone, PartialEq, Eq)]
@@ -13,6 +14,15 @@ pub struct Hev1Box {
     pub frame_count: u16,
     pub depth: u16,
     pub hvcc: HvcCBox,
+
+    /// Whether the entry is a `hev1` or a `hvc1` entry.
+    pub sample_entry: HevcSampleEntry,
+
+    /// Protection scheme information, if the sample entry is an encrypted
+    /// `encv` entry with this original format.
+    pub sinf: Option<SinfBox>,
+
+    pub unknown_boxes: Vec<(BoxType, Vec<u8>)>,
 }
 
 impl Default for Hev1Box {
@@ -26,13 +36,25 @@ impl Default for Hev1Box {
             frame_count: 1,
             depth: 0x0018,
             hvcc: HvcCBox::default(),
+            sample_entry: HevcSampleEntry::Hev1,
+            sinf: None,
+            unknown_boxes: Vec::new(),
         }
     }
 }
 
 impl Hev1Box {
-    pub fn new(config: &HevcConfig) -> Self {
-        Hev1Box {
+    pub fn new(config: &HevcConfig) -> Result<Self> {
+        let mut hvcc = HvcCBox::with_parameter_sets(
+            &config.video_param_set,
+            &config.seq_param_set,
+            &config.pic_param_set,
+        )?;
+        // hvc1 entries keep all parameter sets in the hvcC.
+        for array in hvcc.arrays.iter_mut() {
+            array.completeness = config.sample_entry == HevcSampleEntry::Hvc1;
+        }
+        Ok(Hev1Box {
             data_reference_index: 1,
             width: config.width,
             height: config.height,
@@ -40,16 +62,29 @@ impl Hev1Box {
             vertresolution: FixedPointU16::new(0x48),
             frame_count: 1,
             depth: 0x0018,
-            hvcc: HvcCBox::new(),
-        }
+            hvcc,
+            sample_entry: config.sample_entry,
+            sinf: None,
+            unknown_boxes: Vec::new(),
+        })
     }
 
     pub fn get_type(&self) -> BoxType {
-        BoxType::Hev1Box
+        if self.sinf.is_some() {
+            return BoxType::EncvBox;
+        }
+        match self.sample_entry {
+            HevcSampleEntry::Hev1 => BoxType::Hev1Box,
+            HevcSampleEntry::Hvc1 => BoxType::Hvc1Box,
+        }
     }
 
     pub fn get_size(&self) -> u64 {
-        HEADER_SIZE + 8 + 70 + self.hvcc.box_size()
+        let mut size = HEADER_SIZE + 8 + 70 + self.hvcc.box_size();
+        if let Some(ref sinf) = self.sinf {
+            size += sinf.box_size();
+        }
+        size + unknown_boxes_size(&self.unknown_boxes)
     }
 }
 
@@ -92,31 +127,49 @@ impl<R: Read + Seek> ReadBox<&mut R> for Hev1Box {
         let depth = reader.read_u16::<BigEndian>()?;
         reader.read_i16::<BigEndian>()?; // pre-defined
 
-        let header = BoxHeader::read(reader)?;
-        let BoxHeader { name, size: s } = header;
-        if s > size {
-            return Err(Error::InvalidData(
-                "hev1 box contains a box with a larger size than it",
-            ));
-        }
-        if name == BoxType::HvcCBox {
-            let hvcc = HvcCBox::read_box(reader, s)?;
-
-            skip_bytes_to(reader, start + size)?;
-
-            Ok(Hev1Box {
-                data_reference_index,
-                width,
-                height,
-                horizresolution,
-                vertresolution,
-                frame_count,
-                depth,
-                hvcc,
-            })
-        } else {
-            Err(Error::InvalidData("hvcc not found"))
+        let mut hvcc = None;
+        let mut sinf = None;
+        let mut unknown_boxes = Vec::new();
+
+        let mut current = reader.stream_position()?;
+        let end = start + size;
+        while current < end {
+            let header = BoxHeader::read(reader)?;
+            let BoxHeader { name, size: s } = header;
+            if s > size {
+                return Err(Error::InvalidData(
+                    "hev1 box contains a box with a larger size than it",
+                ));
+            }
+            if name == BoxType::HvcCBox && hvcc.is_none() {
+                hvcc = Some(HvcCBox::read_box(reader, s)?);
+            } else if name == BoxType::SinfBox && sinf.is_none() {
+                sinf = Some(SinfBox::read_box(reader, s)?);
+            } else {
+                unknown_boxes.push(read_unknown_box(reader, name, s)?);
+            }
+            current = reader.stream_position()?;
         }
+
+        let Some(hvcc) = hvcc else {
+            return Err(Error::InvalidData("hvcc not found"));
+        };
+
+        skip_bytes_to(reader, start + size)?;
+
+        Ok(Hev1Box {
+            data_reference_index,
+            width,
+            height,
+            horizresolution,
+            vertresolution,
+            frame_count,
+            depth,
+            hvcc,
+            sample_entry: HevcSampleEntry::Hev1,
+            sinf,
+            unknown_boxes,
+        })
     }
 }
 
@@ -144,6 +197,10 @@ impl<W: Write> WriteBox<&mut W> for Hev1Box {
         writer.write_i16::<BigEndian>(-1)?; // pre-defined
 
         self.hvcc.write_box(writer)?;
+        if let Some(ref sinf) = self.sinf {
+            sinf.write_box(writer)?;
+        }
+        write_unknown_boxes(writer, &self.unknown_boxes)?;
 
         Ok(size)
     }
@@ -178,6 +235,100 @@ impl HvcCBox {
             ..Default::default()
         }
     }
+
+    /// A configuration holding the given VPS, SPS and PPS NAL units, with
+    /// the profile, tier, level, chroma format and bit depths of the SPS.
+    pub fn with_parameter_sets(vps: &[u8], sps: &[u8], pps: &[u8]) -> Result<Self> {
+        // Skip the two byte NAL unit header.
+        let Some(sps_payload) = sps.get(2..) else {
+            return Err(Error::InvalidData("SPS is truncated"));
+        };
+        let rbsp = nal_unit_rbsp(sps_payload);
+        let mut reader = BitReader::new(&rbsp);
+
+        reader.skip_bits(4)?; // sps_video_parameter_set_id
+        let max_sub_layers_minus1 = reader.read_bits(3)? as u8;
+        let temporal_id_nested = reader.read_bit()?;
+
+        // profile_tier_level
+        let general_profile_space = reader.read_bits(2)? as u8;
+        let general_tier_flag = reader.read_bit()?;
+        let general_profile_idc = reader.read_bits(5)? as u8;
+        let general_profile_compatibility_flags = reader.read_bits(32)? as u32;
+        let general_constraint_indicator_flag = reader.read_bits(48)?;
+        let general_level_idc = reader.read_bits(8)? as u8;
+        let mut sub_layers_present = Vec::with_capacity(max_sub_layers_minus1 as usize);
+        for _ in 0..max_sub_layers_minus1 {
+            let profile_present = reader.read_bit()?;
+            let level_present = reader.read_bit()?;
+            sub_layers_present.push((profile_present, level_present));
+        }
+        if max_sub_layers_minus1 > 0 {
+            reader.skip_bits(2 * (8 - max_sub_layers_minus1 as usize))?; // reserved
+        }
+        for (profile_present, level_present) in sub_layers_present {
+            if profile_present {
+                reader.skip_bits(88)?;
+            }
+            if level_present {
+                reader.skip_bits(8)?;
+            }
+        }
+
+        reader.read_ue()?; // sps_seq_parameter_set_id
+        let chroma_format_idc = reader.read_ue()?;
+        if chroma_format_idc == 3 {
+            reader.skip_bits(1)?; // separate_colour_plane_flag
+        }
+        reader.read_ue()?; // pic_width_in_luma_samples
+        reader.read_ue()?; // pic_height_in_luma_samples
+        if reader.read_bit()? {
+            // conformance_window offsets
+            for _ in 0..4 {
+                reader.read_ue()?;
+            }
+        }
+        let bit_depth_luma_minus8 = reader.read_ue()?;
+        let bit_depth_chroma_minus8 = reader.read_ue()?;
+        if chroma_format_idc > 3 || bit_depth_luma_minus8 > 7 || bit_depth_chroma_minus8 > 7 {
+            return Err(Error::InvalidData("SPS has an invalid chroma format"));
+        }
+
+        let array = |nal_unit_type: u8, data: &[u8]| -> Result<HvcCArray> {
+            let size = u16::try_from(data.len())
+                .map_err(|_| Error::InvalidData("parameter set exceeds u16::MAX"))?;
+            Ok(HvcCArray {
+                completeness: false,
+                nal_unit_type,
+                nalus: vec![HvcCArrayNalu {
+                    size,
+                    data: data.to_vec(),
+                }],
+            })
+        };
+
+        Ok(HvcCBox {
+            configuration_version: 1,
+            general_profile_space,
+            general_tier_flag,
+            general_profile_idc,
+            general_profile_compatibility_flags,
+            general_constraint_indicator_flag,
+            general_level_idc,
+            chroma_format_idc: chroma_format_idc as u8,
+            bit_depth_luma_minus8: bit_depth_luma_minus8 as u8,
+            bit_depth_chroma_minus8: bit_depth_chroma_minus8 as u8,
+            num_temporal_layers: max_sub_layers_minus1 + 1,
+            temporal_id_nested,
+            length_size_minus_one: 3,
+            arrays: vec![
+                array(HvcCArray::VPS, vps)?,
+                array(HvcCArray::SPS, sps)?,
+                array(HvcCArray::PPS, pps)?,
+            ],
+            ..HvcCBox::default()
+        })
+    }
 }
 
 impl Mp4Box for HvcCBox {
@@ -232,12 +383,19 @@ pub struct HvcCArray {
     pub nalus: Vec<HvcCArrayNalu>,
 }
 
+impl HvcCArray {
+    /// NAL unit types of the parameter sets.
+    pub const VPS: u8 = 32;
+    pub const SPS: u8 = 33;
+    pub const PPS: u8 = 34;
+}
+
 impl<R: Read + Seek> ReadBox<&mut R> for HvcCBox {
     fn read_box(reader: &mut R, _size: u64) -> Result<Self> {
         let configuration_version = reader.read_u8()?;
         let params = reader.read_u8()?;
-        let general_profile_space = params & 0b11000000 >> 6;
-        let general_tier_flag = (params & 0b00100000 >> 5) > 0;
+        let general_profile_space = (params & 0b11000000) >> 6;
+        let general_tier_flag = (params & 0b00100000) > 0;
         let general_profile_idc = params & 0b00011111;
 
         let general_profile_compatibility_flags = reader.read_u32::<BigEndian>()?;
@@ -251,9 +409,9 @@ impl<R: Read + Seek> ReadBox<&mut R> for HvcCBox {
         let avg_frame_rate = reader.read_u16::<BigEndian>()?;
 
         let params = reader.read_u8()?;
-        let constant_frame_rate = params & 0b11000000 >> 6;
-        let num_temporal_layers = params & 0b00111000 >> 3;
-        let temporal_id_nested = (params & 0b00000100 >> 2) > 0;
+        let constant_frame_rate = (params & 0b11000000) >> 6;
+        let num_temporal_layers = (params & 0b00111000) >> 3;
+        let temporal_id_nested = (params & 0b00000100) > 0;
         let length_size_minus_one = params & 0b000011;
 
         let num_of_arrays = reader.read_u8()?;
@@ -367,6 +525,9 @@ mod tests {
                 configuration_version: 1,
                 ..Default::default()
             },
+            sample_entry: HevcSampleEntry::Hev1,
+            sinf: None,
+            unknown_boxes: vec![],
         };
         let mut buf = Vec::new();
         src_box.write_box(&mut buf).unwrap();
@@ -380,4 +541,43 @@ mod tests {
         let dst_box = Hev1Box::read_box(&mut reader, header.size).unwrap();
         assert_eq!(src_box, dst_box);
     }
+
+    #[test]
+    fn test_hvcc_parameter_sets() {
+        // Main profile, level 3.1, 1280x720 4:2:0 8-bit.
+        let vps = [0x40, 0x01, 0x0c, 0x01];
+        let sps = [
+            0x42, 0x01, 0x01, 0x01, 0x60, 0x00, 0x00, 0x03, 0x00, 0x90, 0x00, 0x00, 0x03, 0x00,
+            0x00, 0x03, 0x00, 0x5d, 0xa0, 0x02, 0x80, 0x80, 0x2d, 0x16, 0x59, 0x59, 0xa4, 0x93,
+            0x2b, 0xc0, 0x5a, 0x70, 0x80, 0x00, 0x01, 0xf4, 0x80, 0x00, 0x30, 0xd4, 0x20,
+        ];
+        let pps = [0x44, 0x01, 0xc1, 0x72, 0xb4, 0x62, 0x40];
+        let src_box = HvcCBox::with_parameter_sets(&vps, &sps, &pps).unwrap();
+        assert_eq!(src_box.general_profile_space, 0);
+        assert!(!src_box.general_tier_flag);
+        assert_eq!(src_box.general_profile_idc, 1);
+        assert_eq!(src_box.general_profile_compatibility_flags, 0x6000_0000);
+        assert_eq!(src_box.general_constraint_indicator_flag, 0x9000_0000_0000);
+        assert_eq!(src_box.general_level_idc, 93);
+        assert_eq!(src_box.chroma_format_idc, 1);
+        assert_eq!(src_box.bit_depth_luma_minus8, 0);
+        assert_eq!(src_box.num_temporal_layers, 1);
+        assert!(src_box.temporal_id_nested);
+        assert_eq!(src_box.length_size_minus_one, 3);
+        let nal_unit_types: Vec<u8> = src_box.arrays.iter().map(|a| a.nal_unit_type).collect();
+        assert_eq!(
+            nal_unit_types,
+            [HvcCArray::VPS, HvcCArray::SPS, HvcCArray::PPS]
+        );
+        assert_eq!(src_box.arrays[1].nalus[0].data, sps);
+
+        let mut buf = Vec::new();
+        src_box.write_box(&mut buf).unwrap();
+        let mut reader = Cursor::new(&buf);
+        let header = BoxHeader::read(&mut reader).unwrap();
+        let dst_box = HvcCBox::read_box(&mut reader, header.size).unwrap();
+        assert_eq!(src_box, dst_box);
+
+        assert!(HvcCBox::with_parameter_sets(&vps, &sps[..10], &pps).is_err());
+    }
 }
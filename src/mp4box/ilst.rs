@@ -10,6 +10,7 @@ use crate::mp4box::*;
 #[derive(Debug, Clone, PartialEq, Eq, Default)]
 pub struct IlstBox {
     pub items: HashMap<MetadataKey, IlstItemBox>,
+    pub unknown_boxes: Vec<(BoxType, Vec<u8>)>,
 }
 
 impl IlstBox {
@@ -22,6 +23,7 @@ impl IlstBox {
         for item in self.items.values() {
             size += item.get_size();
         }
+        size += unknown_boxes_size(&self.unknown_boxes);
         size
     }
 }
@@ -46,6 +48,7 @@ impl<R: Read + Seek> ReadBox<&mut R> for IlstBox {
         let start = box_start(reader)?;
 
         let mut items = HashMap::new();
+        let mut unknown_boxes = Vec::new();
 
         let mut current = reader.stream_position()?;
         let end = start + size;
@@ -73,8 +76,7 @@ impl<R: Read + Seek> ReadBox<&mut R> for IlstBox {
                     items.insert(MetadataKey::Summary, IlstItemBox::read_box(reader, s)?);
                 }
                 _ => {
-                    // XXX warn!()
-                    skip_box(reader, s)?;
+                    unknown_boxes.push(read_unknown_box(reader, name, s)?);
                 }
             }
 
@@ -83,7 +85,10 @@ impl<R: Read + Seek> ReadBox<&mut R> for IlstBox {
 
         skip_bytes_to(reader, start + size)?;
 
-        Ok(IlstBox { items })
+        Ok(IlstBox {
+            items,
+            unknown_boxes,
+        })
     }
 }
 
@@ -101,7 +106,9 @@ impl<W: Write> WriteBox<&mut W> for IlstBox {
             };
             BoxHeader::new(name, value.get_size()).write(writer)?;
             value.data.write_box(writer)?;
+            write_unknown_boxes(writer, &value.unknown_boxes)?;
         }
+        write_unknown_boxes(writer, &self.unknown_boxes)?;
         Ok(size)
     }
 }
@@ -109,11 +116,12 @@ impl<W: Write> WriteBox<&mut W> for IlstBox {
 #[derive(Debug, Clone, PartialEq, Eq, Default)]
 pub struct IlstItemBox {
     pub data: DataBox,
+    pub unknown_boxes: Vec<(BoxType, Vec<u8>)>,
 }
 
 impl IlstItemBox {
     fn get_size(&self) -> u64 {
-        HEADER_SIZE + self.data.box_size()
+        HEADER_SIZE + self.data.box_size() + unknown_boxes_size(&self.unknown_boxes)
     }
 }
 
@@ -122,6 +130,7 @@ impl<R: Read + Seek> ReadBox<&mut R> for IlstItemBox {
         let start = box_start(reader)?;
 
         let mut data = None;
+        let mut unknown_boxes = Vec::new();
 
         let mut current = reader.stream_position()?;
         let end = start + size;
@@ -140,8 +149,7 @@ impl<R: Read + Seek> ReadBox<&mut R> for IlstItemBox {
                     data = Some(DataBox::read_box(reader, s)?);
                 }
                 _ => {
-                    // XXX warn!()
-                    skip_box(reader, s)?;
+                    unknown_boxes.push(read_unknown_box(reader, name, s)?);
                 }
             }
 
@@ -156,12 +164,13 @@ impl<R: Read + Seek> ReadBox<&mut R> for IlstItemBox {
 
         Ok(IlstItemBox {
             data: data.unwrap(),
+            unknown_boxes,
         })
     }
 }
 
 impl<'a> Metadata<'a> for IlstBox {
-    fn title(&self) -> Option<Cow<str>> {
+    fn title(&self) -> Option<Cow<'_, str>> {
         self.items.get(&MetadataKey::Title).map(item_to_str)
     }
 
@@ -173,7 +182,7 @@ impl<'a> Metadata<'a> for IlstBox {
         self.items.get(&MetadataKey::Poster).map(item_to_bytes)
     }
 
-    fn summary(&self) -> Option<Cow<str>> {
+    fn summary(&self) -> Option<Cow<'_, str>> {
         self.items.get(&MetadataKey::Summary).map(item_to_str)
     }
 }
@@ -182,7 +191,7 @@ fn item_to_bytes(item: &IlstItemBox) -> &[u8] {
     &item.data.data
 }
 
-fn item_to_str(item: &IlstItemBox) -> Cow<str> {
+fn item_to_str(item: &IlstItemBox) -> Cow<'_, str> {
     String::from_utf8_lossy(&item.data.data)
 }
 
@@ -207,6 +216,7 @@ mod tests {
                 data_type: DataType::Text,
                 data: b"test_year".to_vec(),
             },
+            unknown_boxes: vec![],
         };
         let src_box = IlstBox {
             items: [
@@ -216,6 +226,7 @@ mod tests {
                 (MetadataKey::Summary, IlstItemBox::default()),
             ]
             .into(),
+            unknown_boxes: vec![(BoxType::UnknownBox(0xa9746f6f), vec![0, 0, 0, 0])],
         };
         let mut buf = Vec::new();
         src_box.write_box(&mut buf).unwrap();
@@ -8,6 +8,7 @@ pub struct MdiaBox {
     pub mdhd: MdhdBox,
     pub hdlr: HdlrBox,
     pub minf: MinfBox,
+    pub unknown_boxes: Vec<(BoxType, Vec<u8>)>,
 }
 
 impl MdiaBox {
@@ -16,7 +17,11 @@ impl MdiaBox {
     }
 
     pub fn get_size(&self) -> u64 {
-        HEADER_SIZE + self.mdhd.box_size() + self.hdlr.box_size() + self.minf.box_size()
+        HEADER_SIZE
+            + self.mdhd.box_size()
+            + self.hdlr.box_size()
+            + self.minf.box_size()
+            + unknown_boxes_size(&self.unknown_boxes)
     }
 }
 
@@ -42,6 +47,7 @@ impl<R: Read + Seek> ReadBox<&mut R> for MdiaBox {
         let mut mdhd = None;
         let mut hdlr = None;
         let mut minf = None;
+        let mut unknown_boxes = Vec::new();
 
         let mut current = reader.stream_position()?;
         let end = start + size;
@@ -66,8 +72,7 @@ impl<R: Read + Seek> ReadBox<&mut R> for MdiaBox {
                     minf = Some(MinfBox::read_box(reader, s)?);
                 }
                 _ => {
-                    // XXX warn!()
-                    skip_box(reader, s)?;
+                    unknown_boxes.push(read_unknown_box(reader, name, s)?);
                 }
             }
 
@@ -90,6 +95,7 @@ impl<R: Read + Seek> ReadBox<&mut R> for MdiaBox {
             mdhd: mdhd.unwrap(),
             hdlr: hdlr.unwrap(),
             minf: minf.unwrap(),
+            unknown_boxes,
         })
     }
 }
@@ -102,6 +108,7 @@ impl<W: Write> WriteBox<&mut W> for MdiaBox {
         self.mdhd.write_box(writer)?;
         self.hdlr.write_box(writer)?;
         self.minf.write_box(writer)?;
+        write_unknown_boxes(writer, &self.unknown_boxes)?;
 
         Ok(size)
     }
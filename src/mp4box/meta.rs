@@ -8,6 +8,7 @@ use crate::mp4box::*;
 pub enum MetaBox {
     Mdir {
         ilst: Option<IlstBox>,
+        unknown_boxes: Vec<(BoxType, Vec<u8>)>,
     },
     Unknown {
         hdlr: HdlrBox,
@@ -25,18 +26,18 @@ impl MetaBox {
     pub fn get_size(&self) -> u64 {
         let mut size = HEADER_SIZE + HEADER_EXT_SIZE;
         match self {
-            Self::Mdir { ilst } => {
+            Self::Mdir {
+                ilst,
+                unknown_boxes,
+            } => {
                 size += HdlrBox::default().box_size();
                 if let Some(ilst) = ilst {
                     size += ilst.box_size();
                 }
+                size += unknown_boxes_size(unknown_boxes);
             }
             Self::Unknown { hdlr, data } => {
-                size += hdlr.box_size()
-                    + data
-                        .iter()
-                        .map(|(_, data)| data.len() as u64 + HEADER_SIZE)
-                        .sum::<u64>()
+                size += hdlr.box_size() + unknown_boxes_size(data);
             }
         }
         size
@@ -108,7 +109,7 @@ impl<R: Read + Seek> ReadBox<&mut R> for MetaBox {
                     hdlr = Some(HdlrBox::read_box(reader, s)?);
                 }
                 _ => {
-                    // XXX warn!()
+                    // Read in the second pass below.
                     skip_box(reader, s)?;
                 }
             }
@@ -128,6 +129,7 @@ impl<R: Read + Seek> ReadBox<&mut R> for MetaBox {
 
         match hdlr.handler_type {
             MDIR => {
+                let mut unknown_boxes = Vec::new();
                 while current < end {
                     // Get box header.
                     let header = BoxHeader::read(reader)?;
@@ -137,16 +139,21 @@ impl<R: Read + Seek> ReadBox<&mut R> for MetaBox {
                         BoxType::IlstBox => {
                             ilst = Some(IlstBox::read_box(reader, s)?);
                         }
-                        _ => {
-                            // XXX warn!()
+                        BoxType::HdlrBox => {
                             skip_box(reader, s)?;
                         }
+                        _ => {
+                            unknown_boxes.push(read_unknown_box(reader, name, s)?);
+                        }
                     }
 
                     current = reader.stream_position()?;
                 }
 
-                Ok(MetaBox::Mdir { ilst })
+                Ok(MetaBox::Mdir {
+                    ilst,
+                    unknown_boxes,
+                })
             }
             _ => {
                 let mut data = Vec::new();
@@ -161,10 +168,7 @@ impl<R: Read + Seek> ReadBox<&mut R> for MetaBox {
                             skip_box(reader, s)?;
                         }
                         _ => {
-                            let mut box_data = vec![0; (s - HEADER_SIZE) as usize];
-                            reader.read_exact(&mut box_data)?;
-
-                            data.push((name, box_data));
+                            data.push(read_unknown_box(reader, name, s)?);
                         }
                     }
 
@@ -194,16 +198,17 @@ impl<W: Write> WriteBox<&mut W> for MetaBox {
         hdlr.write_box(writer)?;
 
         match self {
-            Self::Mdir { ilst } => {
+            Self::Mdir {
+                ilst,
+                unknown_boxes,
+            } => {
                 if let Some(ilst) = ilst {
                     ilst.write_box(writer)?;
                 }
+                write_unknown_boxes(writer, unknown_boxes)?;
             }
             Self::Unknown { data, .. } => {
-                for (box_type, data) in data {
-                    BoxHeader::new(*box_type, data.len() as u64 + HEADER_SIZE).write(writer)?;
-                    writer.write_all(data)?;
-                }
+                write_unknown_boxes(writer, data)?;
             }
         }
         Ok(size)
@@ -218,7 +223,10 @@ mod tests {
 
     #[test]
     fn test_meta_mdir_empty() {
-        let src_box = MetaBox::Mdir { ilst: None };
+        let src_box = MetaBox::Mdir {
+            ilst: None,
+            unknown_boxes: vec![],
+        };
 
         let mut buf = Vec::new();
         src_box.write_box(&mut buf).unwrap();
@@ -237,6 +245,7 @@ mod tests {
     fn test_meta_mdir() {
         let src_box = MetaBox::Mdir {
             ilst: Some(IlstBox::default()),
+            unknown_boxes: vec![(BoxType::UnknownBox(0x6b657973), vec![0, 0, 0, 0])],
         };
 
         let mut buf = Vec::new();
@@ -266,7 +275,11 @@ mod tests {
         assert_eq!(
             meta_box,
             MetaBox::Mdir {
-                ilst: Some(IlstBox::default())
+                ilst: Some(IlstBox {
+                    unknown_boxes: vec![(BoxType::UnknownBox(0xa9746f6f), data[28..93].to_vec())],
+                    ..Default::default()
+                }),
+                unknown_boxes: vec![],
             }
         );
     }
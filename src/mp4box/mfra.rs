@@ -0,0 +1,157 @@
+use std::io::{Read, Seek, Write};
+
+use crate::mp4box::*;
+use crate::mp4box::{mfro::MfroBox, tfra::TfraBox};
+
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct MfraBox {
+    pub tfras: Vec<TfraBox>,
+    pub mfro: MfroBox,
+    pub unknown_boxes: Vec<(BoxType, Vec<u8>)>,
+}
+
+impl MfraBox {
+    pub fn get_type(&self) -> BoxType {
+        BoxType::MfraBox
+    }
+
+    pub fn get_size(&self) -> u64 {
+        HEADER_SIZE
+            + self.tfras.iter().map(|x| x.box_size()).sum::<u64>()
+            + self.mfro.box_size()
+            + unknown_boxes_size(&self.unknown_boxes)
+    }
+
+    /// The `tfra` with the random access samples of a track.
+    pub fn tfra(&self, track_id: u32) -> Option<&TfraBox> {
+        self.tfras.iter().find(|tfra| tfra.track_id == track_id)
+    }
+}
+
+impl Mp4Box for MfraBox {
+    fn box_type(&self) -> BoxType {
+        self.get_type()
+    }
+
+    fn box_size(&self) -> u64 {
+        self.get_size()
+    }
+
+    fn summary(&self) -> Result<String> {
+        let s = format!("tfras={}", self.tfras.len());
+        Ok(s)
+    }
+}
+
+impl<R: Read + Seek> ReadBox<&mut R> for MfraBox {
+    fn read_box(reader: &mut R, size: u64) -> Result<Self> {
+        let start = box_start(reader)?;
+
+        let mut tfras = Vec::new();
+        let mut mfro = None;
+        let mut unknown_boxes = Vec::new();
+
+        let mut current = reader.stream_position()?;
+        let end = start + size;
+        while current < end {
+            // Get box header.
+            let header = BoxHeader::read(reader)?;
+            let BoxHeader { name, size: s } = header;
+            if s > size {
+                return Err(Error::InvalidData(
+                    "mfra box contains a box with a larger size than it",
+                ));
+            }
+
+            match name {
+                BoxType::TfraBox => {
+                    tfras.push(TfraBox::read_box(reader, s)?);
+                }
+                BoxType::MfroBox => {
+                    mfro = Some(MfroBox::read_box(reader, s)?);
+                }
+                _ => {
+                    unknown_boxes.push(read_unknown_box(reader, name, s)?);
+                }
+            }
+
+            current = reader.stream_position()?;
+        }
+
+        let Some(mfro) = mfro else {
+            return Err(Error::BoxNotFound(BoxType::MfroBox));
+        };
+
+        skip_bytes_to(reader, start + size)?;
+
+        Ok(MfraBox {
+            tfras,
+            mfro,
+            unknown_boxes,
+        })
+    }
+}
+
+impl<W: Write> WriteBox<&mut W> for MfraBox {
+    fn write_box(&self, writer: &mut W) -> Result<u64> {
+        let size = self.box_size();
+        BoxHeader::new(self.box_type(), size).write(writer)?;
+
+        for tfra in self.tfras.iter() {
+            tfra.write_box(writer)?;
+        }
+        write_unknown_boxes(writer, &self.unknown_boxes)?;
+        // The mfro has to be the last box, so that it ends the file.
+        self.mfro.write_box(writer)?;
+
+        Ok(size)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mp4box::BoxHeader;
+    use crate::mp4box::tfra::TfraEntry;
+    use std::io::Cursor;
+
+    #[test]
+    fn test_mfra() {
+        let mut src_box = MfraBox {
+            tfras: vec![
+                TfraBox {
+                    track_id: 1,
+                    entries: vec![TfraEntry {
+                        time: 0,
+                        moof_offset: 800,
+                        traf_number: 1,
+                        trun_number: 1,
+                        sample_number: 1,
+                    }],
+                    ..TfraBox::default()
+                },
+                TfraBox {
+                    track_id: 2,
+                    ..TfraBox::default()
+                },
+            ],
+            ..MfraBox::default()
+        };
+        src_box.mfro.size = src_box.box_size() as u32;
+
+        let mut buf = Vec::new();
+        src_box.write_box(&mut buf).unwrap();
+        assert_eq!(buf.len(), src_box.box_size() as usize);
+        assert_eq!(&buf[buf.len() - 4..], (buf.len() as u32).to_be_bytes());
+
+        let mut reader = Cursor::new(&buf);
+        let header = BoxHeader::read(&mut reader).unwrap();
+        assert_eq!(header.name, BoxType::MfraBox);
+        assert_eq!(src_box.box_size(), header.size);
+
+        let dst_box = MfraBox::read_box(&mut reader, header.size).unwrap();
+        assert_eq!(src_box, dst_box);
+        assert_eq!(dst_box.tfra(2).unwrap().track_id, 2);
+        assert!(dst_box.tfra(3).is_none());
+    }
+}
@@ -0,0 +1,68 @@
+use byteorder::{BigEndian, ReadBytesExt, WriteBytesExt};
+use std::io::{Read, Seek, Write};
+
+use crate::mp4box::*;
+
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct MfroBox {
+    pub version: u8,
+    pub flags: u32,
+
+    /// Size of the enclosing `mfra` box, so that it can be found from the end
+    /// of the file.
+    pub size: u32,
+}
+
+impl MfroBox {
+    pub fn get_type(&self) -> BoxType {
+        BoxType::MfroBox
+    }
+
+    pub fn get_size(&self) -> u64 {
+        HEADER_SIZE + HEADER_EXT_SIZE + 4
+    }
+}
+
+impl Mp4Box for MfroBox {
+    fn box_type(&self) -> BoxType {
+        self.get_type()
+    }
+
+    fn box_size(&self) -> u64 {
+        self.get_size()
+    }
+
+    fn summary(&self) -> Result<String> {
+        let s = format!("size={}", self.size);
+        Ok(s)
+    }
+}
+
+impl<R: Read + Seek> ReadBox<&mut R> for MfroBox {
+    fn read_box(reader: &mut R, size: u64) -> Result<Self> {
+        let start = box_start(reader)?;
+
+        let (version, flags) = read_box_header_ext(reader)?;
+        let mfra_size = reader.read_u32::<BigEndian>()?;
+
+        skip_bytes_to(reader, start + size)?;
+
+        Ok(MfroBox {
+            version,
+            flags,
+            size: mfra_size,
+        })
+    }
+}
+
+impl<W: Write> WriteBox<&mut W> for MfroBox {
+    fn write_box(&self, writer: &mut W) -> Result<u64> {
+        let size = self.box_size();
+        BoxHeader::new(self.box_type(), size).write(writer)?;
+
+        write_box_header_ext(writer, self.version, self.flags)?;
+        writer.write_u32::<BigEndian>(self.size)?;
+
+        Ok(size)
+    }
+}
@@ -9,6 +9,7 @@ pub struct MinfBox {
     pub smhd: Option<SmhdBox>,
     pub dinf: DinfBox,
     pub stbl: StblBox,
+    pub unknown_boxes: Vec<(BoxType, Vec<u8>)>,
 }
 
 impl MinfBox {
@@ -26,6 +27,7 @@ impl MinfBox {
         }
         size += self.dinf.box_size();
         size += self.stbl.box_size();
+        size += unknown_boxes_size(&self.unknown_boxes);
         size
     }
 }
@@ -53,6 +55,7 @@ impl<R: Read + Seek> ReadBox<&mut R> for MinfBox {
         let mut smhd = None;
         let mut dinf = None;
         let mut stbl = None;
+        let mut unknown_boxes = Vec::new();
 
         let mut current = reader.stream_position()?;
         let end = start + size;
@@ -80,8 +83,7 @@ impl<R: Read + Seek> ReadBox<&mut R> for MinfBox {
                     stbl = Some(StblBox::read_box(reader, s)?);
                 }
                 _ => {
-                    // XXX warn!()
-                    skip_box(reader, s)?;
+                    unknown_boxes.push(read_unknown_box(reader, name, s)?);
                 }
             }
 
@@ -102,6 +104,7 @@ impl<R: Read + Seek> ReadBox<&mut R> for MinfBox {
             smhd,
             dinf: dinf.unwrap(),
             stbl: stbl.unwrap(),
+            unknown_boxes,
         })
     }
 }
@@ -119,6 +122,7 @@ impl<W: Write> WriteBox<&mut W> for MinfBox {
         }
         self.dinf.write_box(writer)?;
         self.stbl.write_box(writer)?;
+        write_unknown_boxes(writer, &self.unknown_boxes)?;
 
         Ok(size)
     }
@@ -3,6 +3,7 @@
 //! * [ISO/IEC 14496-12](https://en.wikipedia.org/wiki/MPEG-4_Part_14) - ISO Base Media File Format (QuickTime, MPEG-4, etc)
 //! * [ISO/IEC 14496-14](https://en.wikipedia.org/wiki/MPEG-4_Part_14) - MP4 file format
 //! * ISO/IEC 14496-17 - Streaming text format
+//! * ISO/IEC 23001-7 - Common encryption (CENC)
 //! * [ISO 23009-1](https://www.iso.org/standard/79329.html) -Dynamic adaptive streaming over HTTP (DASH)
 //!
 //! http://developer.apple.com/documentation/QuickTime/QTFF/index.html
@@ -26,9 +27,26 @@
 //!                 stbl
 //!                     stsd
 //!                         avc1
+//!                         av01
 //!                         hev1
+//!                         hvc1
 //!                         mp4a
+//!                         Opus
+//!                             dOps
+//!                         fLaC
+//!                             dfLa
+//!                         ac-3
+//!                             dac3
+//!                         ec-3
+//!                             dec3
 //!                         tx3g
+//!                         encv
+//!                         enca
+//!                             sinf
+//!                                 frma
+//!                                 schm
+//!                                 schi
+//!                                     tenc
 //!                     stts
 //!                     stsc
 //!                     stsz
@@ -36,6 +54,12 @@
 //!                     stco
 //!                     co64
 //!                     ctts
+//!                     sgpd
+//!                     sbgp
+//!                     sdtp
+//!                     subs
+//!                     saiz
+//!                     saio
 //!                 dinf
 //!                     dref
 //!                 smhd
@@ -45,15 +69,28 @@
 //!     mvex
 //!         mehd
 //!         trex
+//!     pssh
 //! emsg
+//! sidx
 //! moof
 //!     mfhd
+//!     pssh
 //!     traf
 //!         tfhd
 //!         tfdt
 //!         trun
+//!         sgpd
+//!         sbgp
+//!         sdtp
+//!         subs
+//!         senc
+//!         saiz
+//!         saio
 //! mdat
 //! free
+//! mfra
+//!     tfra
+//!     mfro
 //!
 
 use byteorder::{BigEndian, ReadBytesExt, WriteBytesExt};
@@ -62,14 +99,18 @@ use std::io::{Read, Seek, SeekFrom, Write};
 
 use crate::*;
 
+pub(crate) mod ac3;
+pub(crate) mod av01;
 pub(crate) mod avc1;
 pub(crate) mod co64;
 pub(crate) mod ctts;
 pub(crate) mod data;
 pub(crate) mod dinf;
+pub(crate) mod ec3;
 pub(crate) mod edts;
 pub(crate) mod elst;
 pub(crate) mod emsg;
+pub(crate) mod flac;
 pub(crate) mod ftyp;
 pub(crate) mod hdlr;
 pub(crate) mod hev1;
@@ -79,12 +120,24 @@ pub(crate) mod mdia;
 pub(crate) mod mehd;
 pub(crate) mod meta;
 pub(crate) mod mfhd;
+pub(crate) mod mfra;
+pub(crate) mod mfro;
 pub(crate) mod minf;
 pub(crate) mod moof;
 pub(crate) mod moov;
 pub(crate) mod mp4a;
 pub(crate) mod mvex;
 pub(crate) mod mvhd;
+pub(crate) mod opus;
+pub(crate) mod pssh;
+pub(crate) mod saio;
+pub(crate) mod saiz;
+pub(crate) mod sbgp;
+pub(crate) mod sdtp;
+pub(crate) mod senc;
+pub(crate) mod sgpd;
+pub(crate) mod sidx;
+pub(crate) mod sinf;
 pub(crate) mod smhd;
 pub(crate) mod stbl;
 pub(crate) mod stco;
@@ -93,8 +146,11 @@ pub(crate) mod stsd;
 pub(crate) mod stss;
 pub(crate) mod stsz;
 pub(crate) mod stts;
+pub(crate) mod subs;
+pub(crate) mod tenc;
 pub(crate) mod tfdt;
 pub(crate) mod tfhd;
+pub(crate) mod tfra;
 pub(crate) mod tkhd;
 pub(crate) mod traf;
 pub(crate) mod trak;
@@ -106,29 +162,45 @@ pub(crate) mod vmhd;
 pub(crate) mod vp09;
 pub(crate) mod vpcc;
 
-pub use avc1::Avc1Box;
+pub use ac3::{Ac3Box, Dac3Box};
+pub use av01::{Av01Box, Av1CBox};
+pub use avc1::{Avc1Box, AvcCBox};
 pub use co64::Co64Box;
 pub use ctts::CttsBox;
 pub use data::DataBox;
-pub use dinf::DinfBox;
+pub use dinf::{DinfBox, DrefBox, UrlBox};
+pub use ec3::{Dec3Box, Ec3Box, Ec3Substream};
 pub use edts::EdtsBox;
-pub use elst::ElstBox;
+pub use elst::{ElstBox, ElstEntry};
 pub use emsg::EmsgBox;
+pub use flac::{DflaBox, FlacBox, FlacMetadataBlock, FlacStreamInfo};
 pub use ftyp::FtypBox;
 pub use hdlr::HdlrBox;
-pub use hev1::Hev1Box;
+pub use hev1::{Hev1Box, HvcCArray, HvcCArrayNalu, HvcCBox};
 pub use ilst::IlstBox;
 pub use mdhd::MdhdBox;
 pub use mdia::MdiaBox;
 pub use mehd::MehdBox;
 pub use meta::MetaBox;
 pub use mfhd::MfhdBox;
+pub use mfra::MfraBox;
+pub use mfro::MfroBox;
 pub use minf::MinfBox;
 pub use moof::MoofBox;
 pub use moov::MoovBox;
-pub use mp4a::Mp4aBox;
+pub use mp4a::{EsdsBox, Mp4aBox};
 pub use mvex::MvexBox;
 pub use mvhd::MvhdBox;
+pub use opus::{DopsBox, OpusBox, OpusChannelMapping};
+pub use pssh::PsshBox;
+pub use saio::SaioBox;
+pub use saiz::SaizBox;
+pub use sbgp::{SbgpBox, SbgpEntry};
+pub use sdtp::SdtpBox;
+pub use senc::{SencBox, SencEntry, SencSubsample};
+pub use sgpd::{SampleGroupEntry, SgpdBox};
+pub use sidx::{SidxBox, SidxReference, Subsegment};
+pub use sinf::{FrmaBox, SchiBox, SchmBox, SinfBox};
 pub use smhd::SmhdBox;
 pub use stbl::StblBox;
 pub use stco::StcoBox;
@@ -137,8 +209,11 @@ pub use stsd::StsdBox;
 pub use stss::StssBox;
 pub use stsz::StszBox;
 pub use stts::SttsBox;
+pub use subs::{SubsBox, SubsEntry, SubsSubsample};
+pub use tenc::TencBox;
 pub use tfdt::TfdtBox;
 pub use tfhd::TfhdBox;
+pub use tfra::{TfraBox, TfraEntry};
 pub use tkhd::TkhdBox;
 pub use traf::TrafBox;
 pub use trak::TrakBox;
@@ -193,7 +268,11 @@ boxtype! {
     MehdBox => 0x6d656864,
     TrexBox => 0x74726578,
     EmsgBox => 0x656d7367,
+    SidxBox => 0x73696478,
     MoofBox => 0x6d6f6f66,
+    MfraBox => 0x6d667261,
+    TfraBox => 0x74667261,
+    MfroBox => 0x6d66726f,
     TkhdBox => 0x746b6864,
     TfhdBox => 0x74666864,
     TfdtBox => 0x74666474,
@@ -207,6 +286,13 @@ boxtype! {
     StblBox => 0x7374626c,
     StsdBox => 0x73747364,
     SttsBox => 0x73747473,
+    SgpdBox => 0x73677064,
+    SbgpBox => 0x73626770,
+    SdtpBox => 0x73647470,
+    SubsBox => 0x73756273,
+    SaizBox => 0x7361697a,
+    SaioBox => 0x7361696f,
+    SencBox => 0x73656e63,
     CttsBox => 0x63747473,
     StssBox => 0x73747373,
     StscBox => 0x73747363,
@@ -225,12 +311,23 @@ boxtype! {
     Avc1Box => 0x61766331,
     AvcCBox => 0x61766343,
     Hev1Box => 0x68657631,
+    Hvc1Box => 0x68766331,
     HvcCBox => 0x68766343,
     Mp4aBox => 0x6d703461,
     EsdsBox => 0x65736473,
+    OpusBox => 0x4f707573,
+    DopsBox => 0x644f7073,
+    FlacBox => 0x664c6143,
+    DflaBox => 0x64664c61,
+    Ac3Box  => 0x61632d33,
+    Dac3Box => 0x64616333,
+    Ec3Box  => 0x65632d33,
+    Dec3Box => 0x64656333,
     Tx3gBox => 0x74783367,
     VpccBox => 0x76706343,
     Vp09Box => 0x76703039,
+    Av01Box => 0x61763031,
+    Av1CBox => 0x61763143,
     DataBox => 0x64617461,
     IlstBox => 0x696c7374,
     NameBox => 0xa96e616d,
@@ -238,7 +335,15 @@ boxtype! {
     CovrBox => 0x636f7672,
     DescBox => 0x64657363,
     WideBox => 0x77696465,
-    WaveBox => 0x77617665
+    WaveBox => 0x77617665,
+    EncvBox => 0x656e6376,
+    EncaBox => 0x656e6361,
+    SinfBox => 0x73696e66,
+    FrmaBox => 0x66726d61,
+    SchmBox => 0x7363686d,
+    SchiBox => 0x73636869,
+    TencBox => 0x74656e63,
+    PsshBox => 0x70737368
 }
 
 pub trait Mp4Box: Sized {
@@ -351,6 +456,39 @@ pub fn skip_box<S: Seek>(seeker: &mut S, size: u64) -> Result<()> {
     Ok(())
 }
 
+/// Read the payload of a box that is not parsed, so it can be written back
+/// unchanged with [write_unknown_boxes].
+pub fn read_unknown_box<R: Read + Seek>(
+    reader: &mut R,
+    name: BoxType,
+    size: u64,
+) -> Result<(BoxType, Vec<u8>)> {
+    let payload_size = size
+        .checked_sub(HEADER_SIZE)
+        .ok_or(Error::InvalidData("box size is smaller than its header"))?;
+    let mut payload = Vec::new();
+    reader.take(payload_size).read_to_end(&mut payload)?;
+    if payload.len() as u64 != payload_size {
+        return Err(Error::InvalidData("box is truncated"));
+    }
+    Ok((name, payload))
+}
+
+pub fn unknown_boxes_size(boxes: &[(BoxType, Vec<u8>)]) -> u64 {
+    boxes
+        .iter()
+        .map(|(_, payload)| HEADER_SIZE + payload.len() as u64)
+        .sum()
+}
+
+pub fn write_unknown_boxes<W: Write>(writer: &mut W, boxes: &[(BoxType, Vec<u8>)]) -> Result<u64> {
+    for (name, payload) in boxes.iter() {
+        BoxHeader::new(*name, HEADER_SIZE + payload.len() as u64).write(writer)?;
+        writer.write_all(payload)?;
+    }
+    Ok(unknown_boxes_size(boxes))
+}
+
 pub fn write_zeros<W: Write>(writer: &mut W, size: u64) -> Result<()> {
     for _ in 0..size {
         writer.write_u8(0)?;
@@ -7,6 +7,11 @@ use crate::mp4box::{mfhd::MfhdBox, traf::TrafBox};
 pub struct MoofBox {
     pub mfhd: MfhdBox,
     pub trafs: Vec<TrafBox>,
+
+    /// Protection system specific data that applies from this fragment on.
+    pub psshs: Vec<PsshBox>,
+
+    pub unknown_boxes: Vec<(BoxType, Vec<u8>)>,
 }
 
 impl MoofBox {
@@ -16,9 +21,13 @@ impl MoofBox {
 
     pub fn get_size(&self) -> u64 {
         let mut size = HEADER_SIZE + self.mfhd.box_size();
+        for pssh in self.psshs.iter() {
+            size += pssh.box_size();
+        }
         for traf in self.trafs.iter() {
             size += traf.box_size();
         }
+        size += unknown_boxes_size(&self.unknown_boxes);
         size
     }
 }
@@ -44,6 +53,8 @@ impl<R: Read + Seek> ReadBox<&mut R> for MoofBox {
 
         let mut mfhd = None;
         let mut trafs = Vec::new();
+        let mut psshs = Vec::new();
+        let mut unknown_boxes = Vec::new();
 
         let mut current = reader.stream_position()?;
         let end = start + size;
@@ -65,9 +76,11 @@ impl<R: Read + Seek> ReadBox<&mut R> for MoofBox {
                     let traf = TrafBox::read_box(reader, s)?;
                     trafs.push(traf);
                 }
+                BoxType::PsshBox => {
+                    psshs.push(PsshBox::read_box(reader, s)?);
+                }
                 _ => {
-                    // XXX warn!()
-                    skip_box(reader, s)?;
+                    unknown_boxes.push(read_unknown_box(reader, name, s)?);
                 }
             }
             current = reader.stream_position()?;
@@ -82,6 +95,8 @@ impl<R: Read + Seek> ReadBox<&mut R> for MoofBox {
         Ok(MoofBox {
             mfhd: mfhd.unwrap(),
             trafs,
+            psshs,
+            unknown_boxes,
         })
     }
 }
@@ -92,9 +107,13 @@ impl<W: Write> WriteBox<&mut W> for MoofBox {
         BoxHeader::new(self.box_type(), size).write(writer)?;
 
         self.mfhd.write_box(writer)?;
+        for pssh in self.psshs.iter() {
+            pssh.write_box(writer)?;
+        }
         for traf in self.trafs.iter() {
             traf.write_box(writer)?;
         }
+        write_unknown_boxes(writer, &self.unknown_boxes)?;
         Ok(0)
     }
 }
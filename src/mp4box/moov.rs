@@ -11,6 +11,11 @@ pub struct MoovBox {
     pub mvex: Option<MvexBox>,
     pub traks: Vec<TrakBox>,
     pub udta: Option<UdtaBox>,
+
+    /// Protection system specific data of the encrypted tracks.
+    pub psshs: Vec<PsshBox>,
+
+    pub unknown_boxes: Vec<(BoxType, Vec<u8>)>,
 }
 
 impl MoovBox {
@@ -23,12 +28,19 @@ impl MoovBox {
         for trak in self.traks.iter() {
             size += trak.box_size();
         }
+        if let Some(mvex) = &self.mvex {
+            size += mvex.box_size();
+        }
         if let Some(meta) = &self.meta {
             size += meta.box_size();
         }
         if let Some(udta) = &self.udta {
             size += udta.box_size();
         }
+        for pssh in self.psshs.iter() {
+            size += pssh.box_size();
+        }
+        size += unknown_boxes_size(&self.unknown_boxes);
         size
     }
 }
@@ -57,6 +69,8 @@ impl<R: Read + Seek> ReadBox<&mut R> for MoovBox {
         let mut udta = None;
         let mut mvex = None;
         let mut traks = Vec::new();
+        let mut psshs = Vec::new();
+        let mut unknown_boxes = Vec::new();
 
         let mut current = reader.stream_position()?;
         let end = start + size;
@@ -87,9 +101,11 @@ impl<R: Read + Seek> ReadBox<&mut R> for MoovBox {
                 BoxType::UdtaBox => {
                     udta = Some(UdtaBox::read_box(reader, s)?);
                 }
+                BoxType::PsshBox => {
+                    psshs.push(PsshBox::read_box(reader, s)?);
+                }
                 _ => {
-                    // XXX warn!()
-                    skip_box(reader, s)?;
+                    unknown_boxes.push(read_unknown_box(reader, name, s)?);
                 }
             }
 
@@ -108,6 +124,8 @@ impl<R: Read + Seek> ReadBox<&mut R> for MoovBox {
             udta,
             mvex,
             traks,
+            psshs,
+            unknown_boxes,
         })
     }
 }
@@ -118,15 +136,22 @@ impl<W: Write> WriteBox<&mut W> for MoovBox {
         BoxHeader::new(self.box_type(), size).write(writer)?;
 
         self.mvhd.write_box(writer)?;
+        for pssh in self.psshs.iter() {
+            pssh.write_box(writer)?;
+        }
         for trak in self.traks.iter() {
             trak.write_box(writer)?;
         }
+        if let Some(mvex) = &self.mvex {
+            mvex.write_box(writer)?;
+        }
         if let Some(meta) = &self.meta {
             meta.write_box(writer)?;
         }
         if let Some(udta) = &self.udta {
             udta.write_box(writer)?;
         }
+        write_unknown_boxes(writer, &self.unknown_boxes)?;
         Ok(0)
     }
 }
@@ -141,10 +166,33 @@ mod tests {
     fn test_moov() {
         let src_box = MoovBox {
             mvhd: MvhdBox::default(),
-            mvex: None, // XXX mvex is not written currently
+            mvex: Some(MvexBox {
+                mehd: None,
+                trexs: vec![
+                    TrexBox {
+                        track_id: 1,
+                        default_sample_description_index: 1,
+                        ..TrexBox::default()
+                    },
+                    TrexBox {
+                        track_id: 2,
+                        default_sample_description_index: 1,
+                        default_sample_duration: 1024,
+                        ..TrexBox::default()
+                    },
+                ],
+                unknown_boxes: vec![],
+            }),
             traks: vec![],
             meta: Some(MetaBox::default()),
             udta: Some(UdtaBox::default()),
+            psshs: vec![PsshBox {
+                version: 1,
+                system_id: PsshBox::COMMON_SYSTEM_ID,
+                kids: vec![[1; 16]],
+                ..PsshBox::default()
+            }],
+            unknown_boxes: vec![(BoxType::UnknownBox(0x75756964), vec![1, 2, 3, 4])],
         };
 
         let mut buf = Vec::new();
@@ -1,5 +1,5 @@
 use byteorder::{BigEndian, ReadBytesExt, WriteBytesExt};
-use std::io::{Read, Seek, Write};
+use std::io::{Cursor, Read, Seek, Write};
 
 use crate::mp4box::*;
 
@@ -9,7 +9,25 @@ pub struct Mp4aBox {
     pub channelcount: u16,
     pub samplesize: u16,
     pub samplerate: FixedPointU16,
+
+    /// The QuickTime sound sample description version, 0 in ISO files.
+    pub version: u16,
+
+    /// The fields that follow `samplerate` in version 1 and 2 QuickTime
+    /// sound sample descriptions, 16 and 36 bytes long.
+    pub qt_fields: Vec<u8>,
+
     pub esds: Option<EsdsBox>,
+
+    /// Whether `esds` was read from a QuickTime `wave` box, which is kept in
+    /// `unknown_boxes` and so already holds it.
+    pub esds_in_wave: bool,
+
+    /// Protection scheme information, if the sample entry is an encrypted
+    /// `enca` entry with this original format.
+    pub sinf: Option<SinfBox>,
+
+    pub unknown_boxes: Vec<(BoxType, Vec<u8>)>,
 }
 
 impl Default for Mp4aBox {
@@ -19,7 +37,12 @@ impl Default for Mp4aBox {
             channelcount: 2,
             samplesize: 16,
             samplerate: FixedPointU16::new(48000),
+            version: 0,
+            qt_fields: Vec::new(),
             esds: Some(EsdsBox::default()),
+            esds_in_wave: false,
+            sinf: None,
+            unknown_boxes: Vec::new(),
         }
     }
 }
@@ -31,19 +54,33 @@ impl Mp4aBox {
             channelcount: config.chan_conf as u16,
             samplesize: 16,
             samplerate: FixedPointU16::new(config.freq_index.freq() as u16),
+            version: 0,
+            qt_fields: Vec::new(),
             esds: Some(EsdsBox::new(config)),
+            esds_in_wave: false,
+            sinf: None,
+            unknown_boxes: Vec::new(),
         }
     }
 
     pub fn get_type(&self) -> BoxType {
+        if self.sinf.is_some() {
+            return BoxType::EncaBox;
+        }
         BoxType::Mp4aBox
     }
 
     pub fn get_size(&self) -> u64 {
-        let mut size = HEADER_SIZE + 8 + 20;
-        if let Some(ref esds) = self.esds {
+        let mut size = HEADER_SIZE + 8 + 20 + self.qt_fields.len() as u64;
+        if let Some(ref esds) = self.esds
+            && !self.esds_in_wave
+        {
             size += esds.box_size();
         }
+        if let Some(ref sinf) = self.sinf {
+            size += sinf.box_size();
+        }
+        size += unknown_boxes_size(&self.unknown_boxes);
         size
     }
 }
@@ -83,20 +120,20 @@ impl<R: Read + Seek> ReadBox<&mut R> for Mp4aBox {
         reader.read_u32::<BigEndian>()?; // pre-defined, reserved
         let samplerate = FixedPointU16::new_raw(reader.read_u32::<BigEndian>()?);
 
-        if version == 1 {
-            // Skip QTFF
-            reader.read_u64::<BigEndian>()?;
-            reader.read_u64::<BigEndian>()?;
-        }
+        let mut qt_fields = match version {
+            1 => vec![0; 16],
+            2 => vec![0; 36],
+            _ => Vec::new(),
+        };
+        reader.read_exact(&mut qt_fields)?;
 
         // Find esds in mp4a or wave
         let mut esds = None;
+        let mut esds_in_wave = false;
+        let mut sinf = None;
+        let mut unknown_boxes = Vec::new();
         let end = start + size;
-        loop {
-            let current = reader.stream_position()?;
-            if current >= end {
-                break;
-            }
+        while reader.stream_position()? < end {
             let header = BoxHeader::read(reader)?;
             let BoxHeader { name, size: s } = header;
             if s > size {
@@ -104,15 +141,21 @@ impl<R: Read + Seek> ReadBox<&mut R> for Mp4aBox {
                     "mp4a box contains a box with a larger size than it",
                 ));
             }
-            if name == BoxType::EsdsBox {
+            if name == BoxType::EsdsBox && esds.is_none() {
                 esds = Some(EsdsBox::read_box(reader, s)?);
-                break;
+            } else if name == BoxType::SinfBox && sinf.is_none() {
+                sinf = Some(SinfBox::read_box(reader, s)?);
             } else if name == BoxType::WaveBox {
-                // Typically contains frma, mp4a, esds, and a terminator atom
+                // Typically contains frma, mp4a, esds, and a terminator atom.
+                // It is kept as it is, with its esds parsed as well.
+                let wave = read_unknown_box(reader, name, s)?;
+                if esds.is_none() {
+                    esds = read_wave_esds(&wave.1)?;
+                    esds_in_wave = esds.is_some();
+                }
+                unknown_boxes.push(wave);
             } else {
-                // Skip boxes
-                let skip_to = current + s;
-                skip_bytes_to(reader, skip_to)?;
+                unknown_boxes.push(read_unknown_box(reader, name, s)?);
             }
         }
 
@@ -123,11 +166,34 @@ impl<R: Read + Seek> ReadBox<&mut R> for Mp4aBox {
             channelcount,
             samplesize,
             samplerate,
+            version,
+            qt_fields,
             esds,
+            esds_in_wave,
+            sinf,
+            unknown_boxes,
         })
     }
 }
 
+/// The esds in the payload of a QuickTime `wave` box.
+fn read_wave_esds(wave: &[u8]) -> Result<Option<EsdsBox>> {
+    let mut reader = Cursor::new(wave);
+    while reader.position() + HEADER_SIZE <= wave.len() as u64 {
+        let current = reader.position();
+        let BoxHeader { name, size } = BoxHeader::read(&mut reader)?;
+        // The terminator atom has a size of 8 and a type of 0.
+        if size < HEADER_SIZE || current + size > wave.len() as u64 {
+            break;
+        }
+        if name == BoxType::EsdsBox {
+            return Ok(Some(EsdsBox::read_box(&mut reader, size)?));
+        }
+        skip_bytes_to(&mut reader, current + size)?;
+    }
+    Ok(None)
+}
+
 impl<W: Write> WriteBox<&mut W> for Mp4aBox {
     fn write_box(&self, writer: &mut W) -> Result<u64> {
         let size = self.box_size();
@@ -137,15 +203,24 @@ impl<W: Write> WriteBox<&mut W> for Mp4aBox {
         writer.write_u16::<BigEndian>(0)?; // reserved
         writer.write_u16::<BigEndian>(self.data_reference_index)?;
 
-        writer.write_u64::<BigEndian>(0)?; // reserved
+        writer.write_u16::<BigEndian>(self.version)?;
+        writer.write_u16::<BigEndian>(0)?; // reserved
+        writer.write_u32::<BigEndian>(0)?; // reserved
         writer.write_u16::<BigEndian>(self.channelcount)?;
         writer.write_u16::<BigEndian>(self.samplesize)?;
         writer.write_u32::<BigEndian>(0)?; // reserved
         writer.write_u32::<BigEndian>(self.samplerate.raw_value())?;
+        writer.write_all(&self.qt_fields)?;
 
-        if let Some(ref esds) = self.esds {
+        if let Some(ref esds) = self.esds
+            && !self.esds_in_wave
+        {
             esds.write_box(writer)?;
         }
+        if let Some(ref sinf) = self.sinf {
+            sinf.write_box(writer)?;
+        }
+        write_unknown_boxes(writer, &self.unknown_boxes)?;
 
         Ok(size)
     }
@@ -513,17 +588,16 @@ fn get_chan_conf<R: Read + Seek>(
     freq_index: u8,
     extended_profile: bool,
 ) -> Result<u8> {
-    let chan_conf;
-    if freq_index == 15 {
+    let chan_conf = if freq_index == 15 {
         // Skip the 24 bit sample rate
         let sample_rate = reader.read_u24::<BigEndian>()?;
-        chan_conf = ((sample_rate >> 4) & 0x0F) as u8;
+        ((sample_rate >> 4) & 0x0F) as u8
     } else if extended_profile {
         let byte_c = reader.read_u8()?;
-        chan_conf = (byte_b & 1) | (byte_c & 0xE0);
+        (byte_b & 1) | (byte_c & 0xE0)
     } else {
-        chan_conf = (byte_b >> 3) & 0x0F;
-    }
+        (byte_b >> 3) & 0x0F
+    };
 
     Ok(chan_conf)
 }
@@ -534,14 +608,14 @@ impl<R: Read + Seek> ReadDesc<&mut R> for DecoderSpecificDescriptor {
         let byte_b = reader.read_u8()?;
         let profile = get_audio_object_type(byte_a, byte_b);
         let freq_index;
-        let chan_conf;
-        if profile > 31 {
+
+        let chan_conf = if profile > 31 {
             freq_index = (byte_b >> 1) & 0x0F;
-            chan_conf = get_chan_conf(reader, byte_b, freq_index, true)?;
+            get_chan_conf(reader, byte_b, freq_index, true)?
         } else {
             freq_index = ((byte_a & 0x07) << 1) + (byte_b >> 7);
-            chan_conf = get_chan_conf(reader, byte_b, freq_index, false)?;
-        }
+            get_chan_conf(reader, byte_b, freq_index, false)?
+        };
 
         Ok(DecoderSpecificDescriptor {
             profile,
@@ -613,6 +687,8 @@ mod tests {
             channelcount: 2,
             samplesize: 16,
             samplerate: FixedPointU16::new(48000),
+            version: 0,
+            qt_fields: Vec::new(),
             esds: Some(EsdsBox {
                 version: 0,
                 flags: 0,
@@ -634,6 +710,10 @@ mod tests {
                     sl_config: SLConfigDescriptor::default(),
                 },
             }),
+            esds_in_wave: false,
+            // btrt
+            sinf: None,
+            unknown_boxes: vec![(BoxType::UnknownBox(0x62747274), vec![0; 12])],
         };
         let mut buf = Vec::new();
         src_box.write_box(&mut buf).unwrap();
@@ -648,6 +728,39 @@ mod tests {
         assert_eq!(src_box, dst_box);
     }
 
+    #[test]
+    fn test_mp4a_quicktime_wave() {
+        let mut esds = Vec::new();
+        EsdsBox::default().write_box(&mut esds).unwrap();
+        let mut wave = Vec::new();
+        wave.extend(b"\x00\x00\x00\x0cfrmamp4a");
+        wave.extend(&esds);
+        wave.extend(b"\x00\x00\x00\x08\x00\x00\x00\x00");
+
+        let src_box = Mp4aBox {
+            version: 1,
+            qt_fields: vec![0, 0, 4, 0, 0, 0, 0, 1, 0, 0, 0, 2, 0, 0, 0, 2],
+            esds: None,
+            unknown_boxes: vec![(BoxType::WaveBox, wave)],
+            ..Mp4aBox::default()
+        };
+        let mut buf = Vec::new();
+        src_box.write_box(&mut buf).unwrap();
+        assert_eq!(buf.len(), src_box.box_size() as usize);
+
+        let mut reader = Cursor::new(&buf);
+        let header = BoxHeader::read(&mut reader).unwrap();
+        let dst_box = Mp4aBox::read_box(&mut reader, header.size).unwrap();
+        assert_eq!(dst_box.esds, Some(EsdsBox::default()));
+        assert!(dst_box.esds_in_wave);
+        assert_eq!(dst_box.qt_fields, src_box.qt_fields);
+
+        // The wave box and the QuickTime fields are written back as read.
+        let mut written = Vec::new();
+        dst_box.write_box(&mut written).unwrap();
+        assert_eq!(written, buf);
+    }
+
     #[test]
     fn test_mp4a_no_esds() {
         let src_box = Mp4aBox {
@@ -655,7 +768,12 @@ mod tests {
             channelcount: 2,
             samplesize: 16,
             samplerate: FixedPointU16::new(48000),
+            version: 0,
+            qt_fields: Vec::new(),
             esds: None,
+            esds_in_wave: false,
+            sinf: None,
+            unknown_boxes: vec![],
         };
         let mut buf = Vec::new();
         src_box.write_box(&mut buf).unwrap();
@@ -6,16 +6,25 @@ use crate::mp4box::{mehd::MehdBox, trex::TrexBox};
 #[derive(Debug, Clone, PartialEq, Eq, Default)]
 pub struct MvexBox {
     pub mehd: Option<MehdBox>,
-    pub trex: TrexBox,
+    pub trexs: Vec<TrexBox>,
+    pub unknown_boxes: Vec<(BoxType, Vec<u8>)>,
 }
 
 impl MvexBox {
     pub fn get_type(&self) -> BoxType {
-        BoxType::MdiaBox
+        BoxType::MvexBox
     }
 
     pub fn get_size(&self) -> u64 {
-        HEADER_SIZE + self.mehd.as_ref().map(|x| x.box_size()).unwrap_or(0) + self.trex.box_size()
+        HEADER_SIZE
+            + self.mehd.as_ref().map(|x| x.box_size()).unwrap_or(0)
+            + self.trexs.iter().map(|x| x.box_size()).sum::<u64>()
+            + unknown_boxes_size(&self.unknown_boxes)
+    }
+
+    /// The `trex` with the fragment defaults of a track.
+    pub fn trex(&self, track_id: u32) -> Option<&TrexBox> {
+        self.trexs.iter().find(|trex| trex.track_id == track_id)
     }
 }
 
@@ -39,7 +48,8 @@ impl<R: Read + Seek> ReadBox<&mut R> for MvexBox {
         let start = box_start(reader)?;
 
         let mut mehd = None;
-        let mut trex = None;
+        let mut trexs = Vec::new();
+        let mut unknown_boxes = Vec::new();
 
         let mut current = reader.stream_position()?;
         let end = start + size;
@@ -58,18 +68,17 @@ impl<R: Read + Seek> ReadBox<&mut R> for MvexBox {
                     mehd = Some(MehdBox::read_box(reader, s)?);
                 }
                 BoxType::TrexBox => {
-                    trex = Some(TrexBox::read_box(reader, s)?);
+                    trexs.push(TrexBox::read_box(reader, s)?);
                 }
                 _ => {
-                    // XXX warn!()
-                    skip_box(reader, s)?;
+                    unknown_boxes.push(read_unknown_box(reader, name, s)?);
                 }
             }
 
             current = reader.stream_position()?;
         }
 
-        if trex.is_none() {
+        if trexs.is_empty() {
             return Err(Error::BoxNotFound(BoxType::TrexBox));
         }
 
@@ -77,7 +86,8 @@ impl<R: Read + Seek> ReadBox<&mut R> for MvexBox {
 
         Ok(MvexBox {
             mehd,
-            trex: trex.unwrap(),
+            trexs,
+            unknown_boxes,
         })
     }
 }
@@ -90,7 +100,10 @@ impl<W: Write> WriteBox<&mut W> for MvexBox {
         if let Some(mehd) = &self.mehd {
             mehd.write_box(writer)?;
         }
-        self.trex.write_box(writer)?;
+        for trex in self.trexs.iter() {
+            trex.write_box(writer)?;
+        }
+        write_unknown_boxes(writer, &self.unknown_boxes)?;
 
         Ok(size)
     }
@@ -0,0 +1,365 @@
+use byteorder::{BigEndian, ReadBytesExt, WriteBytesExt};
+use std::io::{Read, Seek, Write};
+
+use crate::mp4box::*;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OpusBox {
+    pub data_reference_index: u16,
+    pub channelcount: u16,
+    pub samplesize: u16,
+    pub samplerate: FixedPointU16,
+    pub dops: DopsBox,
+
+    /// Protection scheme information, if the sample entry is an encrypted
+    /// `enca` entry with this original format.
+    pub sinf: Option<SinfBox>,
+
+    pub unknown_boxes: Vec<(BoxType, Vec<u8>)>,
+}
+
+impl Default for OpusBox {
+    fn default() -> Self {
+        Self {
+            data_reference_index: 0,
+            channelcount: 2,
+            samplesize: 16,
+            samplerate: FixedPointU16::new(48000),
+            dops: DopsBox::default(),
+            sinf: None,
+            unknown_boxes: Vec::new(),
+        }
+    }
+}
+
+impl OpusBox {
+    pub fn new(config: &OpusConfig) -> Result<Self> {
+        match config.channel_mapping {
+            None if config.channel_mapping_family == 0 && matches!(config.channel_count, 1 | 2) => {
+            }
+            Some(ref mapping)
+                if config.channel_mapping_family != 0
+                    && mapping.channel_mapping.len() == config.channel_count as usize => {}
+            _ => {
+                return Err(Error::InvalidData(
+                    "Opus channel mapping does not match the channel count",
+                ));
+            }
+        }
+        Ok(Self {
+            data_reference_index: 1,
+            channelcount: config.channel_count as u16,
+            dops: DopsBox {
+                version: 0,
+                output_channel_count: config.channel_count,
+                pre_skip: config.pre_skip,
+                input_sample_rate: config.input_sample_rate,
+                output_gain: config.output_gain,
+                channel_mapping_family: config.channel_mapping_family,
+                channel_mapping: config.channel_mapping.clone(),
+            },
+            ..OpusBox::default()
+        })
+    }
+
+    pub fn get_type(&self) -> BoxType {
+        if self.sinf.is_some() {
+            return BoxType::EncaBox;
+        }
+        BoxType::OpusBox
+    }
+
+    pub fn get_size(&self) -> u64 {
+        let mut size = HEADER_SIZE + 8 + 20 + self.dops.box_size();
+        if let Some(ref sinf) = self.sinf {
+            size += sinf.box_size();
+        }
+        size + unknown_boxes_size(&self.unknown_boxes)
+    }
+}
+
+impl Mp4Box for OpusBox {
+    fn box_type(&self) -> BoxType {
+        self.get_type()
+    }
+
+    fn box_size(&self) -> u64 {
+        self.get_size()
+    }
+
+    fn summary(&self) -> Result<String> {
+        let s = format!(
+            "channel_count={} sample_size={} sample_rate={}",
+            self.channelcount,
+            self.samplesize,
+            self.samplerate.value()
+        );
+        Ok(s)
+    }
+}
+
+impl<R: Read + Seek> ReadBox<&mut R> for OpusBox {
+    fn read_box(reader: &mut R, size: u64) -> Result<Self> {
+        let start = box_start(reader)?;
+
+        reader.read_u32::<BigEndian>()?; // reserved
+        reader.read_u16::<BigEndian>()?; // reserved
+        let data_reference_index = reader.read_u16::<BigEndian>()?;
+        reader.read_u64::<BigEndian>()?; // reserved
+        let channelcount = reader.read_u16::<BigEndian>()?;
+        let samplesize = reader.read_u16::<BigEndian>()?;
+        reader.read_u32::<BigEndian>()?; // pre-defined, reserved
+        let samplerate = FixedPointU16::new_raw(reader.read_u32::<BigEndian>()?);
+
+        let mut dops = None;
+        let mut sinf = None;
+        let mut unknown_boxes = Vec::new();
+
+        let mut current = reader.stream_position()?;
+        let end = start + size;
+        while current < end {
+            let header = BoxHeader::read(reader)?;
+            let BoxHeader { name, size: s } = header;
+            if s > size {
+                return Err(Error::InvalidData(
+                    "Opus box contains a box with a larger size than it",
+                ));
+            }
+            if name == BoxType::DopsBox && dops.is_none() {
+                dops = Some(DopsBox::read_box(reader, s)?);
+            } else if name == BoxType::SinfBox && sinf.is_none() {
+                sinf = Some(SinfBox::read_box(reader, s)?);
+            } else {
+                unknown_boxes.push(read_unknown_box(reader, name, s)?);
+            }
+            current = reader.stream_position()?;
+        }
+
+        let Some(dops) = dops else {
+            return Err(Error::InvalidData("dops not found"));
+        };
+
+        skip_bytes_to(reader, start + size)?;
+
+        Ok(OpusBox {
+            data_reference_index,
+            channelcount,
+            samplesize,
+            samplerate,
+            dops,
+            sinf,
+            unknown_boxes,
+        })
+    }
+}
+
+impl<W: Write> WriteBox<&mut W> for OpusBox {
+    fn write_box(&self, writer: &mut W) -> Result<u64> {
+        let size = self.box_size();
+        BoxHeader::new(self.box_type(), size).write(writer)?;
+
+        writer.write_u32::<BigEndian>(0)?; // reserved
+        writer.write_u16::<BigEndian>(0)?; // reserved
+        writer.write_u16::<BigEndian>(self.data_reference_index)?;
+
+        writer.write_u64::<BigEndian>(0)?; // reserved
+        writer.write_u16::<BigEndian>(self.channelcount)?;
+        writer.write_u16::<BigEndian>(self.samplesize)?;
+        writer.write_u32::<BigEndian>(0)?; // reserved
+        writer.write_u32::<BigEndian>(self.samplerate.raw_value())?;
+
+        self.dops.write_box(writer)?;
+        if let Some(ref sinf) = self.sinf {
+            sinf.write_box(writer)?;
+        }
+        write_unknown_boxes(writer, &self.unknown_boxes)?;
+
+        Ok(size)
+    }
+}
+
+/// The Opus specific box, see Encapsulation of Opus in ISO Base Media File
+/// Format 4.3.2.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct DopsBox {
+    pub version: u8,
+    pub output_channel_count: u8,
+
+    /// Number of samples at 48 kHz to discard from the decoder output at
+    /// the start of the stream.
+    pub pre_skip: u16,
+
+    pub input_sample_rate: u32,
+
+    /// Gain to apply to the decoder output, in Q7.8 dB.
+    pub output_gain: i16,
+
+    pub channel_mapping_family: u8,
+
+    /// Present if the channel mapping family is not 0.
+    pub channel_mapping: Option<OpusChannelMapping>,
+}
+
+/// The channel mapping table of a `dOps` box.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct OpusChannelMapping {
+    pub stream_count: u8,
+    pub coupled_count: u8,
+
+    /// The decoded channel of each output channel.
+    pub channel_mapping: Vec<u8>,
+}
+
+impl Mp4Box for DopsBox {
+    fn box_type(&self) -> BoxType {
+        BoxType::DopsBox
+    }
+
+    fn box_size(&self) -> u64 {
+        let mut size = HEADER_SIZE + 11;
+        if let Some(ref mapping) = self.channel_mapping {
+            size += 2 + mapping.channel_mapping.len() as u64;
+        }
+        size
+    }
+
+    fn summary(&self) -> Result<String> {
+        let s = format!(
+            "output_channel_count={} pre_skip={} input_sample_rate={} channel_mapping_family={}",
+            self.output_channel_count,
+            self.pre_skip,
+            self.input_sample_rate,
+            self.channel_mapping_family
+        );
+        Ok(s)
+    }
+}
+
+impl<R: Read + Seek> ReadBox<&mut R> for DopsBox {
+    fn read_box(reader: &mut R, size: u64) -> Result<Self> {
+        let start = box_start(reader)?;
+
+        let version = reader.read_u8()?;
+        if version != 0 {
+            return Err(Error::InvalidData("dOps has an unsupported version"));
+        }
+        let output_channel_count = reader.read_u8()?;
+        let pre_skip = reader.read_u16::<BigEndian>()?;
+        let input_sample_rate = reader.read_u32::<BigEndian>()?;
+        let output_gain = reader.read_i16::<BigEndian>()?;
+        let channel_mapping_family = reader.read_u8()?;
+        let channel_mapping = if channel_mapping_family != 0 {
+            let stream_count = reader.read_u8()?;
+            let coupled_count = reader.read_u8()?;
+            let mut channel_mapping = vec![0; output_channel_count as usize];
+            reader.read_exact(&mut channel_mapping)?;
+            Some(OpusChannelMapping {
+                stream_count,
+                coupled_count,
+                channel_mapping,
+            })
+        } else {
+            None
+        };
+
+        skip_bytes_to(reader, start + size)?;
+
+        Ok(DopsBox {
+            version,
+            output_channel_count,
+            pre_skip,
+            input_sample_rate,
+            output_gain,
+            channel_mapping_family,
+            channel_mapping,
+        })
+    }
+}
+
+impl<W: Write> WriteBox<&mut W> for DopsBox {
+    fn write_box(&self, writer: &mut W) -> Result<u64> {
+        let size = self.box_size();
+        BoxHeader::new(self.box_type(), size).write(writer)?;
+
+        writer.write_u8(self.version)?;
+        writer.write_u8(self.output_channel_count)?;
+        writer.write_u16::<BigEndian>(self.pre_skip)?;
+        writer.write_u32::<BigEndian>(self.input_sample_rate)?;
+        writer.write_i16::<BigEndian>(self.output_gain)?;
+        writer.write_u8(self.channel_mapping_family)?;
+        if let Some(ref mapping) = self.channel_mapping {
+            writer.write_u8(mapping.stream_count)?;
+            writer.write_u8(mapping.coupled_count)?;
+            writer.write_all(&mapping.channel_mapping)?;
+        }
+
+        Ok(size)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mp4box::BoxHeader;
+    use std::io::Cursor;
+
+    #[test]
+    fn test_opus() {
+        let src_box = OpusBox::new(&OpusConfig {
+            channel_count: 2,
+            pre_skip: 312,
+            input_sample_rate: 44100,
+            output_gain: -256,
+            channel_mapping_family: 0,
+            channel_mapping: None,
+        })
+        .unwrap();
+        let mut buf = Vec::new();
+        src_box.write_box(&mut buf).unwrap();
+        assert_eq!(buf.len(), src_box.box_size() as usize);
+
+        let mut reader = Cursor::new(&buf);
+        let header = BoxHeader::read(&mut reader).unwrap();
+        assert_eq!(header.name, BoxType::OpusBox);
+        assert_eq!(src_box.box_size(), header.size);
+
+        let dst_box = OpusBox::read_box(&mut reader, header.size).unwrap();
+        assert_eq!(src_box, dst_box);
+    }
+
+    #[test]
+    fn test_dops_channel_mapping() {
+        let src_box = DopsBox {
+            version: 0,
+            output_channel_count: 6,
+            pre_skip: 312,
+            input_sample_rate: 48000,
+            output_gain: 0,
+            channel_mapping_family: 1,
+            channel_mapping: Some(OpusChannelMapping {
+                stream_count: 4,
+                coupled_count: 2,
+                channel_mapping: vec![0, 4, 1, 2, 3, 5],
+            }),
+        };
+        let mut buf = Vec::new();
+        src_box.write_box(&mut buf).unwrap();
+        assert_eq!(buf.len(), src_box.box_size() as usize);
+
+        let mut reader = Cursor::new(&buf);
+        let header = BoxHeader::read(&mut reader).unwrap();
+        assert_eq!(header.name, BoxType::DopsBox);
+        let dst_box = DopsBox::read_box(&mut reader, header.size).unwrap();
+        assert_eq!(src_box, dst_box);
+    }
+
+    #[test]
+    fn test_opus_invalid_channel_mapping() {
+        let config = OpusConfig {
+            channel_count: 6,
+            channel_mapping_family: 1,
+            ..OpusConfig::default()
+        };
+        assert!(OpusBox::new(&config).is_err());
+    }
+}
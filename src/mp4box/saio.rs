@@ -0,0 +1,185 @@
+use byteorder::{BigEndian, ReadBytesExt, WriteBytesExt};
+use std::io::{Read, Seek, Write};
+
+use crate::mp4box::saiz::write_aux_info_type;
+use crate::mp4box::*;
+
+/// Offsets of the sample auxiliary information of a track or track fragment,
+/// see ISO/IEC 14496-12 8.7.9.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct SaioBox {
+    pub version: u8,
+    pub flags: u32,
+
+    /// Type of the auxiliary information, usually the protection scheme.
+    /// Stored if bit 0 of `flags` is set.
+    pub aux_info_type: Option<FourCC>,
+    pub aux_info_type_parameter: u32,
+
+    /// Either a single offset of the information of all samples, or one
+    /// offset per chunk (or `trun` in a track fragment).
+    ///
+    /// In a `stbl` the offsets are file offsets, in a `traf` they are
+    /// relative to the base data offset of the fragment, as for a `trun`.
+    pub offsets: Vec<u64>,
+}
+
+impl SaioBox {
+    pub fn get_type(&self) -> BoxType {
+        BoxType::SaioBox
+    }
+
+    pub fn get_size(&self) -> u64 {
+        let mut size = HEADER_SIZE + HEADER_EXT_SIZE + 4;
+        if self.aux_info_type.is_some() {
+            size += 8;
+        }
+        let offset_size = if self.version == 1 { 8 } else { 4 };
+        size + self.offsets.len() as u64 * offset_size
+    }
+
+    /// Whether the box describes information of the given type, where a box
+    /// without a type has the type of the protection scheme.
+    pub fn has_aux_info_type(&self, aux_info_type: FourCC) -> bool {
+        self.aux_info_type.is_none_or(|t| t == aux_info_type)
+    }
+}
+
+impl Mp4Box for SaioBox {
+    fn box_type(&self) -> BoxType {
+        self.get_type()
+    }
+
+    fn box_size(&self) -> u64 {
+        self.get_size()
+    }
+
+    fn summary(&self) -> Result<String> {
+        let s = format!("offsets={}", self.offsets.len());
+        Ok(s)
+    }
+}
+
+impl<R: Read + Seek> ReadBox<&mut R> for SaioBox {
+    fn read_box(reader: &mut R, size: u64) -> Result<Self> {
+        let start = box_start(reader)?;
+
+        let (version, flags) = read_box_header_ext(reader)?;
+        let (aux_info_type, aux_info_type_parameter) = if flags & 0x1 != 0 {
+            (
+                Some(reader.read_u32::<BigEndian>()?.into()),
+                reader.read_u32::<BigEndian>()?,
+            )
+        } else {
+            (None, 0)
+        };
+        let entry_count = reader.read_u32::<BigEndian>()?;
+
+        let offset_size = if version == 1 { 8 } else { 4 };
+        let header_size = reader.stream_position()? - start;
+        if u64::from(entry_count) > size.saturating_sub(header_size) / offset_size {
+            return Err(Error::InvalidData(
+                "saio entry_count indicates more entries than could fit in the box",
+            ));
+        }
+        let mut offsets = Vec::with_capacity(entry_count as usize);
+        for _ in 0..entry_count {
+            if version == 1 {
+                offsets.push(reader.read_u64::<BigEndian>()?);
+            } else {
+                offsets.push(reader.read_u32::<BigEndian>()? as u64);
+            }
+        }
+
+        skip_bytes_to(reader, start + size)?;
+
+        Ok(SaioBox {
+            version,
+            flags,
+            aux_info_type,
+            aux_info_type_parameter,
+            offsets,
+        })
+    }
+}
+
+impl<W: Write> WriteBox<&mut W> for SaioBox {
+    fn write_box(&self, writer: &mut W) -> Result<u64> {
+        let size = self.box_size();
+        BoxHeader::new(self.box_type(), size).write(writer)?;
+
+        write_aux_info_type(
+            writer,
+            self.version,
+            self.flags,
+            self.aux_info_type,
+            self.aux_info_type_parameter,
+        )?;
+        writer.write_u32::<BigEndian>(self.offsets.len() as u32)?;
+        for offset in self.offsets.iter() {
+            if self.version == 1 {
+                writer.write_u64::<BigEndian>(*offset)?;
+            } else {
+                let offset = u32::try_from(*offset)
+                    .map_err(|_| Error::InvalidData("saio offset requires version 1"))?;
+                writer.write_u32::<BigEndian>(offset)?;
+            }
+        }
+
+        Ok(size)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mp4box::BoxHeader;
+    use std::io::Cursor;
+
+    #[test]
+    fn test_saio() {
+        let src_box = SaioBox {
+            version: 0,
+            flags: 0,
+            aux_info_type: None,
+            aux_info_type_parameter: 0,
+            offsets: vec![1234],
+        };
+        let mut buf = Vec::new();
+        src_box.write_box(&mut buf).unwrap();
+        assert_eq!(buf.len(), src_box.box_size() as usize);
+
+        let mut reader = Cursor::new(&buf);
+        let header = BoxHeader::read(&mut reader).unwrap();
+        assert_eq!(header.name, BoxType::SaioBox);
+        assert_eq!(src_box.box_size(), header.size);
+
+        let dst_box = SaioBox::read_box(&mut reader, header.size).unwrap();
+        assert_eq!(src_box, dst_box);
+    }
+
+    #[test]
+    fn test_saio64() {
+        let src_box = SaioBox {
+            version: 1,
+            flags: 1,
+            aux_info_type: Some(str::parse("cbcs").unwrap()),
+            aux_info_type_parameter: 0,
+            offsets: vec![1 << 33, 1 << 34],
+        };
+        let mut buf = Vec::new();
+        src_box.write_box(&mut buf).unwrap();
+        assert_eq!(buf.len(), src_box.box_size() as usize);
+
+        let mut reader = Cursor::new(&buf);
+        let header = BoxHeader::read(&mut reader).unwrap();
+        let dst_box = SaioBox::read_box(&mut reader, header.size).unwrap();
+        assert_eq!(src_box, dst_box);
+
+        let src_box = SaioBox {
+            version: 0,
+            ..src_box
+        };
+        assert!(src_box.write_box(&mut Vec::new()).is_err());
+    }
+}
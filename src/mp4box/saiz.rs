@@ -0,0 +1,218 @@
+use byteorder::{BigEndian, ReadBytesExt, WriteBytesExt};
+use std::io::{Read, Seek, Write};
+
+use crate::mp4box::*;
+
+/// Sizes of the sample auxiliary information of a track or track fragment,
+/// see ISO/IEC 14496-12 8.7.8.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct SaizBox {
+    pub version: u8,
+    pub flags: u32,
+
+    /// Type of the auxiliary information, usually the protection scheme.
+    /// Stored if bit 0 of `flags` is set.
+    pub aux_info_type: Option<FourCC>,
+    pub aux_info_type_parameter: u32,
+
+    /// Size of the information of every sample, or 0 if the sizes differ.
+    pub default_sample_info_size: u8,
+
+    pub sample_count: u32,
+
+    /// Size of the information of each sample, if `default_sample_info_size`
+    /// is 0.
+    pub sample_info_sizes: Vec<u8>,
+}
+
+impl SaizBox {
+    pub fn get_type(&self) -> BoxType {
+        BoxType::SaizBox
+    }
+
+    pub fn get_size(&self) -> u64 {
+        let mut size = HEADER_SIZE + HEADER_EXT_SIZE + 5;
+        if self.aux_info_type.is_some() {
+            size += 8;
+        }
+        if self.default_sample_info_size == 0 {
+            size += self.sample_info_sizes.len() as u64;
+        }
+        size
+    }
+
+    /// Size of the information of a zero-based sample index.
+    pub fn sample_info_size(&self, sample_idx: u32) -> Option<u8> {
+        if sample_idx >= self.sample_count {
+            return None;
+        }
+        if self.default_sample_info_size != 0 {
+            return Some(self.default_sample_info_size);
+        }
+        self.sample_info_sizes.get(sample_idx as usize).copied()
+    }
+
+    /// Whether the box describes information of the given type, where a box
+    /// without a type has the type of the protection scheme.
+    pub fn has_aux_info_type(&self, aux_info_type: FourCC) -> bool {
+        self.aux_info_type.is_none_or(|t| t == aux_info_type)
+    }
+}
+
+impl Mp4Box for SaizBox {
+    fn box_type(&self) -> BoxType {
+        self.get_type()
+    }
+
+    fn box_size(&self) -> u64 {
+        self.get_size()
+    }
+
+    fn summary(&self) -> Result<String> {
+        let s = format!(
+            "default_sample_info_size={} sample_count={}",
+            self.default_sample_info_size, self.sample_count
+        );
+        Ok(s)
+    }
+}
+
+impl<R: Read + Seek> ReadBox<&mut R> for SaizBox {
+    fn read_box(reader: &mut R, size: u64) -> Result<Self> {
+        let start = box_start(reader)?;
+
+        let (version, flags) = read_box_header_ext(reader)?;
+        let (aux_info_type, aux_info_type_parameter) = if flags & 0x1 != 0 {
+            (
+                Some(reader.read_u32::<BigEndian>()?.into()),
+                reader.read_u32::<BigEndian>()?,
+            )
+        } else {
+            (None, 0)
+        };
+        let default_sample_info_size = reader.read_u8()?;
+        let sample_count = reader.read_u32::<BigEndian>()?;
+
+        let mut sample_info_sizes = Vec::new();
+        if default_sample_info_size == 0 {
+            let header_size = reader.stream_position()? - start;
+            if u64::from(sample_count) > size.saturating_sub(header_size) {
+                return Err(Error::InvalidData(
+                    "saiz sample_count indicates more entries than could fit in the box",
+                ));
+            }
+            sample_info_sizes = vec![0; sample_count as usize];
+            reader.read_exact(&mut sample_info_sizes)?;
+        }
+
+        skip_bytes_to(reader, start + size)?;
+
+        Ok(SaizBox {
+            version,
+            flags,
+            aux_info_type,
+            aux_info_type_parameter,
+            default_sample_info_size,
+            sample_count,
+            sample_info_sizes,
+        })
+    }
+}
+
+impl<W: Write> WriteBox<&mut W> for SaizBox {
+    fn write_box(&self, writer: &mut W) -> Result<u64> {
+        let size = self.box_size();
+        BoxHeader::new(self.box_type(), size).write(writer)?;
+
+        write_aux_info_type(
+            writer,
+            self.version,
+            self.flags,
+            self.aux_info_type,
+            self.aux_info_type_parameter,
+        )?;
+        writer.write_u8(self.default_sample_info_size)?;
+        writer.write_u32::<BigEndian>(self.sample_count)?;
+        if self.default_sample_info_size == 0 {
+            if self.sample_info_sizes.len() != self.sample_count as usize {
+                return Err(Error::InvalidData(
+                    "saiz sample_info_sizes do not match sample_count",
+                ));
+            }
+            writer.write_all(&self.sample_info_sizes)?;
+        }
+
+        Ok(size)
+    }
+}
+
+/// Write the full box header of `saiz` and `saio`, with the optional
+/// auxiliary information type.
+pub(crate) fn write_aux_info_type<W: Write>(
+    writer: &mut W,
+    version: u8,
+    flags: u32,
+    aux_info_type: Option<FourCC>,
+    aux_info_type_parameter: u32,
+) -> Result<()> {
+    if let Some(aux_info_type) = aux_info_type {
+        write_box_header_ext(writer, version, flags | 0x1)?;
+        writer.write_u32::<BigEndian>(aux_info_type.into())?;
+        writer.write_u32::<BigEndian>(aux_info_type_parameter)?;
+    } else {
+        write_box_header_ext(writer, version, flags & !0x1)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mp4box::BoxHeader;
+    use std::io::Cursor;
+
+    #[test]
+    fn test_saiz() {
+        let src_box = SaizBox {
+            version: 0,
+            flags: 1,
+            aux_info_type: Some(str::parse("cenc").unwrap()),
+            aux_info_type_parameter: 0,
+            default_sample_info_size: 0,
+            sample_count: 3,
+            sample_info_sizes: vec![16, 22, 28],
+        };
+        let mut buf = Vec::new();
+        src_box.write_box(&mut buf).unwrap();
+        assert_eq!(buf.len(), src_box.box_size() as usize);
+
+        let mut reader = Cursor::new(&buf);
+        let header = BoxHeader::read(&mut reader).unwrap();
+        assert_eq!(header.name, BoxType::SaizBox);
+        assert_eq!(src_box.box_size(), header.size);
+
+        let dst_box = SaizBox::read_box(&mut reader, header.size).unwrap();
+        assert_eq!(src_box, dst_box);
+        assert_eq!(dst_box.sample_info_size(1), Some(22));
+        assert_eq!(dst_box.sample_info_size(3), None);
+    }
+
+    #[test]
+    fn test_saiz_default_size() {
+        let src_box = SaizBox {
+            default_sample_info_size: 8,
+            sample_count: 100,
+            ..SaizBox::default()
+        };
+        let mut buf = Vec::new();
+        src_box.write_box(&mut buf).unwrap();
+        assert_eq!(buf.len(), src_box.box_size() as usize);
+
+        let mut reader = Cursor::new(&buf);
+        let header = BoxHeader::read(&mut reader).unwrap();
+        let dst_box = SaizBox::read_box(&mut reader, header.size).unwrap();
+        assert_eq!(src_box, dst_box);
+        assert_eq!(dst_box.sample_info_size(99), Some(8));
+        assert!(dst_box.has_aux_info_type(str::parse("cbcs").unwrap()));
+    }
+}
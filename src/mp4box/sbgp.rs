@@ -0,0 +1,179 @@
+use byteorder::{BigEndian, ReadBytesExt, WriteBytesExt};
+use std::io::{Read, Seek, Write};
+
+use crate::mp4box::*;
+
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct SbgpBox {
+    pub version: u8,
+    pub flags: u32,
+    pub grouping_type: FourCC,
+
+    /// Subtype of the grouping. Only used in version 1.
+    pub grouping_type_parameter: u32,
+
+    pub entries: Vec<SbgpEntry>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct SbgpEntry {
+    pub sample_count: u32,
+
+    /// One-based index of the entry in the `sgpd` of the same grouping type,
+    /// or 0 if the samples are not in a group of this type.
+    ///
+    /// In a `traf`, indices above 0x10000 refer to the `sgpd` of the `traf`
+    /// rather than the one in `stbl`.
+    pub group_description_index: u32,
+}
+
+impl SbgpBox {
+    /// Offset of indices that refer to the `sgpd` of a `traf`.
+    pub const FRAGMENT_LOCAL_INDEX_BASE: u32 = 0x10000;
+
+    pub fn get_type(&self) -> BoxType {
+        BoxType::SbgpBox
+    }
+
+    pub fn get_size(&self) -> u64 {
+        let mut size = HEADER_SIZE + HEADER_EXT_SIZE + 8;
+        if self.version == 1 {
+            size += 4;
+        }
+        size + self.entries.len() as u64 * 8
+    }
+
+    /// The group description index of a zero-based sample index, or `None`
+    /// if the sample is past the samples described by this box.
+    pub fn group_description_index(&self, sample_idx: u32) -> Option<u32> {
+        let mut sample_idx = sample_idx;
+        for entry in self.entries.iter() {
+            if sample_idx < entry.sample_count {
+                return Some(entry.group_description_index);
+            }
+            sample_idx -= entry.sample_count;
+        }
+        None
+    }
+}
+
+impl Mp4Box for SbgpBox {
+    fn box_type(&self) -> BoxType {
+        self.get_type()
+    }
+
+    fn box_size(&self) -> u64 {
+        self.get_size()
+    }
+
+    fn summary(&self) -> Result<String> {
+        let s = format!(
+            "grouping_type={} entries={}",
+            self.grouping_type,
+            self.entries.len()
+        );
+        Ok(s)
+    }
+}
+
+impl<R: Read + Seek> ReadBox<&mut R> for SbgpBox {
+    fn read_box(reader: &mut R, size: u64) -> Result<Self> {
+        let start = box_start(reader)?;
+
+        let (version, flags) = read_box_header_ext(reader)?;
+        let grouping_type: FourCC = reader.read_u32::<BigEndian>()?.into();
+        let grouping_type_parameter = if version == 1 {
+            reader.read_u32::<BigEndian>()?
+        } else {
+            0
+        };
+        let entry_count = reader.read_u32::<BigEndian>()?;
+
+        let header_size = reader.stream_position()? - start;
+        if u64::from(entry_count) > size.saturating_sub(header_size) / 8 {
+            return Err(Error::InvalidData(
+                "sbgp entry_count indicates more entries than could fit in the box",
+            ));
+        }
+        let mut entries = Vec::with_capacity(entry_count as usize);
+        for _ in 0..entry_count {
+            entries.push(SbgpEntry {
+                sample_count: reader.read_u32::<BigEndian>()?,
+                group_description_index: reader.read_u32::<BigEndian>()?,
+            });
+        }
+
+        skip_bytes_to(reader, start + size)?;
+
+        Ok(SbgpBox {
+            version,
+            flags,
+            grouping_type,
+            grouping_type_parameter,
+            entries,
+        })
+    }
+}
+
+impl<W: Write> WriteBox<&mut W> for SbgpBox {
+    fn write_box(&self, writer: &mut W) -> Result<u64> {
+        let size = self.box_size();
+        BoxHeader::new(self.box_type(), size).write(writer)?;
+
+        write_box_header_ext(writer, self.version, self.flags)?;
+
+        writer.write_u32::<BigEndian>(self.grouping_type.into())?;
+        if self.version == 1 {
+            writer.write_u32::<BigEndian>(self.grouping_type_parameter)?;
+        }
+        writer.write_u32::<BigEndian>(self.entries.len() as u32)?;
+        for entry in self.entries.iter() {
+            writer.write_u32::<BigEndian>(entry.sample_count)?;
+            writer.write_u32::<BigEndian>(entry.group_description_index)?;
+        }
+
+        Ok(size)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mp4box::BoxHeader;
+    use std::io::Cursor;
+
+    #[test]
+    fn test_sbgp() {
+        let src_box = SbgpBox {
+            version: 1,
+            flags: 0,
+            grouping_type: str::parse("roll").unwrap(),
+            grouping_type_parameter: 7,
+            entries: vec![
+                SbgpEntry {
+                    sample_count: 1,
+                    group_description_index: 1,
+                },
+                SbgpEntry {
+                    sample_count: 10,
+                    group_description_index: 0,
+                },
+            ],
+        };
+        let mut buf = Vec::new();
+        src_box.write_box(&mut buf).unwrap();
+        assert_eq!(buf.len(), src_box.box_size() as usize);
+
+        let mut reader = Cursor::new(&buf);
+        let header = BoxHeader::read(&mut reader).unwrap();
+        assert_eq!(header.name, BoxType::SbgpBox);
+        assert_eq!(src_box.box_size(), header.size);
+
+        let dst_box = SbgpBox::read_box(&mut reader, header.size).unwrap();
+        assert_eq!(src_box, dst_box);
+
+        assert_eq!(dst_box.group_description_index(0), Some(1));
+        assert_eq!(dst_box.group_description_index(10), Some(0));
+        assert_eq!(dst_box.group_description_index(11), None);
+    }
+}
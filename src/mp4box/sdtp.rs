@@ -0,0 +1,119 @@
+use byteorder::{ReadBytesExt, WriteBytesExt};
+use std::io::{Read, Seek, Write};
+
+use crate::mp4box::*;
+
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct SdtpBox {
+    pub version: u8,
+    pub flags: u32,
+
+    /// The dependency of every sample, in sample order.
+    pub entries: Vec<SampleDependency>,
+}
+
+impl SdtpBox {
+    pub fn get_type(&self) -> BoxType {
+        BoxType::SdtpBox
+    }
+
+    pub fn get_size(&self) -> u64 {
+        HEADER_SIZE + HEADER_EXT_SIZE + self.entries.len() as u64
+    }
+}
+
+impl Mp4Box for SdtpBox {
+    fn box_type(&self) -> BoxType {
+        self.get_type()
+    }
+
+    fn box_size(&self) -> u64 {
+        self.get_size()
+    }
+
+    fn summary(&self) -> Result<String> {
+        let s = format!("entries={}", self.entries.len());
+        Ok(s)
+    }
+}
+
+impl<R: Read + Seek> ReadBox<&mut R> for SdtpBox {
+    fn read_box(reader: &mut R, size: u64) -> Result<Self> {
+        let start = box_start(reader)?;
+
+        let (version, flags) = read_box_header_ext(reader)?;
+
+        // The sample count is not stored, the entries fill the box.
+        let header_size = reader.stream_position()? - start;
+        let entry_count = size.saturating_sub(header_size);
+        let mut entries = Vec::with_capacity(entry_count as usize);
+        for _ in 0..entry_count {
+            entries.push(SampleDependency::from(reader.read_u8()?));
+        }
+
+        skip_bytes_to(reader, start + size)?;
+
+        Ok(SdtpBox {
+            version,
+            flags,
+            entries,
+        })
+    }
+}
+
+impl<W: Write> WriteBox<&mut W> for SdtpBox {
+    fn write_box(&self, writer: &mut W) -> Result<u64> {
+        let size = self.box_size();
+        BoxHeader::new(self.box_type(), size).write(writer)?;
+
+        write_box_header_ext(writer, self.version, self.flags)?;
+
+        for entry in self.entries.iter() {
+            writer.write_u8(u8::from(*entry))?;
+        }
+
+        Ok(size)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mp4box::BoxHeader;
+    use std::io::Cursor;
+
+    #[test]
+    fn test_sdtp() {
+        let src_box = SdtpBox {
+            version: 0,
+            flags: 0,
+            entries: vec![
+                SampleDependency {
+                    is_leading: 0,
+                    depends_on: 2,
+                    is_depended_on: 1,
+                    has_redundancy: 0,
+                },
+                SampleDependency {
+                    is_leading: 3,
+                    depends_on: 1,
+                    is_depended_on: 2,
+                    has_redundancy: 2,
+                },
+            ],
+        };
+        let mut buf = Vec::new();
+        src_box.write_box(&mut buf).unwrap();
+        assert_eq!(buf.len(), src_box.box_size() as usize);
+        assert_eq!(buf[12..], [0x24, 0xDA]);
+
+        let mut reader = Cursor::new(&buf);
+        let header = BoxHeader::read(&mut reader).unwrap();
+        assert_eq!(header.name, BoxType::SdtpBox);
+        assert_eq!(src_box.box_size(), header.size);
+
+        let dst_box = SdtpBox::read_box(&mut reader, header.size).unwrap();
+        assert_eq!(src_box, dst_box);
+        assert!(dst_box.entries[1].is_disposable());
+    }
+}
@@ -0,0 +1,264 @@
+use byteorder::{BigEndian, ReadBytesExt, WriteBytesExt};
+use std::io::{Cursor, Read, Seek, Write};
+
+use crate::mp4box::*;
+
+/// Per-sample encryption information of a track fragment, see
+/// ISO/IEC 23001-7 7.2.
+///
+/// The size of the IVs is not stored in the box but in the `tenc` of the
+/// track or a `seig` sample group, so the sample data is kept as it is and
+/// parsed with [SencBox::entries].
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct SencBox {
+    pub version: u8,
+
+    /// [SencBox::USE_SUBSAMPLE_ENCRYPTION] if the samples have subsample
+    /// maps.
+    pub flags: u32,
+
+    pub sample_count: u32,
+
+    /// The encryption information of every sample, in sample order.
+    pub data: Vec<u8>,
+}
+
+/// The encryption information of a sample.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct SencEntry {
+    /// The per-sample IV, empty if the sample uses a constant IV or is not
+    /// protected.
+    pub iv: Vec<u8>,
+
+    /// Clear and protected ranges covering the sample, empty if the whole
+    /// sample is protected.
+    pub subsamples: Vec<SencSubsample>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct SencSubsample {
+    pub bytes_of_clear_data: u16,
+    pub bytes_of_protected_data: u32,
+}
+
+impl SencEntry {
+    /// Read an entry of the given IV size, as stored in a `senc` or
+    /// referenced by `saiz` and `saio`.
+    pub fn read<R: Read>(reader: &mut R, iv_size: u8, has_subsamples: bool) -> Result<Self> {
+        let mut iv = vec![0; iv_size as usize];
+        reader.read_exact(&mut iv)?;
+        let mut subsamples = Vec::new();
+        if has_subsamples {
+            let subsample_count = reader.read_u16::<BigEndian>()?;
+            subsamples.reserve(subsample_count as usize);
+            for _ in 0..subsample_count {
+                subsamples.push(SencSubsample {
+                    bytes_of_clear_data: reader.read_u16::<BigEndian>()?,
+                    bytes_of_protected_data: reader.read_u32::<BigEndian>()?,
+                });
+            }
+        }
+        Ok(SencEntry { iv, subsamples })
+    }
+
+    pub fn write<W: Write>(&self, writer: &mut W, has_subsamples: bool) -> Result<u64> {
+        writer.write_all(&self.iv)?;
+        if has_subsamples {
+            let subsample_count = u16::try_from(self.subsamples.len())
+                .map_err(|_| Error::InvalidData("too many subsamples in a sample"))?;
+            writer.write_u16::<BigEndian>(subsample_count)?;
+            for subsample in self.subsamples.iter() {
+                writer.write_u16::<BigEndian>(subsample.bytes_of_clear_data)?;
+                writer.write_u32::<BigEndian>(subsample.bytes_of_protected_data)?;
+            }
+        }
+        Ok(self.size(has_subsamples))
+    }
+
+    /// Size of the entry, which is the sample info size of `saiz`.
+    pub fn size(&self, has_subsamples: bool) -> u64 {
+        let mut size = self.iv.len() as u64;
+        if has_subsamples {
+            size += 2 + self.subsamples.len() as u64 * 6;
+        }
+        size
+    }
+}
+
+impl SencBox {
+    pub const USE_SUBSAMPLE_ENCRYPTION: u32 = 0x2;
+
+    /// A `senc` holding `entries`, with subsample maps if any entry has one.
+    pub fn new(entries: &[SencEntry]) -> Result<Self> {
+        let has_subsamples = entries.iter().any(|entry| !entry.subsamples.is_empty());
+        let mut data = Vec::new();
+        for entry in entries.iter() {
+            entry.write(&mut data, has_subsamples)?;
+        }
+        Ok(SencBox {
+            version: 0,
+            flags: if has_subsamples {
+                Self::USE_SUBSAMPLE_ENCRYPTION
+            } else {
+                0
+            },
+            sample_count: entries.len() as u32,
+            data,
+        })
+    }
+
+    pub fn get_type(&self) -> BoxType {
+        BoxType::SencBox
+    }
+
+    pub fn get_size(&self) -> u64 {
+        HEADER_SIZE + HEADER_EXT_SIZE + 4 + self.data.len() as u64
+    }
+
+    pub fn has_subsamples(&self) -> bool {
+        self.flags & Self::USE_SUBSAMPLE_ENCRYPTION != 0
+    }
+
+    /// Parse the entries, with IVs of `iv_size` bytes.
+    pub fn entries(&self, iv_size: u8) -> Result<Vec<SencEntry>> {
+        self.entries_with(|_| iv_size)
+    }
+
+    /// Parse the entries, with the IV size of each zero-based sample index
+    /// given by `iv_size`.
+    pub fn entries_with(&self, mut iv_size: impl FnMut(u32) -> u8) -> Result<Vec<SencEntry>> {
+        let mut reader = Cursor::new(&self.data);
+        let mut entries = Vec::new();
+        for sample_idx in 0..self.sample_count {
+            let entry = SencEntry::read(&mut reader, iv_size(sample_idx), self.has_subsamples())
+                .map_err(|_| Error::InvalidData("senc entries do not match the IV size"))?;
+            entries.push(entry);
+        }
+        Ok(entries)
+    }
+}
+
+impl Mp4Box for SencBox {
+    fn box_type(&self) -> BoxType {
+        self.get_type()
+    }
+
+    fn box_size(&self) -> u64 {
+        self.get_size()
+    }
+
+    fn summary(&self) -> Result<String> {
+        let s = format!(
+            "sample_count={} subsamples={}",
+            self.sample_count,
+            self.has_subsamples()
+        );
+        Ok(s)
+    }
+}
+
+impl<R: Read + Seek> ReadBox<&mut R> for SencBox {
+    fn read_box(reader: &mut R, size: u64) -> Result<Self> {
+        let start = box_start(reader)?;
+
+        let (version, flags) = read_box_header_ext(reader)?;
+        let sample_count = reader.read_u32::<BigEndian>()?;
+
+        let header_size = reader.stream_position()? - start;
+        let data_size = size
+            .checked_sub(header_size)
+            .ok_or(Error::InvalidData("senc box is smaller than its header"))?;
+        let mut data = Vec::new();
+        reader.take(data_size).read_to_end(&mut data)?;
+        if data.len() as u64 != data_size {
+            return Err(Error::InvalidData("senc box is truncated"));
+        }
+
+        skip_bytes_to(reader, start + size)?;
+
+        Ok(SencBox {
+            version,
+            flags,
+            sample_count,
+            data,
+        })
+    }
+}
+
+impl<W: Write> WriteBox<&mut W> for SencBox {
+    fn write_box(&self, writer: &mut W) -> Result<u64> {
+        let size = self.box_size();
+        BoxHeader::new(self.box_type(), size).write(writer)?;
+
+        write_box_header_ext(writer, self.version, self.flags)?;
+        writer.write_u32::<BigEndian>(self.sample_count)?;
+        writer.write_all(&self.data)?;
+
+        Ok(size)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mp4box::BoxHeader;
+
+    #[test]
+    fn test_senc() {
+        let entries = vec![
+            SencEntry {
+                iv: vec![1; 8],
+                subsamples: vec![
+                    SencSubsample {
+                        bytes_of_clear_data: 5,
+                        bytes_of_protected_data: 32,
+                    },
+                    SencSubsample {
+                        bytes_of_clear_data: 3,
+                        bytes_of_protected_data: 100_000,
+                    },
+                ],
+            },
+            SencEntry {
+                iv: vec![2; 8],
+                subsamples: vec![],
+            },
+        ];
+        let src_box = SencBox::new(&entries).unwrap();
+        assert!(src_box.has_subsamples());
+        let mut buf = Vec::new();
+        src_box.write_box(&mut buf).unwrap();
+        assert_eq!(buf.len(), src_box.box_size() as usize);
+
+        let mut reader = Cursor::new(&buf);
+        let header = BoxHeader::read(&mut reader).unwrap();
+        assert_eq!(header.name, BoxType::SencBox);
+        assert_eq!(src_box.box_size(), header.size);
+
+        let dst_box = SencBox::read_box(&mut reader, header.size).unwrap();
+        assert_eq!(src_box, dst_box);
+        assert_eq!(dst_box.entries(8).unwrap(), entries);
+        assert!(dst_box.entries(16).is_err());
+    }
+
+    #[test]
+    fn test_senc_without_subsamples() {
+        let entries = vec![
+            SencEntry {
+                iv: vec![1; 16],
+                subsamples: vec![],
+            };
+            3
+        ];
+        let senc = SencBox::new(&entries).unwrap();
+        assert!(!senc.has_subsamples());
+        assert_eq!(senc.box_size(), HEADER_SIZE + HEADER_EXT_SIZE + 4 + 48);
+        assert_eq!(senc.entries(16).unwrap(), entries);
+        assert_eq!(
+            senc.entries_with(|i| if i == 0 { 16 } else { 0 })
+                .unwrap()
+                .len(),
+            3
+        );
+    }
+}
@@ -0,0 +1,439 @@
+use byteorder::{BigEndian, ReadBytesExt, WriteBytesExt};
+use std::io::{Read, Seek, Write};
+
+use crate::mp4box::*;
+
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct SgpdBox {
+    pub version: u8,
+    pub flags: u32,
+    pub grouping_type: FourCC,
+
+    /// Size of every entry, or 0 if each entry is preceded by its size.
+    /// Only used from version 1 on.
+    pub default_length: u32,
+
+    /// One-based index of the entry that applies to samples not mapped by
+    /// any `sbgp`, or 0 for none. Only used from version 2 on.
+    pub default_sample_description_index: u32,
+
+    pub entries: Vec<SampleGroupEntry>,
+}
+
+/// A sample group description entry.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SampleGroupEntry {
+    /// `roll` and `prol` (ISO/IEC 14496-12 10.1): the number of samples
+    /// after (or, if negative, before) a sample that must be decoded for it
+    /// to be correct.
+    Roll { roll_distance: i16 },
+
+    /// `rap ` (ISO/IEC 14496-12 10.4): random access points, such as open
+    /// GOP intra frames, that may be followed by undecodable leading samples.
+    Rap {
+        num_leading_samples_known: bool,
+        num_leading_samples: u8,
+    },
+
+    /// `sync` (ISO/IEC 14496-15): sync samples with their NAL unit type.
+    Sync { nal_unit_type: u8 },
+
+    /// `seig` (ISO/IEC 23001-7): encryption parameters for a group of
+    /// samples.
+    Seig {
+        crypt_byte_block: u8,
+        skip_byte_block: u8,
+        is_protected: bool,
+        per_sample_iv_size: u8,
+        kid: [u8; 16],
+        constant_iv: Vec<u8>,
+    },
+
+    /// An entry of any other grouping type.
+    Raw(Vec<u8>),
+}
+
+impl SampleGroupEntry {
+    pub const ROLL: FourCC = FourCC { value: *b"roll" };
+    pub const PROL: FourCC = FourCC { value: *b"prol" };
+    pub const RAP: FourCC = FourCC { value: *b"rap " };
+    pub const SYNC: FourCC = FourCC { value: *b"sync" };
+    pub const SEIG: FourCC = FourCC { value: *b"seig" };
+
+    /// Parse an entry of a grouping type from the start of `data`, returning
+    /// the entry and its size, or `None` if the type is not known or `data`
+    /// is too short.
+    fn parse(grouping_type: FourCC, data: &[u8]) -> Option<(Self, usize)> {
+        match grouping_type {
+            Self::ROLL | Self::PROL => {
+                let bytes = data.get(..2)?;
+                let roll_distance = i16::from_be_bytes([bytes[0], bytes[1]]);
+                Some((SampleGroupEntry::Roll { roll_distance }, 2))
+            }
+            Self::RAP => {
+                let byte = *data.first()?;
+                let entry = SampleGroupEntry::Rap {
+                    num_leading_samples_known: byte & 0x80 != 0,
+                    num_leading_samples: byte & 0x7F,
+                };
+                Some((entry, 1))
+            }
+            Self::SYNC => {
+                let byte = *data.first()?;
+                let entry = SampleGroupEntry::Sync {
+                    nal_unit_type: byte & 0x3F,
+                };
+                Some((entry, 1))
+            }
+            Self::SEIG => {
+                let header = data.get(..20)?;
+                let is_protected = header[2] != 0;
+                let per_sample_iv_size = header[3];
+                let mut kid = [0u8; 16];
+                kid.copy_from_slice(&header[4..20]);
+                let (constant_iv, size) = if is_protected && per_sample_iv_size == 0 {
+                    let iv_size = *data.get(20)? as usize;
+                    (data.get(21..21 + iv_size)?.to_vec(), 21 + iv_size)
+                } else {
+                    (Vec::new(), 20)
+                };
+                let entry = SampleGroupEntry::Seig {
+                    crypt_byte_block: header[1] >> 4,
+                    skip_byte_block: header[1] & 0xF,
+                    is_protected,
+                    per_sample_iv_size,
+                    kid,
+                    constant_iv,
+                };
+                Some((entry, size))
+            }
+            _ => None,
+        }
+    }
+
+    fn to_bytes(&self) -> Vec<u8> {
+        match self {
+            SampleGroupEntry::Roll { roll_distance } => roll_distance.to_be_bytes().to_vec(),
+            SampleGroupEntry::Rap {
+                num_leading_samples_known,
+                num_leading_samples,
+            } => vec![(*num_leading_samples_known as u8) << 7 | num_leading_samples & 0x7F],
+            SampleGroupEntry::Sync { nal_unit_type } => vec![nal_unit_type & 0x3F],
+            SampleGroupEntry::Seig {
+                crypt_byte_block,
+                skip_byte_block,
+                is_protected,
+                per_sample_iv_size,
+                kid,
+                constant_iv,
+            } => {
+                let mut bytes = vec![
+                    0,
+                    (crypt_byte_block & 0xF) << 4 | skip_byte_block & 0xF,
+                    *is_protected as u8,
+                    *per_sample_iv_size,
+                ];
+                bytes.extend_from_slice(kid);
+                if *is_protected && *per_sample_iv_size == 0 {
+                    bytes.push(constant_iv.len() as u8);
+                    bytes.extend_from_slice(constant_iv);
+                }
+                bytes
+            }
+            SampleGroupEntry::Raw(bytes) => bytes.clone(),
+        }
+    }
+}
+
+impl SgpdBox {
+    pub fn get_type(&self) -> BoxType {
+        BoxType::SgpdBox
+    }
+
+    pub fn get_size(&self) -> u64 {
+        let mut size = HEADER_SIZE + HEADER_EXT_SIZE + 8;
+        if self.version >= 1 {
+            size += 4;
+        }
+        if self.version >= 2 {
+            size += 4;
+        }
+        for entry in self.entries.iter() {
+            if self.version >= 1 && self.default_length == 0 {
+                size += 4;
+            }
+            size += entry.to_bytes().len() as u64;
+        }
+        size
+    }
+
+    /// The entry with a one-based description index.
+    pub fn entry(&self, group_description_index: u32) -> Option<&SampleGroupEntry> {
+        let idx = group_description_index.checked_sub(1)?;
+        self.entries.get(idx as usize)
+    }
+}
+
+impl Mp4Box for SgpdBox {
+    fn box_type(&self) -> BoxType {
+        self.get_type()
+    }
+
+    fn box_size(&self) -> u64 {
+        self.get_size()
+    }
+
+    fn summary(&self) -> Result<String> {
+        let s = format!(
+            "grouping_type={} entries={}",
+            self.grouping_type,
+            self.entries.len()
+        );
+        Ok(s)
+    }
+}
+
+impl<R: Read + Seek> ReadBox<&mut R> for SgpdBox {
+    fn read_box(reader: &mut R, size: u64) -> Result<Self> {
+        let start = box_start(reader)?;
+
+        let (version, flags) = read_box_header_ext(reader)?;
+        let grouping_type: FourCC = reader.read_u32::<BigEndian>()?.into();
+        let default_length = if version >= 1 {
+            reader.read_u32::<BigEndian>()?
+        } else {
+            0
+        };
+        let default_sample_description_index = if version >= 2 {
+            reader.read_u32::<BigEndian>()?
+        } else {
+            0
+        };
+        let entry_count = reader.read_u32::<BigEndian>()?;
+
+        let header_size = reader.stream_position()? - start;
+        let mut data = vec![0u8; size.saturating_sub(header_size) as usize];
+        reader.read_exact(&mut data)?;
+        if entry_count as usize > data.len() {
+            return Err(Error::InvalidData(
+                "sgpd entry_count indicates more entries than could fit in the box",
+            ));
+        }
+
+        let mut entries = Vec::with_capacity(entry_count as usize);
+        let mut pos = 0;
+        for i in 0..entry_count as usize {
+            let length = if version >= 1 && default_length == 0 {
+                let bytes = data
+                    .get(pos..pos + 4)
+                    .ok_or(Error::InvalidData("sgpd entry is truncated"))?;
+                pos += 4;
+                Some(u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]) as usize)
+            } else if version >= 1 {
+                Some(default_length as usize)
+            } else {
+                None
+            };
+
+            let entry = match length {
+                Some(length) => {
+                    let bytes = data
+                        .get(pos..pos + length)
+                        .ok_or(Error::InvalidData("sgpd entry is truncated"))?;
+                    pos += length;
+                    // Entries that do not match the size of their type are
+                    // kept as is.
+                    match SampleGroupEntry::parse(grouping_type, bytes) {
+                        Some((entry, size)) if size == length => entry,
+                        _ => SampleGroupEntry::Raw(bytes.to_vec()),
+                    }
+                }
+                // Version 0 entries have the size implied by their type.
+                None => match SampleGroupEntry::parse(grouping_type, &data[pos..]) {
+                    Some((entry, size)) => {
+                        pos += size;
+                        entry
+                    }
+                    None => {
+                        let length = (data.len() - pos) / (entry_count as usize - i);
+                        pos += length;
+                        SampleGroupEntry::Raw(data[pos - length..pos].to_vec())
+                    }
+                },
+            };
+            entries.push(entry);
+        }
+
+        skip_bytes_to(reader, start + size)?;
+
+        Ok(SgpdBox {
+            version,
+            flags,
+            grouping_type,
+            default_length,
+            default_sample_description_index,
+            entries,
+        })
+    }
+}
+
+impl<W: Write> WriteBox<&mut W> for SgpdBox {
+    fn write_box(&self, writer: &mut W) -> Result<u64> {
+        let size = self.box_size();
+        BoxHeader::new(self.box_type(), size).write(writer)?;
+
+        write_box_header_ext(writer, self.version, self.flags)?;
+
+        writer.write_u32::<BigEndian>(self.grouping_type.into())?;
+        if self.version >= 1 {
+            writer.write_u32::<BigEndian>(self.default_length)?;
+        }
+        if self.version >= 2 {
+            writer.write_u32::<BigEndian>(self.default_sample_description_index)?;
+        }
+        writer.write_u32::<BigEndian>(self.entries.len() as u32)?;
+        for entry in self.entries.iter() {
+            let bytes = entry.to_bytes();
+            if self.version >= 1 {
+                if self.default_length == 0 {
+                    writer.write_u32::<BigEndian>(bytes.len() as u32)?;
+                } else if bytes.len() != self.default_length as usize {
+                    return Err(Error::InvalidData(
+                        "sgpd entry size does not match default_length",
+                    ));
+                }
+            }
+            writer.write_all(&bytes)?;
+        }
+
+        Ok(size)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mp4box::BoxHeader;
+    use std::io::Cursor;
+
+    fn round_trip(src_box: &SgpdBox) -> SgpdBox {
+        let mut buf = Vec::new();
+        src_box.write_box(&mut buf).unwrap();
+        assert_eq!(buf.len(), src_box.box_size() as usize);
+
+        let mut reader = Cursor::new(&buf);
+        let header = BoxHeader::read(&mut reader).unwrap();
+        assert_eq!(header.name, BoxType::SgpdBox);
+        assert_eq!(src_box.box_size(), header.size);
+
+        SgpdBox::read_box(&mut reader, header.size).unwrap()
+    }
+
+    #[test]
+    fn test_sgpd_roll() {
+        let src_box = SgpdBox {
+            version: 1,
+            flags: 0,
+            grouping_type: SampleGroupEntry::ROLL,
+            default_length: 2,
+            default_sample_description_index: 0,
+            entries: vec![SampleGroupEntry::Roll { roll_distance: -1 }],
+        };
+        assert_eq!(round_trip(&src_box), src_box);
+    }
+
+    #[test]
+    fn test_sgpd_rap_v2() {
+        let src_box = SgpdBox {
+            version: 2,
+            flags: 0,
+            grouping_type: SampleGroupEntry::RAP,
+            default_length: 1,
+            default_sample_description_index: 1,
+            entries: vec![
+                SampleGroupEntry::Rap {
+                    num_leading_samples_known: true,
+                    num_leading_samples: 3,
+                },
+                SampleGroupEntry::Rap {
+                    num_leading_samples_known: false,
+                    num_leading_samples: 0,
+                },
+            ],
+        };
+        assert_eq!(round_trip(&src_box), src_box);
+    }
+
+    #[test]
+    fn test_sgpd_seig_variable_length() {
+        let src_box = SgpdBox {
+            version: 1,
+            flags: 0,
+            grouping_type: SampleGroupEntry::SEIG,
+            default_length: 0,
+            default_sample_description_index: 0,
+            entries: vec![
+                SampleGroupEntry::Seig {
+                    crypt_byte_block: 1,
+                    skip_byte_block: 9,
+                    is_protected: true,
+                    per_sample_iv_size: 0,
+                    kid: [7; 16],
+                    constant_iv: vec![1; 16],
+                },
+                SampleGroupEntry::Seig {
+                    crypt_byte_block: 0,
+                    skip_byte_block: 0,
+                    is_protected: true,
+                    per_sample_iv_size: 8,
+                    kid: [8; 16],
+                    constant_iv: Vec::new(),
+                },
+            ],
+        };
+        assert_eq!(round_trip(&src_box), src_box);
+    }
+
+    #[test]
+    fn test_sgpd_v0() {
+        let src_box = SgpdBox {
+            version: 0,
+            flags: 0,
+            grouping_type: SampleGroupEntry::SYNC,
+            default_length: 0,
+            default_sample_description_index: 0,
+            entries: vec![
+                SampleGroupEntry::Sync { nal_unit_type: 19 },
+                SampleGroupEntry::Sync { nal_unit_type: 21 },
+            ],
+        };
+        assert_eq!(round_trip(&src_box), src_box);
+
+        let src_box = SgpdBox {
+            version: 0,
+            grouping_type: str::parse("tele").unwrap(),
+            entries: vec![
+                SampleGroupEntry::Raw(vec![0x80]),
+                SampleGroupEntry::Raw(vec![0x00]),
+            ],
+            ..SgpdBox::default()
+        };
+        assert_eq!(round_trip(&src_box), src_box);
+    }
+
+    #[test]
+    fn test_sgpd_raw() {
+        let src_box = SgpdBox {
+            version: 1,
+            flags: 0,
+            grouping_type: str::parse("scif").unwrap(),
+            default_length: 0,
+            default_sample_description_index: 0,
+            entries: vec![
+                SampleGroupEntry::Raw(vec![1, 2, 3]),
+                SampleGroupEntry::Raw(vec![4]),
+            ],
+        };
+        assert_eq!(round_trip(&src_box), src_box);
+    }
+}
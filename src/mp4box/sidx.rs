@@ -0,0 +1,284 @@
+use byteorder::{BigEndian, ReadBytesExt, WriteBytesExt};
+use std::io::{Read, Seek, Write};
+use std::ops::Range;
+
+use crate::mp4box::*;
+
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct SidxBox {
+    pub version: u8,
+    pub flags: u32,
+    pub reference_id: u32,
+    pub timescale: u32,
+    pub earliest_presentation_time: u64,
+    pub first_offset: u64,
+    pub references: Vec<SidxReference>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct SidxReference {
+    /// Whether the reference is to another `sidx`, rather than to media.
+    pub reference_type: bool,
+    pub referenced_size: u32,
+    pub subsegment_duration: u32,
+    pub starts_with_sap: bool,
+    pub sap_type: u8,
+    pub sap_delta_time: u32,
+}
+
+/// A subsegment indexed by a [SidxBox].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Subsegment {
+    /// File offsets of the subsegment, or of the `sidx` it references.
+    pub byte_range: Range<u64>,
+
+    /// Earliest presentation time, in the timescale of the `sidx`.
+    pub start_time: u64,
+
+    /// Duration, in the timescale of the `sidx`.
+    pub duration: u32,
+
+    pub starts_with_sap: bool,
+
+    /// Whether the subsegment is indexed by another `sidx` at `byte_range`.
+    pub is_index: bool,
+}
+
+impl SidxBox {
+    pub fn get_type(&self) -> BoxType {
+        BoxType::SidxBox
+    }
+
+    pub fn get_size(&self) -> u64 {
+        let mut size = HEADER_SIZE + HEADER_EXT_SIZE + 8;
+        if self.version == 1 {
+            size += 16;
+        } else {
+            size += 8;
+        }
+        size += 4 + self.references.len() as u64 * 12;
+        size
+    }
+
+    /// The subsegments indexed by this box, whose offsets are relative to
+    /// `anchor`, the file offset of the first byte after the box.
+    pub fn subsegments(&self, anchor: u64) -> Vec<Subsegment> {
+        let mut start = anchor + self.first_offset;
+        let mut start_time = self.earliest_presentation_time;
+        let mut subsegments = Vec::with_capacity(self.references.len());
+        for reference in self.references.iter() {
+            let end = start + reference.referenced_size as u64;
+            subsegments.push(Subsegment {
+                byte_range: start..end,
+                start_time,
+                duration: reference.subsegment_duration,
+                starts_with_sap: reference.starts_with_sap,
+                is_index: reference.reference_type,
+            });
+            start = end;
+            start_time += reference.subsegment_duration as u64;
+        }
+        subsegments
+    }
+}
+
+impl Mp4Box for SidxBox {
+    fn box_type(&self) -> BoxType {
+        self.get_type()
+    }
+
+    fn box_size(&self) -> u64 {
+        self.get_size()
+    }
+
+    fn summary(&self) -> Result<String> {
+        let s = format!(
+            "reference_id={} timescale={} references={}",
+            self.reference_id,
+            self.timescale,
+            self.references.len()
+        );
+        Ok(s)
+    }
+}
+
+impl<R: Read + Seek> ReadBox<&mut R> for SidxBox {
+    fn read_box(reader: &mut R, size: u64) -> Result<Self> {
+        let start = box_start(reader)?;
+
+        let (version, flags) = read_box_header_ext(reader)?;
+
+        let reference_id = reader.read_u32::<BigEndian>()?;
+        let timescale = reader.read_u32::<BigEndian>()?;
+        let (earliest_presentation_time, first_offset) = if version == 1 {
+            (
+                reader.read_u64::<BigEndian>()?,
+                reader.read_u64::<BigEndian>()?,
+            )
+        } else {
+            (
+                reader.read_u32::<BigEndian>()? as u64,
+                reader.read_u32::<BigEndian>()? as u64,
+            )
+        };
+        reader.read_u16::<BigEndian>()?; // reserved
+        let reference_count = reader.read_u16::<BigEndian>()?;
+
+        let header_size = reader.stream_position()? - start;
+        if u64::from(reference_count) > size.saturating_sub(header_size) / 12 {
+            return Err(Error::InvalidData(
+                "sidx reference_count indicates more references than could fit in the box",
+            ));
+        }
+        let mut references = Vec::with_capacity(reference_count as usize);
+        for _ in 0..reference_count {
+            let size = reader.read_u32::<BigEndian>()?;
+            let subsegment_duration = reader.read_u32::<BigEndian>()?;
+            let sap = reader.read_u32::<BigEndian>()?;
+            references.push(SidxReference {
+                reference_type: size >> 31 != 0,
+                referenced_size: size & 0x7FFF_FFFF,
+                subsegment_duration,
+                starts_with_sap: sap >> 31 != 0,
+                sap_type: ((sap >> 28) & 0x7) as u8,
+                sap_delta_time: sap & 0x0FFF_FFFF,
+            });
+        }
+
+        skip_bytes_to(reader, start + size)?;
+
+        Ok(SidxBox {
+            version,
+            flags,
+            reference_id,
+            timescale,
+            earliest_presentation_time,
+            first_offset,
+            references,
+        })
+    }
+}
+
+impl<W: Write> WriteBox<&mut W> for SidxBox {
+    fn write_box(&self, writer: &mut W) -> Result<u64> {
+        let size = self.box_size();
+        BoxHeader::new(self.box_type(), size).write(writer)?;
+
+        write_box_header_ext(writer, self.version, self.flags)?;
+
+        writer.write_u32::<BigEndian>(self.reference_id)?;
+        writer.write_u32::<BigEndian>(self.timescale)?;
+        if self.version == 1 {
+            writer.write_u64::<BigEndian>(self.earliest_presentation_time)?;
+            writer.write_u64::<BigEndian>(self.first_offset)?;
+        } else {
+            writer.write_u32::<BigEndian>(self.earliest_presentation_time as u32)?;
+            writer.write_u32::<BigEndian>(self.first_offset as u32)?;
+        }
+        writer.write_u16::<BigEndian>(0)?; // reserved
+        let reference_count = u16::try_from(self.references.len())
+            .map_err(|_| Error::InvalidData("too many sidx references"))?;
+        writer.write_u16::<BigEndian>(reference_count)?;
+        for reference in self.references.iter() {
+            if reference.referenced_size > 0x7FFF_FFFF {
+                return Err(Error::InvalidData("sidx referenced_size exceeds 31 bits"));
+            }
+            writer.write_u32::<BigEndian>(
+                (reference.reference_type as u32) << 31 | reference.referenced_size,
+            )?;
+            writer.write_u32::<BigEndian>(reference.subsegment_duration)?;
+            writer.write_u32::<BigEndian>(
+                (reference.starts_with_sap as u32) << 31
+                    | (reference.sap_type as u32 & 0x7) << 28
+                    | reference.sap_delta_time & 0x0FFF_FFFF,
+            )?;
+        }
+
+        Ok(size)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mp4box::BoxHeader;
+    use std::io::Cursor;
+
+    #[test]
+    fn test_sidx() {
+        let src_box = SidxBox {
+            version: 0,
+            flags: 0,
+            reference_id: 1,
+            timescale: 90000,
+            earliest_presentation_time: 3000,
+            first_offset: 100,
+            references: vec![
+                SidxReference {
+                    reference_type: false,
+                    referenced_size: 5000,
+                    subsegment_duration: 180000,
+                    starts_with_sap: true,
+                    sap_type: 1,
+                    sap_delta_time: 0,
+                },
+                SidxReference {
+                    reference_type: false,
+                    referenced_size: 4000,
+                    subsegment_duration: 90000,
+                    starts_with_sap: false,
+                    sap_type: 0,
+                    sap_delta_time: 1500,
+                },
+            ],
+        };
+        let mut buf = Vec::new();
+        src_box.write_box(&mut buf).unwrap();
+        assert_eq!(buf.len(), src_box.box_size() as usize);
+
+        let mut reader = Cursor::new(&buf);
+        let header = BoxHeader::read(&mut reader).unwrap();
+        assert_eq!(header.name, BoxType::SidxBox);
+        assert_eq!(src_box.box_size(), header.size);
+
+        let dst_box = SidxBox::read_box(&mut reader, header.size).unwrap();
+        assert_eq!(src_box, dst_box);
+
+        let subsegments = dst_box.subsegments(1000 + dst_box.box_size());
+        assert_eq!(subsegments.len(), 2);
+        assert_eq!(subsegments[0].byte_range, 1156..6156);
+        assert_eq!(subsegments[0].start_time, 3000);
+        assert!(subsegments[0].starts_with_sap);
+        assert_eq!(subsegments[1].byte_range, 6156..10156);
+        assert_eq!(subsegments[1].start_time, 183000);
+        assert_eq!(subsegments[1].duration, 90000);
+    }
+
+    #[test]
+    fn test_sidx64() {
+        let src_box = SidxBox {
+            version: 1,
+            flags: 0,
+            reference_id: 2,
+            timescale: 48000,
+            earliest_presentation_time: 1 << 40,
+            first_offset: 0,
+            references: vec![SidxReference {
+                reference_type: true,
+                referenced_size: 0x7FFF_FFFF,
+                subsegment_duration: 96000,
+                starts_with_sap: true,
+                sap_type: 2,
+                sap_delta_time: 0x0FFF_FFFF,
+            }],
+        };
+        let mut buf = Vec::new();
+        src_box.write_box(&mut buf).unwrap();
+        assert_eq!(buf.len(), src_box.box_size() as usize);
+
+        let mut reader = Cursor::new(&buf);
+        let header = BoxHeader::read(&mut reader).unwrap();
+        let dst_box = SidxBox::read_box(&mut reader, header.size).unwrap();
+        assert_eq!(src_box, dst_box);
+    }
+}
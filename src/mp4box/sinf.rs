@@ -0,0 +1,459 @@
+use byteorder::{BigEndian, ReadBytesExt, WriteBytesExt};
+use std::io::{Read, Seek, Write};
+
+use crate::mp4box::tenc::TencBox;
+use crate::mp4box::*;
+
+/// Protection scheme information of a protected (`encv`, `enca`) sample
+/// entry, see ISO/IEC 14496-12 8.12.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct SinfBox {
+    pub frma: FrmaBox,
+    pub schm: Option<SchmBox>,
+    pub schi: Option<SchiBox>,
+    pub unknown_boxes: Vec<(BoxType, Vec<u8>)>,
+}
+
+impl SinfBox {
+    pub fn get_type(&self) -> BoxType {
+        BoxType::SinfBox
+    }
+
+    pub fn get_size(&self) -> u64 {
+        let mut size = HEADER_SIZE + self.frma.box_size();
+        if let Some(ref schm) = self.schm {
+            size += schm.box_size();
+        }
+        if let Some(ref schi) = self.schi {
+            size += schi.box_size();
+        }
+        size + unknown_boxes_size(&self.unknown_boxes)
+    }
+
+    /// The `tenc` with the default encryption parameters of the track.
+    pub fn tenc(&self) -> Option<&TencBox> {
+        self.schi.as_ref().and_then(|schi| schi.tenc.as_ref())
+    }
+}
+
+impl Mp4Box for SinfBox {
+    fn box_type(&self) -> BoxType {
+        self.get_type()
+    }
+
+    fn box_size(&self) -> u64 {
+        self.get_size()
+    }
+
+    fn summary(&self) -> Result<String> {
+        let mut s = format!("original_format={}", self.frma.original_format);
+        if let Some(ref schm) = self.schm {
+            s += &format!(" scheme_type={}", schm.scheme_type);
+        }
+        Ok(s)
+    }
+}
+
+impl<R: Read + Seek> ReadBox<&mut R> for SinfBox {
+    fn read_box(reader: &mut R, size: u64) -> Result<Self> {
+        let start = box_start(reader)?;
+
+        let mut frma = None;
+        let mut schm = None;
+        let mut schi = None;
+        let mut unknown_boxes = Vec::new();
+
+        let mut current = reader.stream_position()?;
+        let end = start + size;
+        while current < end {
+            // Get box header.
+            let header = BoxHeader::read(reader)?;
+            let BoxHeader { name, size: s } = header;
+            if s > size {
+                return Err(Error::InvalidData(
+                    "sinf box contains a box with a larger size than it",
+                ));
+            }
+
+            match name {
+                BoxType::FrmaBox => {
+                    frma = Some(FrmaBox::read_box(reader, s)?);
+                }
+                BoxType::SchmBox => {
+                    schm = Some(SchmBox::read_box(reader, s)?);
+                }
+                BoxType::SchiBox => {
+                    schi = Some(SchiBox::read_box(reader, s)?);
+                }
+                _ => {
+                    unknown_boxes.push(read_unknown_box(reader, name, s)?);
+                }
+            }
+
+            current = reader.stream_position()?;
+        }
+
+        let Some(frma) = frma else {
+            return Err(Error::BoxNotFound(BoxType::FrmaBox));
+        };
+
+        skip_bytes_to(reader, start + size)?;
+
+        Ok(SinfBox {
+            frma,
+            schm,
+            schi,
+            unknown_boxes,
+        })
+    }
+}
+
+impl<W: Write> WriteBox<&mut W> for SinfBox {
+    fn write_box(&self, writer: &mut W) -> Result<u64> {
+        let size = self.box_size();
+        BoxHeader::new(self.box_type(), size).write(writer)?;
+
+        self.frma.write_box(writer)?;
+        if let Some(ref schm) = self.schm {
+            schm.write_box(writer)?;
+        }
+        if let Some(ref schi) = self.schi {
+            schi.write_box(writer)?;
+        }
+        write_unknown_boxes(writer, &self.unknown_boxes)?;
+
+        Ok(size)
+    }
+}
+
+/// The original format of a protected sample entry.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct FrmaBox {
+    pub original_format: FourCC,
+}
+
+impl FrmaBox {
+    pub fn get_type(&self) -> BoxType {
+        BoxType::FrmaBox
+    }
+
+    pub fn get_size(&self) -> u64 {
+        HEADER_SIZE + 4
+    }
+}
+
+impl Mp4Box for FrmaBox {
+    fn box_type(&self) -> BoxType {
+        self.get_type()
+    }
+
+    fn box_size(&self) -> u64 {
+        self.get_size()
+    }
+
+    fn summary(&self) -> Result<String> {
+        let s = format!("original_format={}", self.original_format);
+        Ok(s)
+    }
+}
+
+impl<R: Read + Seek> ReadBox<&mut R> for FrmaBox {
+    fn read_box(reader: &mut R, size: u64) -> Result<Self> {
+        let start = box_start(reader)?;
+
+        let original_format = reader.read_u32::<BigEndian>()?.into();
+
+        skip_bytes_to(reader, start + size)?;
+
+        Ok(FrmaBox { original_format })
+    }
+}
+
+impl<W: Write> WriteBox<&mut W> for FrmaBox {
+    fn write_box(&self, writer: &mut W) -> Result<u64> {
+        let size = self.box_size();
+        BoxHeader::new(self.box_type(), size).write(writer)?;
+
+        writer.write_u32::<BigEndian>(self.original_format.into())?;
+
+        Ok(size)
+    }
+}
+
+/// The protection scheme of a protected sample entry.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct SchmBox {
+    pub version: u8,
+    pub flags: u32,
+    pub scheme_type: FourCC,
+    pub scheme_version: u32,
+
+    /// Stored if bit 0 of `flags` is set.
+    pub scheme_uri: Option<String>,
+}
+
+impl SchmBox {
+    /// AES-CTR full sample encryption (ISO/IEC 23001-7).
+    pub const CENC: FourCC = FourCC { value: *b"cenc" };
+    /// AES-CBC full sample encryption (ISO/IEC 23001-7).
+    pub const CBC1: FourCC = FourCC { value: *b"cbc1" };
+    /// AES-CTR pattern encryption (ISO/IEC 23001-7).
+    pub const CENS: FourCC = FourCC { value: *b"cens" };
+    /// AES-CBC pattern encryption with a constant IV (ISO/IEC 23001-7).
+    pub const CBCS: FourCC = FourCC { value: *b"cbcs" };
+
+    pub fn get_type(&self) -> BoxType {
+        BoxType::SchmBox
+    }
+
+    pub fn get_size(&self) -> u64 {
+        let mut size = HEADER_SIZE + HEADER_EXT_SIZE + 8;
+        if let Some(ref scheme_uri) = self.scheme_uri {
+            size += scheme_uri.len() as u64 + 1;
+        }
+        size
+    }
+}
+
+impl Mp4Box for SchmBox {
+    fn box_type(&self) -> BoxType {
+        self.get_type()
+    }
+
+    fn box_size(&self) -> u64 {
+        self.get_size()
+    }
+
+    fn summary(&self) -> Result<String> {
+        let s = format!(
+            "scheme_type={} scheme_version={:#x}",
+            self.scheme_type, self.scheme_version
+        );
+        Ok(s)
+    }
+}
+
+impl<R: Read + Seek> ReadBox<&mut R> for SchmBox {
+    fn read_box(reader: &mut R, size: u64) -> Result<Self> {
+        let start = box_start(reader)?;
+
+        let (version, flags) = read_box_header_ext(reader)?;
+        let scheme_type = reader.read_u32::<BigEndian>()?.into();
+        let scheme_version = reader.read_u32::<BigEndian>()?;
+
+        let scheme_uri = if flags & 0x1 != 0 {
+            let header_size = reader.stream_position()? - start;
+            let mut buf = Vec::new();
+            reader
+                .take(size.saturating_sub(header_size))
+                .read_to_end(&mut buf)?;
+            if let Some(end) = buf.iter().position(|&b| b == 0) {
+                buf.truncate(end);
+            }
+            Some(String::from_utf8(buf).map_err(|_| Error::InvalidData("invalid schm uri"))?)
+        } else {
+            None
+        };
+
+        skip_bytes_to(reader, start + size)?;
+
+        Ok(SchmBox {
+            version,
+            flags,
+            scheme_type,
+            scheme_version,
+            scheme_uri,
+        })
+    }
+}
+
+impl<W: Write> WriteBox<&mut W> for SchmBox {
+    fn write_box(&self, writer: &mut W) -> Result<u64> {
+        let size = self.box_size();
+        BoxHeader::new(self.box_type(), size).write(writer)?;
+
+        let flags = if self.scheme_uri.is_some() {
+            self.flags | 0x1
+        } else {
+            self.flags & !0x1
+        };
+        write_box_header_ext(writer, self.version, flags)?;
+        writer.write_u32::<BigEndian>(self.scheme_type.into())?;
+        writer.write_u32::<BigEndian>(self.scheme_version)?;
+        if let Some(ref scheme_uri) = self.scheme_uri {
+            writer.write_all(scheme_uri.as_bytes())?;
+            writer.write_u8(0)?;
+        }
+
+        Ok(size)
+    }
+}
+
+/// Scheme specific information of a protected sample entry.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct SchiBox {
+    pub tenc: Option<TencBox>,
+    pub unknown_boxes: Vec<(BoxType, Vec<u8>)>,
+}
+
+impl SchiBox {
+    pub fn get_type(&self) -> BoxType {
+        BoxType::SchiBox
+    }
+
+    pub fn get_size(&self) -> u64 {
+        let mut size = HEADER_SIZE;
+        if let Some(ref tenc) = self.tenc {
+            size += tenc.box_size();
+        }
+        size + unknown_boxes_size(&self.unknown_boxes)
+    }
+}
+
+impl Mp4Box for SchiBox {
+    fn box_type(&self) -> BoxType {
+        self.get_type()
+    }
+
+    fn box_size(&self) -> u64 {
+        self.get_size()
+    }
+
+    fn summary(&self) -> Result<String> {
+        let s = format!("tenc={}", self.tenc.is_some());
+        Ok(s)
+    }
+}
+
+impl<R: Read + Seek> ReadBox<&mut R> for SchiBox {
+    fn read_box(reader: &mut R, size: u64) -> Result<Self> {
+        let start = box_start(reader)?;
+
+        let mut tenc = None;
+        let mut unknown_boxes = Vec::new();
+
+        let mut current = reader.stream_position()?;
+        let end = start + size;
+        while current < end {
+            // Get box header.
+            let header = BoxHeader::read(reader)?;
+            let BoxHeader { name, size: s } = header;
+            if s > size {
+                return Err(Error::InvalidData(
+                    "schi box contains a box with a larger size than it",
+                ));
+            }
+
+            if name == BoxType::TencBox && tenc.is_none() {
+                tenc = Some(TencBox::read_box(reader, s)?);
+            } else {
+                unknown_boxes.push(read_unknown_box(reader, name, s)?);
+            }
+
+            current = reader.stream_position()?;
+        }
+
+        skip_bytes_to(reader, start + size)?;
+
+        Ok(SchiBox {
+            tenc,
+            unknown_boxes,
+        })
+    }
+}
+
+impl<W: Write> WriteBox<&mut W> for SchiBox {
+    fn write_box(&self, writer: &mut W) -> Result<u64> {
+        let size = self.box_size();
+        BoxHeader::new(self.box_type(), size).write(writer)?;
+
+        if let Some(ref tenc) = self.tenc {
+            tenc.write_box(writer)?;
+        }
+        write_unknown_boxes(writer, &self.unknown_boxes)?;
+
+        Ok(size)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mp4box::BoxHeader;
+    use std::io::Cursor;
+
+    #[test]
+    fn test_sinf() {
+        let src_box = SinfBox {
+            frma: FrmaBox {
+                original_format: str::parse("avc1").unwrap(),
+            },
+            schm: Some(SchmBox {
+                scheme_type: SchmBox::CBCS,
+                scheme_version: 0x10000,
+                ..SchmBox::default()
+            }),
+            schi: Some(SchiBox {
+                tenc: Some(TencBox {
+                    version: 1,
+                    default_crypt_byte_block: 1,
+                    default_skip_byte_block: 9,
+                    default_is_protected: true,
+                    default_kid: [7; 16],
+                    default_constant_iv: vec![1; 16],
+                    ..TencBox::default()
+                }),
+                unknown_boxes: vec![],
+            }),
+            unknown_boxes: vec![],
+        };
+        let mut buf = Vec::new();
+        src_box.write_box(&mut buf).unwrap();
+        assert_eq!(buf.len(), src_box.box_size() as usize);
+
+        let mut reader = Cursor::new(&buf);
+        let header = BoxHeader::read(&mut reader).unwrap();
+        assert_eq!(header.name, BoxType::SinfBox);
+        assert_eq!(src_box.box_size(), header.size);
+
+        let dst_box = SinfBox::read_box(&mut reader, header.size).unwrap();
+        assert_eq!(src_box, dst_box);
+        assert_eq!(dst_box.tenc().unwrap().default_kid, [7; 16]);
+    }
+
+    #[test]
+    fn test_schm_uri() {
+        let src_box = SchmBox {
+            scheme_type: SchmBox::CENC,
+            scheme_version: 0x10000,
+            scheme_uri: Some("https://example.com/drm".to_string()),
+            flags: 1,
+            ..SchmBox::default()
+        };
+        let mut buf = Vec::new();
+        src_box.write_box(&mut buf).unwrap();
+        assert_eq!(buf.len(), src_box.box_size() as usize);
+
+        let mut reader = Cursor::new(&buf);
+        let header = BoxHeader::read(&mut reader).unwrap();
+        let dst_box = SchmBox::read_box(&mut reader, header.size).unwrap();
+        assert_eq!(src_box, dst_box);
+    }
+
+    #[test]
+    fn test_sinf_missing_frma() {
+        let schi = SchiBox::default();
+        let mut buf = Vec::new();
+        BoxHeader::new(BoxType::SinfBox, HEADER_SIZE + schi.box_size())
+            .write(&mut buf)
+            .unwrap();
+        schi.write_box(&mut buf).unwrap();
+
+        let mut reader = Cursor::new(&buf);
+        let header = BoxHeader::read(&mut reader).unwrap();
+        assert!(matches!(
+            SinfBox::read_box(&mut reader, header.size),
+            Err(Error::BoxNotFound(BoxType::FrmaBox))
+        ));
+    }
+}
@@ -2,8 +2,8 @@ use std::io::{Read, Seek, Write};
 
 use crate::mp4box::*;
 use crate::mp4box::{
-    co64::Co64Box, ctts::CttsBox, stco::StcoBox, stsc::StscBox, stsd::StsdBox, stss::StssBox,
-    stsz::StszBox, stts::SttsBox,
+    co64::Co64Box, ctts::CttsBox, sbgp::SbgpBox, sdtp::SdtpBox, sgpd::SgpdBox, stco::StcoBox,
+    stsc::StscBox, stsd::StsdBox, stss::StssBox, stsz::StszBox, stts::SttsBox, subs::SubsBox,
 };
 
 #[derive(Debug, Clone, PartialEq, Eq, Default)]
@@ -16,6 +16,17 @@ pub struct StblBox {
     pub stsz: StszBox,
     pub stco: Option<StcoBox>,
     pub co64: Option<Co64Box>,
+    pub sgpds: Vec<SgpdBox>,
+    pub sbgps: Vec<SbgpBox>,
+    pub sdtp: Option<SdtpBox>,
+    pub subs: Vec<SubsBox>,
+
+    /// Sizes and offsets of the sample auxiliary information, such as the
+    /// encryption parameters of each sample.
+    pub saizs: Vec<SaizBox>,
+    pub saios: Vec<SaioBox>,
+
+    pub unknown_boxes: Vec<(BoxType, Vec<u8>)>,
 }
 
 impl StblBox {
@@ -41,6 +52,25 @@ impl StblBox {
         if let Some(ref co64) = self.co64 {
             size += co64.box_size();
         }
+        for sgpd in self.sgpds.iter() {
+            size += sgpd.box_size();
+        }
+        for sbgp in self.sbgps.iter() {
+            size += sbgp.box_size();
+        }
+        if let Some(ref sdtp) = self.sdtp {
+            size += sdtp.box_size();
+        }
+        for subs in self.subs.iter() {
+            size += subs.box_size();
+        }
+        for saiz in self.saizs.iter() {
+            size += saiz.box_size();
+        }
+        for saio in self.saios.iter() {
+            size += saio.box_size();
+        }
+        size += unknown_boxes_size(&self.unknown_boxes);
         size
     }
 }
@@ -72,6 +102,13 @@ impl<R: Read + Seek> ReadBox<&mut R> for StblBox {
         let mut stsz = None;
         let mut stco = None;
         let mut co64 = None;
+        let mut sgpds = Vec::new();
+        let mut sbgps = Vec::new();
+        let mut sdtp = None;
+        let mut subs = Vec::new();
+        let mut saizs = Vec::new();
+        let mut saios = Vec::new();
+        let mut unknown_boxes = Vec::new();
 
         let mut current = reader.stream_position()?;
         let end = start + size;
@@ -110,9 +147,26 @@ impl<R: Read + Seek> ReadBox<&mut R> for StblBox {
                 BoxType::Co64Box => {
                     co64 = Some(Co64Box::read_box(reader, s)?);
                 }
+                BoxType::SgpdBox => {
+                    sgpds.push(SgpdBox::read_box(reader, s)?);
+                }
+                BoxType::SbgpBox => {
+                    sbgps.push(SbgpBox::read_box(reader, s)?);
+                }
+                BoxType::SdtpBox => {
+                    sdtp = Some(SdtpBox::read_box(reader, s)?);
+                }
+                BoxType::SubsBox => {
+                    subs.push(SubsBox::read_box(reader, s)?);
+                }
+                BoxType::SaizBox => {
+                    saizs.push(SaizBox::read_box(reader, s)?);
+                }
+                BoxType::SaioBox => {
+                    saios.push(SaioBox::read_box(reader, s)?);
+                }
                 _ => {
-                    // XXX warn!()
-                    skip_box(reader, s)?;
+                    unknown_boxes.push(read_unknown_box(reader, name, s)?);
                 }
             }
             current = reader.stream_position()?;
@@ -145,6 +199,13 @@ impl<R: Read + Seek> ReadBox<&mut R> for StblBox {
             stsz: stsz.unwrap(),
             stco,
             co64,
+            sgpds,
+            sbgps,
+            sdtp,
+            subs,
+            saizs,
+            saios,
+            unknown_boxes,
         })
     }
 }
@@ -170,6 +231,25 @@ impl<W: Write> WriteBox<&mut W> for StblBox {
         if let Some(ref co64) = self.co64 {
             co64.write_box(writer)?;
         }
+        for sgpd in self.sgpds.iter() {
+            sgpd.write_box(writer)?;
+        }
+        for sbgp in self.sbgps.iter() {
+            sbgp.write_box(writer)?;
+        }
+        if let Some(ref sdtp) = self.sdtp {
+            sdtp.write_box(writer)?;
+        }
+        for subs in self.subs.iter() {
+            subs.write_box(writer)?;
+        }
+        for saiz in self.saizs.iter() {
+            saiz.write_box(writer)?;
+        }
+        for saio in self.saios.iter() {
+            saio.write_box(writer)?;
+        }
+        write_unknown_boxes(writer, &self.unknown_boxes)?;
 
         Ok(size)
     }
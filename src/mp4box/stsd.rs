@@ -1,9 +1,13 @@
 use byteorder::{BigEndian, ReadBytesExt, WriteBytesExt};
-use std::io::{Read, Seek, Write};
+use std::io::{Read, Seek, SeekFrom, Write};
 
+use crate::mp4box::av01::Av01Box;
 use crate::mp4box::vp09::Vp09Box;
 use crate::mp4box::*;
-use crate::mp4box::{avc1::Avc1Box, hev1::Hev1Box, mp4a::Mp4aBox, tx3g::Tx3gBox};
+use crate::mp4box::{
+    ac3::Ac3Box, avc1::Avc1Box, ec3::Ec3Box, flac::FlacBox, hev1::Hev1Box, mp4a::Mp4aBox,
+    opus::OpusBox, tx3g::Tx3gBox,
+};
 
 #[derive(Debug, Clone, PartialEq, Eq, Default)]
 pub struct StsdBox {
@@ -12,8 +16,20 @@ pub struct StsdBox {
     pub avc1: Option<Avc1Box>,
     pub hev1: Option<Hev1Box>,
     pub vp09: Option<Vp09Box>,
+    pub av01: Option<Av01Box>,
     pub mp4a: Option<Mp4aBox>,
+    pub opus: Option<OpusBox>,
+    pub flac: Option<FlacBox>,
+    pub ac3: Option<Ac3Box>,
+    pub ec3: Option<Ec3Box>,
     pub tx3g: Option<Tx3gBox>,
+
+    /// The entries other than the parsed one, in file order.
+    pub unknown_boxes: Vec<(BoxType, Vec<u8>)>,
+
+    /// Position of the parsed entry among all entries, so that the sample
+    /// description indices of `stsc` keep pointing at the same entries.
+    pub entry_index: usize,
 }
 
 impl StsdBox {
@@ -29,11 +45,22 @@ impl StsdBox {
             size += hev1.box_size();
         } else if let Some(ref vp09) = self.vp09 {
             size += vp09.box_size();
+        } else if let Some(ref av01) = self.av01 {
+            size += av01.box_size();
         } else if let Some(ref mp4a) = self.mp4a {
             size += mp4a.box_size();
+        } else if let Some(ref opus) = self.opus {
+            size += opus.box_size();
+        } else if let Some(ref flac) = self.flac {
+            size += flac.box_size();
+        } else if let Some(ref ac3) = self.ac3 {
+            size += ac3.box_size();
+        } else if let Some(ref ec3) = self.ec3 {
+            size += ec3.box_size();
         } else if let Some(ref tx3g) = self.tx3g {
             size += tx3g.box_size();
         }
+        size += unknown_boxes_size(&self.unknown_boxes);
         size
     }
 }
@@ -59,40 +86,102 @@ impl<R: Read + Seek> ReadBox<&mut R> for StsdBox {
 
         let (version, flags) = read_box_header_ext(reader)?;
 
-        reader.read_u32::<BigEndian>()?; // XXX entry_count
+        let entry_count = reader.read_u32::<BigEndian>()?;
 
         let mut avc1 = None;
         let mut hev1 = None;
         let mut vp09 = None;
+        let mut av01 = None;
         let mut mp4a = None;
+        let mut opus = None;
+        let mut flac = None;
+        let mut ac3 = None;
+        let mut ec3 = None;
         let mut tx3g = None;
+        let mut unknown_boxes = Vec::new();
+        let mut entry_index = None;
 
-        // Get box header.
-        let header = BoxHeader::read(reader)?;
-        let BoxHeader { name, size: s } = header;
-        if s > size {
-            return Err(Error::InvalidData(
-                "stsd box contains a box with a larger size than it",
-            ));
-        }
-
-        match name {
-            BoxType::Avc1Box => {
-                avc1 = Some(Avc1Box::read_box(reader, s)?);
-            }
-            BoxType::Hev1Box => {
-                hev1 = Some(Hev1Box::read_box(reader, s)?);
+        let end = start + size;
+        for _ in 0..entry_count {
+            if reader.stream_position()? >= end {
+                break;
             }
-            BoxType::Vp09Box => {
-                vp09 = Some(Vp09Box::read_box(reader, s)?);
-            }
-            BoxType::Mp4aBox => {
-                mp4a = Some(Mp4aBox::read_box(reader, s)?);
+
+            // Get box header.
+            let header = BoxHeader::read(reader)?;
+            let BoxHeader { name, size: s } = header;
+            if s > size {
+                return Err(Error::InvalidData(
+                    "stsd box contains a box with a larger size than it",
+                ));
             }
-            BoxType::Tx3gBox => {
-                tx3g = Some(Tx3gBox::read_box(reader, s)?);
+
+            // Protected entries are parsed as their original format.
+            let format = match name {
+                BoxType::EncvBox | BoxType::EncaBox => {
+                    protected_original_format(reader, name, s)?.unwrap_or(name)
+                }
+                _ => name,
+            };
+
+            // Only the first entry of a known type is parsed, any others are
+            // kept as they are.
+            let parsed = match format {
+                BoxType::Avc1Box if entry_index.is_none() => {
+                    avc1 = Some(Avc1Box::read_box(reader, s)?);
+                    true
+                }
+                BoxType::Hev1Box if entry_index.is_none() => {
+                    hev1 = Some(Hev1Box::read_box(reader, s)?);
+                    true
+                }
+                BoxType::Hvc1Box if entry_index.is_none() => {
+                    hev1 = Some(Hev1Box {
+                        sample_entry: HevcSampleEntry::Hvc1,
+                        ..Hev1Box::read_box(reader, s)?
+                    });
+                    true
+                }
+                BoxType::Vp09Box if entry_index.is_none() => {
+                    vp09 = Some(Vp09Box::read_box(reader, s)?);
+                    true
+                }
+                BoxType::Av01Box if entry_index.is_none() => {
+                    av01 = Some(Av01Box::read_box(reader, s)?);
+                    true
+                }
+                BoxType::Mp4aBox if entry_index.is_none() => {
+                    mp4a = Some(Mp4aBox::read_box(reader, s)?);
+                    true
+                }
+                BoxType::OpusBox if entry_index.is_none() => {
+                    opus = Some(OpusBox::read_box(reader, s)?);
+                    true
+                }
+                BoxType::FlacBox if entry_index.is_none() => {
+                    flac = Some(FlacBox::read_box(reader, s)?);
+                    true
+                }
+                BoxType::Ac3Box if entry_index.is_none() => {
+                    ac3 = Some(Ac3Box::read_box(reader, s)?);
+                    true
+                }
+                BoxType::Ec3Box if entry_index.is_none() => {
+                    ec3 = Some(Ec3Box::read_box(reader, s)?);
+                    true
+                }
+                BoxType::Tx3gBox if entry_index.is_none() => {
+                    tx3g = Some(Tx3gBox::read_box(reader, s)?);
+                    true
+                }
+                _ => {
+                    unknown_boxes.push(read_unknown_box(reader, name, s)?);
+                    false
+                }
+            };
+            if parsed {
+                entry_index = Some(unknown_boxes.len());
             }
-            _ => {}
         }
 
         skip_bytes_to(reader, start + size)?;
@@ -103,8 +192,15 @@ impl<R: Read + Seek> ReadBox<&mut R> for StsdBox {
             avc1,
             hev1,
             vp09,
+            av01,
             mp4a,
+            opus,
+            flac,
+            ac3,
+            ec3,
             tx3g,
+            unknown_boxes,
+            entry_index: entry_index.unwrap_or(0),
         })
     }
 }
@@ -116,20 +212,272 @@ impl<W: Write> WriteBox<&mut W> for StsdBox {
 
         write_box_header_ext(writer, self.version, self.flags)?;
 
-        writer.write_u32::<BigEndian>(1)?; // entry_count
+        let has_entry = self.avc1.is_some()
+            || self.hev1.is_some()
+            || self.vp09.is_some()
+            || self.av01.is_some()
+            || self.mp4a.is_some()
+            || self.opus.is_some()
+            || self.flac.is_some()
+            || self.ac3.is_some()
+            || self.ec3.is_some()
+            || self.tx3g.is_some();
+        let entry_count = has_entry as usize + self.unknown_boxes.len();
+        writer.write_u32::<BigEndian>(entry_count as u32)?;
 
+        let (before, after) = self
+            .unknown_boxes
+            .split_at(self.entry_index.min(self.unknown_boxes.len()));
+        write_unknown_boxes(writer, before)?;
         if let Some(ref avc1) = self.avc1 {
             avc1.write_box(writer)?;
         } else if let Some(ref hev1) = self.hev1 {
             hev1.write_box(writer)?;
         } else if let Some(ref vp09) = self.vp09 {
             vp09.write_box(writer)?;
+        } else if let Some(ref av01) = self.av01 {
+            av01.write_box(writer)?;
         } else if let Some(ref mp4a) = self.mp4a {
             mp4a.write_box(writer)?;
+        } else if let Some(ref opus) = self.opus {
+            opus.write_box(writer)?;
+        } else if let Some(ref flac) = self.flac {
+            flac.write_box(writer)?;
+        } else if let Some(ref ac3) = self.ac3 {
+            ac3.write_box(writer)?;
+        } else if let Some(ref ec3) = self.ec3 {
+            ec3.write_box(writer)?;
         } else if let Some(ref tx3g) = self.tx3g {
             tx3g.write_box(writer)?;
         }
+        write_unknown_boxes(writer, after)?;
 
         Ok(size)
     }
 }
+
+/// The original format of an `encv` or `enca` entry whose header has just
+/// been read, from the `frma` in its `sinf`. The reader is left at the start
+/// of the entry payload.
+fn protected_original_format<R: Read + Seek>(
+    reader: &mut R,
+    name: BoxType,
+    size: u64,
+) -> Result<Option<BoxType>> {
+    let payload_start = reader.stream_position()?;
+    let end = payload_start + size.saturating_sub(HEADER_SIZE);
+
+    // Children start after the fields of the visual or audio sample entry,
+    // where version 1 and 2 audio entries have 16 and 36 bytes of QuickTime
+    // fields.
+    let children_offset = if name == BoxType::EncvBox {
+        78
+    } else if end - payload_start < 10 {
+        return Ok(None);
+    } else {
+        reader.seek(SeekFrom::Start(payload_start + 8))?;
+        match reader.read_u16::<BigEndian>()? {
+            1 => 44,
+            2 => 64,
+            _ => 28,
+        }
+    };
+
+    let mut format = None;
+    if let Some((sinf_start, sinf_end)) = find_child(
+        reader,
+        payload_start + children_offset,
+        end,
+        BoxType::SinfBox,
+    )? && let Some((frma_start, frma_end)) =
+        find_child(reader, sinf_start, sinf_end, BoxType::FrmaBox)?
+        && frma_end - frma_start >= 4
+    {
+        reader.seek(SeekFrom::Start(frma_start))?;
+        format = Some(BoxType::from(reader.read_u32::<BigEndian>()?));
+    }
+    reader.seek(SeekFrom::Start(payload_start))?;
+    Ok(format)
+}
+
+/// The payload range of the first child box of a type among the boxes
+/// between `start` and `end`.
+fn find_child<R: Read + Seek>(
+    reader: &mut R,
+    start: u64,
+    end: u64,
+    name: BoxType,
+) -> Result<Option<(u64, u64)>> {
+    let mut current = start;
+    while current + HEADER_SIZE <= end {
+        reader.seek(SeekFrom::Start(current))?;
+        let header = BoxHeader::read(reader)?;
+        let data_start = reader.stream_position()?;
+        if header.size < HEADER_SIZE
+            || data_start > end
+            || header.size - HEADER_SIZE > end - data_start
+        {
+            return Ok(None);
+        }
+        let data_end = data_start + header.size - HEADER_SIZE;
+        if header.name == name {
+            return Ok(Some((data_start, data_end)));
+        }
+        current = data_end;
+    }
+    Ok(None)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mp4box::BoxHeader;
+    use std::io::Cursor;
+
+    fn sinf(original_format: &str) -> SinfBox {
+        SinfBox {
+            frma: FrmaBox {
+                original_format: str::parse(original_format).unwrap(),
+            },
+            schm: Some(SchmBox {
+                scheme_type: SchmBox::CENC,
+                scheme_version: 0x10000,
+                ..SchmBox::default()
+            }),
+            ..SinfBox::default()
+        }
+    }
+
+    fn round_trip(src_box: &StsdBox) -> (Vec<u8>, StsdBox) {
+        let mut buf = Vec::new();
+        src_box.write_box(&mut buf).unwrap();
+        assert_eq!(buf.len(), src_box.box_size() as usize);
+
+        let mut reader = Cursor::new(&buf);
+        let header = BoxHeader::read(&mut reader).unwrap();
+        assert_eq!(header.name, BoxType::StsdBox);
+        let dst_box = StsdBox::read_box(&mut reader, header.size).unwrap();
+        (buf, dst_box)
+    }
+
+    #[test]
+    fn test_stsd_encv() {
+        let src_box = StsdBox {
+            avc1: Some(Avc1Box {
+                width: 320,
+                height: 240,
+                sinf: Some(sinf("avc1")),
+                ..Avc1Box::default()
+            }),
+            ..StsdBox::default()
+        };
+        let (buf, dst_box) = round_trip(&src_box);
+        assert_eq!(&buf[20..24], b"encv");
+        assert_eq!(src_box, dst_box);
+    }
+
+    #[test]
+    fn test_stsd_enca() {
+        let src_box = StsdBox {
+            mp4a: Some(Mp4aBox {
+                sinf: Some(sinf("mp4a")),
+                ..Mp4aBox::default()
+            }),
+            ..StsdBox::default()
+        };
+        let (buf, dst_box) = round_trip(&src_box);
+        assert_eq!(&buf[20..24], b"enca");
+        assert_eq!(src_box, dst_box);
+    }
+
+    #[test]
+    fn test_stsd_enca_quicktime_v2() {
+        let src_box = StsdBox {
+            mp4a: Some(Mp4aBox {
+                version: 2,
+                qt_fields: vec![1; 36],
+                sinf: Some(sinf("mp4a")),
+                ..Mp4aBox::default()
+            }),
+            ..StsdBox::default()
+        };
+        let (buf, dst_box) = round_trip(&src_box);
+        assert_eq!(&buf[20..24], b"enca");
+        assert_eq!(src_box, dst_box);
+    }
+
+    #[test]
+    fn test_stsd_hvc1() {
+        let src_box = StsdBox {
+            hev1: Some(Hev1Box {
+                sample_entry: HevcSampleEntry::Hvc1,
+                ..Hev1Box::default()
+            }),
+            ..StsdBox::default()
+        };
+        let (buf, dst_box) = round_trip(&src_box);
+        assert_eq!(&buf[20..24], b"hvc1");
+        assert_eq!(src_box, dst_box);
+
+        let src_box = StsdBox {
+            hev1: Some(Hev1Box {
+                sample_entry: HevcSampleEntry::Hvc1,
+                sinf: Some(sinf("hvc1")),
+                ..Hev1Box::default()
+            }),
+            ..StsdBox::default()
+        };
+        let (buf, dst_box) = round_trip(&src_box);
+        assert_eq!(&buf[20..24], b"encv");
+        assert_eq!(src_box, dst_box);
+    }
+
+    #[test]
+    fn test_stsd_entry_order() {
+        let vp08 = Vp09Box::default();
+        let mut vp08_entry = Vec::new();
+        vp08.write_box(&mut vp08_entry).unwrap();
+        let av01 = Av01Box {
+            sinf: Some(sinf("av01")),
+            ..Av01Box::default()
+        };
+        let mut av01_entry = Vec::new();
+        av01.write_box(&mut av01_entry).unwrap();
+
+        // Only the first known entry is parsed, the others keep their place.
+        let src_box = StsdBox {
+            avc1: Some(Avc1Box::default()),
+            unknown_boxes: vec![
+                (
+                    BoxType::from(u32::from_be_bytes(*b"vp08")),
+                    vp08_entry[8..].to_vec(),
+                ),
+                (BoxType::EncvBox, av01_entry[8..].to_vec()),
+            ],
+            entry_index: 1,
+            ..StsdBox::default()
+        };
+        let (buf, dst_box) = round_trip(&src_box);
+        assert_eq!(&buf[20..24], b"vp08");
+        assert_eq!(&buf[20 + vp08_entry.len()..24 + vp08_entry.len()], b"avc1");
+        assert_eq!(&buf[buf.len() - av01_entry.len()..], av01_entry);
+        assert_eq!(src_box, dst_box);
+    }
+
+    #[test]
+    fn test_stsd_encv_unknown_format() {
+        // An encrypted entry of a format that is not parsed is kept as is.
+        let avc1 = Avc1Box {
+            sinf: Some(sinf("vp08")),
+            ..Avc1Box::default()
+        };
+        let mut entry = Vec::new();
+        avc1.write_box(&mut entry).unwrap();
+        let src_box = StsdBox {
+            unknown_boxes: vec![(BoxType::EncvBox, entry[8..].to_vec())],
+            ..StsdBox::default()
+        };
+        let (_, dst_box) = round_trip(&src_box);
+        assert_eq!(src_box, dst_box);
+    }
+}
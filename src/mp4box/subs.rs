@@ -0,0 +1,230 @@
+use byteorder::{BigEndian, ReadBytesExt, WriteBytesExt};
+use std::io::{Read, Seek, Write};
+
+use crate::mp4box::*;
+
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct SubsBox {
+    pub version: u8,
+
+    /// Codec specific, for example whether the subsamples of an HEVC track
+    /// are NAL units or slices.
+    pub flags: u32,
+
+    pub entries: Vec<SubsEntry>,
+}
+
+/// The subsamples of one sample.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct SubsEntry {
+    /// Difference between the sample number of this entry and the previous
+    /// one, or the sample number itself for the first entry.
+    pub sample_delta: u32,
+
+    pub subsamples: Vec<SubsSubsample>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct SubsSubsample {
+    pub subsample_size: u32,
+    pub subsample_priority: u8,
+    pub discardable: bool,
+    pub codec_specific_parameters: u32,
+}
+
+impl SubsBox {
+    pub fn get_type(&self) -> BoxType {
+        BoxType::SubsBox
+    }
+
+    pub fn get_size(&self) -> u64 {
+        let subsample_size = if self.version == 1 { 10 } else { 8 };
+        let mut size = HEADER_SIZE + HEADER_EXT_SIZE + 4;
+        for entry in self.entries.iter() {
+            size += 6 + entry.subsamples.len() as u64 * subsample_size;
+        }
+        size
+    }
+
+    /// The subsamples of a one-based sample number.
+    pub fn entry(&self, sample_number: u32) -> Option<&SubsEntry> {
+        let mut current = 0u32;
+        for entry in self.entries.iter() {
+            current = current.checked_add(entry.sample_delta)?;
+            if current >= sample_number {
+                return (current == sample_number).then_some(entry);
+            }
+        }
+        None
+    }
+}
+
+impl Mp4Box for SubsBox {
+    fn box_type(&self) -> BoxType {
+        self.get_type()
+    }
+
+    fn box_size(&self) -> u64 {
+        self.get_size()
+    }
+
+    fn summary(&self) -> Result<String> {
+        let s = format!("entries={}", self.entries.len());
+        Ok(s)
+    }
+}
+
+impl<R: Read + Seek> ReadBox<&mut R> for SubsBox {
+    fn read_box(reader: &mut R, size: u64) -> Result<Self> {
+        let start = box_start(reader)?;
+
+        let (version, flags) = read_box_header_ext(reader)?;
+        let entry_count = reader.read_u32::<BigEndian>()?;
+
+        let header_size = reader.stream_position()? - start;
+        if u64::from(entry_count) > size.saturating_sub(header_size) / 6 {
+            return Err(Error::InvalidData(
+                "subs entry_count indicates more entries than could fit in the box",
+            ));
+        }
+        let mut entries = Vec::with_capacity(entry_count as usize);
+        for _ in 0..entry_count {
+            let sample_delta = reader.read_u32::<BigEndian>()?;
+            let subsample_count = reader.read_u16::<BigEndian>()?;
+            let mut subsamples = Vec::with_capacity(subsample_count as usize);
+            for _ in 0..subsample_count {
+                let subsample_size = if version == 1 {
+                    reader.read_u32::<BigEndian>()?
+                } else {
+                    reader.read_u16::<BigEndian>()? as u32
+                };
+                subsamples.push(SubsSubsample {
+                    subsample_size,
+                    subsample_priority: reader.read_u8()?,
+                    discardable: reader.read_u8()? != 0,
+                    codec_specific_parameters: reader.read_u32::<BigEndian>()?,
+                });
+            }
+            entries.push(SubsEntry {
+                sample_delta,
+                subsamples,
+            });
+        }
+
+        skip_bytes_to(reader, start + size)?;
+
+        Ok(SubsBox {
+            version,
+            flags,
+            entries,
+        })
+    }
+}
+
+impl<W: Write> WriteBox<&mut W> for SubsBox {
+    fn write_box(&self, writer: &mut W) -> Result<u64> {
+        let size = self.box_size();
+        BoxHeader::new(self.box_type(), size).write(writer)?;
+
+        write_box_header_ext(writer, self.version, self.flags)?;
+
+        writer.write_u32::<BigEndian>(self.entries.len() as u32)?;
+        for entry in self.entries.iter() {
+            writer.write_u32::<BigEndian>(entry.sample_delta)?;
+            let subsample_count = u16::try_from(entry.subsamples.len())
+                .map_err(|_| Error::InvalidData("too many subsamples in a sample"))?;
+            writer.write_u16::<BigEndian>(subsample_count)?;
+            for subsample in entry.subsamples.iter() {
+                if self.version == 1 {
+                    writer.write_u32::<BigEndian>(subsample.subsample_size)?;
+                } else {
+                    let subsample_size = u16::try_from(subsample.subsample_size).map_err(|_| {
+                        Error::InvalidData("subsample size requires subs version 1")
+                    })?;
+                    writer.write_u16::<BigEndian>(subsample_size)?;
+                }
+                writer.write_u8(subsample.subsample_priority)?;
+                writer.write_u8(subsample.discardable as u8)?;
+                writer.write_u32::<BigEndian>(subsample.codec_specific_parameters)?;
+            }
+        }
+
+        Ok(size)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mp4box::BoxHeader;
+    use std::io::Cursor;
+
+    fn subsample(subsample_size: u32, discardable: bool) -> SubsSubsample {
+        SubsSubsample {
+            subsample_size,
+            subsample_priority: 0,
+            discardable,
+            codec_specific_parameters: 0,
+        }
+    }
+
+    #[test]
+    fn test_subs() {
+        let src_box = SubsBox {
+            version: 0,
+            flags: 0,
+            entries: vec![
+                SubsEntry {
+                    sample_delta: 1,
+                    subsamples: vec![subsample(20, false), subsample(300, true)],
+                },
+                SubsEntry {
+                    sample_delta: 3,
+                    subsamples: vec![subsample(50, false)],
+                },
+            ],
+        };
+        let mut buf = Vec::new();
+        src_box.write_box(&mut buf).unwrap();
+        assert_eq!(buf.len(), src_box.box_size() as usize);
+
+        let mut reader = Cursor::new(&buf);
+        let header = BoxHeader::read(&mut reader).unwrap();
+        assert_eq!(header.name, BoxType::SubsBox);
+        assert_eq!(src_box.box_size(), header.size);
+
+        let dst_box = SubsBox::read_box(&mut reader, header.size).unwrap();
+        assert_eq!(src_box, dst_box);
+
+        assert_eq!(dst_box.entry(1).unwrap().subsamples.len(), 2);
+        assert!(dst_box.entry(2).is_none());
+        assert_eq!(dst_box.entry(4).unwrap().subsamples.len(), 1);
+        assert!(dst_box.entry(5).is_none());
+    }
+
+    #[test]
+    fn test_subs_v1() {
+        let src_box = SubsBox {
+            version: 1,
+            flags: 0,
+            entries: vec![SubsEntry {
+                sample_delta: 2,
+                subsamples: vec![subsample(100_000, false)],
+            }],
+        };
+        let mut buf = Vec::new();
+        src_box.write_box(&mut buf).unwrap();
+        assert_eq!(buf.len(), src_box.box_size() as usize);
+
+        let mut reader = Cursor::new(&buf);
+        let header = BoxHeader::read(&mut reader).unwrap();
+        let dst_box = SubsBox::read_box(&mut reader, header.size).unwrap();
+        assert_eq!(src_box, dst_box);
+
+        let src_box = SubsBox {
+            version: 0,
+            ..src_box
+        };
+        assert!(src_box.write_box(&mut Vec::new()).is_err());
+    }
+}
@@ -0,0 +1,194 @@
+use byteorder::{ReadBytesExt, WriteBytesExt};
+use std::io::{Read, Seek, Write};
+
+use crate::mp4box::*;
+
+/// Default encryption parameters of a track, see ISO/IEC 23001-7 8.2.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct TencBox {
+    pub version: u8,
+    pub flags: u32,
+
+    /// Number of encrypted 16-byte blocks of the pattern. Only used from
+    /// version 1 on.
+    pub default_crypt_byte_block: u8,
+
+    /// Number of unencrypted 16-byte blocks of the pattern. Only used from
+    /// version 1 on.
+    pub default_skip_byte_block: u8,
+
+    pub default_is_protected: bool,
+
+    /// Size of the IV stored for each sample, 0, 8 or 16. If 0, protected
+    /// samples use `default_constant_iv`.
+    pub default_per_sample_iv_size: u8,
+
+    pub default_kid: [u8; 16],
+
+    /// Stored if the samples are protected and have no per-sample IV.
+    pub default_constant_iv: Vec<u8>,
+}
+
+impl TencBox {
+    pub fn get_type(&self) -> BoxType {
+        BoxType::TencBox
+    }
+
+    pub fn get_size(&self) -> u64 {
+        let mut size = HEADER_SIZE + HEADER_EXT_SIZE + 20;
+        if self.has_constant_iv() {
+            size += 1 + self.default_constant_iv.len() as u64;
+        }
+        size
+    }
+
+    fn has_constant_iv(&self) -> bool {
+        self.default_is_protected && self.default_per_sample_iv_size == 0
+    }
+}
+
+impl Mp4Box for TencBox {
+    fn box_type(&self) -> BoxType {
+        self.get_type()
+    }
+
+    fn box_size(&self) -> u64 {
+        self.get_size()
+    }
+
+    fn summary(&self) -> Result<String> {
+        let s = format!(
+            "is_protected={} per_sample_iv_size={} pattern={}:{}",
+            self.default_is_protected,
+            self.default_per_sample_iv_size,
+            self.default_crypt_byte_block,
+            self.default_skip_byte_block
+        );
+        Ok(s)
+    }
+}
+
+impl<R: Read + Seek> ReadBox<&mut R> for TencBox {
+    fn read_box(reader: &mut R, size: u64) -> Result<Self> {
+        let start = box_start(reader)?;
+
+        let (version, flags) = read_box_header_ext(reader)?;
+        reader.read_u8()?; // reserved
+        let pattern = reader.read_u8()?;
+        let (default_crypt_byte_block, default_skip_byte_block) = if version >= 1 {
+            (pattern >> 4, pattern & 0xF)
+        } else {
+            (0, 0)
+        };
+        let default_is_protected = reader.read_u8()? != 0;
+        let default_per_sample_iv_size = reader.read_u8()?;
+        let mut default_kid = [0u8; 16];
+        reader.read_exact(&mut default_kid)?;
+
+        let mut tenc = TencBox {
+            version,
+            flags,
+            default_crypt_byte_block,
+            default_skip_byte_block,
+            default_is_protected,
+            default_per_sample_iv_size,
+            default_kid,
+            default_constant_iv: Vec::new(),
+        };
+        if tenc.has_constant_iv() {
+            let iv_size = reader.read_u8()?;
+            tenc.default_constant_iv = vec![0; iv_size as usize];
+            reader.read_exact(&mut tenc.default_constant_iv)?;
+        }
+
+        skip_bytes_to(reader, start + size)?;
+
+        Ok(tenc)
+    }
+}
+
+impl<W: Write> WriteBox<&mut W> for TencBox {
+    fn write_box(&self, writer: &mut W) -> Result<u64> {
+        let size = self.box_size();
+        BoxHeader::new(self.box_type(), size).write(writer)?;
+
+        write_box_header_ext(writer, self.version, self.flags)?;
+        writer.write_u8(0)?; // reserved
+        if self.version >= 1 {
+            writer.write_u8(
+                (self.default_crypt_byte_block & 0xF) << 4 | self.default_skip_byte_block & 0xF,
+            )?;
+        } else {
+            writer.write_u8(0)?; // reserved
+        }
+        writer.write_u8(self.default_is_protected as u8)?;
+        writer.write_u8(self.default_per_sample_iv_size)?;
+        writer.write_all(&self.default_kid)?;
+        if self.has_constant_iv() {
+            let iv_size = u8::try_from(self.default_constant_iv.len())
+                .map_err(|_| Error::InvalidData("tenc constant IV is too long"))?;
+            writer.write_u8(iv_size)?;
+            writer.write_all(&self.default_constant_iv)?;
+        }
+
+        Ok(size)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mp4box::BoxHeader;
+    use std::io::Cursor;
+
+    #[test]
+    fn test_tenc() {
+        let src_box = TencBox {
+            version: 0,
+            flags: 0,
+            default_crypt_byte_block: 0,
+            default_skip_byte_block: 0,
+            default_is_protected: true,
+            default_per_sample_iv_size: 8,
+            default_kid: [
+                0x90, 0x5e, 0x58, 0x41, 0x8c, 0x32, 0x4f, 0x72, 0x9b, 0x2d, 0x3c, 0x50, 0x12, 0x7a,
+                0x0e, 0x05,
+            ],
+            default_constant_iv: vec![],
+        };
+        let mut buf = Vec::new();
+        src_box.write_box(&mut buf).unwrap();
+        assert_eq!(buf.len(), src_box.box_size() as usize);
+
+        let mut reader = Cursor::new(&buf);
+        let header = BoxHeader::read(&mut reader).unwrap();
+        assert_eq!(header.name, BoxType::TencBox);
+        assert_eq!(src_box.box_size(), header.size);
+
+        let dst_box = TencBox::read_box(&mut reader, header.size).unwrap();
+        assert_eq!(src_box, dst_box);
+    }
+
+    #[test]
+    fn test_tenc_constant_iv() {
+        let src_box = TencBox {
+            version: 1,
+            flags: 0,
+            default_crypt_byte_block: 1,
+            default_skip_byte_block: 9,
+            default_is_protected: true,
+            default_per_sample_iv_size: 0,
+            default_kid: [1; 16],
+            default_constant_iv: vec![2; 16],
+        };
+        let mut buf = Vec::new();
+        src_box.write_box(&mut buf).unwrap();
+        assert_eq!(buf.len(), src_box.box_size() as usize);
+        assert_eq!(buf[13], 0x19);
+
+        let mut reader = Cursor::new(&buf);
+        let header = BoxHeader::read(&mut reader).unwrap();
+        let dst_box = TencBox::read_box(&mut reader, header.size).unwrap();
+        assert_eq!(src_box, dst_box);
+    }
+}
@@ -0,0 +1,275 @@
+use byteorder::{BigEndian, ReadBytesExt, WriteBytesExt};
+use std::io::{Read, Seek, Write};
+
+use crate::mp4box::*;
+
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct TfraBox {
+    pub version: u8,
+    pub flags: u32,
+    pub track_id: u32,
+
+    /// Size minus one, in bytes, of the `traf_number` of each entry.
+    pub length_size_of_traf_num: u8,
+
+    /// Size minus one, in bytes, of the `trun_number` of each entry.
+    pub length_size_of_trun_num: u8,
+
+    /// Size minus one, in bytes, of the `sample_number` of each entry.
+    pub length_size_of_sample_num: u8,
+
+    pub entries: Vec<TfraEntry>,
+}
+
+/// A random access sample of a track.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct TfraEntry {
+    /// Presentation time of the sample, in the timescale of the track.
+    pub time: u64,
+
+    /// File offset of the `moof` holding the sample.
+    pub moof_offset: u64,
+
+    /// One-based index of the `traf` in the `moof`.
+    pub traf_number: u32,
+
+    /// One-based index of the `trun` in the `traf`.
+    pub trun_number: u32,
+
+    /// One-based index of the sample in the `trun`.
+    pub sample_number: u32,
+}
+
+impl TfraBox {
+    pub fn get_type(&self) -> BoxType {
+        BoxType::TfraBox
+    }
+
+    pub fn get_size(&self) -> u64 {
+        let mut entry_size = if self.version == 1 { 16 } else { 8 };
+        entry_size += self.length_size_of_traf_num as u64 + 1;
+        entry_size += self.length_size_of_trun_num as u64 + 1;
+        entry_size += self.length_size_of_sample_num as u64 + 1;
+        HEADER_SIZE + HEADER_EXT_SIZE + 12 + self.entries.len() as u64 * entry_size
+    }
+
+    /// The last entry at or before `time`, or the first entry if there is none.
+    pub fn find_entry(&self, time: u64) -> Option<&TfraEntry> {
+        self.entries
+            .iter()
+            .rev()
+            .find(|entry| entry.time <= time)
+            .or(self.entries.first())
+    }
+}
+
+impl Mp4Box for TfraBox {
+    fn box_type(&self) -> BoxType {
+        self.get_type()
+    }
+
+    fn box_size(&self) -> u64 {
+        self.get_size()
+    }
+
+    fn summary(&self) -> Result<String> {
+        let s = format!("track_id={} entries={}", self.track_id, self.entries.len());
+        Ok(s)
+    }
+}
+
+fn read_number<R: Read>(reader: &mut R, length_size: u8) -> Result<u32> {
+    Ok(reader.read_uint::<BigEndian>(length_size as usize + 1)? as u32)
+}
+
+fn write_number<W: Write>(writer: &mut W, length_size: u8, value: u32) -> Result<()> {
+    let bytes = length_size as usize + 1;
+    if bytes < 4 && value >> (bytes * 8) != 0 {
+        return Err(Error::InvalidData(
+            "tfra number does not fit its length size",
+        ));
+    }
+    writer.write_uint::<BigEndian>(value as u64, bytes)?;
+    Ok(())
+}
+
+impl<R: Read + Seek> ReadBox<&mut R> for TfraBox {
+    fn read_box(reader: &mut R, size: u64) -> Result<Self> {
+        let start = box_start(reader)?;
+
+        let (version, flags) = read_box_header_ext(reader)?;
+        if version > 1 {
+            return Err(Error::InvalidData("version must be 0 or 1"));
+        }
+
+        let track_id = reader.read_u32::<BigEndian>()?;
+        let length_sizes = reader.read_u32::<BigEndian>()?;
+        let length_size_of_traf_num = ((length_sizes >> 4) & 0x3) as u8;
+        let length_size_of_trun_num = ((length_sizes >> 2) & 0x3) as u8;
+        let length_size_of_sample_num = (length_sizes & 0x3) as u8;
+        let entry_count = reader.read_u32::<BigEndian>()?;
+
+        let mut tfra = TfraBox {
+            version,
+            flags,
+            track_id,
+            length_size_of_traf_num,
+            length_size_of_trun_num,
+            length_size_of_sample_num,
+            entries: Vec::new(),
+        };
+
+        let entry_size = (tfra.get_size() - (HEADER_SIZE + HEADER_EXT_SIZE + 12)).max(1);
+        let header_size = reader.stream_position()? - start;
+        if u64::from(entry_count) > size.saturating_sub(header_size) / entry_size {
+            return Err(Error::InvalidData(
+                "tfra entry_count indicates more entries than could fit in the box",
+            ));
+        }
+        tfra.entries.reserve(entry_count as usize);
+        for _ in 0..entry_count {
+            let (time, moof_offset) = if version == 1 {
+                (
+                    reader.read_u64::<BigEndian>()?,
+                    reader.read_u64::<BigEndian>()?,
+                )
+            } else {
+                (
+                    reader.read_u32::<BigEndian>()? as u64,
+                    reader.read_u32::<BigEndian>()? as u64,
+                )
+            };
+            tfra.entries.push(TfraEntry {
+                time,
+                moof_offset,
+                traf_number: read_number(reader, length_size_of_traf_num)?,
+                trun_number: read_number(reader, length_size_of_trun_num)?,
+                sample_number: read_number(reader, length_size_of_sample_num)?,
+            });
+        }
+
+        skip_bytes_to(reader, start + size)?;
+
+        Ok(tfra)
+    }
+}
+
+impl<W: Write> WriteBox<&mut W> for TfraBox {
+    fn write_box(&self, writer: &mut W) -> Result<u64> {
+        let size = self.box_size();
+        BoxHeader::new(self.box_type(), size).write(writer)?;
+
+        write_box_header_ext(writer, self.version, self.flags)?;
+
+        writer.write_u32::<BigEndian>(self.track_id)?;
+        writer.write_u32::<BigEndian>(
+            (self.length_size_of_traf_num as u32 & 0x3) << 4
+                | (self.length_size_of_trun_num as u32 & 0x3) << 2
+                | self.length_size_of_sample_num as u32 & 0x3,
+        )?;
+        writer.write_u32::<BigEndian>(self.entries.len() as u32)?;
+        for entry in self.entries.iter() {
+            if self.version == 1 {
+                writer.write_u64::<BigEndian>(entry.time)?;
+                writer.write_u64::<BigEndian>(entry.moof_offset)?;
+            } else {
+                writer.write_u32::<BigEndian>(entry.time as u32)?;
+                writer.write_u32::<BigEndian>(entry.moof_offset as u32)?;
+            }
+            write_number(writer, self.length_size_of_traf_num, entry.traf_number)?;
+            write_number(writer, self.length_size_of_trun_num, entry.trun_number)?;
+            write_number(writer, self.length_size_of_sample_num, entry.sample_number)?;
+        }
+
+        Ok(size)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mp4box::BoxHeader;
+    use std::io::Cursor;
+
+    #[test]
+    fn test_tfra() {
+        let src_box = TfraBox {
+            version: 0,
+            flags: 0,
+            track_id: 1,
+            length_size_of_traf_num: 0,
+            length_size_of_trun_num: 1,
+            length_size_of_sample_num: 2,
+            entries: vec![
+                TfraEntry {
+                    time: 0,
+                    moof_offset: 1000,
+                    traf_number: 1,
+                    trun_number: 1,
+                    sample_number: 1,
+                },
+                TfraEntry {
+                    time: 90000,
+                    moof_offset: 50000,
+                    traf_number: 2,
+                    trun_number: 300,
+                    sample_number: 70000,
+                },
+            ],
+        };
+        let mut buf = Vec::new();
+        src_box.write_box(&mut buf).unwrap();
+        assert_eq!(buf.len(), src_box.box_size() as usize);
+
+        let mut reader = Cursor::new(&buf);
+        let header = BoxHeader::read(&mut reader).unwrap();
+        assert_eq!(header.name, BoxType::TfraBox);
+        assert_eq!(src_box.box_size(), header.size);
+
+        let dst_box = TfraBox::read_box(&mut reader, header.size).unwrap();
+        assert_eq!(src_box, dst_box);
+
+        assert_eq!(dst_box.find_entry(0).unwrap().moof_offset, 1000);
+        assert_eq!(dst_box.find_entry(89999).unwrap().moof_offset, 1000);
+        assert_eq!(dst_box.find_entry(100000).unwrap().moof_offset, 50000);
+    }
+
+    #[test]
+    fn test_tfra64() {
+        let src_box = TfraBox {
+            version: 1,
+            flags: 0,
+            track_id: 2,
+            length_size_of_traf_num: 3,
+            length_size_of_trun_num: 3,
+            length_size_of_sample_num: 3,
+            entries: vec![TfraEntry {
+                time: 1 << 40,
+                moof_offset: 1 << 33,
+                traf_number: 1,
+                trun_number: 1,
+                sample_number: u32::MAX,
+            }],
+        };
+        let mut buf = Vec::new();
+        src_box.write_box(&mut buf).unwrap();
+        assert_eq!(buf.len(), src_box.box_size() as usize);
+
+        let mut reader = Cursor::new(&buf);
+        let header = BoxHeader::read(&mut reader).unwrap();
+        let dst_box = TfraBox::read_box(&mut reader, header.size).unwrap();
+        assert_eq!(src_box, dst_box);
+    }
+
+    #[test]
+    fn test_tfra_number_too_large() {
+        let tfra = TfraBox {
+            entries: vec![TfraEntry {
+                sample_number: 256,
+                ..TfraEntry::default()
+            }],
+            ..TfraBox::default()
+        };
+        assert!(tfra.write_box(&mut Vec::new()).is_err());
+    }
+}
@@ -1,13 +1,30 @@
 use std::io::{Read, Seek, Write};
 
 use crate::mp4box::*;
-use crate::mp4box::{tfdt::TfdtBox, tfhd::TfhdBox, trun::TrunBox};
+use crate::mp4box::{
+    sbgp::SbgpBox, sdtp::SdtpBox, sgpd::SgpdBox, subs::SubsBox, tfdt::TfdtBox, tfhd::TfhdBox,
+    trun::TrunBox,
+};
 
 #[derive(Debug, Clone, PartialEq, Eq, Default)]
 pub struct TrafBox {
     pub tfhd: TfhdBox,
     pub tfdt: Option<TfdtBox>,
-    pub trun: Option<TrunBox>,
+    pub truns: Vec<TrunBox>,
+    pub sgpds: Vec<SgpdBox>,
+    pub sbgps: Vec<SbgpBox>,
+    pub sdtp: Option<SdtpBox>,
+    pub subs: Vec<SubsBox>,
+
+    /// Sizes and offsets of the sample auxiliary information, such as the
+    /// encryption parameters of each sample.
+    pub saizs: Vec<SaizBox>,
+    pub saios: Vec<SaioBox>,
+
+    /// Encryption parameters of each sample of the fragment.
+    pub senc: Option<SencBox>,
+
+    pub unknown_boxes: Vec<(BoxType, Vec<u8>)>,
 }
 
 impl TrafBox {
@@ -21,11 +38,61 @@ impl TrafBox {
         if let Some(ref tfdt) = self.tfdt {
             size += tfdt.box_size();
         }
-        if let Some(ref trun) = self.trun {
+        for trun in self.truns.iter() {
             size += trun.box_size();
         }
+        for sgpd in self.sgpds.iter() {
+            size += sgpd.box_size();
+        }
+        for sbgp in self.sbgps.iter() {
+            size += sbgp.box_size();
+        }
+        if let Some(ref sdtp) = self.sdtp {
+            size += sdtp.box_size();
+        }
+        for subs in self.subs.iter() {
+            size += subs.box_size();
+        }
+        if let Some(ref senc) = self.senc {
+            size += senc.box_size();
+        }
+        for saiz in self.saizs.iter() {
+            size += saiz.box_size();
+        }
+        for saio in self.saios.iter() {
+            size += saio.box_size();
+        }
+        size += unknown_boxes_size(&self.unknown_boxes);
         size
     }
+
+    /// Offset of the first `senc` entry from the start of the box, as it is
+    /// written, which `saio` offsets point at.
+    pub fn senc_entries_offset(&self) -> Option<u64> {
+        self.senc.as_ref()?;
+        let mut offset = HEADER_SIZE;
+        offset += self.tfhd.box_size();
+        if let Some(ref tfdt) = self.tfdt {
+            offset += tfdt.box_size();
+        }
+        for trun in self.truns.iter() {
+            offset += trun.box_size();
+        }
+        for sgpd in self.sgpds.iter() {
+            offset += sgpd.box_size();
+        }
+        for sbgp in self.sbgps.iter() {
+            offset += sbgp.box_size();
+        }
+        if let Some(ref sdtp) = self.sdtp {
+            offset += sdtp.box_size();
+        }
+        for subs in self.subs.iter() {
+            offset += subs.box_size();
+        }
+        // The senc header and sample_count.
+        Some(offset + HEADER_SIZE + HEADER_EXT_SIZE + 4)
+    }
 }
 
 impl Mp4Box for TrafBox {
@@ -49,7 +116,15 @@ impl<R: Read + Seek> ReadBox<&mut R> for TrafBox {
 
         let mut tfhd = None;
         let mut tfdt = None;
-        let mut trun = None;
+        let mut truns = Vec::new();
+        let mut sgpds = Vec::new();
+        let mut sbgps = Vec::new();
+        let mut sdtp = None;
+        let mut subs = Vec::new();
+        let mut saizs = Vec::new();
+        let mut saios = Vec::new();
+        let mut senc = None;
+        let mut unknown_boxes = Vec::new();
 
         let mut current = reader.stream_position()?;
         let end = start + size;
@@ -71,11 +146,31 @@ impl<R: Read + Seek> ReadBox<&mut R> for TrafBox {
                     tfdt = Some(TfdtBox::read_box(reader, s)?);
                 }
                 BoxType::TrunBox => {
-                    trun = Some(TrunBox::read_box(reader, s)?);
+                    truns.push(TrunBox::read_box(reader, s)?);
+                }
+                BoxType::SgpdBox => {
+                    sgpds.push(SgpdBox::read_box(reader, s)?);
+                }
+                BoxType::SbgpBox => {
+                    sbgps.push(SbgpBox::read_box(reader, s)?);
+                }
+                BoxType::SdtpBox => {
+                    sdtp = Some(SdtpBox::read_box(reader, s)?);
+                }
+                BoxType::SubsBox => {
+                    subs.push(SubsBox::read_box(reader, s)?);
+                }
+                BoxType::SencBox => {
+                    senc = Some(SencBox::read_box(reader, s)?);
+                }
+                BoxType::SaizBox => {
+                    saizs.push(SaizBox::read_box(reader, s)?);
+                }
+                BoxType::SaioBox => {
+                    saios.push(SaioBox::read_box(reader, s)?);
                 }
                 _ => {
-                    // XXX warn!()
-                    skip_box(reader, s)?;
+                    unknown_boxes.push(read_unknown_box(reader, name, s)?);
                 }
             }
 
@@ -91,7 +186,15 @@ impl<R: Read + Seek> ReadBox<&mut R> for TrafBox {
         Ok(TrafBox {
             tfhd: tfhd.unwrap(),
             tfdt,
-            trun,
+            truns,
+            sgpds,
+            sbgps,
+            sdtp,
+            subs,
+            saizs,
+            saios,
+            senc,
+            unknown_boxes,
         })
     }
 }
@@ -105,9 +208,31 @@ impl<W: Write> WriteBox<&mut W> for TrafBox {
         if let Some(ref tfdt) = self.tfdt {
             tfdt.write_box(writer)?;
         }
-        if let Some(ref trun) = self.trun {
+        for trun in self.truns.iter() {
             trun.write_box(writer)?;
         }
+        for sgpd in self.sgpds.iter() {
+            sgpd.write_box(writer)?;
+        }
+        for sbgp in self.sbgps.iter() {
+            sbgp.write_box(writer)?;
+        }
+        if let Some(ref sdtp) = self.sdtp {
+            sdtp.write_box(writer)?;
+        }
+        for subs in self.subs.iter() {
+            subs.write_box(writer)?;
+        }
+        if let Some(ref senc) = self.senc {
+            senc.write_box(writer)?;
+        }
+        for saiz in self.saizs.iter() {
+            saiz.write_box(writer)?;
+        }
+        for saio in self.saios.iter() {
+            saio.write_box(writer)?;
+        }
+        write_unknown_boxes(writer, &self.unknown_boxes)?;
 
         Ok(size)
     }
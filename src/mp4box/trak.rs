@@ -10,6 +10,7 @@ pub struct TrakBox {
     pub edts: Option<EdtsBox>,
     pub meta: Option<MetaBox>,
     pub mdia: MdiaBox,
+    pub unknown_boxes: Vec<(BoxType, Vec<u8>)>,
 }
 
 impl TrakBox {
@@ -23,7 +24,11 @@ impl TrakBox {
         if let Some(ref edts) = self.edts {
             size += edts.box_size();
         }
+        if let Some(ref meta) = self.meta {
+            size += meta.box_size();
+        }
         size += self.mdia.box_size();
+        size += unknown_boxes_size(&self.unknown_boxes);
         size
     }
 }
@@ -51,6 +56,7 @@ impl<R: Read + Seek> ReadBox<&mut R> for TrakBox {
         let mut edts = None;
         let mut meta = None;
         let mut mdia = None;
+        let mut unknown_boxes = Vec::new();
 
         let mut current = reader.stream_position()?;
         let end = start + size;
@@ -78,8 +84,7 @@ impl<R: Read + Seek> ReadBox<&mut R> for TrakBox {
                     mdia = Some(MdiaBox::read_box(reader, s)?);
                 }
                 _ => {
-                    // XXX warn!()
-                    skip_box(reader, s)?;
+                    unknown_boxes.push(read_unknown_box(reader, name, s)?);
                 }
             }
 
@@ -100,6 +105,7 @@ impl<R: Read + Seek> ReadBox<&mut R> for TrakBox {
             edts,
             meta,
             mdia: mdia.unwrap(),
+            unknown_boxes,
         })
     }
 }
@@ -113,7 +119,11 @@ impl<W: Write> WriteBox<&mut W> for TrakBox {
         if let Some(ref edts) = self.edts {
             edts.write_box(writer)?;
         }
+        if let Some(ref meta) = self.meta {
+            meta.write_box(writer)?;
+        }
         self.mdia.write_box(writer)?;
+        write_unknown_boxes(writer, &self.unknown_boxes)?;
 
         Ok(size)
     }
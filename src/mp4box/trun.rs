@@ -175,8 +175,17 @@ impl<W: Write> WriteBox<&mut W> for TrunBox {
         if let Some(v) = self.first_sample_flags {
             writer.write_u32::<BigEndian>(v)?;
         }
-        if self.sample_count != self.sample_sizes.len() as u32 {
-            return Err(Error::InvalidData("sample count out of sync"));
+        // Only the fields signalled in the flags need an entry per sample.
+        let sample_count = self.sample_count as usize;
+        for (flag, len) in [
+            (TrunBox::FLAG_SAMPLE_DURATION, self.sample_durations.len()),
+            (TrunBox::FLAG_SAMPLE_SIZE, self.sample_sizes.len()),
+            (TrunBox::FLAG_SAMPLE_FLAGS, self.sample_flags.len()),
+            (TrunBox::FLAG_SAMPLE_CTS, self.sample_cts.len()),
+        ] {
+            if flag & self.flags > 0 && len != sample_count {
+                return Err(Error::InvalidData("sample count out of sync"));
+            }
         }
         for i in 0..self.sample_count as usize {
             if TrunBox::FLAG_SAMPLE_DURATION & self.flags > 0 {
@@ -257,4 +266,33 @@ mod tests {
         let dst_box = TrunBox::read_box(&mut reader, header.size).unwrap();
         assert_eq!(src_box, dst_box);
     }
+
+    #[test]
+    fn test_trun_default_sizes() {
+        let src_box = TrunBox {
+            version: 0,
+            flags: TrunBox::FLAG_DATA_OFFSET | TrunBox::FLAG_SAMPLE_FLAGS,
+            data_offset: Some(120),
+            sample_count: 3,
+            sample_sizes: vec![],
+            sample_flags: vec![0x0200_0000, 0x0101_0000, 0x0101_0000],
+            first_sample_flags: None,
+            sample_durations: vec![],
+            sample_cts: vec![],
+        };
+        let mut buf = Vec::new();
+        src_box.write_box(&mut buf).unwrap();
+        assert_eq!(buf.len(), src_box.box_size() as usize);
+
+        let mut reader = Cursor::new(&buf);
+        let header = BoxHeader::read(&mut reader).unwrap();
+        let dst_box = TrunBox::read_box(&mut reader, header.size).unwrap();
+        assert_eq!(src_box, dst_box);
+
+        let bad_box = TrunBox {
+            sample_count: 4,
+            ..src_box
+        };
+        assert!(bad_box.write_box(&mut Vec::new()).is_err());
+    }
 }
@@ -12,6 +12,7 @@ pub struct Tx3gBox {
     pub bg_color_rgba: RgbaColor,
     pub box_record: [i16; 4],
     pub style_record: [u8; 12],
+    pub unknown_boxes: Vec<(BoxType, Vec<u8>)>,
 }
 
 #[derive(Debug, Clone, PartialEq, Eq, Default)]
@@ -37,6 +38,7 @@ impl Default for Tx3gBox {
             },
             box_record: [0, 0, 0, 0],
             style_record: [0, 0, 0, 0, 0, 1, 0, 16, 255, 255, 255, 255],
+            unknown_boxes: Vec::new(),
         }
     }
 }
@@ -47,7 +49,7 @@ impl Tx3gBox {
     }
 
     pub fn get_size(&self) -> u64 {
-        HEADER_SIZE + 6 + 32
+        HEADER_SIZE + 6 + 32 + unknown_boxes_size(&self.unknown_boxes)
     }
 }
 
@@ -113,6 +115,21 @@ impl<R: Read + Seek> ReadBox<&mut R> for Tx3gBox {
             reader.read_u8()?,
         ];
 
+        let mut unknown_boxes = Vec::new();
+        let mut current = reader.stream_position()?;
+        let end = start + size;
+        while current < end {
+            let header = BoxHeader::read(reader)?;
+            let BoxHeader { name, size: s } = header;
+            if s > size {
+                return Err(Error::InvalidData(
+                    "tx3g box contains a box with a larger size than it",
+                ));
+            }
+            unknown_boxes.push(read_unknown_box(reader, name, s)?);
+            current = reader.stream_position()?;
+        }
+
         skip_bytes_to(reader, start + size)?;
 
         Ok(Tx3gBox {
@@ -123,6 +140,7 @@ impl<R: Read + Seek> ReadBox<&mut R> for Tx3gBox {
             bg_color_rgba,
             box_record,
             style_record,
+            unknown_boxes,
         })
     }
 }
@@ -148,6 +166,7 @@ impl<W: Write> WriteBox<&mut W> for Tx3gBox {
         for n in 0..12 {
             writer.write_u8(self.style_record[n])?;
         }
+        write_unknown_boxes(writer, &self.unknown_boxes)?;
 
         Ok(size)
     }
@@ -174,6 +193,8 @@ mod tests {
             },
             box_record: [0, 0, 0, 0],
             style_record: [0, 0, 0, 0, 0, 1, 0, 16, 255, 255, 255, 255],
+            // ftab
+            unknown_boxes: vec![(BoxType::UnknownBox(0x66746162), vec![0, 0])],
         };
         let mut buf = Vec::new();
         src_box.write_box(&mut buf).unwrap();
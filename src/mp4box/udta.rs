@@ -6,6 +6,7 @@ use crate::mp4box::*;
 #[derive(Debug, Clone, PartialEq, Eq, Default)]
 pub struct UdtaBox {
     pub meta: Option<MetaBox>,
+    pub unknown_boxes: Vec<(BoxType, Vec<u8>)>,
 }
 
 impl UdtaBox {
@@ -18,6 +19,7 @@ impl UdtaBox {
         if let Some(meta) = &self.meta {
             size += meta.box_size();
         }
+        size += unknown_boxes_size(&self.unknown_boxes);
         size
     }
 }
@@ -41,6 +43,7 @@ impl<R: Read + Seek> ReadBox<&mut R> for UdtaBox {
         let start = box_start(reader)?;
 
         let mut meta = None;
+        let mut unknown_boxes = Vec::new();
 
         let mut current = reader.stream_position()?;
         let end = start + size;
@@ -59,8 +62,7 @@ impl<R: Read + Seek> ReadBox<&mut R> for UdtaBox {
                     meta = Some(MetaBox::read_box(reader, s)?);
                 }
                 _ => {
-                    // XXX warn!()
-                    skip_box(reader, s)?;
+                    unknown_boxes.push(read_unknown_box(reader, name, s)?);
                 }
             }
 
@@ -69,7 +71,10 @@ impl<R: Read + Seek> ReadBox<&mut R> for UdtaBox {
 
         skip_bytes_to(reader, start + size)?;
 
-        Ok(UdtaBox { meta })
+        Ok(UdtaBox {
+            meta,
+            unknown_boxes,
+        })
     }
 }
 
@@ -81,6 +86,7 @@ impl<W: Write> WriteBox<&mut W> for UdtaBox {
         if let Some(meta) = &self.meta {
             meta.write_box(writer)?;
         }
+        write_unknown_boxes(writer, &self.unknown_boxes)?;
         Ok(size)
     }
 }
@@ -93,7 +99,10 @@ mod tests {
 
     #[test]
     fn test_udta_empty() {
-        let src_box = UdtaBox { meta: None };
+        let src_box = UdtaBox {
+            meta: None,
+            unknown_boxes: vec![],
+        };
 
         let mut buf = Vec::new();
         src_box.write_box(&mut buf).unwrap();
@@ -112,6 +121,7 @@ mod tests {
     fn test_udta() {
         let src_box = UdtaBox {
             meta: Some(MetaBox::default()),
+            unknown_boxes: vec![(BoxType::UnknownBox(0xa978797a), vec![0, 1, 2])],
         };
 
         let mut buf = Vec::new();
@@ -19,6 +19,12 @@ pub struct Vp09Box {
     pub depth: u16,
     pub end_code: u16,
     pub vpcc: VpccBox,
+
+    /// Protection scheme information, if the sample entry is an encrypted
+    /// `encv` entry with this original format.
+    pub sinf: Option<SinfBox>,
+
+    pub unknown_boxes: Vec<(BoxType, Vec<u8>)>,
 }
 
 impl Vp09Box {
@@ -60,17 +66,26 @@ impl Vp09Box {
                 matrix_coefficients: 0,
                 codec_initialization_data_size: 0,
             },
+            sinf: None,
+            unknown_boxes: Vec::new(),
         }
     }
 }
 
 impl Mp4Box for Vp09Box {
     fn box_type(&self) -> BoxType {
+        if self.sinf.is_some() {
+            return BoxType::EncvBox;
+        }
         BoxType::Vp09Box
     }
 
     fn box_size(&self) -> u64 {
-        0x6A
+        let mut size = 0x6A;
+        if let Some(ref sinf) = self.sinf {
+            size += sinf.box_size();
+        }
+        size + unknown_boxes_size(&self.unknown_boxes)
     }
 
     fn summary(&self) -> Result<String> {
@@ -124,6 +139,26 @@ impl<R: Read + Seek> ReadBox<&mut R> for Vp09Box {
             VpccBox::read_box(reader, header.size)?
         };
 
+        let mut sinf = None;
+        let mut unknown_boxes = Vec::new();
+        let mut current = reader.stream_position()?;
+        let end = start + size;
+        while current < end {
+            let header = BoxHeader::read(reader)?;
+            let BoxHeader { name, size: s } = header;
+            if s > size {
+                return Err(Error::InvalidData(
+                    "vp09 box contains a box with a larger size than it",
+                ));
+            }
+            if name == BoxType::SinfBox && sinf.is_none() {
+                sinf = Some(SinfBox::read_box(reader, s)?);
+            } else {
+                unknown_boxes.push(read_unknown_box(reader, name, s)?);
+            }
+            current = reader.stream_position()?;
+        }
+
         skip_bytes_to(reader, start + size)?;
 
         Ok(Self {
@@ -142,6 +177,8 @@ impl<R: Read + Seek> ReadBox<&mut R> for Vp09Box {
             depth,
             end_code,
             vpcc,
+            sinf,
+            unknown_boxes,
         })
     }
 }
@@ -168,6 +205,10 @@ impl<W: Write> WriteBox<&mut W> for Vp09Box {
         writer.write_u16::<BigEndian>(self.depth)?;
         writer.write_u16::<BigEndian>(self.end_code)?;
         VpccBox::write_box(&self.vpcc, writer)?;
+        if let Some(ref sinf) = self.sinf {
+            sinf.write_box(writer)?;
+        }
+        write_unknown_boxes(writer, &self.unknown_boxes)?;
 
         Ok(size)
     }
@@ -1,5 +1,6 @@
 use std::collections::HashMap;
-use std::io::{Read, Seek};
+use std::convert::TryFrom;
+use std::io::{self, Read, Seek, SeekFrom, Write};
 use std::time::Duration;
 
 use crate::meta::MetaBox;
@@ -7,25 +8,32 @@ use crate::*;
 
 #[derive(Debug)]
 pub struct Mp4Reader<R> {
-    reader: R,
+    pub(crate) reader: R,
     pub ftyp: FtypBox,
     pub moov: MoovBox,
     pub moofs: Vec<MoofBox>,
     pub emsgs: Vec<EmsgBox>,
+    pub sidxs: Vec<SidxBox>,
+    pub mfra: Option<MfraBox>,
 
-    tracks: HashMap<u32, Mp4Track>,
+    /// Top-level boxes that are not parsed, other than `mdat` and `free`.
+    pub unknown_boxes: Vec<(BoxType, Vec<u8>)>,
+
+    pub(crate) tracks: HashMap<u32, Mp4Track>,
     size: u64,
+
+    // Position and size of every mdat box, including its header.
+    mdats: Vec<(u64, u64)>,
+
+    // Position and size of every sidx box, including its header.
+    sidx_ranges: Vec<(u64, u64)>,
 }
 
 impl<R: Read + Seek> Mp4Reader<R> {
     pub fn read_header(mut reader: R, size: u64) -> Result<Self> {
         let start = reader.stream_position()?;
 
-        let mut ftyp = None;
-        let mut moov = None;
-        let mut moofs = Vec::new();
-        let mut moof_offsets = Vec::new();
-        let mut emsgs = Vec::new();
+        let mut boxes = TopLevelBoxes::default();
 
         let mut current = start;
         while current < size {
@@ -44,89 +52,146 @@ impl<R: Read + Seek> Mp4Reader<R> {
             }
 
             // Match and parse the atom boxes.
-            match name {
-                BoxType::FtypBox => {
-                    ftyp = Some(FtypBox::read_box(&mut reader, s)?);
-                }
-                BoxType::FreeBox => {
-                    skip_box(&mut reader, s)?;
-                }
-                BoxType::MdatBox => {
-                    skip_box(&mut reader, s)?;
-                }
-                BoxType::MoovBox => {
-                    moov = Some(MoovBox::read_box(&mut reader, s)?);
-                }
-                BoxType::MoofBox => {
-                    let moof_offset = reader.stream_position()? - 8;
-                    let moof = MoofBox::read_box(&mut reader, s)?;
-                    moofs.push(moof);
-                    moof_offsets.push(moof_offset);
-                }
-                BoxType::EmsgBox => {
-                    let emsg = EmsgBox::read_box(&mut reader, s)?;
-                    emsgs.push(emsg);
-                }
-                _ => {
-                    // XXX warn!()
-                    skip_box(&mut reader, s)?;
+            if TopLevelBoxes::is_parsed(name) {
+                let box_size = reader.stream_position()? - current - HEADER_SIZE + s;
+                boxes.read_box(&mut reader, name, s, current, box_size)?;
+            } else {
+                skip_box(&mut reader, s)?;
+                if name == BoxType::MdatBox {
+                    let mdat_size = reader.stream_position()? - current;
+                    boxes.mdats.push((current, mdat_size));
                 }
             }
             current = reader.stream_position()?;
         }
 
-        if ftyp.is_none() {
-            return Err(Error::BoxNotFound(BoxType::FtypBox));
-        }
-        if moov.is_none() {
-            return Err(Error::BoxNotFound(BoxType::MoovBox));
-        }
+        Self::from_boxes(reader, boxes, current - start)
+    }
 
-        let size = current - start;
-        let mut tracks = if let Some(ref moov) = moov {
-            if moov.traks.iter().any(|trak| trak.tkhd.track_id == 0) {
-                return Err(Error::InvalidData("illegal track id 0"));
+    /// Read the boxes preceding the first fragment and the `mfra` at the end
+    /// of a fragmented file, without reading any `moof`.
+    ///
+    /// No fragments are loaded, use [Mp4Reader::read_fragment_at] to load
+    /// the fragment for a given time.
+    pub fn read_header_from_mfra(mut reader: R, size: u64) -> Result<Self> {
+        let start = reader.stream_position()?;
+
+        let mut boxes = TopLevelBoxes::default();
+
+        let mut current = start;
+        while current < size {
+            // Get box header.
+            let header = BoxHeader::read(&mut reader)?;
+            let BoxHeader { name, size: s } = header;
+            if s > size {
+                return Err(Error::InvalidData(
+                    "file contains a box with a larger size than it",
+                ));
             }
-            moov.traks
-                .iter()
-                .map(|trak| (trak.tkhd.track_id, Mp4Track::from(trak)))
-                .collect()
-        } else {
-            HashMap::new()
-        };
 
-        // Update tracks if any fragmented (moof) boxes are found.
-        if !moofs.is_empty() {
-            let mut default_sample_duration = 0;
-            if let Some(moov) = &moov {
-                if let Some(mvex) = &moov.mvex {
-                    default_sample_duration = mvex.trex.default_sample_duration
-                }
+            // Break if size zero BoxHeader, which can result in dead-loop.
+            if s == 0 || name == BoxType::MoofBox {
+                break;
             }
 
-            for (moof, moof_offset) in moofs.iter().zip(moof_offsets) {
-                for traf in moof.trafs.iter() {
-                    let track_id = traf.tfhd.track_id;
-                    if let Some(track) = tracks.get_mut(&track_id) {
-                        track.default_sample_duration = default_sample_duration;
-                        track.moof_offsets.push(moof_offset);
-                        track.trafs.push(traf.clone())
-                    } else {
-                        return Err(Error::TrakNotFound(track_id));
-                    }
+            if TopLevelBoxes::is_parsed(name) {
+                let box_size = reader.stream_position()? - current - HEADER_SIZE + s;
+                boxes.read_box(&mut reader, name, s, current, box_size)?;
+            } else {
+                skip_box(&mut reader, s)?;
+                if name == BoxType::MdatBox {
+                    let mdat_size = reader.stream_position()? - current;
+                    boxes.mdats.push((current, mdat_size));
                 }
             }
+            current = reader.stream_position()?;
         }
 
-        Ok(Mp4Reader {
-            reader,
-            ftyp: ftyp.unwrap(),
-            moov: moov.unwrap(),
-            moofs,
-            emsgs,
-            size,
-            tracks,
-        })
+        // The mfro ends the file and gives the size of the mfra.
+        let mfro_size = MfroBox::default().box_size();
+        if size < current + mfro_size {
+            return Err(Error::BoxNotFound(BoxType::MfraBox));
+        }
+        reader.seek(SeekFrom::Start(size - mfro_size))?;
+        let header = BoxHeader::read(&mut reader)?;
+        if header.name != BoxType::MfroBox || header.size != mfro_size {
+            return Err(Error::BoxNotFound(BoxType::MfraBox));
+        }
+        let mfro = MfroBox::read_box(&mut reader, header.size)?;
+
+        let mfra_offset = size
+            .checked_sub(mfro.size as u64)
+            .filter(|offset| *offset >= current)
+            .ok_or(Error::InvalidData("mfro size is larger than the file"))?;
+        reader.seek(SeekFrom::Start(mfra_offset))?;
+        let header = BoxHeader::read(&mut reader)?;
+        if header.name != BoxType::MfraBox || header.size != mfro.size as u64 {
+            return Err(Error::BoxNotFound(BoxType::MfraBox));
+        }
+        let box_size = reader.stream_position()? - mfra_offset - HEADER_SIZE + header.size;
+        boxes.read_box(&mut reader, header.name, header.size, mfra_offset, box_size)?;
+
+        Self::from_boxes(reader, boxes, size - start)
+    }
+
+    /// Load the fragment with the random access sample of a track at or
+    /// before `time`, located through the `mfra`.
+    ///
+    /// The fragments loaded in the reader are replaced by this one fragment,
+    /// so sample ids refer to its samples. Returns the id and presentation
+    /// time of the random access sample.
+    pub fn read_fragment_at(&mut self, track_id: u32, time: Duration) -> Result<(u32, Duration)> {
+        let track = self
+            .tracks
+            .get(&track_id)
+            .ok_or(Error::TrakNotFound(track_id))?;
+        let timescale = track.timescale().max(1) as u128;
+        let tfra = self
+            .mfra
+            .as_ref()
+            .ok_or(Error::BoxNotFound(BoxType::MfraBox))?
+            .tfra(track_id)
+            .ok_or(Error::BoxNotFound(BoxType::TfraBox))?;
+        let target = (time.as_micros() * timescale / 1_000_000) as u64;
+        let entry = tfra
+            .find_entry(target)
+            .ok_or(Error::InvalidData("tfra has no entries"))?
+            .clone();
+
+        self.reader.seek(SeekFrom::Start(entry.moof_offset))?;
+        let header = BoxHeader::read(&mut self.reader)?;
+        if header.name != BoxType::MoofBox {
+            return Err(Error::InvalidData("tfra entry does not point at a moof"));
+        }
+        let moof = MoofBox::read_box(&mut self.reader, header.size)?;
+
+        // Samples of the track in the trafs and truns preceding the entry.
+        let traf_idx = entry.traf_number as usize;
+        let trun_idx = entry.trun_number as usize;
+        let traf = traf_idx
+            .checked_sub(1)
+            .and_then(|idx| moof.trafs.get(idx))
+            .filter(|traf| traf.tfhd.track_id == track_id)
+            .ok_or(Error::InvalidData(
+                "tfra entry does not point at a traf of its track",
+            ))?;
+        if trun_idx == 0 || trun_idx > traf.truns.len() || entry.sample_number == 0 {
+            return Err(Error::InvalidData("tfra entry does not point at a sample"));
+        }
+        let preceding: u32 = moof.trafs[..traf_idx - 1]
+            .iter()
+            .filter(|traf| traf.tfhd.track_id == track_id)
+            .flat_map(|traf| traf.truns.iter())
+            .chain(traf.truns[..trun_idx - 1].iter())
+            .map(|trun| trun.sample_count)
+            .sum();
+        let sample_id = preceding + entry.sample_number;
+
+        self.moofs = vec![moof];
+        self.tracks = build_tracks(&self.moov, &self.moofs, &[entry.moof_offset])?;
+
+        let time = Duration::from_micros((entry.time as u128 * 1_000_000 / timescale) as u64);
+        Ok((sample_id, time))
     }
 
     pub fn read_fragment_header<FR: Read + Seek>(
@@ -138,6 +203,9 @@ impl<R: Read + Seek> Mp4Reader<R> {
 
         let mut moofs = Vec::new();
         let mut moof_offsets = Vec::new();
+        let mut sidxs = Vec::new();
+        let mut sidx_ranges = Vec::new();
+        let mut mdats = Vec::new();
 
         let mut current = start;
         while current < size {
@@ -159,6 +227,7 @@ impl<R: Read + Seek> Mp4Reader<R> {
             match name {
                 BoxType::MdatBox => {
                     skip_box(&mut reader, s)?;
+                    mdats.push((current, reader.stream_position()? - current));
                 }
                 BoxType::MoofBox => {
                     let moof_offset = reader.stream_position()? - 8;
@@ -166,6 +235,11 @@ impl<R: Read + Seek> Mp4Reader<R> {
                     moofs.push(moof);
                     moof_offsets.push(moof_offset);
                 }
+                BoxType::SidxBox => {
+                    let sidx = SidxBox::read_box(&mut reader, s)?;
+                    sidxs.push(sidx);
+                    sidx_ranges.push((current, reader.stream_position()? - current));
+                }
                 _ => {
                     // XXX warn!()
                     skip_box(&mut reader, s)?;
@@ -179,30 +253,7 @@ impl<R: Read + Seek> Mp4Reader<R> {
         }
 
         let size = current - start;
-        let mut tracks: HashMap<u32, Mp4Track> = self
-            .moov
-            .traks
-            .iter()
-            .map(|trak| (trak.tkhd.track_id, Mp4Track::from(trak)))
-            .collect();
-
-        let mut default_sample_duration = 0;
-        if let Some(mvex) = &self.moov.mvex {
-            default_sample_duration = mvex.trex.default_sample_duration
-        }
-
-        for (moof, moof_offset) in moofs.iter().zip(moof_offsets) {
-            for traf in moof.trafs.iter() {
-                let track_id = traf.tfhd.track_id;
-                if let Some(track) = tracks.get_mut(&track_id) {
-                    track.default_sample_duration = default_sample_duration;
-                    track.moof_offsets.push(moof_offset);
-                    track.trafs.push(traf.clone())
-                } else {
-                    return Err(Error::TrakNotFound(track_id));
-                }
-            }
-        }
+        let tracks = build_tracks(&self.moov, &moofs, &moof_offsets)?;
 
         Ok(Mp4Reader {
             reader,
@@ -210,11 +261,448 @@ impl<R: Read + Seek> Mp4Reader<R> {
             moov: self.moov.clone(),
             moofs,
             emsgs: Vec::new(),
+            sidxs,
+            mfra: None,
+            unknown_boxes: Vec::new(),
             tracks,
             size,
+            mdats,
+            sidx_ranges,
         })
     }
 
+    pub fn read_sample(&mut self, track_id: u32, sample_id: u32) -> Result<Option<Mp4Sample>> {
+        if let Some(track) = self.tracks.get(&track_id) {
+            track.read_sample(&mut self.reader, sample_id)
+        } else {
+            Err(Error::TrakNotFound(track_id))
+        }
+    }
+
+    /// How a sample of an encrypted track is encrypted, or `None` if the
+    /// track is not encrypted or the sample is not protected.
+    ///
+    /// Unlike [Mp4Track::sample_encryption], this also reads per-sample
+    /// information that `saiz` and `saio` locate outside of a `senc` box.
+    pub fn sample_encryption(
+        &mut self,
+        track_id: u32,
+        sample_id: u32,
+    ) -> Result<Option<SampleEncryption>> {
+        if let Some(track) = self.tracks.get(&track_id) {
+            track.read_sample_encryption(&mut self.reader, sample_id)
+        } else {
+            Err(Error::TrakNotFound(track_id))
+        }
+    }
+
+    /// Iterate over the samples of all tracks in decode time order.
+    ///
+    /// Samples of different tracks are ordered by their decode time in
+    /// seconds, taking each track's timescale into account. Samples with
+    /// the same decode time are returned in track id order.
+    pub fn samples(&mut self) -> Mp4Samples<'_, R> {
+        let mut track_ids: Vec<u32> = self.tracks.keys().copied().collect();
+        track_ids.sort_unstable();
+        Mp4Samples::new(self, &track_ids)
+    }
+
+    /// Iterate over the samples of the given tracks in decode time order.
+    ///
+    /// See [Mp4Reader::samples].
+    pub fn track_samples(&mut self, track_ids: &[u32]) -> Result<Mp4Samples<'_, R>> {
+        for track_id in track_ids.iter() {
+            if !self.tracks.contains_key(track_id) {
+                return Err(Error::TrakNotFound(*track_id));
+            }
+        }
+        let mut track_ids = track_ids.to_vec();
+        track_ids.sort_unstable();
+        track_ids.dedup();
+        Ok(Mp4Samples::new(self, &track_ids))
+    }
+
+    /// Write a copy of the file with `moov` placed before the media data.
+    ///
+    /// Players can then start playback without fetching the end of the file
+    /// first ("faststart"). The `mdat` boxes are copied as-is and the chunk
+    /// offsets in every `stco`/`co64` box are rewritten to match. Tracks are
+    /// switched from `stco` to `co64` where the new offsets require it.
+    /// Other top-level boxes, such as `emsg`, are written between `moov` and
+    /// the media data, except `free`.
+    pub fn write_faststart<W: Write>(&mut self, writer: &mut W) -> Result<()> {
+        if self.is_fragmented() {
+            return Err(Error::InvalidData(
+                "faststart is not supported for fragmented files",
+            ));
+        }
+
+        let mut moov = self.moov.clone();
+        loop {
+            // Place the mdat boxes after ftyp and moov, in their original order.
+            let mut pos = self.ftyp.box_size()
+                + moov.box_size()
+                + self.emsgs.iter().map(|emsg| emsg.box_size()).sum::<u64>()
+                + unknown_boxes_size(&self.unknown_boxes);
+            let mut relocations = Vec::with_capacity(self.mdats.len());
+            for &(mdat_pos, mdat_size) in self.mdats.iter() {
+                relocations.push((mdat_pos, mdat_size, pos));
+                pos += mdat_size;
+            }
+
+            // Relocating may move an offset past 4GiB, which requires co64 and
+            // in turn grows the moov. Retry until the layout is stable.
+            let mut resized = false;
+            for (trak, src_trak) in moov.traks.iter_mut().zip(self.moov.traks.iter()) {
+                let src_stbl = &src_trak.mdia.minf.stbl;
+                let stbl = &mut trak.mdia.minf.stbl;
+                let offsets: Vec<u64> = if let Some(ref stco) = src_stbl.stco {
+                    stco.entries.iter().map(|offset| *offset as u64).collect()
+                } else if let Some(ref co64) = src_stbl.co64 {
+                    co64.entries.clone()
+                } else {
+                    continue;
+                };
+                let sizes = self.tracks[&src_trak.tkhd.track_id].chunk_sizes()?;
+                let offsets = offsets
+                    .into_iter()
+                    .enumerate()
+                    .map(|(i, offset)| {
+                        let size = sizes.get(i).copied().unwrap_or(0);
+                        relocate_offset(&relocations, offset, size)
+                    })
+                    .collect::<Result<Vec<u64>>>()?;
+
+                if let Some(ref mut co64) = stbl.co64 {
+                    co64.entries = offsets;
+                } else if let Some(ref mut stco) = stbl.stco {
+                    let entries = offsets
+                        .iter()
+                        .map(|offset| u32::try_from(*offset))
+                        .collect();
+                    if let Ok(entries) = entries {
+                        stco.entries = entries;
+                    } else {
+                        stbl.stco = None;
+                        stbl.co64 = Some(Co64Box {
+                            version: 0,
+                            flags: 0,
+                            entries: offsets,
+                        });
+                        resized = true;
+                    }
+                }
+
+                // Auxiliary information, such as the IVs of encrypted
+                // samples, is usually stored in the mdat as well.
+                for (saio, src_saio) in stbl.saios.iter_mut().zip(src_stbl.saios.iter()) {
+                    saio.offsets = src_saio
+                        .offsets
+                        .iter()
+                        .map(|offset| relocate_offset(&relocations, *offset, 0))
+                        .collect::<Result<Vec<u64>>>()?;
+                    if saio.version == 0 && saio.offsets.iter().any(|o| *o > u32::MAX as u64) {
+                        saio.version = 1;
+                        resized = true;
+                    }
+                }
+            }
+            if !resized {
+                break;
+            }
+        }
+
+        self.ftyp.write_box(writer)?;
+        moov.write_box(writer)?;
+        for emsg in self.emsgs.iter() {
+            emsg.write_box(writer)?;
+        }
+        write_unknown_boxes(writer, &self.unknown_boxes)?;
+        for &(mdat_pos, mdat_size) in self.mdats.iter() {
+            self.reader.seek(SeekFrom::Start(mdat_pos))?;
+            let copied = io::copy(&mut (&mut self.reader).take(mdat_size), writer)?;
+            if copied != mdat_size {
+                return Err(Error::InvalidData("mdat box is truncated"));
+            }
+        }
+        Ok(())
+    }
+
+    /// Read a sample and decrypt it with the key of its key ID in `keys`,
+    /// which maps key IDs to keys. Samples that are not protected are
+    /// returned as they are.
+    #[cfg(feature = "cenc")]
+    pub fn read_decrypted_sample(
+        &mut self,
+        track_id: u32,
+        sample_id: u32,
+        keys: &HashMap<[u8; 16], [u8; 16]>,
+    ) -> Result<Option<Mp4Sample>> {
+        let Some(mut sample) = self.read_sample(track_id, sample_id)? else {
+            return Ok(None);
+        };
+        if let Some(encryption) = self.sample_encryption(track_id, sample_id)? {
+            let key = keys
+                .get(&encryption.kid)
+                .ok_or(Error::KeyNotFound(encryption.kid))?;
+            cenc::decrypt_sample(key, &encryption, &mut sample.bytes)?;
+        }
+        Ok(Some(sample))
+    }
+
+    /// Write an unprotected copy of the file, decrypting the samples of all
+    /// encrypted tracks with the keys in `keys`, which maps key IDs to keys.
+    ///
+    /// Encrypted sample entries such as `encv` get back their original
+    /// format, e.g. `avc1`, and the `pssh`, `senc`, `saiz`, `saio` and `seig`
+    /// boxes of encrypted tracks are removed. The space they took up is
+    /// filled with a `free` box, so the layout of the file and every offset
+    /// into it stay the same. All fragments must have been read.
+    #[cfg(feature = "cenc")]
+    pub fn write_decrypted<W: Write>(
+        &mut self,
+        writer: &mut W,
+        keys: &HashMap<[u8; 16], [u8; 16]>,
+    ) -> Result<()> {
+        let (moov, schemes) = cenc::remove_moov_protection(&self.moov);
+
+        // Samples of the encrypted tracks, by file offset.
+        let mut samples = Vec::new();
+        for (track_id, track) in self.tracks.iter() {
+            if !schemes.contains_key(track_id) {
+                continue;
+            }
+            for sample_id in 1..=track.sample_count() {
+                if let Some((offset, size)) = track.sample_location(sample_id)? {
+                    samples.push((offset, size, *track_id, sample_id));
+                }
+            }
+        }
+        samples.sort_unstable();
+        let mut samples = samples.into_iter().peekable();
+
+        let mut moof_count = 0;
+        let mut current = 0;
+        while current < self.size {
+            self.reader.seek(SeekFrom::Start(current))?;
+            let header = BoxHeader::read(&mut self.reader)?;
+            let end = if header.size == 0 {
+                self.size
+            } else {
+                box_start(&mut self.reader)? + header.size
+            };
+            match header.name {
+                BoxType::MoovBox => {
+                    write_padded(writer, &moov, end - current)?;
+                }
+                BoxType::MoofBox => {
+                    moof_count += 1;
+                    if moof_count > self.moofs.len() {
+                        return Err(Error::InvalidData(
+                            "all fragments must be read to decrypt the file",
+                        ));
+                    }
+                    let moof = MoofBox::read_box(&mut self.reader, header.size)?;
+                    let moof = cenc::remove_moof_protection(&moof, &schemes);
+                    write_padded(writer, &moof, end - current)?;
+                }
+                _ => {
+                    let mut pos = current;
+                    while let Some(&(offset, size, track_id, sample_id)) = samples.peek()
+                        && offset < end
+                    {
+                        if offset < pos {
+                            return Err(Error::InvalidData("samples overlap"));
+                        }
+                        self.copy_range(writer, pos, offset)?;
+                        let sample = self
+                            .read_decrypted_sample(track_id, sample_id, keys)?
+                            .ok_or(Error::InvalidData("sample is missing"))?;
+                        writer.write_all(&sample.bytes)?;
+                        pos = offset + size as u64;
+                        samples.next();
+                    }
+                    self.copy_range(writer, pos, end)?;
+                }
+            }
+            current = end;
+        }
+        Ok(())
+    }
+
+    /// Copy the bytes from `start` to `end` of the file.
+    #[cfg(feature = "cenc")]
+    fn copy_range<W: Write>(&mut self, writer: &mut W, start: u64, end: u64) -> Result<()> {
+        if end < start {
+            return Err(Error::InvalidData("sample extends past its box"));
+        }
+        self.reader.seek(SeekFrom::Start(start))?;
+        let copied = io::copy(&mut (&mut self.reader).take(end - start), writer)?;
+        if copied != end - start {
+            return Err(Error::InvalidData("file is truncated"));
+        }
+        Ok(())
+    }
+}
+
+/// Write a box followed by a `free` box, which together take up `size`
+/// bytes.
+#[cfg(feature = "cenc")]
+fn write_padded<W: Write, B: Mp4Box + for<'a> WriteBox<&'a mut W>>(
+    writer: &mut W,
+    mp4box: &B,
+    size: u64,
+) -> Result<()> {
+    let padding = size
+        .checked_sub(mp4box.box_size())
+        .ok_or(Error::InvalidData("box grew larger than its original size"))?;
+    if padding > 0 && padding < HEADER_SIZE {
+        return Err(Error::InvalidData("box shrank by less than a free box"));
+    }
+    mp4box.write_box(writer)?;
+    if padding > 0 {
+        BoxHeader::new(BoxType::FreeBox, padding).write(writer)?;
+        io::copy(&mut io::repeat(0).take(padding - HEADER_SIZE), writer)?;
+    }
+    Ok(())
+}
+
+/// Map the file offset of a chunk of `size` bytes into an mdat onto the
+/// position the mdat is moved to. An empty chunk may sit at the very end of
+/// an mdat.
+fn relocate_offset(relocations: &[(u64, u64, u64)], offset: u64, size: u64) -> Result<u64> {
+    for &(mdat_pos, mdat_size, new_pos) in relocations.iter() {
+        let mdat_end = mdat_pos + mdat_size;
+        if offset >= mdat_pos && (offset < mdat_end || size == 0 && offset == mdat_end) {
+            return Ok(offset - mdat_pos + new_pos);
+        }
+    }
+    Err(Error::InvalidData(
+        "chunk offset is outside of any mdat box",
+    ))
+}
+
+/// Iterator over the samples of multiple tracks in decode time order.
+///
+/// Created by [Mp4Reader::samples] and [Mp4Reader::track_samples]. Yields the
+/// track id together with each sample.
+#[derive(Debug)]
+pub struct Mp4Samples<'a, R> {
+    reader: &'a mut Mp4Reader<R>,
+    cursors: Vec<SampleCursor>,
+}
+
+#[derive(Debug)]
+struct SampleCursor {
+    track_id: u32,
+    timescale: u32,
+    sample_id: u32,
+    sample_count: u32,
+    /// Decode time of the sample at `sample_id`.
+    start_time: u64,
+    /// Decode times of the trafs with a tfdt, see
+    /// [Mp4Track::fragment_decode_times], and how many of them were passed.
+    fragment_times: Vec<(u32, u64)>,
+    fragment_idx: usize,
+}
+
+impl SampleCursor {
+    fn new(track_id: u32, track: &Mp4Track) -> Self {
+        let mut cursor = SampleCursor {
+            track_id,
+            timescale: track.timescale().max(1),
+            sample_id: 1,
+            sample_count: track.sample_count(),
+            start_time: 0,
+            fragment_times: track.fragment_decode_times(),
+            fragment_idx: 0,
+        };
+        cursor.apply_fragment_times();
+        cursor
+    }
+
+    /// Move on from a sample with the given decode time and duration.
+    fn advance(&mut self, start_time: u64, duration: u32) {
+        self.sample_id += 1;
+        self.start_time = start_time.saturating_add(duration as u64);
+        self.apply_fragment_times();
+    }
+
+    /// Jump to the tfdt of the traf that starts at the current sample.
+    fn apply_fragment_times(&mut self) {
+        while let Some(&(sample_id, start_time)) = self.fragment_times.get(self.fragment_idx)
+            && sample_id <= self.sample_id
+        {
+            if sample_id == self.sample_id {
+                self.start_time = start_time;
+            }
+            self.fragment_idx += 1;
+        }
+    }
+}
+
+impl<'a, R: Read + Seek> Mp4Samples<'a, R> {
+    fn new(reader: &'a mut Mp4Reader<R>, track_ids: &[u32]) -> Self {
+        let cursors = track_ids
+            .iter()
+            .map(|track_id| SampleCursor::new(*track_id, &reader.tracks[track_id]))
+            .collect();
+        Mp4Samples { reader, cursors }
+    }
+
+    /// Index of the cursor whose next sample has the earliest decode time.
+    fn next_cursor(&self) -> Option<usize> {
+        let mut next: Option<&SampleCursor> = None;
+        let mut next_idx = None;
+        for (i, cursor) in self.cursors.iter().enumerate() {
+            if cursor.sample_id > cursor.sample_count {
+                continue;
+            }
+            let earlier = match next {
+                // Compare start_time / timescale without losing precision.
+                Some(next) => {
+                    (cursor.start_time as u128 * next.timescale as u128)
+                        < (next.start_time as u128 * cursor.timescale as u128)
+                }
+                None => true,
+            };
+            if earlier {
+                next = Some(cursor);
+                next_idx = Some(i);
+            }
+        }
+        next_idx
+    }
+}
+
+impl<R: Read + Seek> Iterator for Mp4Samples<'_, R> {
+    type Item = Result<(u32, Mp4Sample)>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let i = self.next_cursor()?;
+            let cursor = &self.cursors[i];
+            let track_id = cursor.track_id;
+
+            match self.reader.read_sample(track_id, cursor.sample_id) {
+                Ok(Some(sample)) => {
+                    self.cursors[i].advance(sample.start_time, sample.duration);
+                    return Some(Ok((track_id, sample)));
+                }
+                Ok(None) => {
+                    // The track ended early, continue with the others.
+                    self.cursors.remove(i);
+                }
+                Err(err) => {
+                    self.cursors.clear();
+                    return Some(Err(err));
+                }
+            }
+        }
+    }
+}
+
+impl<R> Mp4Reader<R> {
     pub fn size(&self) -> u64 {
         self.size
     }
@@ -243,6 +731,15 @@ impl<R: Read + Seek> Mp4Reader<R> {
         !self.moofs.is_empty()
     }
 
+    /// The subsegments indexed by the top-level `sidx` boxes, in file order.
+    pub fn subsegments(&self) -> Vec<Subsegment> {
+        self.sidxs
+            .iter()
+            .zip(self.sidx_ranges.iter())
+            .flat_map(|(sidx, (offset, size))| sidx.subsegments(offset + size))
+            .collect()
+    }
+
     pub fn tracks(&self) -> &HashMap<u32, Mp4Track> {
         &self.tracks
     }
@@ -255,30 +752,177 @@ impl<R: Read + Seek> Mp4Reader<R> {
         }
     }
 
-    pub fn read_sample(&mut self, track_id: u32, sample_id: u32) -> Result<Option<Mp4Sample>> {
+    pub fn sample_offset(&mut self, track_id: u32, sample_id: u32) -> Result<u64> {
         if let Some(track) = self.tracks.get(&track_id) {
-            track.read_sample(&mut self.reader, sample_id)
+            track.sample_offset(sample_id)
         } else {
             Err(Error::TrakNotFound(track_id))
         }
     }
 
-    pub fn sample_offset(&mut self, track_id: u32, sample_id: u32) -> Result<u64> {
+    /// Precompute a sample index for every track.
+    ///
+    /// See [Mp4Track::build_sample_index].
+    pub fn build_sample_index(&mut self) -> Result<()> {
+        for track in self.tracks.values_mut() {
+            track.build_sample_index()?;
+        }
+        Ok(())
+    }
+
+    /// Find the sample of a track to start playback from at `time`.
+    ///
+    /// See [Mp4Track::seek].
+    pub fn seek(&self, track_id: u32, time: Duration, mode: SeekMode) -> Result<(u32, Duration)> {
         if let Some(track) = self.tracks.get(&track_id) {
-            track.sample_offset(sample_id)
+            track.seek(time, mode)
         } else {
             Err(Error::TrakNotFound(track_id))
         }
     }
-}
 
-impl<R> Mp4Reader<R> {
     pub fn metadata(&self) -> impl Metadata<'_> {
         self.moov.udta.as_ref().and_then(|udta| {
             udta.meta.as_ref().and_then(|meta| match meta {
-                MetaBox::Mdir { ilst } => ilst.as_ref(),
+                MetaBox::Mdir { ilst, .. } => ilst.as_ref(),
                 _ => None,
             })
         })
     }
 }
+
+impl<R> Mp4Reader<R> {
+    /// Assemble a reader from the top-level boxes of a file of `size` bytes.
+    pub(crate) fn from_boxes(reader: R, boxes: TopLevelBoxes, size: u64) -> Result<Self> {
+        let TopLevelBoxes {
+            ftyp,
+            moov,
+            moofs,
+            moof_offsets,
+            emsgs,
+            sidxs,
+            sidx_ranges,
+            mfra,
+            unknown_boxes,
+            mdats,
+        } = boxes;
+
+        let ftyp = ftyp.ok_or(Error::BoxNotFound(BoxType::FtypBox))?;
+        let moov = moov.ok_or(Error::BoxNotFound(BoxType::MoovBox))?;
+
+        if moov.traks.iter().any(|trak| trak.tkhd.track_id == 0) {
+            return Err(Error::InvalidData("illegal track id 0"));
+        }
+        let tracks = build_tracks(&moov, &moofs, &moof_offsets)?;
+
+        Ok(Mp4Reader {
+            reader,
+            ftyp,
+            moov,
+            moofs,
+            emsgs,
+            sidxs,
+            mfra,
+            unknown_boxes,
+            size,
+            tracks,
+            mdats,
+            sidx_ranges,
+        })
+    }
+}
+
+/// Top-level boxes collected while scanning a file.
+#[derive(Debug, Default)]
+pub(crate) struct TopLevelBoxes {
+    pub(crate) ftyp: Option<FtypBox>,
+    pub(crate) moov: Option<MoovBox>,
+    pub(crate) moofs: Vec<MoofBox>,
+    pub(crate) moof_offsets: Vec<u64>,
+    pub(crate) emsgs: Vec<EmsgBox>,
+    pub(crate) sidxs: Vec<SidxBox>,
+    pub(crate) sidx_ranges: Vec<(u64, u64)>,
+    pub(crate) mfra: Option<MfraBox>,
+    pub(crate) unknown_boxes: Vec<(BoxType, Vec<u8>)>,
+
+    // Position and size of every mdat box, including its header.
+    pub(crate) mdats: Vec<(u64, u64)>,
+}
+
+impl TopLevelBoxes {
+    /// Whether a top-level box is read, rather than skipped.
+    pub(crate) fn is_parsed(name: BoxType) -> bool {
+        !matches!(name, BoxType::MdatBox | BoxType::FreeBox)
+    }
+
+    /// Parse a top-level box at `offset` whose header has just been read.
+    /// `box_size` is its size in the file, including the header.
+    pub(crate) fn read_box<R: Read + Seek>(
+        &mut self,
+        reader: &mut R,
+        name: BoxType,
+        size: u64,
+        offset: u64,
+        box_size: u64,
+    ) -> Result<()> {
+        match name {
+            BoxType::FtypBox => {
+                self.ftyp = Some(FtypBox::read_box(reader, size)?);
+            }
+            BoxType::MoovBox => {
+                self.moov = Some(MoovBox::read_box(reader, size)?);
+            }
+            BoxType::MoofBox => {
+                let moof = MoofBox::read_box(reader, size)?;
+                self.moofs.push(moof);
+                self.moof_offsets.push(offset);
+            }
+            BoxType::EmsgBox => {
+                let emsg = EmsgBox::read_box(reader, size)?;
+                self.emsgs.push(emsg);
+            }
+            BoxType::SidxBox => {
+                let sidx = SidxBox::read_box(reader, size)?;
+                self.sidxs.push(sidx);
+                self.sidx_ranges.push((offset, box_size));
+            }
+            BoxType::MfraBox => {
+                self.mfra = Some(MfraBox::read_box(reader, size)?);
+            }
+            _ => {
+                self.unknown_boxes
+                    .push(read_unknown_box(reader, name, size)?);
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Build the tracks of a movie, including the samples of any fragments.
+fn build_tracks(
+    moov: &MoovBox,
+    moofs: &[MoofBox],
+    moof_offsets: &[u64],
+) -> Result<HashMap<u32, Mp4Track>> {
+    let mut tracks: HashMap<u32, Mp4Track> = moov
+        .traks
+        .iter()
+        .map(|trak| (trak.tkhd.track_id, Mp4Track::from(trak, moov)))
+        .collect();
+
+    for (moof, moof_offset) in moofs.iter().zip(moof_offsets) {
+        for traf in moof.trafs.iter() {
+            let track_id = traf.tfhd.track_id;
+            if let Some(track) = tracks.get_mut(&track_id) {
+                track.moof_offsets.push(*moof_offset);
+                track.trafs.push(traf.clone())
+            } else {
+                return Err(Error::TrakNotFound(track_id));
+            }
+        }
+    }
+    for track in tracks.values_mut() {
+        track.parse_senc_entries()?;
+    }
+    Ok(tracks)
+}
@@ -0,0 +1,1226 @@
+//! Parsing of the parameter sets and slice headers of AVC and HEVC, to find
+//! where the slice data of a NAL unit starts.
+
+use std::collections::HashMap;
+
+use crate::bitreader::{BitReader, nal_unit_rbsp, nal_unit_size};
+use crate::*;
+
+/// Number of bits of a value below `n`, `Ceil(Log2(n))` in the H.264 and
+/// H.265 syntax.
+fn ceil_log2(n: u64) -> usize {
+    if n <= 1 {
+        0
+    } else {
+        64 - (n - 1).leading_zeros() as usize
+    }
+}
+
+/// Size of a NAL unit up to the end of its slice header, which ends at
+/// `header_bits` into the RBSP after the NAL unit header. A header that
+/// ends within a byte keeps that byte.
+fn header_size(nal_unit: &[u8], nal_header_size: usize, rbsp: &[u8], reader: &BitReader) -> usize {
+    let header_bits = rbsp.len() * 8 - reader.bits_left();
+    nal_header_size + nal_unit_size(&nal_unit[nal_header_size..], header_bits.div_ceil(8))
+}
+
+/// The sequence and picture parameter sets of an AVC track, by their ids.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct AvcParameterSets {
+    sps: HashMap<u32, AvcSps>,
+    pps: HashMap<u32, AvcPps>,
+}
+
+#[derive(Debug, Clone)]
+struct AvcSps {
+    separate_colour_plane_flag: bool,
+    chroma_array_type: u32,
+    log2_max_frame_num: usize,
+    pic_order_cnt_type: u32,
+    log2_max_pic_order_cnt_lsb: usize,
+    delta_pic_order_always_zero_flag: bool,
+    frame_mbs_only_flag: bool,
+    pic_size_in_map_units: u64,
+}
+
+#[derive(Debug, Clone)]
+struct AvcPps {
+    seq_parameter_set_id: u32,
+    entropy_coding_mode_flag: bool,
+    bottom_field_pic_order_in_frame_present_flag: bool,
+    num_slice_groups_minus1: u32,
+    slice_group_map_type: u32,
+    slice_group_change_rate: u64,
+    num_ref_idx_l0_default_active_minus1: u32,
+    num_ref_idx_l1_default_active_minus1: u32,
+    weighted_pred_flag: bool,
+    weighted_bipred_idc: u64,
+    deblocking_filter_control_present_flag: bool,
+    redundant_pic_cnt_present_flag: bool,
+}
+
+const AVC_SLICE_P: u32 = 0;
+const AVC_SLICE_B: u32 = 1;
+const AVC_SLICE_I: u32 = 2;
+const AVC_SLICE_SP: u32 = 3;
+const AVC_SLICE_SI: u32 = 4;
+
+impl AvcParameterSets {
+    /// The parameter sets of an `avcC`.
+    pub(crate) fn new(avcc: &AvcCBox) -> Result<Self> {
+        let mut parameter_sets = Self::default();
+        for nal_unit in avcc
+            .sequence_parameter_sets
+            .iter()
+            .chain(avcc.picture_parameter_sets.iter())
+        {
+            parameter_sets.read_nal_unit(&nal_unit.bytes)?;
+        }
+        Ok(parameter_sets)
+    }
+
+    /// Keep the parameter set in a NAL unit, or return the size of the NAL
+    /// unit header and slice header of a slice.
+    pub(crate) fn read_nal_unit(&mut self, nal_unit: &[u8]) -> Result<Option<usize>> {
+        let Some((header, payload)) = nal_unit.split_first() else {
+            return Ok(None);
+        };
+        match header & 0x1f {
+            1..=5 => return self.slice_header_size(nal_unit).map(Some),
+            7 => {
+                let (id, sps) = AvcSps::read(&nal_unit_rbsp(payload))?;
+                self.sps.insert(id, sps);
+            }
+            8 => {
+                let (id, pps) = AvcPps::read(&nal_unit_rbsp(payload))?;
+                self.pps.insert(id, pps);
+            }
+            _ => {}
+        }
+        Ok(None)
+    }
+
+    fn slice_header_size(&self, nal_unit: &[u8]) -> Result<usize> {
+        let nal_ref_idc = nal_unit[0] >> 5 & 3;
+        let idr_pic_flag = nal_unit[0] & 0x1f == 5;
+        let rbsp = nal_unit_rbsp(&nal_unit[1..]);
+        let mut reader = BitReader::new(&rbsp);
+
+        reader.read_ue()?; // first_mb_in_slice
+        let slice_type = reader.read_ue()? % 5;
+        let pps = self
+            .pps
+            .get(&reader.read_ue()?)
+            .ok_or(Error::InvalidData("slice refers to an unknown PPS"))?;
+        let sps = self
+            .sps
+            .get(&pps.seq_parameter_set_id)
+            .ok_or(Error::InvalidData("PPS refers to an unknown SPS"))?;
+        let is_p = slice_type == AVC_SLICE_P || slice_type == AVC_SLICE_SP;
+        let is_b = slice_type == AVC_SLICE_B;
+        let is_intra = slice_type == AVC_SLICE_I || slice_type == AVC_SLICE_SI;
+
+        if sps.separate_colour_plane_flag {
+            reader.skip_bits(2)?; // colour_plane_id
+        }
+        reader.skip_bits(sps.log2_max_frame_num)?; // frame_num
+        let mut field_pic_flag = false;
+        if !sps.frame_mbs_only_flag {
+            field_pic_flag = reader.read_bit()?;
+            if field_pic_flag {
+                reader.skip_bits(1)?; // bottom_field_flag
+            }
+        }
+        if idr_pic_flag {
+            reader.read_ue()?; // idr_pic_id
+        }
+        let has_bottom = pps.bottom_field_pic_order_in_frame_present_flag && !field_pic_flag;
+        if sps.pic_order_cnt_type == 0 {
+            reader.skip_bits(sps.log2_max_pic_order_cnt_lsb)?; // pic_order_cnt_lsb
+            if has_bottom {
+                reader.read_se()?; // delta_pic_order_cnt_bottom
+            }
+        }
+        if sps.pic_order_cnt_type == 1 && !sps.delta_pic_order_always_zero_flag {
+            reader.read_se()?; // delta_pic_order_cnt[0]
+            if has_bottom {
+                reader.read_se()?; // delta_pic_order_cnt[1]
+            }
+        }
+        if pps.redundant_pic_cnt_present_flag {
+            reader.read_ue()?; // redundant_pic_cnt
+        }
+        if is_b {
+            reader.skip_bits(1)?; // direct_spatial_mv_pred_flag
+        }
+
+        let mut num_ref_idx_l0_active_minus1 = pps.num_ref_idx_l0_default_active_minus1;
+        let mut num_ref_idx_l1_active_minus1 = pps.num_ref_idx_l1_default_active_minus1;
+        if (is_p || is_b) && reader.read_bit()? {
+            // num_ref_idx_active_override_flag
+            num_ref_idx_l0_active_minus1 = reader.read_ue()?;
+            if is_b {
+                num_ref_idx_l1_active_minus1 = reader.read_ue()?;
+            }
+        }
+        if num_ref_idx_l0_active_minus1 > 31 || num_ref_idx_l1_active_minus1 > 31 {
+            return Err(Error::InvalidData("slice has too many reference pictures"));
+        }
+
+        // ref_pic_list_modification
+        if !is_intra {
+            skip_avc_ref_pic_list_modification(&mut reader)?;
+        }
+        if is_b {
+            skip_avc_ref_pic_list_modification(&mut reader)?;
+        }
+
+        if (pps.weighted_pred_flag && is_p) || (pps.weighted_bipred_idc == 1 && is_b) {
+            // pred_weight_table
+            let has_chroma = sps.chroma_array_type != 0;
+            reader.read_ue()?; // luma_log2_weight_denom
+            if has_chroma {
+                reader.read_ue()?; // chroma_log2_weight_denom
+            }
+            skip_avc_weights(&mut reader, num_ref_idx_l0_active_minus1 + 1, has_chroma)?;
+            if is_b {
+                skip_avc_weights(&mut reader, num_ref_idx_l1_active_minus1 + 1, has_chroma)?;
+            }
+        }
+
+        if nal_ref_idc != 0 {
+            // dec_ref_pic_marking
+            if idr_pic_flag {
+                reader.skip_bits(2)?; // no_output_of_prior_pics_flag, long_term_reference_flag
+            } else if reader.read_bit()? {
+                // adaptive_ref_pic_marking_mode_flag
+                loop {
+                    let memory_management_control_operation = reader.read_ue()?;
+                    match memory_management_control_operation {
+                        0 => break,
+                        // difference_of_pic_nums_minus1, long_term_pic_num,
+                        // long_term_frame_idx or
+                        // max_long_term_frame_idx_plus1
+                        1 | 2 | 4 | 6 => {
+                            reader.read_ue()?;
+                        }
+                        3 => {
+                            reader.read_ue()?;
+                            reader.read_ue()?;
+                        }
+                        _ => {}
+                    }
+                }
+            }
+        }
+
+        if pps.entropy_coding_mode_flag && !is_intra {
+            reader.read_ue()?; // cabac_init_idc
+        }
+        reader.read_se()?; // slice_qp_delta
+        if slice_type == AVC_SLICE_SP || slice_type == AVC_SLICE_SI {
+            if slice_type == AVC_SLICE_SP {
+                reader.skip_bits(1)?; // sp_for_switch_flag
+            }
+            reader.read_se()?; // slice_qs_delta
+        }
+        if pps.deblocking_filter_control_present_flag && reader.read_ue()? != 1 {
+            // disable_deblocking_filter_idc
+            reader.read_se()?; // slice_alpha_c0_offset_div2
+            reader.read_se()?; // slice_beta_offset_div2
+        }
+        if pps.num_slice_groups_minus1 > 0 && (3..=5).contains(&pps.slice_group_map_type) {
+            // slice_group_change_cycle, of
+            // Ceil(Log2(PicSizeInMapUnits ÷ SliceGroupChangeRate + 1)) bits
+            let changes = sps
+                .pic_size_in_map_units
+                .div_ceil(pps.slice_group_change_rate);
+            reader.skip_bits(ceil_log2(changes + 1))?;
+        }
+
+        // The slice data of CABAC coded slices starts at a byte boundary.
+        Ok(header_size(nal_unit, 1, &rbsp, &reader))
+    }
+}
+
+impl AvcSps {
+    fn read(rbsp: &[u8]) -> Result<(u32, Self)> {
+        let mut reader = BitReader::new(rbsp);
+        let profile_idc = reader.read_bits(8)?;
+        reader.skip_bits(16)?; // constraint_set flags, level_idc
+        let id = reader.read_ue()?;
+
+        let mut chroma_format_idc = 1;
+        let mut separate_colour_plane_flag = false;
+        if matches!(
+            profile_idc,
+            100 | 110 | 122 | 244 | 44 | 83 | 86 | 118 | 128 | 138 | 139 | 134 | 135
+        ) {
+            chroma_format_idc = reader.read_ue()?;
+            if chroma_format_idc == 3 {
+                separate_colour_plane_flag = reader.read_bit()?;
+            }
+            reader.read_ue()?; // bit_depth_luma_minus8
+            reader.read_ue()?; // bit_depth_chroma_minus8
+            reader.skip_bits(1)?; // qpprime_y_zero_transform_bypass_flag
+            if reader.read_bit()? {
+                // seq_scaling_matrix_present_flag
+                let count = if chroma_format_idc != 3 { 8 } else { 12 };
+                for i in 0..count {
+                    if reader.read_bit()? {
+                        skip_avc_scaling_list(&mut reader, if i < 6 { 16 } else { 64 })?;
+                    }
+                }
+            }
+        }
+
+        let log2_max_frame_num_minus4 = reader.read_ue()?;
+        let pic_order_cnt_type = reader.read_ue()?;
+        let mut log2_max_pic_order_cnt_lsb_minus4 = 0;
+        let mut delta_pic_order_always_zero_flag = false;
+        if pic_order_cnt_type == 0 {
+            log2_max_pic_order_cnt_lsb_minus4 = reader.read_ue()?;
+        } else if pic_order_cnt_type == 1 {
+            delta_pic_order_always_zero_flag = reader.read_bit()?;
+            reader.read_se()?; // offset_for_non_ref_pic
+            reader.read_se()?; // offset_for_top_to_bottom_field
+            for _ in 0..reader.read_ue()? {
+                reader.read_se()?; // offset_for_ref_frame
+            }
+        }
+        reader.read_ue()?; // max_num_ref_frames
+        reader.skip_bits(1)?; // gaps_in_frame_num_value_allowed_flag
+        let pic_width_in_mbs = reader.read_ue()? as u64 + 1;
+        let pic_height_in_map_units = reader.read_ue()? as u64 + 1;
+        let frame_mbs_only_flag = reader.read_bit()?;
+
+        if id > 31
+            || chroma_format_idc > 3
+            || log2_max_frame_num_minus4 > 12
+            || log2_max_pic_order_cnt_lsb_minus4 > 12
+        {
+            return Err(Error::InvalidData("SPS is invalid"));
+        }
+        let sps = AvcSps {
+            separate_colour_plane_flag,
+            chroma_array_type: if separate_colour_plane_flag {
+                0
+            } else {
+                chroma_format_idc
+            },
+            log2_max_frame_num: log2_max_frame_num_minus4 as usize + 4,
+            pic_order_cnt_type,
+            log2_max_pic_order_cnt_lsb: log2_max_pic_order_cnt_lsb_minus4 as usize + 4,
+            delta_pic_order_always_zero_flag,
+            frame_mbs_only_flag,
+            pic_size_in_map_units: pic_width_in_mbs * pic_height_in_map_units,
+        };
+        Ok((id, sps))
+    }
+}
+
+impl AvcPps {
+    fn read(rbsp: &[u8]) -> Result<(u32, Self)> {
+        let mut reader = BitReader::new(rbsp);
+        let id = reader.read_ue()?;
+        let seq_parameter_set_id = reader.read_ue()?;
+        let entropy_coding_mode_flag = reader.read_bit()?;
+        let bottom_field_pic_order_in_frame_present_flag = reader.read_bit()?;
+
+        let num_slice_groups_minus1 = reader.read_ue()?;
+        let mut slice_group_map_type = 0;
+        let mut slice_group_change_rate = 1;
+        if num_slice_groups_minus1 > 7 {
+            return Err(Error::InvalidData("PPS is invalid"));
+        }
+        if num_slice_groups_minus1 > 0 {
+            slice_group_map_type = reader.read_ue()?;
+            match slice_group_map_type {
+                0 => {
+                    for _ in 0..=num_slice_groups_minus1 {
+                        reader.read_ue()?; // run_length_minus1
+                    }
+                }
+                2 => {
+                    for _ in 0..num_slice_groups_minus1 {
+                        reader.read_ue()?; // top_left
+                        reader.read_ue()?; // bottom_right
+                    }
+                }
+                3..=5 => {
+                    reader.skip_bits(1)?; // slice_group_change_direction_flag
+                    slice_group_change_rate = reader.read_ue()? as u64 + 1;
+                }
+                6 => {
+                    let pic_size_in_map_units = reader.read_ue()? as usize + 1;
+                    let bits = ceil_log2(num_slice_groups_minus1 as u64 + 1);
+                    reader.skip_bits(pic_size_in_map_units.saturating_mul(bits))?; // slice_group_id
+                }
+                _ => {}
+            }
+        }
+
+        let num_ref_idx_l0_default_active_minus1 = reader.read_ue()?;
+        let num_ref_idx_l1_default_active_minus1 = reader.read_ue()?;
+        let weighted_pred_flag = reader.read_bit()?;
+        let weighted_bipred_idc = reader.read_bits(2)?;
+        reader.read_se()?; // pic_init_qp_minus26
+        reader.read_se()?; // pic_init_qs_minus26
+        reader.read_se()?; // chroma_qp_index_offset
+        let deblocking_filter_control_present_flag = reader.read_bit()?;
+        reader.skip_bits(1)?; // constrained_intra_pred_flag
+        let redundant_pic_cnt_present_flag = reader.read_bit()?;
+
+        if id > 255 || seq_parameter_set_id > 31 {
+            return Err(Error::InvalidData("PPS is invalid"));
+        }
+        let pps = AvcPps {
+            seq_parameter_set_id,
+            entropy_coding_mode_flag,
+            bottom_field_pic_order_in_frame_present_flag,
+            num_slice_groups_minus1,
+            slice_group_map_type,
+            slice_group_change_rate,
+            num_ref_idx_l0_default_active_minus1,
+            num_ref_idx_l1_default_active_minus1,
+            weighted_pred_flag,
+            weighted_bipred_idc,
+            deblocking_filter_control_present_flag,
+            redundant_pic_cnt_present_flag,
+        };
+        Ok((id, pps))
+    }
+}
+
+fn skip_avc_scaling_list(reader: &mut BitReader, size: usize) -> Result<()> {
+    let mut last_scale = 8;
+    let mut next_scale = 8;
+    for _ in 0..size {
+        if next_scale != 0 {
+            let delta_scale = reader.read_se()? as i64;
+            next_scale = (last_scale + delta_scale).rem_euclid(256);
+        }
+        if next_scale != 0 {
+            last_scale = next_scale;
+        }
+    }
+    Ok(())
+}
+
+fn skip_avc_ref_pic_list_modification(reader: &mut BitReader) -> Result<()> {
+    if reader.read_bit()? {
+        // ref_pic_list_modification_flag
+        loop {
+            match reader.read_ue()? {
+                // modification_of_pic_nums_idc
+                3 => break,
+                // abs_diff_pic_num_minus1 or long_term_pic_num
+                _ => reader.read_ue()?,
+            };
+        }
+    }
+    Ok(())
+}
+
+fn skip_avc_weights(reader: &mut BitReader, count: u32, has_chroma: bool) -> Result<()> {
+    for _ in 0..count {
+        if reader.read_bit()? {
+            // luma_weight_flag
+            reader.read_se()?; // luma_weight
+            reader.read_se()?; // luma_offset
+        }
+        if has_chroma && reader.read_bit()? {
+            // chroma_weight_flag
+            for _ in 0..4 {
+                reader.read_se()?; // chroma_weight, chroma_offset
+            }
+        }
+    }
+    Ok(())
+}
+
+/// The sequence and picture parameter sets of an HEVC track, by their ids.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct HevcParameterSets {
+    sps: HashMap<u32, HevcSps>,
+    pps: HashMap<u32, HevcPps>,
+}
+
+#[derive(Debug, Clone)]
+struct HevcSps {
+    separate_colour_plane_flag: bool,
+    chroma_array_type: u32,
+    log2_max_pic_order_cnt_lsb: usize,
+    pic_size_in_ctbs: u64,
+    sample_adaptive_offset_enabled_flag: bool,
+    short_term_ref_pic_sets: Vec<ShortTermRefPicSet>,
+    long_term_ref_pics_present_flag: bool,
+    used_by_curr_pic_lt_sps_flags: Vec<bool>,
+    temporal_mvp_enabled_flag: bool,
+}
+
+#[derive(Debug, Clone)]
+struct HevcPps {
+    seq_parameter_set_id: u32,
+    dependent_slice_segments_enabled_flag: bool,
+    output_flag_present_flag: bool,
+    num_extra_slice_header_bits: usize,
+    cabac_init_present_flag: bool,
+    num_ref_idx_l0_default_active_minus1: u32,
+    num_ref_idx_l1_default_active_minus1: u32,
+    slice_chroma_qp_offsets_present_flag: bool,
+    weighted_pred_flag: bool,
+    weighted_bipred_flag: bool,
+    tiles_enabled_flag: bool,
+    entropy_coding_sync_enabled_flag: bool,
+    loop_filter_across_slices_enabled_flag: bool,
+    deblocking_filter_override_enabled_flag: bool,
+    deblocking_filter_disabled_flag: bool,
+    lists_modification_present_flag: bool,
+    slice_segment_header_extension_present_flag: bool,
+    chroma_qp_offset_list_enabled_flag: bool,
+
+    // The multilayer, 3D and screen content coding extensions add fields
+    // to the slice header that are not parsed.
+    has_unsupported_extension: bool,
+}
+
+/// A short-term reference picture set, with the POC delta and
+/// `used_by_curr_pic` flag of each picture before and after the current
+/// one.
+#[derive(Debug, Clone, Default)]
+struct ShortTermRefPicSet {
+    negative: Vec<(i64, bool)>,
+    positive: Vec<(i64, bool)>,
+}
+
+const HEVC_SLICE_B: u32 = 0;
+const HEVC_SLICE_P: u32 = 1;
+const HEVC_SLICE_I: u32 = 2;
+
+impl HevcParameterSets {
+    /// The parameter sets of an `hvcC`.
+    pub(crate) fn new(hvcc: &HvcCBox) -> Result<Self> {
+        let mut parameter_sets = Self::default();
+        for nalu in hvcc.arrays.iter().flat_map(|array| array.nalus.iter()) {
+            parameter_sets.read_nal_unit(&nalu.data)?;
+        }
+        Ok(parameter_sets)
+    }
+
+    /// Keep the parameter set in a NAL unit, or return the size of the NAL
+    /// unit header and slice segment header of a slice segment.
+    pub(crate) fn read_nal_unit(&mut self, nal_unit: &[u8]) -> Result<Option<usize>> {
+        let Some(payload) = nal_unit.get(2..) else {
+            return Ok(None);
+        };
+        match nal_unit[0] >> 1 & 0x3f {
+            0..=9 | 16..=21 => return self.slice_header_size(nal_unit).map(Some),
+            33 => {
+                let (id, sps) = HevcSps::read(&nal_unit_rbsp(payload))?;
+                self.sps.insert(id, sps);
+            }
+            34 => {
+                let (id, pps) = HevcPps::read(&nal_unit_rbsp(payload))?;
+                self.pps.insert(id, pps);
+            }
+            _ => {}
+        }
+        Ok(None)
+    }
+
+    fn slice_header_size(&self, nal_unit: &[u8]) -> Result<usize> {
+        let nal_unit_type = nal_unit[0] >> 1 & 0x3f;
+        let nuh_layer_id = (nal_unit[0] & 1) << 5 | nal_unit[1] >> 3;
+        if nuh_layer_id > 0 {
+            return Err(Error::InvalidData(
+                "slices of HEVC enhancement layers are not supported",
+            ));
+        }
+        let rbsp = nal_unit_rbsp(&nal_unit[2..]);
+        let mut reader = BitReader::new(&rbsp);
+
+        let first_slice_segment_in_pic_flag = reader.read_bit()?;
+        if (16..=23).contains(&nal_unit_type) {
+            reader.skip_bits(1)?; // no_output_of_prior_pics_flag
+        }
+        let pps = self
+            .pps
+            .get(&reader.read_ue()?)
+            .ok_or(Error::InvalidData("slice refers to an unknown PPS"))?;
+        let sps = self
+            .sps
+            .get(&pps.seq_parameter_set_id)
+            .ok_or(Error::InvalidData("PPS refers to an unknown SPS"))?;
+        if pps.has_unsupported_extension {
+            return Err(Error::InvalidData(
+                "slice headers of HEVC extensions are not supported",
+            ));
+        }
+
+        let mut dependent_slice_segment_flag = false;
+        if !first_slice_segment_in_pic_flag {
+            if pps.dependent_slice_segments_enabled_flag {
+                dependent_slice_segment_flag = reader.read_bit()?;
+            }
+            reader.skip_bits(ceil_log2(sps.pic_size_in_ctbs))?; // slice_segment_address
+        }
+
+        if !dependent_slice_segment_flag {
+            reader.skip_bits(pps.num_extra_slice_header_bits)?; // slice_reserved_flag
+            let slice_type = reader.read_ue()?;
+            if slice_type > HEVC_SLICE_I {
+                return Err(Error::InvalidData("slice type is invalid"));
+            }
+            let is_b = slice_type == HEVC_SLICE_B;
+            if pps.output_flag_present_flag {
+                reader.skip_bits(1)?; // pic_output_flag
+            }
+            if sps.separate_colour_plane_flag {
+                reader.skip_bits(2)?; // colour_plane_id
+            }
+
+            // Number of pictures used for inter prediction of the current
+            // one, NumPicTotalCurr.
+            let mut num_pic_total_curr = 0;
+            let mut slice_temporal_mvp_enabled_flag = false;
+            // Not an IDR picture.
+            if nal_unit_type != 19 && nal_unit_type != 20 {
+                reader.skip_bits(sps.log2_max_pic_order_cnt_lsb)?; // slice_pic_order_cnt_lsb
+                let sets = &sps.short_term_ref_pic_sets;
+                let slice_set;
+                let set = if !reader.read_bit()? {
+                    // short_term_ref_pic_set_sps_flag
+                    slice_set = ShortTermRefPicSet::read(&mut reader, sets, true)?;
+                    &slice_set
+                } else {
+                    let idx = if sets.len() > 1 {
+                        // short_term_ref_pic_set_idx
+                        reader.read_bits(ceil_log2(sets.len() as u64) as u32)? as usize
+                    } else {
+                        0
+                    };
+                    sets.get(idx).ok_or(Error::InvalidData(
+                        "slice refers to an unknown reference picture set",
+                    ))?
+                };
+                num_pic_total_curr += set.num_used_by_curr_pic();
+
+                if sps.long_term_ref_pics_present_flag {
+                    let lt_sps_flags = &sps.used_by_curr_pic_lt_sps_flags;
+                    let num_long_term_sps = if !lt_sps_flags.is_empty() {
+                        reader.read_ue()?
+                    } else {
+                        0
+                    };
+                    let num_long_term_pics = reader.read_ue()?;
+                    if num_long_term_sps as usize > lt_sps_flags.len() || num_long_term_pics > 32 {
+                        return Err(Error::InvalidData(
+                            "slice has too many long-term reference pictures",
+                        ));
+                    }
+                    for i in 0..num_long_term_sps + num_long_term_pics {
+                        let used_by_curr_pic_lt_flag = if i < num_long_term_sps {
+                            let lt_idx_sps = if lt_sps_flags.len() > 1 {
+                                let bits = ceil_log2(lt_sps_flags.len() as u64);
+                                reader.read_bits(bits as u32)? as usize
+                            } else {
+                                0
+                            };
+                            *lt_sps_flags.get(lt_idx_sps).ok_or(Error::InvalidData(
+                                "slice refers to an unknown long-term reference picture",
+                            ))?
+                        } else {
+                            reader.skip_bits(sps.log2_max_pic_order_cnt_lsb)?; // poc_lsb_lt
+                            reader.read_bit()?
+                        };
+                        if used_by_curr_pic_lt_flag {
+                            num_pic_total_curr += 1;
+                        }
+                        if reader.read_bit()? {
+                            // delta_poc_msb_present_flag
+                            reader.read_ue()?; // delta_poc_msb_cycle_lt
+                        }
+                    }
+                }
+                if sps.temporal_mvp_enabled_flag {
+                    slice_temporal_mvp_enabled_flag = reader.read_bit()?;
+                }
+            }
+
+            let mut slice_sao_luma_flag = false;
+            let mut slice_sao_chroma_flag = false;
+            if sps.sample_adaptive_offset_enabled_flag {
+                slice_sao_luma_flag = reader.read_bit()?;
+                if sps.chroma_array_type != 0 {
+                    slice_sao_chroma_flag = reader.read_bit()?;
+                }
+            }
+
+            if slice_type != HEVC_SLICE_I {
+                let mut num_ref_idx_l0_active_minus1 = pps.num_ref_idx_l0_default_active_minus1;
+                let mut num_ref_idx_l1_active_minus1 = pps.num_ref_idx_l1_default_active_minus1;
+                if reader.read_bit()? {
+                    // num_ref_idx_active_override_flag
+                    num_ref_idx_l0_active_minus1 = reader.read_ue()?;
+                    if is_b {
+                        num_ref_idx_l1_active_minus1 = reader.read_ue()?;
+                    }
+                }
+                if num_ref_idx_l0_active_minus1 > 14 || num_ref_idx_l1_active_minus1 > 14 {
+                    return Err(Error::InvalidData("slice has too many reference pictures"));
+                }
+
+                if pps.lists_modification_present_flag && num_pic_total_curr > 1 {
+                    // ref_pic_lists_modification
+                    let bits = ceil_log2(num_pic_total_curr);
+                    if reader.read_bit()? {
+                        // list_entry_l0
+                        reader.skip_bits((num_ref_idx_l0_active_minus1 as usize + 1) * bits)?;
+                    }
+                    if is_b && reader.read_bit()? {
+                        // list_entry_l1
+                        reader.skip_bits((num_ref_idx_l1_active_minus1 as usize + 1) * bits)?;
+                    }
+                }
+                if is_b {
+                    reader.skip_bits(1)?; // mvd_l1_zero_flag
+                }
+                if pps.cabac_init_present_flag {
+                    reader.skip_bits(1)?; // cabac_init_flag
+                }
+                if slice_temporal_mvp_enabled_flag {
+                    let collocated_from_l0_flag = !is_b || reader.read_bit()?;
+                    if (collocated_from_l0_flag && num_ref_idx_l0_active_minus1 > 0)
+                        || (!collocated_from_l0_flag && num_ref_idx_l1_active_minus1 > 0)
+                    {
+                        reader.read_ue()?; // collocated_ref_idx
+                    }
+                }
+                if (pps.weighted_pred_flag && slice_type == HEVC_SLICE_P)
+                    || (pps.weighted_bipred_flag && is_b)
+                {
+                    // pred_weight_table
+                    let has_chroma = sps.chroma_array_type != 0;
+                    reader.read_ue()?; // luma_log2_weight_denom
+                    if has_chroma {
+                        reader.read_se()?; // delta_chroma_log2_weight_denom
+                    }
+                    skip_hevc_weights(&mut reader, num_ref_idx_l0_active_minus1 + 1, has_chroma)?;
+                    if is_b {
+                        skip_hevc_weights(
+                            &mut reader,
+                            num_ref_idx_l1_active_minus1 + 1,
+                            has_chroma,
+                        )?;
+                    }
+                }
+                reader.read_ue()?; // five_minus_max_num_merge_cand
+            }
+
+            reader.read_se()?; // slice_qp_delta
+            if pps.slice_chroma_qp_offsets_present_flag {
+                reader.read_se()?; // slice_cb_qp_offset
+                reader.read_se()?; // slice_cr_qp_offset
+            }
+            if pps.chroma_qp_offset_list_enabled_flag {
+                reader.skip_bits(1)?; // cu_chroma_qp_offset_enabled_flag
+            }
+            let deblocking_filter_override_flag =
+                pps.deblocking_filter_override_enabled_flag && reader.read_bit()?;
+            let mut slice_deblocking_filter_disabled_flag = pps.deblocking_filter_disabled_flag;
+            if deblocking_filter_override_flag {
+                slice_deblocking_filter_disabled_flag = reader.read_bit()?;
+                if !slice_deblocking_filter_disabled_flag {
+                    reader.read_se()?; // slice_beta_offset_div2
+                    reader.read_se()?; // slice_tc_offset_div2
+                }
+            }
+            if pps.loop_filter_across_slices_enabled_flag
+                && (slice_sao_luma_flag
+                    || slice_sao_chroma_flag
+                    || !slice_deblocking_filter_disabled_flag)
+            {
+                reader.skip_bits(1)?; // slice_loop_filter_across_slices_enabled_flag
+            }
+        }
+
+        if pps.tiles_enabled_flag || pps.entropy_coding_sync_enabled_flag {
+            let num_entry_point_offsets = reader.read_ue()? as usize;
+            if num_entry_point_offsets > 0 {
+                let offset_len_minus1 = reader.read_ue()? as usize;
+                if offset_len_minus1 > 31 {
+                    return Err(Error::InvalidData("slice entry points are invalid"));
+                }
+                // entry_point_offset_minus1
+                reader.skip_bits(num_entry_point_offsets.saturating_mul(offset_len_minus1 + 1))?;
+            }
+        }
+        if pps.slice_segment_header_extension_present_flag {
+            let slice_segment_header_extension_length = reader.read_ue()? as usize;
+            // slice_segment_header_extension_data_byte
+            reader.skip_bits(slice_segment_header_extension_length.saturating_mul(8))?;
+        }
+        reader.skip_bits(1)?; // alignment_bit_equal_to_one
+
+        Ok(header_size(nal_unit, 2, &rbsp, &reader))
+    }
+}
+
+impl HevcSps {
+    fn read(rbsp: &[u8]) -> Result<(u32, Self)> {
+        let mut reader = BitReader::new(rbsp);
+        reader.skip_bits(4)?; // sps_video_parameter_set_id
+        let max_sub_layers_minus1 = reader.read_bits(3)? as usize;
+        reader.skip_bits(1)?; // sps_temporal_id_nesting_flag
+
+        // profile_tier_level
+        reader.skip_bits(3)?; // general_profile_space, general_tier_flag
+        let general_profile_idc = reader.read_bits(5)?;
+        reader.skip_bits(88)?; // general compatibility and constraint flags, level
+        let mut sub_layers_present = Vec::with_capacity(max_sub_layers_minus1);
+        for _ in 0..max_sub_layers_minus1 {
+            let profile_present = reader.read_bit()?;
+            let level_present = reader.read_bit()?;
+            sub_layers_present.push((profile_present, level_present));
+        }
+        if max_sub_layers_minus1 > 0 {
+            reader.skip_bits(2 * (8 - max_sub_layers_minus1))?; // reserved
+        }
+        for (profile_present, level_present) in sub_layers_present {
+            if profile_present {
+                reader.skip_bits(88)?;
+            }
+            if level_present {
+                reader.skip_bits(8)?;
+            }
+        }
+        // The screen content coding profiles add fields to the slice header
+        // that are not parsed.
+        if general_profile_idc == 9 || general_profile_idc == 11 {
+            return Err(Error::InvalidData(
+                "HEVC screen content coding is not supported",
+            ));
+        }
+
+        let id = reader.read_ue()?;
+        let chroma_format_idc = reader.read_ue()?;
+        let mut separate_colour_plane_flag = false;
+        if chroma_format_idc == 3 {
+            separate_colour_plane_flag = reader.read_bit()?;
+        }
+        let pic_width_in_luma_samples = reader.read_ue()? as u64;
+        let pic_height_in_luma_samples = reader.read_ue()? as u64;
+        if reader.read_bit()? {
+            // conformance_window offsets
+            for _ in 0..4 {
+                reader.read_ue()?;
+            }
+        }
+        reader.read_ue()?; // bit_depth_luma_minus8
+        reader.read_ue()?; // bit_depth_chroma_minus8
+        let log2_max_pic_order_cnt_lsb_minus4 = reader.read_ue()?;
+        let sub_layer_ordering_info_present_flag = reader.read_bit()?;
+        let first_sub_layer = if sub_layer_ordering_info_present_flag {
+            0
+        } else {
+            max_sub_layers_minus1
+        };
+        for _ in first_sub_layer..=max_sub_layers_minus1 {
+            reader.read_ue()?; // sps_max_dec_pic_buffering_minus1
+            reader.read_ue()?; // sps_max_num_reorder_pics
+            reader.read_ue()?; // sps_max_latency_increase_plus1
+        }
+        let log2_min_luma_coding_block_size_minus3 = reader.read_ue()?;
+        let log2_diff_max_min_luma_coding_block_size = reader.read_ue()?;
+        reader.read_ue()?; // log2_min_luma_transform_block_size_minus2
+        reader.read_ue()?; // log2_diff_max_min_luma_transform_block_size
+        reader.read_ue()?; // max_transform_hierarchy_depth_inter
+        reader.read_ue()?; // max_transform_hierarchy_depth_intra
+        if reader.read_bit()? && reader.read_bit()? {
+            // scaling_list_enabled_flag, sps_scaling_list_data_present_flag
+            skip_hevc_scaling_list_data(&mut reader)?;
+        }
+        reader.skip_bits(1)?; // amp_enabled_flag
+        let sample_adaptive_offset_enabled_flag = reader.read_bit()?;
+        if reader.read_bit()? {
+            // pcm_enabled_flag
+            reader.skip_bits(8)?; // pcm_sample_bit_depth_luma_minus1 and _chroma_minus1
+            reader.read_ue()?; // log2_min_pcm_luma_coding_block_size_minus3
+            reader.read_ue()?; // log2_diff_max_min_pcm_luma_coding_block_size
+            reader.skip_bits(1)?; // pcm_loop_filter_disabled_flag
+        }
+
+        let num_short_term_ref_pic_sets = reader.read_ue()?;
+        if num_short_term_ref_pic_sets > 64 {
+            return Err(Error::InvalidData("SPS is invalid"));
+        }
+        let mut short_term_ref_pic_sets = Vec::with_capacity(num_short_term_ref_pic_sets as usize);
+        for _ in 0..num_short_term_ref_pic_sets {
+            let set = ShortTermRefPicSet::read(&mut reader, &short_term_ref_pic_sets, false)?;
+            short_term_ref_pic_sets.push(set);
+        }
+        let long_term_ref_pics_present_flag = reader.read_bit()?;
+        let mut used_by_curr_pic_lt_sps_flags = Vec::new();
+        if long_term_ref_pics_present_flag {
+            let num_long_term_ref_pics_sps = reader.read_ue()?;
+            if num_long_term_ref_pics_sps > 32 {
+                return Err(Error::InvalidData("SPS is invalid"));
+            }
+            for _ in 0..num_long_term_ref_pics_sps {
+                // lt_ref_pic_poc_lsb_sps
+                reader.skip_bits(log2_max_pic_order_cnt_lsb_minus4 as usize + 4)?;
+                used_by_curr_pic_lt_sps_flags.push(reader.read_bit()?);
+            }
+        }
+        let temporal_mvp_enabled_flag = reader.read_bit()?;
+
+        let ctb_log2_size = log2_min_luma_coding_block_size_minus3 as u64
+            + 3
+            + log2_diff_max_min_luma_coding_block_size as u64;
+        if id > 15
+            || chroma_format_idc > 3
+            || log2_max_pic_order_cnt_lsb_minus4 > 12
+            || ctb_log2_size > 6
+        {
+            return Err(Error::InvalidData("SPS is invalid"));
+        }
+        let pic_width_in_ctbs = pic_width_in_luma_samples.div_ceil(1 << ctb_log2_size);
+        let pic_height_in_ctbs = pic_height_in_luma_samples.div_ceil(1 << ctb_log2_size);
+        let sps = HevcSps {
+            separate_colour_plane_flag,
+            chroma_array_type: if separate_colour_plane_flag {
+                0
+            } else {
+                chroma_format_idc
+            },
+            log2_max_pic_order_cnt_lsb: log2_max_pic_order_cnt_lsb_minus4 as usize + 4,
+            pic_size_in_ctbs: pic_width_in_ctbs * pic_height_in_ctbs,
+            sample_adaptive_offset_enabled_flag,
+            short_term_ref_pic_sets,
+            long_term_ref_pics_present_flag,
+            used_by_curr_pic_lt_sps_flags,
+            temporal_mvp_enabled_flag,
+        };
+        Ok((id, sps))
+    }
+}
+
+impl HevcPps {
+    fn read(rbsp: &[u8]) -> Result<(u32, Self)> {
+        let mut reader = BitReader::new(rbsp);
+        let id = reader.read_ue()?;
+        let seq_parameter_set_id = reader.read_ue()?;
+        let dependent_slice_segments_enabled_flag = reader.read_bit()?;
+        let output_flag_present_flag = reader.read_bit()?;
+        let num_extra_slice_header_bits = reader.read_bits(3)? as usize;
+        reader.skip_bits(1)?; // sign_data_hiding_enabled_flag
+        let cabac_init_present_flag = reader.read_bit()?;
+        let num_ref_idx_l0_default_active_minus1 = reader.read_ue()?;
+        let num_ref_idx_l1_default_active_minus1 = reader.read_ue()?;
+        reader.read_se()?; // init_qp_minus26
+        reader.skip_bits(1)?; // constrained_intra_pred_flag
+        let transform_skip_enabled_flag = reader.read_bit()?;
+        if reader.read_bit()? {
+            // cu_qp_delta_enabled_flag
+            reader.read_ue()?; // diff_cu_qp_delta_depth
+        }
+        reader.read_se()?; // pps_cb_qp_offset
+        reader.read_se()?; // pps_cr_qp_offset
+        let slice_chroma_qp_offsets_present_flag = reader.read_bit()?;
+        let weighted_pred_flag = reader.read_bit()?;
+        let weighted_bipred_flag = reader.read_bit()?;
+        reader.skip_bits(1)?; // transquant_bypass_enabled_flag
+        let tiles_enabled_flag = reader.read_bit()?;
+        let entropy_coding_sync_enabled_flag = reader.read_bit()?;
+        if tiles_enabled_flag {
+            let num_tile_columns_minus1 = reader.read_ue()? as u64;
+            let num_tile_rows_minus1 = reader.read_ue()? as u64;
+            if !reader.read_bit()? {
+                // uniform_spacing_flag
+                for _ in 0..num_tile_columns_minus1 + num_tile_rows_minus1 {
+                    reader.read_ue()?; // column_width_minus1, row_height_minus1
+                }
+            }
+            reader.skip_bits(1)?; // loop_filter_across_tiles_enabled_flag
+        }
+        let loop_filter_across_slices_enabled_flag = reader.read_bit()?;
+        let mut deblocking_filter_override_enabled_flag = false;
+        let mut deblocking_filter_disabled_flag = false;
+        if reader.read_bit()? {
+            // deblocking_filter_control_present_flag
+            deblocking_filter_override_enabled_flag = reader.read_bit()?;
+            deblocking_filter_disabled_flag = reader.read_bit()?;
+            if !deblocking_filter_disabled_flag {
+                reader.read_se()?; // pps_beta_offset_div2
+                reader.read_se()?; // pps_tc_offset_div2
+            }
+        }
+        if reader.read_bit()? {
+            // pps_scaling_list_data_present_flag
+            skip_hevc_scaling_list_data(&mut reader)?;
+        }
+        let lists_modification_present_flag = reader.read_bit()?;
+        reader.read_ue()?; // log2_parallel_merge_level_minus2
+        let slice_segment_header_extension_present_flag = reader.read_bit()?;
+
+        let mut chroma_qp_offset_list_enabled_flag = false;
+        let mut has_unsupported_extension = false;
+        if reader.read_bit()? {
+            // pps_extension_present_flag
+            let range_extension_flag = reader.read_bit()?;
+            // pps_multilayer_extension_flag, pps_3d_extension_flag,
+            // pps_scc_extension_flag
+            has_unsupported_extension = reader.read_bits(3)? != 0;
+            reader.skip_bits(4)?; // pps_extension_4bits
+            if range_extension_flag {
+                if transform_skip_enabled_flag {
+                    reader.read_ue()?; // log2_max_transform_skip_block_size_minus2
+                }
+                reader.skip_bits(1)?; // cross_component_prediction_enabled_flag
+                chroma_qp_offset_list_enabled_flag = reader.read_bit()?;
+            }
+        }
+
+        if id > 63
+            || seq_parameter_set_id > 15
+            || num_ref_idx_l0_default_active_minus1 > 14
+            || num_ref_idx_l1_default_active_minus1 > 14
+        {
+            return Err(Error::InvalidData("PPS is invalid"));
+        }
+        let pps = HevcPps {
+            seq_parameter_set_id,
+            dependent_slice_segments_enabled_flag,
+            output_flag_present_flag,
+            num_extra_slice_header_bits,
+            cabac_init_present_flag,
+            num_ref_idx_l0_default_active_minus1,
+            num_ref_idx_l1_default_active_minus1,
+            slice_chroma_qp_offsets_present_flag,
+            weighted_pred_flag,
+            weighted_bipred_flag,
+            tiles_enabled_flag,
+            entropy_coding_sync_enabled_flag,
+            loop_filter_across_slices_enabled_flag,
+            deblocking_filter_override_enabled_flag,
+            deblocking_filter_disabled_flag,
+            lists_modification_present_flag,
+            slice_segment_header_extension_present_flag,
+            chroma_qp_offset_list_enabled_flag,
+            has_unsupported_extension,
+        };
+        Ok((id, pps))
+    }
+}
+
+impl ShortTermRefPicSet {
+    /// Read `st_ref_pic_set(sets.len())`, in an SPS or, following the sets
+    /// of the SPS, in a slice header.
+    fn read(
+        reader: &mut BitReader,
+        sets: &[ShortTermRefPicSet],
+        in_slice_header: bool,
+    ) -> Result<Self> {
+        if !sets.is_empty() && reader.read_bit()? {
+            // inter_ref_pic_set_prediction_flag
+            let delta_idx = if in_slice_header {
+                reader.read_ue()? as usize + 1 // delta_idx_minus1
+            } else {
+                1
+            };
+            let Some(ref_set) = sets.len().checked_sub(delta_idx).map(|idx| &sets[idx]) else {
+                return Err(Error::InvalidData(
+                    "reference picture set is predicted from an unknown set",
+                ));
+            };
+            let delta_rps_sign = reader.read_bit()?;
+            let abs_delta_rps = reader.read_ue()? as i64 + 1; // abs_delta_rps_minus1
+            let delta_rps = if delta_rps_sign {
+                -abs_delta_rps
+            } else {
+                abs_delta_rps
+            };
+
+            // The used_by_curr_pic_flag and use_delta_flag of each picture
+            // of the reference set, followed by the reference picture.
+            let num_negative = ref_set.negative.len();
+            let num_delta_pocs = num_negative + ref_set.positive.len();
+            let mut flags = Vec::with_capacity(num_delta_pocs + 1);
+            for _ in 0..=num_delta_pocs {
+                let used_by_curr_pic_flag = reader.read_bit()?;
+                let use_delta_flag = used_by_curr_pic_flag || reader.read_bit()?;
+                flags.push((used_by_curr_pic_flag, use_delta_flag));
+            }
+
+            // The derivation of equations 7-61 and 7-62 of H.265.
+            let mut set = ShortTermRefPicSet::default();
+            let (ref_used, ref_use_delta) = flags[num_delta_pocs];
+            for (i, (delta_poc, _)) in ref_set.positive.iter().enumerate().rev() {
+                let (used, use_delta) = flags[num_negative + i];
+                if delta_poc + delta_rps < 0 && use_delta {
+                    set.negative.push((delta_poc + delta_rps, used));
+                }
+            }
+            if delta_rps < 0 && ref_use_delta {
+                set.negative.push((delta_rps, ref_used));
+            }
+            for (i, (delta_poc, _)) in ref_set.negative.iter().enumerate() {
+                let (used, use_delta) = flags[i];
+                if delta_poc + delta_rps < 0 && use_delta {
+                    set.negative.push((delta_poc + delta_rps, used));
+                }
+            }
+            for (i, (delta_poc, _)) in ref_set.negative.iter().enumerate().rev() {
+                let (used, use_delta) = flags[i];
+                if delta_poc + delta_rps > 0 && use_delta {
+                    set.positive.push((delta_poc + delta_rps, used));
+                }
+            }
+            if delta_rps > 0 && ref_use_delta {
+                set.positive.push((delta_rps, ref_used));
+            }
+            for (i, (delta_poc, _)) in ref_set.positive.iter().enumerate() {
+                let (used, use_delta) = flags[num_negative + i];
+                if delta_poc + delta_rps > 0 && use_delta {
+                    set.positive.push((delta_poc + delta_rps, used));
+                }
+            }
+            Ok(set)
+        } else {
+            let num_negative_pics = reader.read_ue()?;
+            let num_positive_pics = reader.read_ue()?;
+            if num_negative_pics > 16 || num_positive_pics > 16 {
+                return Err(Error::InvalidData("reference picture set is invalid"));
+            }
+            let mut set = ShortTermRefPicSet::default();
+            let mut delta_poc = 0;
+            for _ in 0..num_negative_pics {
+                delta_poc -= reader.read_ue()? as i64 + 1; // delta_poc_s0_minus1
+                let used_by_curr_pic_s0_flag = reader.read_bit()?;
+                set.negative.push((delta_poc, used_by_curr_pic_s0_flag));
+            }
+            delta_poc = 0;
+            for _ in 0..num_positive_pics {
+                delta_poc += reader.read_ue()? as i64 + 1; // delta_poc_s1_minus1
+                let used_by_curr_pic_s1_flag = reader.read_bit()?;
+                set.positive.push((delta_poc, used_by_curr_pic_s1_flag));
+            }
+            Ok(set)
+        }
+    }
+
+    fn num_used_by_curr_pic(&self) -> u64 {
+        self.negative
+            .iter()
+            .chain(self.positive.iter())
+            .filter(|(_, used)| *used)
+            .count() as u64
+    }
+}
+
+fn skip_hevc_scaling_list_data(reader: &mut BitReader) -> Result<()> {
+    for size_id in 0..4 {
+        let matrix_count = if size_id == 3 { 2 } else { 6 };
+        for _ in 0..matrix_count {
+            if !reader.read_bit()? {
+                // scaling_list_pred_mode_flag
+                reader.read_ue()?; // scaling_list_pred_matrix_id_delta
+            } else {
+                if size_id > 1 {
+                    reader.read_se()?; // scaling_list_dc_coef_minus8
+                }
+                let coef_num = (1 << (4 + (size_id << 1))).min(64);
+                for _ in 0..coef_num {
+                    reader.read_se()?; // scaling_list_delta_coef
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+fn skip_hevc_weights(reader: &mut BitReader, count: u32, has_chroma: bool) -> Result<()> {
+    let mut luma_weight_flags = Vec::with_capacity(count as usize);
+    for _ in 0..count {
+        luma_weight_flags.push(reader.read_bit()?);
+    }
+    let mut chroma_weight_flags = vec![false; count as usize];
+    if has_chroma {
+        for flag in chroma_weight_flags.iter_mut() {
+            *flag = reader.read_bit()?;
+        }
+    }
+    for (luma_weight_flag, chroma_weight_flag) in
+        luma_weight_flags.into_iter().zip(chroma_weight_flags)
+    {
+        if luma_weight_flag {
+            reader.read_se()?; // delta_luma_weight
+            reader.read_se()?; // luma_offset
+        }
+        if chroma_weight_flag {
+            for _ in 0..4 {
+                reader.read_se()?; // delta_chroma_weight, delta_chroma_offset
+            }
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_avc_slice_header_size() {
+        // A Baseline profile SPS with 16 bit frame numbers and POCs, and a
+        // CAVLC PPS.
+        let mut parameter_sets = AvcParameterSets::default();
+        let sps = [0x67, 0x42, 0xc0, 0x0d, 0x43, 0x63, 0x50, 0x28, 0x3f, 0x20];
+        let pps = [0x68, 0x48, 0xe3, 0x88];
+        assert_eq!(parameter_sets.read_nal_unit(&sps).unwrap(), None);
+        assert_eq!(parameter_sets.read_nal_unit(&pps).unwrap(), None);
+
+        // A non-reference P slice, with an emulation prevention byte in its
+        // 40 bit header.
+        let slice = [0x01, 0xd0, 0x00, 0x00, 0x03, 0x00, 0x01, 0xbf, 0x12, 0x34];
+        assert_eq!(parameter_sets.read_nal_unit(&slice).unwrap(), Some(7));
+
+        // The slice refers to PPS 1, which is unknown here.
+        let mut parameter_sets = AvcParameterSets::default();
+        parameter_sets.read_nal_unit(&sps).unwrap();
+        assert!(parameter_sets.read_nal_unit(&slice).is_err());
+        assert!(parameter_sets.read_nal_unit(&slice[..4]).is_err());
+    }
+
+    #[test]
+    fn test_hevc_slice_header_size() {
+        let sps = [
+            0x42, 0x01, 0x01, 0x01, 0x60, 0x00, 0x00, 0x03, 0x00, 0x90, 0x00, 0x00, 0x03, 0x00,
+            0x00, 0x03, 0x00, 0x5d, 0xa0, 0x02, 0x80, 0x80, 0x2d, 0x16, 0x59, 0x59, 0xa4, 0x93,
+            0x2b, 0xc0, 0x5a, 0x70, 0x80, 0x00, 0x01, 0xf4, 0x80, 0x00, 0x30, 0xd4, 0x20,
+        ];
+        let pps = [0x44, 0x01, 0xc1, 0x72, 0xb4, 0x62, 0x40];
+        let mut parameter_sets = HevcParameterSets::default();
+        assert_eq!(parameter_sets.read_nal_unit(&sps).unwrap(), None);
+        assert_eq!(parameter_sets.read_nal_unit(&pps).unwrap(), None);
+
+        // The first slice of an IDR picture, with two entry points.
+        let slice = [0x26, 0x01, 0xaf, 0xd8, 0xa4, 0xb2, 0x05, 0x80, 0x12, 0x34];
+        assert_eq!(parameter_sets.read_nal_unit(&slice).unwrap(), Some(8));
+
+        // A P slice at CTB 120, with its own reference picture set and a
+        // weight table.
+        let slice = [
+            0x02, 0x01, 0x5e, 0x10, 0x29, 0x7e, 0x3c, 0x9f, 0x80, 0x12, 0x34,
+        ];
+        assert_eq!(parameter_sets.read_nal_unit(&slice).unwrap(), Some(9));
+        assert!(parameter_sets.read_nal_unit(&slice[..6]).is_err());
+
+        // Slices of an enhancement layer are not parsed.
+        let slice = [
+            0x02, 0x09, 0x5e, 0x10, 0x29, 0x7e, 0x3c, 0x9f, 0x80, 0x12, 0x34,
+        ];
+        assert!(parameter_sets.read_nal_unit(&slice).is_err());
+    }
+}
@@ -0,0 +1,291 @@
+use std::collections::HashMap;
+use std::io::{self, Cursor, Read};
+
+use crate::*;
+
+/// Reader for fragmented MP4 from a source that can not seek, such as a
+/// pipe or a socket.
+///
+/// The init segment (`ftyp` and `moov`) is read up front. Samples are then
+/// read fragment by fragment: each `moof` and the `mdat` following it are
+/// buffered until all of their samples have been returned.
+#[derive(Debug)]
+pub struct Mp4StreamReader<R> {
+    reader: R,
+    pub ftyp: FtypBox,
+    pub moov: MoovBox,
+
+    tracks: HashMap<u32, Mp4Track>,
+
+    // Number of bytes read so far.
+    position: u64,
+
+    // Decode time following the last sample of every track.
+    next_decode_times: HashMap<u32, u64>,
+
+    fragment: Option<StreamFragment>,
+}
+
+/// The fragment whose samples are being returned.
+#[derive(Debug)]
+struct StreamFragment {
+    // One track per traf, holding only that traf.
+    tracks: Vec<Mp4Track>,
+
+    // Stream position and contents of the mdat payload.
+    data_offset: u64,
+    data: Vec<u8>,
+
+    track_idx: usize,
+    sample_id: u32,
+}
+
+impl<R: Read> Mp4StreamReader<R> {
+    /// Read the init segment from the start of a stream.
+    pub fn read_init(reader: R) -> Result<Self> {
+        let mut stream = Mp4StreamReader {
+            reader,
+            ftyp: FtypBox::default(),
+            moov: MoovBox::default(),
+            tracks: HashMap::new(),
+            position: 0,
+            next_decode_times: HashMap::new(),
+            fragment: None,
+        };
+
+        let mut ftyp = None;
+        let mut moov = None;
+        while moov.is_none() {
+            let Some((header, _)) = stream.read_box_header()? else {
+                break;
+            };
+            match header.name {
+                BoxType::FtypBox => {
+                    ftyp = Some(stream.read_box(header)?);
+                }
+                BoxType::MoovBox => {
+                    moov = Some(stream.read_box(header)?);
+                }
+                _ => {
+                    // XXX warn!()
+                    stream.skip_box(header)?;
+                }
+            }
+        }
+
+        stream.ftyp = ftyp.ok_or(Error::BoxNotFound(BoxType::FtypBox))?;
+        let moov: MoovBox = moov.ok_or(Error::BoxNotFound(BoxType::MoovBox))?;
+        if moov.mvex.is_none() {
+            return Err(Error::BoxNotFound(BoxType::MvexBox));
+        }
+        if moov.traks.iter().any(|trak| trak.tkhd.track_id == 0) {
+            return Err(Error::InvalidData("illegal track id 0"));
+        }
+
+        stream.tracks = moov
+            .traks
+            .iter()
+            .map(|trak| (trak.tkhd.track_id, Mp4Track::from(trak, &moov)))
+            .collect();
+        stream.moov = moov;
+        Ok(stream)
+    }
+
+    /// The tracks declared in the init segment.
+    ///
+    /// These do not contain any samples, see [Mp4StreamReader::next_sample].
+    pub fn tracks(&self) -> &HashMap<u32, Mp4Track> {
+        &self.tracks
+    }
+
+    /// Read the next sample from the stream, together with its track id.
+    ///
+    /// Samples are returned fragment by fragment, and within a fragment
+    /// track by track. Returns `None` at the end of the stream. A `moof`
+    /// that is not followed by its `mdat` is an error.
+    pub fn next_sample(&mut self) -> Result<Option<(u32, Mp4Sample)>> {
+        loop {
+            if let Some(fragment) = &mut self.fragment {
+                while let Some(track) = fragment.tracks.get(fragment.track_idx) {
+                    let sample_id = fragment.sample_id;
+                    if sample_id > track.sample_count() {
+                        fragment.track_idx += 1;
+                        fragment.sample_id = 1;
+                        continue;
+                    }
+                    fragment.sample_id += 1;
+
+                    let Some((offset, size)) = track.sample_location(sample_id)? else {
+                        continue;
+                    };
+                    let bytes = offset
+                        .checked_sub(fragment.data_offset)
+                        .and_then(|start| {
+                            let start = usize::try_from(start).ok()?;
+                            fragment.data.get(start..start.checked_add(size as usize)?)
+                        })
+                        .ok_or(Error::InvalidData("sample data is outside of the mdat box"))?;
+                    let sample = track.sample_with_bytes(sample_id, bytes.to_vec());
+                    return Ok(Some((track.track_id(), sample)));
+                }
+                self.fragment = None;
+            }
+
+            if !self.read_fragment()? {
+                return Ok(None);
+            }
+        }
+    }
+
+    /// Read the next `moof` and the `mdat` following it. Returns `false` at
+    /// the end of the stream.
+    fn read_fragment(&mut self) -> Result<bool> {
+        let mut moof = None;
+        loop {
+            let Some((header, header_size)) = self.read_box_header()? else {
+                return match moof {
+                    Some(_) => Err(Error::BoxNotFound(BoxType::MdatBox)),
+                    None => Ok(false),
+                };
+            };
+            match header.name {
+                BoxType::MoofBox => {
+                    if moof.is_some() {
+                        return Err(Error::InvalidData(
+                            "moof box is followed by another moof box before its mdat box",
+                        ));
+                    }
+                    let moof_offset = self.position - header_size;
+                    let box_moof: MoofBox = self.read_box(header)?;
+                    moof = Some((box_moof, moof_offset));
+                }
+                BoxType::MdatBox if moof.is_some() => {
+                    let data_offset = self.position;
+                    let data = self.read_payload(header)?;
+                    let (moof, moof_offset) = moof.unwrap();
+                    self.fragment =
+                        Some(self.new_fragment(moof, moof_offset, data_offset, data)?);
+                    return Ok(true);
+                }
+                _ => {
+                    // XXX warn!()
+                    self.skip_box(header)?;
+                }
+            }
+        }
+    }
+
+    fn new_fragment(
+        &mut self,
+        moof: MoofBox,
+        moof_offset: u64,
+        data_offset: u64,
+        data: Vec<u8>,
+    ) -> Result<StreamFragment> {
+        let mut tracks = Vec::with_capacity(moof.trafs.len());
+        for mut traf in moof.trafs {
+            let track_id = traf.tfhd.track_id;
+            let trak = match self.tracks.get(&track_id) {
+                Some(track) => &track.trak,
+                None => return Err(Error::TrakNotFound(track_id)),
+            };
+
+            // Continue from the previous fragment if the decode time is not
+            // signalled.
+            let next_decode_time = self.next_decode_times.entry(track_id).or_default();
+            if traf.tfdt.is_none() {
+                traf.tfdt = Some(TfdtBox {
+                    version: 1,
+                    flags: 0,
+                    base_media_decode_time: *next_decode_time,
+                });
+            }
+
+            let mut track = Mp4Track::from(trak, &self.moov);
+            track.moof_offsets.push(moof_offset);
+            track.trafs.push(traf);
+            track.parse_senc_entries()?;
+
+            let sample_count = track.sample_count();
+            if sample_count > 0 {
+                let (start_time, duration) = track.sample_time(sample_count)?;
+                *next_decode_time = start_time + duration as u64;
+            }
+            tracks.push(track);
+        }
+
+        Ok(StreamFragment {
+            tracks,
+            data_offset,
+            data,
+            track_idx: 0,
+            sample_id: 1,
+        })
+    }
+
+    /// Read a box header, returning it with the number of bytes it occupies.
+    /// Returns `None` at the end of the stream.
+    fn read_box_header(&mut self) -> Result<Option<(BoxHeader, u64)>> {
+        let mut buf = [0u8; 8];
+        let mut len = 0;
+        while len < buf.len() {
+            match self.reader.read(&mut buf[len..]) {
+                Ok(0) if len == 0 => return Ok(None),
+                Ok(0) => return Err(Error::InvalidData("box header is truncated")),
+                Ok(n) => len += n,
+                Err(err) if err.kind() == io::ErrorKind::Interrupted => {}
+                Err(err) => return Err(err.into()),
+            }
+        }
+
+        let header_size = if buf[0..4] == [0, 0, 0, 1] { 16 } else { 8 };
+        let header = BoxHeader::read(&mut (&buf[..]).chain(&mut self.reader))?;
+        self.position += header_size;
+
+        // A size of zero means the box extends to the end of the stream,
+        // which can not be buffered.
+        if header.size < HEADER_SIZE {
+            return Err(Error::InvalidData("box size is smaller than its header"));
+        }
+        Ok(Some((header, header_size)))
+    }
+
+    /// Read the remainder of a box whose header has just been read.
+    fn read_payload(&mut self, header: BoxHeader) -> Result<Vec<u8>> {
+        // The box size excludes the largesize field, see BoxHeader::read.
+        let payload_size = header.size - HEADER_SIZE;
+        let mut payload = Vec::new();
+        (&mut self.reader)
+            .take(payload_size)
+            .read_to_end(&mut payload)?;
+        if payload.len() as u64 != payload_size {
+            return Err(Error::InvalidData("box is truncated"));
+        }
+        self.position += payload_size;
+        Ok(payload)
+    }
+
+    /// Read and parse a box whose header has just been read.
+    fn read_box<T>(&mut self, header: BoxHeader) -> Result<T>
+    where
+        for<'a> T: ReadBox<&'a mut Cursor<Vec<u8>>>,
+    {
+        // Leave room for the header, box parsers expect to be positioned
+        // right after it.
+        let mut buf = vec![0u8; HEADER_SIZE as usize];
+        buf.extend(self.read_payload(header)?);
+        let mut cursor = Cursor::new(buf);
+        cursor.set_position(HEADER_SIZE);
+        T::read_box(&mut cursor, header.size)
+    }
+
+    fn skip_box(&mut self, header: BoxHeader) -> Result<()> {
+        let payload_size = header.size - HEADER_SIZE;
+        let skipped = io::copy(&mut (&mut self.reader).take(payload_size), &mut io::sink())?;
+        if skipped != payload_size {
+            return Err(Error::InvalidData("box is truncated"));
+        }
+        self.position += payload_size;
+        Ok(())
+    }
+}
@@ -7,10 +7,12 @@ use crate::mp4box::traf::TrafBox;
 use crate::mp4box::trak::TrakBox;
 use crate::mp4box::trun::TrunBox;
 use crate::mp4box::{
-    avc1::Avc1Box, co64::Co64Box, ctts::CttsBox, ctts::CttsEntry, hev1::Hev1Box, mp4a::Mp4aBox,
+    ac3::Ac3Box, ac3::ac3_sample_rate, av01::Av01Box, avc1::Avc1Box, co64::Co64Box, ctts::CttsBox,
+    ctts::CttsEntry, ec3::Ec3Box, flac::FlacBox, hev1::Hev1Box, mp4a::Mp4aBox, opus::OpusBox,
     smhd::SmhdBox, stco::StcoBox, stsc::StscEntry, stss::StssBox, stts::SttsEntry, tx3g::Tx3gBox,
     vmhd::VmhdBox, vp09::Vp09Box,
 };
+use crate::slice_header::{AvcParameterSets, HevcParameterSets};
 use crate::*;
 
 #[derive(Debug, Clone, PartialEq, Eq)]
@@ -19,6 +21,10 @@ pub struct TrackConfig {
     pub timescale: u32,
     pub language: String,
     pub media_conf: MediaConfig,
+
+    /// Encrypt the samples of the track. Only supported by
+    /// [Mp4FragmentWriter], with the `cenc` feature.
+    pub encryption: Option<EncryptionConfig>,
 }
 
 impl From<MediaConfig> for TrackConfig {
@@ -27,8 +33,13 @@ impl From<MediaConfig> for TrackConfig {
             MediaConfig::AvcConfig(avc_conf) => Self::from(avc_conf),
             MediaConfig::HevcConfig(hevc_conf) => Self::from(hevc_conf),
             MediaConfig::AacConfig(aac_conf) => Self::from(aac_conf),
+            MediaConfig::OpusConfig(opus_conf) => Self::from(opus_conf),
+            MediaConfig::FlacConfig(flac_conf) => Self::from(flac_conf),
+            MediaConfig::Ac3Config(ac3_conf) => Self::from(ac3_conf),
+            MediaConfig::Ec3Config(ec3_conf) => Self::from(ec3_conf),
             MediaConfig::TtxtConfig(ttxt_conf) => Self::from(ttxt_conf),
             MediaConfig::Vp9Config(vp9_config) => Self::from(vp9_config),
+            MediaConfig::Av1Config(av1_config) => Self::from(av1_config),
         }
     }
 }
@@ -40,6 +51,7 @@ impl From<AvcConfig> for TrackConfig {
             timescale: 1000,               // XXX
             language: String::from("und"), // XXX
             media_conf: MediaConfig::AvcConfig(avc_conf),
+            encryption: None,
         }
     }
 }
@@ -51,6 +63,7 @@ impl From<HevcConfig> for TrackConfig {
             timescale: 1000,               // XXX
             language: String::from("und"), // XXX
             media_conf: MediaConfig::HevcConfig(hevc_conf),
+            encryption: None,
         }
     }
 }
@@ -62,6 +75,56 @@ impl From<AacConfig> for TrackConfig {
             timescale: 1000,               // XXX
             language: String::from("und"), // XXX
             media_conf: MediaConfig::AacConfig(aac_conf),
+            encryption: None,
+        }
+    }
+}
+
+impl From<OpusConfig> for TrackConfig {
+    fn from(opus_conf: OpusConfig) -> Self {
+        Self {
+            track_type: TrackType::Audio,
+            timescale: 48000,
+            language: String::from("und"), // XXX
+            media_conf: MediaConfig::OpusConfig(opus_conf),
+            encryption: None,
+        }
+    }
+}
+
+impl From<FlacConfig> for TrackConfig {
+    fn from(flac_conf: FlacConfig) -> Self {
+        Self {
+            track_type: TrackType::Audio,
+            timescale: flac_conf.stream_info.sample_rate,
+            language: String::from("und"), // XXX
+            media_conf: MediaConfig::FlacConfig(flac_conf),
+            encryption: None,
+        }
+    }
+}
+
+impl From<Ac3Config> for TrackConfig {
+    fn from(ac3_conf: Ac3Config) -> Self {
+        Self {
+            track_type: TrackType::Audio,
+            timescale: ac3_sample_rate(ac3_conf.fscod).unwrap_or(48000),
+            language: String::from("und"), // XXX
+            media_conf: MediaConfig::Ac3Config(ac3_conf),
+            encryption: None,
+        }
+    }
+}
+
+impl From<Ec3Config> for TrackConfig {
+    fn from(ec3_conf: Ec3Config) -> Self {
+        let fscod = ec3_conf.substreams.first().map_or(0, |s| s.fscod);
+        Self {
+            track_type: TrackType::Audio,
+            timescale: ac3_sample_rate(fscod).unwrap_or(48000),
+            language: String::from("und"), // XXX
+            media_conf: MediaConfig::Ec3Config(ec3_conf),
+            encryption: None,
         }
     }
 }
@@ -73,6 +136,7 @@ impl From<TtxtConfig> for TrackConfig {
             timescale: 1000,               // XXX
             language: String::from("und"), // XXX
             media_conf: MediaConfig::TtxtConfig(txtt_conf),
+            encryption: None,
         }
     }
 }
@@ -84,6 +148,19 @@ impl From<Vp9Config> for TrackConfig {
             timescale: 1000,               // XXX
             language: String::from("und"), // XXX
             media_conf: MediaConfig::Vp9Config(vp9_conf),
+            encryption: None,
+        }
+    }
+}
+
+impl From<Av1Config> for TrackConfig {
+    fn from(av1_conf: Av1Config) -> Self {
+        Self {
+            track_type: TrackType::Video,
+            timescale: 1000,               // XXX
+            language: String::from("und"), // XXX
+            media_conf: MediaConfig::Av1Config(av1_conf),
+            encryption: None,
         }
     }
 }
@@ -96,16 +173,37 @@ pub struct Mp4Track {
 
     // Fragmented Tracks Defaults.
     pub default_sample_duration: u32,
+    pub default_sample_size: u32,
+    pub default_sample_flags: u32,
+
+    // Timescale of the movie, in which edit list durations are given.
+    movie_timescale: u32,
+
+    sample_index: Option<Vec<SampleIndexEntry>>,
+
+    // Entries of the senc of each traf, see [Mp4Track::parse_senc_entries].
+    senc_entries: Vec<Vec<SencEntry>>,
 }
 
 impl Mp4Track {
-    pub(crate) fn from(trak: &TrakBox) -> Self {
+    /// A track of `moov`, taking its fragment defaults from the matching
+    /// `trex`.
+    pub(crate) fn from(trak: &TrakBox, moov: &MoovBox) -> Self {
+        let trex = moov
+            .mvex
+            .as_ref()
+            .and_then(|mvex| mvex.trex(trak.tkhd.track_id));
         let trak = trak.clone();
         Self {
             trak,
             trafs: Vec::new(),
             moof_offsets: Vec::new(),
-            default_sample_duration: 0,
+            default_sample_duration: trex.map(|t| t.default_sample_duration).unwrap_or(0),
+            default_sample_size: trex.map(|t| t.default_sample_size).unwrap_or(0),
+            default_sample_flags: trex.map(|t| t.default_sample_flags).unwrap_or(0),
+            movie_timescale: moov.mvhd.timescale,
+            sample_index: None,
+            senc_entries: Vec::new(),
         }
     }
 
@@ -124,8 +222,18 @@ impl Mp4Track {
             Ok(MediaType::H265)
         } else if self.trak.mdia.minf.stbl.stsd.vp09.is_some() {
             Ok(MediaType::VP9)
+        } else if self.trak.mdia.minf.stbl.stsd.av01.is_some() {
+            Ok(MediaType::AV1)
         } else if self.trak.mdia.minf.stbl.stsd.mp4a.is_some() {
             Ok(MediaType::AAC)
+        } else if self.trak.mdia.minf.stbl.stsd.opus.is_some() {
+            Ok(MediaType::OPUS)
+        } else if self.trak.mdia.minf.stbl.stsd.flac.is_some() {
+            Ok(MediaType::FLAC)
+        } else if self.trak.mdia.minf.stbl.stsd.ac3.is_some() {
+            Ok(MediaType::AC3)
+        } else if self.trak.mdia.minf.stbl.stsd.ec3.is_some() {
+            Ok(MediaType::EAC3)
         } else if self.trak.mdia.minf.stbl.stsd.tx3g.is_some() {
             Ok(MediaType::TTXT)
         } else {
@@ -136,12 +244,25 @@ impl Mp4Track {
     pub fn box_type(&self) -> Result<FourCC> {
         if self.trak.mdia.minf.stbl.stsd.avc1.is_some() {
             Ok(FourCC::from(BoxType::Avc1Box))
-        } else if self.trak.mdia.minf.stbl.stsd.hev1.is_some() {
-            Ok(FourCC::from(BoxType::Hev1Box))
+        } else if let Some(ref hev1) = self.trak.mdia.minf.stbl.stsd.hev1 {
+            match hev1.sample_entry {
+                HevcSampleEntry::Hev1 => Ok(FourCC::from(BoxType::Hev1Box)),
+                HevcSampleEntry::Hvc1 => Ok(FourCC::from(BoxType::Hvc1Box)),
+            }
         } else if self.trak.mdia.minf.stbl.stsd.vp09.is_some() {
             Ok(FourCC::from(BoxType::Vp09Box))
+        } else if self.trak.mdia.minf.stbl.stsd.av01.is_some() {
+            Ok(FourCC::from(BoxType::Av01Box))
         } else if self.trak.mdia.minf.stbl.stsd.mp4a.is_some() {
             Ok(FourCC::from(BoxType::Mp4aBox))
+        } else if self.trak.mdia.minf.stbl.stsd.opus.is_some() {
+            Ok(FourCC::from(BoxType::OpusBox))
+        } else if self.trak.mdia.minf.stbl.stsd.flac.is_some() {
+            Ok(FourCC::from(BoxType::FlacBox))
+        } else if self.trak.mdia.minf.stbl.stsd.ac3.is_some() {
+            Ok(FourCC::from(BoxType::Ac3Box))
+        } else if self.trak.mdia.minf.stbl.stsd.ec3.is_some() {
+            Ok(FourCC::from(BoxType::Ec3Box))
         } else if self.trak.mdia.minf.stbl.stsd.tx3g.is_some() {
             Ok(FourCC::from(BoxType::Tx3gBox))
         } else {
@@ -198,6 +319,101 @@ impl Mp4Track {
         }
     }
 
+    /// The sample rate of an audio track in Hz. For FLAC it is taken from
+    /// STREAMINFO, which also holds rates above 65535 Hz.
+    pub fn sample_rate(&self) -> Result<u32> {
+        let stsd = &self.trak.mdia.minf.stbl.stsd;
+        if let Some(ref flac) = stsd.flac {
+            Ok(flac.dfla.stream_info.sample_rate)
+        } else if stsd.mp4a.is_some() {
+            Ok(self.sample_freq_index()?.freq())
+        } else if stsd.opus.is_some() {
+            // Opus is always decoded at 48 kHz.
+            Ok(48000)
+        } else if let Some(ref ac3) = stsd.ac3 {
+            ac3.dac3
+                .sample_rate()
+                .ok_or(Error::InvalidData("dac3 has an invalid sample rate code"))
+        } else if let Some(ref ec3) = stsd.ec3 {
+            ec3.dec3
+                .sample_rate()
+                .ok_or(Error::InvalidData("dec3 has an invalid sample rate code"))
+        } else {
+            Err(Error::InvalidData("unsupported media type"))
+        }
+    }
+
+    /// The number of channels of an audio track.
+    pub fn channel_count(&self) -> Result<u16> {
+        let stsd = &self.trak.mdia.minf.stbl.stsd;
+        if let Some(ref flac) = stsd.flac {
+            Ok(flac.dfla.stream_info.channels as u16)
+        } else if let Some(ref mp4a) = stsd.mp4a {
+            Ok(mp4a.channelcount)
+        } else if let Some(ref opus) = stsd.opus {
+            Ok(opus.dops.output_channel_count as u16)
+        } else if stsd.ac3.is_some() || stsd.ec3.is_some() {
+            Ok(self.channel_layout()?.channel_count())
+        } else {
+            Err(Error::InvalidData("unsupported media type"))
+        }
+    }
+
+    /// The bits per sample of a FLAC track.
+    pub fn bits_per_sample(&self) -> Result<u8> {
+        match self.trak.mdia.minf.stbl.stsd.flac {
+            Some(ref flac) => Ok(flac.dfla.stream_info.bits_per_sample),
+            None => Err(Error::BoxInStblNotFound(self.track_id(), BoxType::FlacBox)),
+        }
+    }
+
+    /// The `dfLa` of a FLAC track, with STREAMINFO and any other metadata
+    /// blocks.
+    pub fn flac_metadata(&self) -> Result<&DflaBox> {
+        match self.trak.mdia.minf.stbl.stsd.flac {
+            Some(ref flac) => Ok(&flac.dfla),
+            None => Err(Error::BoxInStblNotFound(self.track_id(), BoxType::FlacBox)),
+        }
+    }
+
+    /// The loudspeaker layout of an AC-3 or E-AC-3 track. For E-AC-3 it
+    /// includes the channels of the dependent substreams.
+    pub fn channel_layout(&self) -> Result<DolbyChannelLayout> {
+        let stsd = &self.trak.mdia.minf.stbl.stsd;
+        if let Some(ref ac3) = stsd.ac3 {
+            Ok(ac3.dac3.channel_layout())
+        } else if let Some(ref ec3) = stsd.ec3 {
+            Ok(ec3.dec3.channel_layout())
+        } else {
+            Err(Error::InvalidData("unsupported media type"))
+        }
+    }
+
+    /// The `dac3` of an AC-3 track.
+    pub fn ac3_config(&self) -> Result<&Dac3Box> {
+        match self.trak.mdia.minf.stbl.stsd.ac3 {
+            Some(ref ac3) => Ok(&ac3.dac3),
+            None => Err(Error::BoxInStblNotFound(self.track_id(), BoxType::Ac3Box)),
+        }
+    }
+
+    /// The `dec3` of an E-AC-3 track, with its substreams and the Dolby
+    /// Atmos JOC extension.
+    pub fn ec3_config(&self) -> Result<&Dec3Box> {
+        match self.trak.mdia.minf.stbl.stsd.ec3 {
+            Some(ref ec3) => Ok(&ec3.dec3),
+            None => Err(Error::BoxInStblNotFound(self.track_id(), BoxType::Ec3Box)),
+        }
+    }
+
+    /// The `dOps` of an Opus track, with its channel mapping and pre-skip.
+    pub fn opus_header(&self) -> Result<&DopsBox> {
+        match self.trak.mdia.minf.stbl.stsd.opus {
+            Some(ref opus) => Ok(&opus.dops),
+            None => Err(Error::BoxInStblNotFound(self.track_id(), BoxType::OpusBox)),
+        }
+    }
+
     pub fn language(&self) -> &str {
         &self.trak.mdia.mdhd.language
     }
@@ -220,6 +436,10 @@ impl Mp4Track {
                 0
             }
             // mp4a.esds.es_desc.dec_config.avg_bitrate
+        } else if let Some(ref ac3) = self.trak.mdia.minf.stbl.stsd.ac3 {
+            ac3.dac3.bitrate().unwrap_or(0)
+        } else if let Some(ref ec3) = self.trak.mdia.minf.stbl.stsd.ec3 {
+            ec3.dec3.bitrate()
         } else {
             let dur = self.duration();
             if dur.is_zero() {
@@ -234,12 +454,10 @@ impl Mp4Track {
     pub fn sample_count(&self) -> u32 {
         if !self.trafs.is_empty() {
             let mut sample_count = 0u32;
-            for traf in self.trafs.iter() {
-                if let Some(ref trun) = traf.trun {
-                    sample_count = sample_count
-                        .checked_add(trun.sample_count)
-                        .expect("attempt to sum trun sample_count with overflow");
-                }
+            for trun in self.trafs.iter().flat_map(|traf| traf.truns.iter()) {
+                sample_count = sample_count
+                    .checked_add(trun.sample_count)
+                    .expect("attempt to sum trun sample_count with overflow");
             }
             sample_count
         } else {
@@ -268,6 +486,8 @@ impl Mp4Track {
                     0,
                 )),
             }
+        } else if self.trak.mdia.minf.stbl.stsd.hev1.is_some() {
+            self.hevc_parameter_set(HvcCArray::SPS)
         } else {
             Err(Error::BoxInStblNotFound(self.track_id(), BoxType::Avc1Box))
         }
@@ -283,11 +503,51 @@ impl Mp4Track {
                     0,
                 )),
             }
+        } else if self.trak.mdia.minf.stbl.stsd.hev1.is_some() {
+            self.hevc_parameter_set(HvcCArray::PPS)
         } else {
             Err(Error::BoxInStblNotFound(self.track_id(), BoxType::Avc1Box))
         }
     }
 
+    /// The video parameter set of an HEVC track.
+    pub fn video_parameter_set(&self) -> Result<&[u8]> {
+        self.hevc_parameter_set(HvcCArray::VPS)
+    }
+
+    /// The first parameter set of the given NAL unit type in the `hvcC`.
+    fn hevc_parameter_set(&self, nal_unit_type: u8) -> Result<&[u8]> {
+        let Some(ref hev1) = self.trak.mdia.minf.stbl.stsd.hev1 else {
+            return Err(Error::BoxInStblNotFound(self.track_id(), BoxType::Hev1Box));
+        };
+        hev1.hvcc
+            .arrays
+            .iter()
+            .filter(|array| array.nal_unit_type == nal_unit_type)
+            .flat_map(|array| array.nalus.first())
+            .map(|nalu| nalu.data.as_ref())
+            .next()
+            .ok_or(Error::EntryInStblNotFound(
+                self.track_id(),
+                BoxType::HvcCBox,
+                0,
+            ))
+    }
+
+    /// The sequence header OBU of an AV1 track.
+    pub fn sequence_header(&self) -> Result<&[u8]> {
+        let Some(ref av01) = self.trak.mdia.minf.stbl.stsd.av01 else {
+            return Err(Error::BoxInStblNotFound(self.track_id(), BoxType::Av01Box));
+        };
+        av01.av1c
+            .sequence_header()
+            .ok_or(Error::EntryInStblNotFound(
+                self.track_id(),
+                BoxType::Av1CBox,
+                0,
+            ))
+    }
+
     pub fn audio_profile(&self) -> Result<AudioObjectType> {
         if let Some(ref mp4a) = self.trak.mdia.minf.stbl.stsd.mp4a {
             if let Some(ref esds) = mp4a.esds {
@@ -300,6 +560,266 @@ impl Mp4Track {
         }
     }
 
+    /// Protection scheme information of an encrypted track, whose sample
+    /// entry is `encv` or `enca`.
+    pub fn protection_scheme_info(&self) -> Option<&SinfBox> {
+        let stsd = &self.trak.mdia.minf.stbl.stsd;
+        if let Some(ref avc1) = stsd.avc1 {
+            avc1.sinf.as_ref()
+        } else if let Some(ref hev1) = stsd.hev1 {
+            hev1.sinf.as_ref()
+        } else if let Some(ref vp09) = stsd.vp09 {
+            vp09.sinf.as_ref()
+        } else if let Some(ref av01) = stsd.av01 {
+            av01.sinf.as_ref()
+        } else if let Some(ref mp4a) = stsd.mp4a {
+            mp4a.sinf.as_ref()
+        } else if let Some(ref opus) = stsd.opus {
+            opus.sinf.as_ref()
+        } else if let Some(ref flac) = stsd.flac {
+            flac.sinf.as_ref()
+        } else if let Some(ref ac3) = stsd.ac3 {
+            ac3.sinf.as_ref()
+        } else if let Some(ref ec3) = stsd.ec3 {
+            ec3.sinf.as_ref()
+        } else {
+            None
+        }
+    }
+
+    pub fn is_encrypted(&self) -> bool {
+        self.protection_scheme_info().is_some()
+    }
+
+    /// The format of the samples before encryption, such as `avc1`, or
+    /// `None` if the track is not encrypted.
+    pub fn original_format(&self) -> Option<FourCC> {
+        Some(self.protection_scheme_info()?.frma.original_format)
+    }
+
+    /// The protection scheme, such as `cenc` or `cbcs`, or `None` if the
+    /// track is not encrypted.
+    pub fn protection_scheme(&self) -> Option<FourCC> {
+        Some(self.protection_scheme_info()?.schm.as_ref()?.scheme_type)
+    }
+
+    /// The key ID that samples are encrypted with, unless a `seig` sample
+    /// group says otherwise.
+    pub fn default_kid(&self) -> Option<[u8; 16]> {
+        Some(self.protection_scheme_info()?.tenc()?.default_kid)
+    }
+
+    /// How a sample is encrypted, or `None` if the track is not encrypted or
+    /// the sample is not protected.
+    ///
+    /// This only supports per-sample information stored in a `senc` box, as
+    /// is usual for fragments. Use [Mp4Reader::sample_encryption] to also
+    /// read information stored elsewhere in the file.
+    pub fn sample_encryption(&self, sample_id: u32) -> Result<Option<SampleEncryption>> {
+        self.sample_encryption_with(sample_id, |_, _| {
+            Err(Error::InvalidData(
+                "sample encryption information is not in a senc box",
+            ))
+        })
+    }
+
+    /// Like [Mp4Track::sample_encryption], reading per-sample information
+    /// referenced by `saiz` and `saio` from `reader`.
+    pub(crate) fn read_sample_encryption<R: Read + Seek>(
+        &self,
+        reader: &mut R,
+        sample_id: u32,
+    ) -> Result<Option<SampleEncryption>> {
+        self.sample_encryption_with(sample_id, |offset, size| {
+            let mut buffer = vec![0; size as usize];
+            reader.seek(SeekFrom::Start(offset))?;
+            reader.read_exact(&mut buffer)?;
+            Ok(buffer)
+        })
+    }
+
+    fn sample_encryption_with(
+        &self,
+        sample_id: u32,
+        read_aux_info: impl FnOnce(u64, u8) -> Result<Vec<u8>>,
+    ) -> Result<Option<SampleEncryption>> {
+        let Some(sinf) = self.protection_scheme_info() else {
+            return Ok(None);
+        };
+        if sample_id == 0 || sample_id > self.sample_count() {
+            return Ok(None);
+        }
+        let Some((is_protected, iv_size, mut encryption)) = self.encryption_defaults(sample_id)
+        else {
+            return Err(Error::BoxInStblNotFound(self.track_id(), BoxType::TencBox));
+        };
+        if !is_protected {
+            return Ok(None);
+        }
+
+        let scheme_type = sinf.schm.as_ref().map(|schm| schm.scheme_type);
+        let entry = match self.sample_aux_info(sample_id, scheme_type)? {
+            Some(AuxInfo::Entry(entry)) => entry,
+            Some(AuxInfo::Location(offset, size)) => {
+                let data = read_aux_info(offset, size)?;
+                let has_subsamples = data.len() > iv_size as usize;
+                SencEntry::read(&mut data.as_slice(), iv_size, has_subsamples)?
+            }
+            // Samples with a constant IV and no subsamples need no
+            // per-sample information.
+            None if iv_size == 0 => SencEntry::default(),
+            None => {
+                return Err(Error::InvalidData(
+                    "sample encryption information not found",
+                ));
+            }
+        };
+
+        if iv_size > 0 {
+            encryption.iv = entry.iv;
+        }
+        encryption.subsamples = entry.subsamples;
+        Ok(Some(encryption))
+    }
+
+    /// Whether a sample is protected, its per-sample IV size and its
+    /// encryption parameters without the per-sample information, from the
+    /// `tenc` of the track or the `seig` sample group of the sample.
+    fn encryption_defaults(&self, sample_id: u32) -> Option<(bool, u8, SampleEncryption)> {
+        let sinf = self.protection_scheme_info()?;
+        let tenc = sinf.tenc()?;
+        let scheme_type = sinf.schm.as_ref().map(|schm| schm.scheme_type);
+        let mut encryption = SampleEncryption {
+            scheme_type: scheme_type.unwrap_or_default(),
+            kid: tenc.default_kid,
+            iv: tenc.default_constant_iv.clone(),
+            crypt_byte_block: tenc.default_crypt_byte_block,
+            skip_byte_block: tenc.default_skip_byte_block,
+            subsamples: Vec::new(),
+        };
+        let mut is_protected = tenc.default_is_protected;
+        let mut iv_size = tenc.default_per_sample_iv_size;
+
+        if let Some(SampleGroupEntry::Seig {
+            crypt_byte_block,
+            skip_byte_block,
+            is_protected: seig_is_protected,
+            per_sample_iv_size,
+            kid,
+            constant_iv,
+        }) = self.sample_group(sample_id, SampleGroupEntry::SEIG)
+        {
+            is_protected = *seig_is_protected;
+            iv_size = *per_sample_iv_size;
+            encryption.kid = *kid;
+            encryption.iv = constant_iv.clone();
+            encryption.crypt_byte_block = *crypt_byte_block;
+            encryption.skip_byte_block = *skip_byte_block;
+        }
+        Some((is_protected, iv_size, encryption))
+    }
+
+    /// Parse the `senc` of every `traf`. Samples of a fragment may have
+    /// different IV sizes, given by the `tenc` of the track or the `seig`
+    /// sample group of each sample, so this is called once all `trafs` are
+    /// added.
+    pub(crate) fn parse_senc_entries(&mut self) -> Result<()> {
+        let has_tenc = self
+            .protection_scheme_info()
+            .and_then(|sinf| sinf.tenc())
+            .is_some();
+        let mut senc_entries = Vec::with_capacity(self.trafs.len());
+        let mut first_sample_id = 1;
+        for traf in self.trafs.iter() {
+            let entries = match traf.senc {
+                Some(ref senc) if has_tenc => {
+                    senc.entries_with(|i| match self.encryption_defaults(first_sample_id + i) {
+                        Some((true, iv_size, _)) => iv_size,
+                        _ => 0,
+                    })?
+                }
+                _ => Vec::new(),
+            };
+            senc_entries.push(entries);
+            first_sample_id += traf.truns.iter().map(|trun| trun.sample_count).sum::<u32>();
+        }
+        self.senc_entries = senc_entries;
+        Ok(())
+    }
+
+    /// The per-sample encryption information of a sample, either from the
+    /// `senc` of its `traf` or located by `saiz` and `saio`.
+    fn sample_aux_info(
+        &self,
+        sample_id: u32,
+        scheme_type: Option<FourCC>,
+    ) -> Result<Option<AuxInfo>> {
+        let has_type =
+            |aux_info_type: Option<FourCC>| aux_info_type.is_none() || aux_info_type == scheme_type;
+
+        if !self.trafs.is_empty() {
+            let Some((traf_idx, sample_idx)) = self.find_traf_and_sample_idx(sample_id) else {
+                return Ok(None);
+            };
+            let traf = &self.trafs[traf_idx];
+
+            if traf.senc.is_some() {
+                return Ok(self
+                    .senc_entries
+                    .get(traf_idx)
+                    .and_then(|entries| entries.get(sample_idx as usize))
+                    .map(|entry| AuxInfo::Entry(entry.clone())));
+            }
+
+            let saiz = traf.saizs.iter().find(|saiz| has_type(saiz.aux_info_type));
+            let saio = traf.saios.iter().find(|saio| has_type(saio.aux_info_type));
+            let (Some(saiz), Some(saio)) = (saiz, saio) else {
+                return Ok(None);
+            };
+            let base_offset = traf
+                .tfhd
+                .base_data_offset
+                .unwrap_or(self.moof_offsets[traf_idx]);
+
+            // Either one offset for the fragment, or one per trun.
+            let (offset, first_sample_idx) = if saio.offsets.len() > 1 {
+                let (_, trun_idx, idx_in_trun) = self.find_trun_and_sample_idx(sample_id).unwrap();
+                (
+                    saio.offsets.get(trun_idx).copied(),
+                    sample_idx - idx_in_trun as u32,
+                )
+            } else {
+                (saio.offsets.first().copied(), 0)
+            };
+            let Some(offset) = offset else {
+                return Ok(None);
+            };
+            return aux_info_location(saiz, base_offset + offset, first_sample_idx, sample_idx);
+        }
+
+        let stbl = &self.trak.mdia.minf.stbl;
+        let saiz = stbl.saizs.iter().find(|saiz| has_type(saiz.aux_info_type));
+        let saio = stbl.saios.iter().find(|saio| has_type(saio.aux_info_type));
+        let (Some(saiz), Some(saio)) = (saiz, saio) else {
+            return Ok(None);
+        };
+
+        // Either one offset for the track, or one per chunk.
+        let (offset, first_sample_id) = if saio.offsets.len() > 1 {
+            let (chunk_id, first_sample_in_chunk) = self.sample_chunk(sample_id)?;
+            (
+                saio.offsets.get(chunk_id as usize - 1).copied(),
+                first_sample_in_chunk,
+            )
+        } else {
+            (saio.offsets.first().copied(), 1)
+        };
+        let Some(offset) = offset else {
+            return Ok(None);
+        };
+        aux_info_location(saiz, offset, first_sample_id - 1, sample_id - 1)
+    }
+
     fn stsc_index(&self, sample_id: u32) -> Result<usize> {
         if self.trak.mdia.minf.stbl.stsc.entries.is_empty() {
             return Err(Error::InvalidData("no stsc entries"));
@@ -316,6 +836,30 @@ impl Mp4Track {
         Ok(self.trak.mdia.minf.stbl.stsc.entries.len() - 1)
     }
 
+    /// return `(chunk_id, first_sample_id_in_chunk)`
+    fn sample_chunk(&self, sample_id: u32) -> Result<(u32, u32)> {
+        let stsc_index = self.stsc_index(sample_id)?;
+
+        let stsc = &self.trak.mdia.minf.stbl.stsc;
+        let stsc_entry = stsc.entries.get(stsc_index).unwrap();
+
+        let first_chunk = stsc_entry.first_chunk;
+        let first_sample = stsc_entry.first_sample;
+        let samples_per_chunk = stsc_entry.samples_per_chunk;
+
+        let chunk_id = sample_id
+            .checked_sub(first_sample)
+            .map(|n| n / samples_per_chunk)
+            .and_then(|n| n.checked_add(first_chunk))
+            .ok_or(Error::InvalidData(
+                "attempt to calculate stsc chunk_id with overflow",
+            ))?;
+
+        let first_sample_in_chunk = sample_id - (sample_id - first_sample) % samples_per_chunk;
+
+        Ok((chunk_id, first_sample_in_chunk))
+    }
+
     fn chunk_offset(&self, chunk_id: u32) -> Result<u64> {
         if self.trak.mdia.minf.stbl.stco.is_none() && self.trak.mdia.minf.stbl.co64.is_none() {
             return Err(Error::InvalidData("must have either stco or co64 boxes"));
@@ -367,44 +911,96 @@ impl Mp4Track {
         ))
     }
 
-    /// return `(traf_idx, sample_idx_in_trun)`
-    fn find_traf_idx_and_sample_idx(&self, sample_id: u32) -> Option<(usize, usize)> {
-        let global_idx = sample_id - 1;
-        let mut offset = 0;
-        for traf_idx in 0..self.trafs.len() {
-            if let Some(trun) = &self.trafs[traf_idx].trun {
-                let sample_count = trun.sample_count;
-                if sample_count > (global_idx - offset) {
-                    return Some((traf_idx, (global_idx - offset) as _));
+    /// return `(traf_idx, trun_idx, sample_idx_in_trun)`
+    fn find_trun_and_sample_idx(&self, sample_id: u32) -> Option<(usize, usize, usize)> {
+        let mut sample_idx = sample_id.checked_sub(1)?;
+        for (traf_idx, traf) in self.trafs.iter().enumerate() {
+            for (trun_idx, trun) in traf.truns.iter().enumerate() {
+                if sample_idx < trun.sample_count {
+                    return Some((traf_idx, trun_idx, sample_idx as usize));
                 }
-                offset = offset
-                    .checked_add(sample_count)
-                    .expect("attempt to sum trun sample_count with overflow");
+                sample_idx -= trun.sample_count;
             }
         }
         None
     }
 
+    /// return `(traf_idx, sample_idx_in_traf)`
+    fn find_traf_and_sample_idx(&self, sample_id: u32) -> Option<(usize, u32)> {
+        let (traf_idx, trun_idx, sample_idx) = self.find_trun_and_sample_idx(sample_id)?;
+        let preceding: u32 = self.trafs[traf_idx].truns[..trun_idx]
+            .iter()
+            .map(|trun| trun.sample_count)
+            .sum();
+        Some((traf_idx, preceding + sample_idx as u32))
+    }
+
+    /// Size of a sample in a `trun`, falling back to the `tfhd` and then the
+    /// `trex` default.
+    fn trun_sample_size(&self, traf: &TrafBox, trun: &TrunBox, sample_idx: usize) -> u32 {
+        trun.sample_sizes
+            .get(sample_idx)
+            .copied()
+            .or(traf.tfhd.default_sample_size)
+            .unwrap_or(self.default_sample_size)
+    }
+
+    /// Duration of a sample in a `trun`, falling back to the `tfhd` and then
+    /// the `trex` default.
+    fn trun_sample_duration(&self, traf: &TrafBox, trun: &TrunBox, sample_idx: usize) -> u32 {
+        trun.sample_durations
+            .get(sample_idx)
+            .copied()
+            .or(traf.tfhd.default_sample_duration)
+            .unwrap_or(self.default_sample_duration)
+    }
+
+    /// File offsets of the sample data of every `trun` in a `traf`.
+    fn trun_data_offsets(&self, traf_idx: usize) -> Result<Vec<u64>> {
+        let traf = &self.trafs[traf_idx];
+        let base_offset = traf
+            .tfhd
+            .base_data_offset
+            .unwrap_or(self.moof_offsets[traf_idx]);
+
+        let mut offsets = Vec::with_capacity(traf.truns.len());
+        let mut next_offset = base_offset;
+        for trun in traf.truns.iter() {
+            // Without a data offset, the data of a trun follows that of the
+            // previous one.
+            let offset =
+                match trun.data_offset {
+                    Some(data_offset) => base_offset.checked_add_signed(data_offset as i64).ok_or(
+                        Error::InvalidData("attempt to calculate trun sample offset with overflow"),
+                    )?,
+                    None => next_offset,
+                };
+            offsets.push(offset);
+
+            next_offset = offset;
+            for i in 0..trun.sample_count as usize {
+                next_offset = next_offset
+                    .checked_add(self.trun_sample_size(traf, trun, i) as u64)
+                    .ok_or(Error::InvalidData(
+                        "attempt to calculate trun entry sample offset with overflow",
+                    ))?;
+            }
+        }
+        Ok(offsets)
+    }
+
     fn sample_size(&self, sample_id: u32) -> Result<u32> {
         if !self.trafs.is_empty() {
-            if let Some((traf_idx, sample_idx)) = self.find_traf_idx_and_sample_idx(sample_id) {
-                if let Some(size) = self.trafs[traf_idx]
-                    .trun
-                    .as_ref()
-                    .unwrap()
-                    .sample_sizes
-                    .get(sample_idx)
-                {
-                    Ok(*size)
-                } else {
-                    Err(Error::EntryInTrunNotFound(
-                        self.track_id(),
-                        BoxType::TrunBox,
-                        sample_id,
-                    ))
-                }
+            if let Some((traf_idx, trun_idx, sample_idx)) = self.find_trun_and_sample_idx(sample_id)
+            {
+                let traf = &self.trafs[traf_idx];
+                Ok(self.trun_sample_size(traf, &traf.truns[trun_idx], sample_idx))
             } else {
-                Err(Error::BoxInTrafNotFound(self.track_id(), BoxType::TrafBox))
+                Err(Error::EntryInTrunNotFound(
+                    self.track_id(),
+                    BoxType::TrunBox,
+                    sample_id,
+                ))
             }
         } else {
             let stsz = &self.trak.mdia.minf.stbl.stsz;
@@ -437,27 +1033,18 @@ impl Mp4Track {
     }
 
     pub fn sample_offset(&self, sample_id: u32) -> Result<u64> {
+        if let Some(entry) = self.sample_index_entry(sample_id) {
+            return Ok(entry.offset);
+        }
         if !self.trafs.is_empty() {
-            if let Some((traf_idx, sample_idx)) = self.find_traf_idx_and_sample_idx(sample_id) {
-                let mut sample_offset = self.trafs[traf_idx]
-                    .tfhd
-                    .base_data_offset
-                    .unwrap_or(self.moof_offsets[traf_idx]);
-
-                if let Some(data_offset) = self.trafs[traf_idx]
-                    .trun
-                    .as_ref()
-                    .and_then(|trun| trun.data_offset)
-                {
-                    sample_offset = sample_offset.checked_add_signed(data_offset as i64).ok_or(
-                        Error::InvalidData("attempt to calculate trun sample offset with overflow"),
-                    )?;
-                }
-
-                let first_sample_in_trun = sample_id - sample_idx as u32;
-                for i in first_sample_in_trun..sample_id {
+            if let Some((traf_idx, trun_idx, sample_idx)) = self.find_trun_and_sample_idx(sample_id)
+            {
+                let traf = &self.trafs[traf_idx];
+                let trun = &traf.truns[trun_idx];
+                let mut sample_offset = self.trun_data_offsets(traf_idx)?[trun_idx];
+                for i in 0..sample_idx {
                     sample_offset = sample_offset
-                        .checked_add(self.sample_size(i)? as u64)
+                        .checked_add(self.trun_sample_size(traf, trun, i) as u64)
                         .ok_or(Error::InvalidData(
                             "attempt to calculate trun entry sample offset with overflow",
                         ))?;
@@ -468,27 +1055,9 @@ impl Mp4Track {
                 Err(Error::BoxInTrafNotFound(self.track_id(), BoxType::TrafBox))
             }
         } else {
-            let stsc_index = self.stsc_index(sample_id)?;
-
-            let stsc = &self.trak.mdia.minf.stbl.stsc;
-            let stsc_entry = stsc.entries.get(stsc_index).unwrap();
-
-            let first_chunk = stsc_entry.first_chunk;
-            let first_sample = stsc_entry.first_sample;
-            let samples_per_chunk = stsc_entry.samples_per_chunk;
-
-            let chunk_id = sample_id
-                .checked_sub(first_sample)
-                .map(|n| n / samples_per_chunk)
-                .and_then(|n| n.checked_add(first_chunk))
-                .ok_or(Error::InvalidData(
-                    "attempt to calculate stsc chunk_id with overflow",
-                ))?;
-
+            let (chunk_id, first_sample_in_chunk) = self.sample_chunk(sample_id)?;
             let chunk_offset = self.chunk_offset(chunk_id)?;
 
-            let first_sample_in_chunk = sample_id - (sample_id - first_sample) % samples_per_chunk;
-
             let mut sample_offset = 0;
             for i in first_sample_in_chunk..sample_id {
                 sample_offset += self.sample_size(i)?;
@@ -498,33 +1067,79 @@ impl Mp4Track {
         }
     }
 
-    fn sample_time(&self, sample_id: u32) -> Result<(u64, u32)> {
-        if !self.trafs.is_empty() {
-            let mut base_start_time = 0;
-            let mut default_sample_duration = self.default_sample_duration;
-            if let Some((traf_idx, sample_idx)) = self.find_traf_idx_and_sample_idx(sample_id) {
-                let traf = &self.trafs[traf_idx];
-                if let Some(tfdt) = &traf.tfdt {
-                    base_start_time = tfdt.base_media_decode_time;
-                }
-                if let Some(duration) = traf.tfhd.default_sample_duration {
-                    default_sample_duration = duration;
+    /// The first sample id and decode time of each traf with a tfdt. The
+    /// decode times of the other samples follow on from the sample before.
+    pub(crate) fn fragment_decode_times(&self) -> Vec<(u32, u64)> {
+        let mut times = Vec::new();
+        let mut sample_id = 1u32;
+        for traf in self.trafs.iter() {
+            if let Some(ref tfdt) = traf.tfdt {
+                times.push((sample_id, tfdt.base_media_decode_time));
+            }
+            for trun in traf.truns.iter() {
+                sample_id = sample_id.saturating_add(trun.sample_count);
+            }
+        }
+        times
+    }
+
+    /// The decode time of the first sample of a traf. Without a tfdt, it
+    /// follows on from the samples of the preceding trafs.
+    fn traf_start_time(&self, traf_idx: usize) -> Result<u64> {
+        let first = self.trafs[..=traf_idx]
+            .iter()
+            .rposition(|traf| traf.tfdt.is_some())
+            .unwrap_or(0);
+        let mut start_time = self.trafs[first]
+            .tfdt
+            .as_ref()
+            .map(|tfdt| tfdt.base_media_decode_time)
+            .unwrap_or(0);
+        for traf in &self.trafs[first..traf_idx] {
+            for trun in traf.truns.iter() {
+                for i in 0..trun.sample_count as usize {
+                    start_time = start_time
+                        .checked_add(self.trun_sample_duration(traf, trun, i) as u64)
+                        .ok_or(Error::InvalidData(
+                            "attempt to sum sample durations with overflow",
+                        ))?;
                 }
-                if let Some(trun) = &traf.trun {
-                    if TrunBox::FLAG_SAMPLE_DURATION & trun.flags != 0 {
-                        let mut start_offset = 0u64;
-                        for duration in &trun.sample_durations[..sample_idx] {
-                            start_offset = start_offset.checked_add(*duration as u64).ok_or(
-                                Error::InvalidData("attempt to sum sample durations with overflow"),
-                            )?;
-                        }
-                        let duration = trun.sample_durations[sample_idx];
-                        return Ok((base_start_time + start_offset, duration));
-                    }
+            }
+        }
+        Ok(start_time)
+    }
+
+    pub(crate) fn sample_time(&self, sample_id: u32) -> Result<(u64, u32)> {
+        if let Some(entry) = self.sample_index_entry(sample_id) {
+            return Ok((entry.timing.start_time, entry.timing.duration));
+        }
+        if !self.trafs.is_empty() {
+            let Some((traf_idx, trun_idx, sample_idx)) = self.find_trun_and_sample_idx(sample_id)
+            else {
+                return Err(Error::EntryInTrunNotFound(
+                    self.track_id(),
+                    BoxType::TrunBox,
+                    sample_id,
+                ));
+            };
+            let traf = &self.trafs[traf_idx];
+            let mut start_time = self.traf_start_time(traf_idx)?;
+            for (i, trun) in traf.truns[..=trun_idx].iter().enumerate() {
+                let sample_count = if i == trun_idx {
+                    sample_idx
+                } else {
+                    trun.sample_count as usize
+                };
+                for j in 0..sample_count {
+                    start_time = start_time
+                        .checked_add(self.trun_sample_duration(traf, trun, j) as u64)
+                        .ok_or(Error::InvalidData(
+                            "attempt to sum sample durations with overflow",
+                        ))?;
                 }
             }
-            let start_offset = ((sample_id - 1) * default_sample_duration) as u64;
-            Ok((base_start_time + start_offset, default_sample_duration))
+            let duration = self.trun_sample_duration(traf, &traf.truns[trun_idx], sample_idx);
+            Ok((start_time, duration))
         } else {
             let stts = &self.trak.mdia.minf.stbl.stts;
 
@@ -558,28 +1173,27 @@ impl Mp4Track {
 
     fn sample_rendering_offset(&self, sample_id: u32) -> i32 {
         if !self.trafs.is_empty() {
-            if let Some((traf_idx, sample_idx)) = self.find_traf_idx_and_sample_idx(sample_id) {
-                if let Some(cts) = self.trafs[traf_idx]
-                    .trun
-                    .as_ref()
-                    .and_then(|trun| trun.sample_cts.get(sample_idx))
-                {
-                    return *cts as i32;
-                }
-            }
-        } else if let Some(ref ctts) = self.trak.mdia.minf.stbl.ctts {
-            if let Ok((ctts_index, _)) = self.ctts_index(sample_id) {
-                let ctts_entry = ctts.entries.get(ctts_index).unwrap();
-                return ctts_entry.sample_offset;
+            if let Some((traf_idx, trun_idx, sample_idx)) = self.find_trun_and_sample_idx(sample_id)
+                && let Some(cts) = self.trafs[traf_idx].truns[trun_idx]
+                    .sample_cts
+                    .get(sample_idx)
+            {
+                return *cts as i32;
             }
+        } else if let Some(ref ctts) = self.trak.mdia.minf.stbl.ctts
+            && let Ok((ctts_index, _)) = self.ctts_index(sample_id)
+        {
+            let ctts_entry = ctts.entries.get(ctts_index).unwrap();
+            return ctts_entry.sample_offset;
         }
         0
     }
 
     fn is_sync_sample(&self, sample_id: u32) -> bool {
         if !self.trafs.is_empty() {
-            let sample_sizes_count = self.sample_count() / self.trafs.len() as u32;
-            return sample_id == 1 || sample_id % sample_sizes_count == 0;
+            return self
+                .sample_flags(sample_id)
+                .is_some_and(|flags| !flags.is_non_sync_sample);
         }
 
         if let Some(ref stss) = self.trak.mdia.minf.stbl.stss {
@@ -589,38 +1203,779 @@ impl Mp4Track {
         }
     }
 
-    pub(crate) fn read_sample<R: Read + Seek>(
-        &self,
-        reader: &mut R,
-        sample_id: u32,
-    ) -> Result<Option<Mp4Sample>> {
-        let sample_offset = match self.sample_offset(sample_id) {
-            Ok(offset) => offset,
-            Err(Error::EntryInStblNotFound(_, _, _)) => return Ok(None),
-            Err(err) => return Err(err),
+    /// The flags of a sample in a track fragment, or `None` for tracks that
+    /// are not fragmented.
+    ///
+    /// The flags of the first sample of a `trun` take precedence over its
+    /// per-sample flags, which take precedence over the `tfhd` and then the
+    /// `trex` defaults.
+    fn sample_flags(&self, sample_id: u32) -> Option<SampleFlags> {
+        let (traf_idx, trun_idx, sample_idx) = self.find_trun_and_sample_idx(sample_id)?;
+        let traf = &self.trafs[traf_idx];
+        let trun = &traf.truns[trun_idx];
+
+        let flags = if sample_idx == 0
+            && let Some(flags) = trun.first_sample_flags
+        {
+            flags
+        } else if let Some(flags) = trun.sample_flags.get(sample_idx) {
+            *flags
+        } else {
+            traf.tfhd
+                .default_sample_flags
+                .unwrap_or(self.default_sample_flags)
         };
-        let sample_size = match self.sample_size(sample_id) {
-            Ok(size) => size,
-            Err(Error::EntryInStblNotFound(_, _, _)) => return Ok(None),
-            Err(err) => return Err(err),
+        Some(SampleFlags::from(flags))
+    }
+
+    /// The sample group description of a grouping type that a sample maps
+    /// to, or `None` if the sample is not in a group of that type.
+    ///
+    /// Samples of fragments are mapped by the `sbgp` of their `traf`, which
+    /// may refer to descriptions in the `sgpd` of the `traf` or of `stbl`.
+    /// Samples not mapped by any `sbgp` take the default description of a
+    /// version 2 `sgpd`, if any.
+    pub fn sample_group(&self, sample_id: u32, grouping_type: FourCC) -> Option<&SampleGroupEntry> {
+        let stbl = &self.trak.mdia.minf.stbl;
+        let (sbgps, sgpds, sample_idx) = if !self.trafs.is_empty() {
+            let (traf_idx, sample_idx) = self.find_traf_and_sample_idx(sample_id)?;
+            let traf = &self.trafs[traf_idx];
+            (&traf.sbgps, &traf.sgpds[..], sample_idx)
+        } else {
+            if sample_id == 0 || sample_id > self.sample_count() {
+                return None;
+            }
+            (&stbl.sbgps, &[][..], sample_id - 1)
         };
 
-        let mut buffer = vec![0x0u8; sample_size as usize];
-        reader.seek(SeekFrom::Start(sample_offset))?;
-        reader.read_exact(&mut buffer)?;
+        fn find_sgpd(sgpds: &[SgpdBox], grouping_type: FourCC) -> Option<&SgpdBox> {
+            sgpds
+                .iter()
+                .find(|sgpd| sgpd.grouping_type == grouping_type)
+        }
+        let index = sbgps
+            .iter()
+            .filter(|sbgp| sbgp.grouping_type == grouping_type)
+            .find_map(|sbgp| sbgp.group_description_index(sample_idx));
+        match index {
+            Some(0) => None,
+            Some(index) if index > SbgpBox::FRAGMENT_LOCAL_INDEX_BASE => {
+                find_sgpd(sgpds, grouping_type)?.entry(index - SbgpBox::FRAGMENT_LOCAL_INDEX_BASE)
+            }
+            Some(index) => find_sgpd(&stbl.sgpds, grouping_type)?.entry(index),
+            None => {
+                let sgpd =
+                    find_sgpd(sgpds, grouping_type).or(find_sgpd(&stbl.sgpds, grouping_type))?;
+                if sgpd.version >= 2 {
+                    sgpd.entry(sgpd.default_sample_description_index)
+                } else {
+                    None
+                }
+            }
+        }
+    }
 
-        let (start_time, duration) = self.sample_time(sample_id).unwrap(); // XXX
-        let rendering_offset = self.sample_rendering_offset(sample_id);
-        let is_sync = self.is_sync_sample(sample_id);
+    /// How a sample depends on other samples, from the `sdtp` box of its
+    /// `stbl` or `traf`, or else from its sample flags.
+    pub fn sample_dependency(&self, sample_id: u32) -> Option<SampleDependency> {
+        if !self.trafs.is_empty() {
+            let (traf_idx, sample_idx) = self.find_traf_and_sample_idx(sample_id)?;
+            let sdtp = self.trafs[traf_idx].sdtp.as_ref();
+            return match sdtp.and_then(|sdtp| sdtp.entries.get(sample_idx as usize)) {
+                Some(dependency) => Some(*dependency),
+                None => self.sample_flags(sample_id).map(SampleDependency::from),
+            };
+        }
 
-        Ok(Some(Mp4Sample {
-            start_time,
-            duration,
-            rendering_offset,
-            is_sync,
-            bytes: buffer,
-        }))
+        let sdtp = self.trak.mdia.minf.stbl.sdtp.as_ref()?;
+        sdtp.entries
+            .get(sample_id.checked_sub(1)? as usize)
+            .copied()
     }
+
+    /// The subsamples of a sample, from the first `subs` box of its `stbl`
+    /// or `traf`. Empty if the sample has no subsample information.
+    pub fn subsamples(&self, sample_id: u32) -> Vec<Subsample> {
+        let entry = if !self.trafs.is_empty() {
+            self.find_traf_and_sample_idx(sample_id)
+                .and_then(|(traf_idx, sample_idx)| {
+                    self.trafs[traf_idx].subs.first()?.entry(sample_idx + 1)
+                })
+        } else {
+            self.trak
+                .mdia
+                .minf
+                .stbl
+                .subs
+                .first()
+                .and_then(|subs| subs.entry(sample_id))
+        };
+        let Some(entry) = entry else {
+            return Vec::new();
+        };
+
+        let mut start = 0;
+        entry
+            .subsamples
+            .iter()
+            .map(|subsample| {
+                let end = start + subsample.subsample_size as usize;
+                let range = start..end;
+                start = end;
+                Subsample {
+                    range,
+                    priority: subsample.subsample_priority,
+                    discardable: subsample.discardable,
+                    codec_specific_parameters: subsample.codec_specific_parameters,
+                }
+            })
+            .collect()
+    }
+
+    /// Precompute the offset, size, timing and sync flag of every sample.
+    ///
+    /// Without an index, every sample lookup walks the sample tables (or the
+    /// `trun` boxes of fragmented tracks) from the start. With an index,
+    /// lookups take constant time at the cost of about 32 bytes of memory per
+    /// sample. The index is used by all sample reading methods once built.
+    ///
+    /// Fails if the timing tables and the chunk (or `trun`) tables disagree
+    /// on the number of samples.
+    pub fn build_sample_index(&mut self) -> Result<()> {
+        self.sample_index = None;
+
+        let timings = self.sample_timings();
+        let locations = if !self.trafs.is_empty() {
+            self.fragment_sample_locations()?
+        } else {
+            self.chunk_sample_locations()?
+        };
+        if timings.len() != locations.len() {
+            return Err(Error::InvalidData(
+                "sample timing and location tables have different sample counts",
+            ));
+        }
+
+        let index = timings
+            .into_iter()
+            .zip(locations)
+            .enumerate()
+            .map(|(i, (timing, (offset, size)))| SampleIndexEntry {
+                offset,
+                size,
+                timing,
+                is_sync: self.is_sync_sample(i as u32 + 1),
+                flags: self.sample_flags(i as u32 + 1),
+                dependency: self.sample_dependency(i as u32 + 1),
+            })
+            .collect();
+        self.sample_index = Some(index);
+        Ok(())
+    }
+
+    /// Whether [Mp4Track::build_sample_index] was called.
+    pub fn has_sample_index(&self) -> bool {
+        self.sample_index.is_some()
+    }
+
+    fn sample_index_entry(&self, sample_id: u32) -> Option<&SampleIndexEntry> {
+        let index = self.sample_index.as_ref()?;
+        index.get(sample_id.checked_sub(1)? as usize)
+    }
+
+    /// Offsets and sizes of all samples of a progressive track, walking the
+    /// chunks in order.
+    fn chunk_sample_locations(&self) -> Result<Vec<(u64, u32)>> {
+        let mut locations = Vec::with_capacity(self.sample_count() as usize);
+        self.walk_chunk_samples(|_, offset, size| locations.push((offset, size)))?;
+        Ok(locations)
+    }
+
+    /// Total size of the samples in each chunk of a progressive track,
+    /// indexed by chunk id minus one. Chunks without samples have size 0.
+    pub(crate) fn chunk_sizes(&self) -> Result<Vec<u64>> {
+        let mut sizes = vec![0u64; self.chunk_count() as usize];
+        self.walk_chunk_samples(|chunk_id, _, size| {
+            if let Some(chunk_size) = chunk_id
+                .checked_sub(1)
+                .and_then(|i| sizes.get_mut(i as usize))
+            {
+                *chunk_size += size as u64;
+            }
+        })?;
+        Ok(sizes)
+    }
+
+    fn chunk_count(&self) -> u32 {
+        let stbl = &self.trak.mdia.minf.stbl;
+        if let Some(ref stco) = stbl.stco {
+            stco.entries.len() as u32
+        } else if let Some(ref co64) = stbl.co64 {
+            co64.entries.len() as u32
+        } else {
+            0
+        }
+    }
+
+    /// Call `f` with the chunk id, offset and size of each sample of a
+    /// progressive track, walking the chunks in order.
+    fn walk_chunk_samples(&self, mut f: impl FnMut(u32, u64, u32)) -> Result<()> {
+        let stbl = &self.trak.mdia.minf.stbl;
+        let sample_count = self.sample_count();
+        let chunk_count = self.chunk_count();
+
+        let mut sample_id = 1;
+        'stsc: for (i, entry) in stbl.stsc.entries.iter().enumerate() {
+            if entry.samples_per_chunk == 0 {
+                continue;
+            }
+            let last_chunk = match stbl.stsc.entries.get(i + 1) {
+                Some(next) => next.first_chunk.saturating_sub(1),
+                None => chunk_count,
+            };
+            for chunk_id in entry.first_chunk..=last_chunk.min(chunk_count) {
+                let mut offset = self.chunk_offset(chunk_id)?;
+                for _ in 0..entry.samples_per_chunk {
+                    if sample_id > sample_count {
+                        break 'stsc;
+                    }
+                    let size = self.sample_size(sample_id)?;
+                    f(chunk_id, offset, size);
+                    offset = offset.checked_add(size as u64).ok_or(Error::InvalidData(
+                        "attempt to calculate chunk sample offset with overflow",
+                    ))?;
+                    sample_id += 1;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Offsets and sizes of all samples of a fragmented track, walking the
+    /// `trun` boxes in order.
+    fn fragment_sample_locations(&self) -> Result<Vec<(u64, u32)>> {
+        let mut locations = Vec::with_capacity(self.sample_count() as usize);
+        for (traf_idx, traf) in self.trafs.iter().enumerate() {
+            let offsets = self.trun_data_offsets(traf_idx)?;
+            for (trun, mut offset) in traf.truns.iter().zip(offsets) {
+                for i in 0..trun.sample_count as usize {
+                    let size = self.trun_sample_size(traf, trun, i);
+                    locations.push((offset, size));
+                    offset += size as u64;
+                }
+            }
+        }
+        Ok(locations)
+    }
+
+    /// Decode times, durations and rendering offsets of all samples, in a
+    /// single pass over the timing tables.
+    fn sample_timings(&self) -> Vec<SampleTiming> {
+        if let Some(ref index) = self.sample_index {
+            return index.iter().map(|entry| entry.timing).collect();
+        }
+
+        let sample_count = self.sample_count() as usize;
+        let mut timings = Vec::with_capacity(sample_count);
+
+        if !self.trafs.is_empty() {
+            let mut start_time = 0u64;
+            for traf in self.trafs.iter() {
+                if let Some(ref tfdt) = traf.tfdt {
+                    start_time = tfdt.base_media_decode_time;
+                }
+                for trun in traf.truns.iter() {
+                    for i in 0..trun.sample_count as usize {
+                        let duration = self.trun_sample_duration(traf, trun, i);
+                        let rendering_offset =
+                            trun.sample_cts.get(i).map(|cts| *cts as i32).unwrap_or(0);
+                        timings.push(SampleTiming {
+                            start_time,
+                            duration,
+                            rendering_offset,
+                        });
+                        start_time = start_time.saturating_add(duration as u64);
+                    }
+                }
+            }
+            return timings;
+        }
+
+        let stbl = &self.trak.mdia.minf.stbl;
+        let mut start_time = 0u64;
+        'stts: for entry in stbl.stts.entries.iter() {
+            for _ in 0..entry.sample_count {
+                if timings.len() == sample_count {
+                    break 'stts;
+                }
+                timings.push(SampleTiming {
+                    start_time,
+                    duration: entry.sample_delta,
+                    rendering_offset: 0,
+                });
+                start_time = start_time.saturating_add(entry.sample_delta as u64);
+            }
+        }
+        if let Some(ref ctts) = stbl.ctts {
+            let mut timings = timings.iter_mut();
+            'ctts: for entry in ctts.entries.iter() {
+                for _ in 0..entry.sample_count {
+                    match timings.next() {
+                        Some(timing) => timing.rendering_offset = entry.sample_offset,
+                        None => break 'ctts,
+                    }
+                }
+            }
+        }
+        timings
+    }
+
+    /// Find the sample to start playback from at the presentation time `time`.
+    ///
+    /// Times are on the presentation timeline of the track's edit list, see
+    /// [Mp4Track::presentation_time]. Samples that are not presented are
+    /// skipped. Returns the sample id and the presentation time of that
+    /// sample. See [SeekMode] for how the sample is chosen.
+    pub fn seek(&self, time: Duration, mode: SeekMode) -> Result<(u32, Duration)> {
+        let timescale = self.timescale();
+        if timescale == 0 {
+            return Err(Error::InvalidData("track timescale is zero"));
+        }
+        let target = time.as_nanos() * timescale as u128 / 1_000_000_000;
+        let target = i64::try_from(target).unwrap_or(i64::MAX);
+
+        let edits = self.edits();
+        let timings: Box<dyn Iterator<Item = SampleTiming> + '_> = match self.sample_index {
+            Some(ref index) => Box::new(index.iter().map(|entry| entry.timing)),
+            None => Box::new(self.sample_timings().into_iter()),
+        };
+        let is_sync = |sample_id: u32| match self.sample_index_entry(sample_id) {
+            Some(entry) => entry.is_sync,
+            None => self.is_sync_sample(sample_id),
+        };
+        let mut found: Option<(u32, i64)> = None;
+        // The earliest sample that may be picked, for seeking before the
+        // first sample.
+        let mut first: Option<(u32, i64)> = None;
+        for (i, timing) in timings.enumerate() {
+            let sample_id = i as u32 + 1;
+            let Some(pts) = edit_presentation_time(&edits, &timing) else {
+                continue;
+            };
+            let eligible = mode == SeekMode::Exact || is_sync(sample_id);
+            if eligible && first.is_none_or(|(_, first_pts)| pts < first_pts) {
+                first = Some((sample_id, pts));
+            }
+            let candidate = match mode {
+                SeekMode::Exact | SeekMode::PreviousSync => eligible && pts <= target,
+                SeekMode::NextSync => eligible && pts >= target,
+            };
+            if !candidate {
+                continue;
+            }
+            let better = match (mode, found) {
+                (_, None) => true,
+                (SeekMode::NextSync, Some((_, best))) => pts < best,
+                (_, Some((_, best))) => pts > best,
+            };
+            if better {
+                found = Some((sample_id, pts));
+            }
+        }
+
+        // Seeking before the first sample starts at the beginning.
+        if found.is_none() && mode != SeekMode::NextSync {
+            found = first;
+        }
+
+        match found {
+            Some((sample_id, pts)) => {
+                let nanos = pts.max(0) as u128 * 1_000_000_000 / timescale as u128;
+                let nanos = u64::try_from(nanos).unwrap_or(u64::MAX);
+                Ok((sample_id, Duration::from_nanos(nanos)))
+            }
+            None => Err(Error::InvalidData("no sample found at seek position")),
+        }
+    }
+
+    /// The presentation time of a sample on the timeline defined by the
+    /// track's edit list, in the track's timescale.
+    ///
+    /// Returns `None` if the sample is not presented, for example when it is
+    /// trimmed by the edit list. A sample presented by more than one edit
+    /// returns the time of its first presentation. The time is negative for
+    /// a sample that starts before the media time of its edit, such as a
+    /// partially trimmed audio priming frame. Without an edit list this is
+    /// the decode time plus the rendering offset.
+    pub fn presentation_time(&self, sample_id: u32) -> Result<Option<i64>> {
+        let (start_time, duration) = self.sample_time(sample_id)?;
+        let rendering_offset = match self.sample_index_entry(sample_id) {
+            Some(entry) => entry.timing.rendering_offset,
+            None => self.sample_rendering_offset(sample_id),
+        };
+        let timing = SampleTiming {
+            start_time,
+            duration,
+            rendering_offset,
+        };
+        Ok(edit_presentation_time(&self.edits(), &timing))
+    }
+
+    /// The duration of the track after applying its edit list, including
+    /// empty edits. Without an edit list this is [Mp4Track::duration].
+    pub fn presentation_duration(&self) -> Duration {
+        let edits = self.edits();
+        let Some(last) = edits.last() else {
+            return self.duration();
+        };
+        let duration = match (last.duration, last.media_time) {
+            (Some(duration), _) => duration,
+            // The last edit extends to the end of the media.
+            (None, Some(media_time)) if last.media_rate > 0 => {
+                let media_end = self
+                    .sample_timings()
+                    .last()
+                    .map(|timing| timing.start_time + timing.duration as u64)
+                    .unwrap_or(0)
+                    .max(self.trak.mdia.mdhd.duration);
+                (media_end.saturating_sub(media_time) as u128 * 0x10000 / last.media_rate as u128)
+                    as u64
+            }
+            (None, _) => 0,
+        };
+
+        let timescale = self.timescale();
+        if timescale == 0 {
+            return Duration::ZERO;
+        }
+        let nanos = (last.start + duration) as u128 * 1_000_000_000 / timescale as u128;
+        Duration::from_nanos(u64::try_from(nanos).unwrap_or(u64::MAX))
+    }
+
+    /// The edits of the track's edit list, on the track's timescale.
+    ///
+    /// Edits with a negative media rate are not supported and left out.
+    fn edits(&self) -> Vec<Edit> {
+        let Some(elst) = self.trak.edts.as_ref().and_then(|edts| edts.elst.as_ref()) else {
+            return Vec::new();
+        };
+
+        // Durations are in the movie timescale, media times in the track's.
+        let timescale = self.timescale() as u128;
+        let movie_timescale = match self.movie_timescale {
+            0 => timescale.max(1),
+            movie_timescale => movie_timescale as u128,
+        };
+
+        let mut edits = Vec::with_capacity(elst.entries.len());
+        let mut start = 0u64;
+        for entry in elst.entries.iter() {
+            // A zero duration, as used in fragmented files, extends the edit
+            // to the end of the media.
+            let duration = match entry.segment_duration {
+                0 => None,
+                duration => {
+                    let duration = duration as u128 * timescale / movie_timescale;
+                    Some(u64::try_from(duration).unwrap_or(u64::MAX))
+                }
+            };
+            let media_rate =
+                ((entry.media_rate as i16 as i32) << 16) | entry.media_rate_fraction as i32;
+            if let Ok(media_rate) = u32::try_from(media_rate) {
+                edits.push(Edit {
+                    start,
+                    duration,
+                    media_time: (!entry.is_empty_edit()).then_some(entry.media_time),
+                    media_rate,
+                });
+            }
+            start = start.saturating_add(duration.unwrap_or(0));
+        }
+        edits
+    }
+
+    pub(crate) fn read_sample<R: Read + Seek>(
+        &self,
+        reader: &mut R,
+        sample_id: u32,
+    ) -> Result<Option<Mp4Sample>> {
+        let (sample_offset, sample_size) = match self.sample_location(sample_id)? {
+            Some(location) => location,
+            None => return Ok(None),
+        };
+
+        let mut buffer = vec![0x0u8; sample_size as usize];
+        reader.seek(SeekFrom::Start(sample_offset))?;
+        reader.read_exact(&mut buffer)?;
+
+        Ok(Some(self.sample_with_bytes(sample_id, buffer)))
+    }
+
+    /// File offset and size of a sample, or `None` if the track has no such
+    /// sample.
+    pub(crate) fn sample_location(&self, sample_id: u32) -> Result<Option<(u64, u32)>> {
+        if let Some(entry) = self.sample_index_entry(sample_id) {
+            return Ok(Some((entry.offset, entry.size)));
+        }
+
+        let sample_offset = match self.sample_offset(sample_id) {
+            Ok(offset) => offset,
+            Err(Error::EntryInStblNotFound(_, _, _)) => return Ok(None),
+            Err(err) => return Err(err),
+        };
+        let sample_size = match self.sample_size(sample_id) {
+            Ok(size) => size,
+            Err(Error::EntryInStblNotFound(_, _, _)) => return Ok(None),
+            Err(err) => return Err(err),
+        };
+        Ok(Some((sample_offset, sample_size)))
+    }
+
+    /// Build a sample from its data, as read from [Mp4Track::sample_location].
+    pub(crate) fn sample_with_bytes(&self, sample_id: u32, bytes: Vec<u8>) -> Mp4Sample {
+        if let Some(entry) = self.sample_index_entry(sample_id) {
+            return Mp4Sample {
+                start_time: entry.timing.start_time,
+                duration: entry.timing.duration,
+                rendering_offset: entry.timing.rendering_offset,
+                is_sync: entry.is_sync,
+                flags: entry.flags,
+                dependency: entry.dependency,
+                subsamples: self.subsamples(sample_id),
+                bytes,
+            };
+        }
+
+        let (start_time, duration) = self.sample_time(sample_id).unwrap(); // XXX
+        let rendering_offset = self.sample_rendering_offset(sample_id);
+        let is_sync = self.is_sync_sample(sample_id);
+        let flags = self.sample_flags(sample_id);
+        let dependency = self.sample_dependency(sample_id);
+        let subsamples = self.subsamples(sample_id);
+
+        Mp4Sample {
+            start_time,
+            duration,
+            rendering_offset,
+            is_sync,
+            flags,
+            dependency,
+            subsamples,
+            bytes,
+        }
+    }
+}
+
+/// Timing of a single sample, in the track's timescale.
+#[derive(Debug, Clone, Copy)]
+struct SampleTiming {
+    start_time: u64,
+    duration: u32,
+    rendering_offset: i32,
+}
+
+/// An edit of the presentation timeline, in the track's timescale.
+#[derive(Debug, Clone, Copy)]
+struct Edit {
+    // Presentation time at which the edit starts.
+    start: u64,
+
+    // Duration on the presentation timeline, `None` if the edit extends to
+    // the end of the media.
+    duration: Option<u64>,
+
+    // Media time presented at the start of the edit, `None` for an empty
+    // edit.
+    media_time: Option<u64>,
+
+    // Media rate in 16.16 fixed point, zero for a dwell edit.
+    media_rate: u32,
+}
+
+impl Edit {
+    /// Length of media presented during `duration` on the presentation
+    /// timeline.
+    fn media_duration(&self, duration: u64) -> u64 {
+        (duration as u128 * self.media_rate as u128 / 0x10000) as u64
+    }
+}
+
+/// The presentation time of a sample with the given timing on the timeline
+/// of `edits`, see [Mp4Track::presentation_time].
+fn edit_presentation_time(edits: &[Edit], timing: &SampleTiming) -> Option<i64> {
+    let pts = timing.start_time as i64 + timing.rendering_offset as i64;
+
+    if edits.is_empty() {
+        return Some(pts);
+    }
+    let end = pts + timing.duration.max(1) as i64;
+    for edit in edits.iter() {
+        let Some(media_time) = edit.media_time else {
+            continue;
+        };
+        let media_time = media_time as i64;
+        if edit.media_rate == 0 {
+            // A dwell edit presents the sample at media_time for the
+            // duration of the edit.
+            if pts <= media_time && media_time < end {
+                return Some(edit.start as i64);
+            }
+            continue;
+        }
+        let media_end = edit
+            .duration
+            .map(|duration| media_time + edit.media_duration(duration) as i64);
+        if end > media_time && media_end.is_none_or(|media_end| pts < media_end) {
+            let offset = (pts - media_time) as i128 * 0x10000 / edit.media_rate as i128;
+            return Some(edit.start as i64 + offset as i64);
+        }
+    }
+    None
+}
+
+/// Location, timing and flags of a single sample, as precomputed by
+/// [Mp4Track::build_sample_index].
+#[derive(Debug, Clone, Copy)]
+struct SampleIndexEntry {
+    offset: u64,
+    size: u32,
+    timing: SampleTiming,
+    is_sync: bool,
+    flags: Option<SampleFlags>,
+    dependency: Option<SampleDependency>,
+}
+
+/// The per-sample encryption information of a sample.
+enum AuxInfo {
+    /// Parsed from a `senc` box.
+    Entry(SencEntry),
+
+    /// File offset and size of the information, from `saio` and `saiz`.
+    Location(u64, u8),
+}
+
+/// Locate the auxiliary information of a zero-based sample index, given the
+/// offset of the information of the sample at `first_sample_idx` and the
+/// sizes in `saiz`.
+fn aux_info_location(
+    saiz: &SaizBox,
+    offset: u64,
+    first_sample_idx: u32,
+    sample_idx: u32,
+) -> Result<Option<AuxInfo>> {
+    let Some(size) = saiz.sample_info_size(sample_idx) else {
+        return Ok(None);
+    };
+    let mut offset = offset;
+    for idx in first_sample_idx..sample_idx {
+        offset += saiz.sample_info_size(idx).unwrap_or(0) as u64;
+    }
+    Ok(Some(AuxInfo::Location(offset, size)))
+}
+
+/// Build the `trak` skeleton (headers and sample description) for a track
+/// configuration. Sample tables are left empty.
+fn new_trak(track_id: u32, config: &TrackConfig) -> Result<TrakBox> {
+    let mut trak = TrakBox::default();
+    trak.tkhd.track_id = track_id;
+    trak.mdia.mdhd.timescale = config.timescale;
+    trak.mdia.mdhd.language = config.language.to_owned();
+    trak.mdia.hdlr.handler_type = config.track_type.into();
+    match config.media_conf {
+        MediaConfig::AvcConfig(ref avc_config) => {
+            trak.tkhd.set_width(avc_config.width);
+            trak.tkhd.set_height(avc_config.height);
+
+            let vmhd = VmhdBox::default();
+            trak.mdia.minf.vmhd = Some(vmhd);
+
+            let avc1 = Avc1Box::new(avc_config);
+            trak.mdia.minf.stbl.stsd.avc1 = Some(avc1);
+        }
+        MediaConfig::HevcConfig(ref hevc_config) => {
+            trak.tkhd.set_width(hevc_config.width);
+            trak.tkhd.set_height(hevc_config.height);
+
+            let vmhd = VmhdBox::default();
+            trak.mdia.minf.vmhd = Some(vmhd);
+
+            let hev1 = Hev1Box::new(hevc_config)?;
+            trak.mdia.minf.stbl.stsd.hev1 = Some(hev1);
+        }
+        MediaConfig::Vp9Config(ref config) => {
+            trak.tkhd.set_width(config.width);
+            trak.tkhd.set_height(config.height);
+
+            trak.mdia.minf.stbl.stsd.vp09 = Some(Vp09Box::new(config));
+        }
+        MediaConfig::Av1Config(ref config) => {
+            trak.tkhd.set_width(config.width);
+            trak.tkhd.set_height(config.height);
+
+            let vmhd = VmhdBox::default();
+            trak.mdia.minf.vmhd = Some(vmhd);
+
+            let av01 = Av01Box::new(config)?;
+            trak.mdia.minf.stbl.stsd.av01 = Some(av01);
+        }
+        MediaConfig::AacConfig(ref aac_config) => {
+            let smhd = SmhdBox::default();
+            trak.mdia.minf.smhd = Some(smhd);
+
+            let mp4a = Mp4aBox::new(aac_config);
+            trak.mdia.minf.stbl.stsd.mp4a = Some(mp4a);
+        }
+        MediaConfig::OpusConfig(ref opus_config) => {
+            let smhd = SmhdBox::default();
+            trak.mdia.minf.smhd = Some(smhd);
+
+            let opus = OpusBox::new(opus_config)?;
+            trak.mdia.minf.stbl.stsd.opus = Some(opus);
+
+            // Skip the pre-skip samples at the start of the presentation.
+            // Until the duration is known, the edit extends to the end of
+            // the media.
+            let media_time = opus_config.pre_skip as u64 * config.timescale as u64 / 48000;
+            trak.edts = Some(EdtsBox {
+                elst: Some(ElstBox {
+                    version: 0,
+                    flags: 0,
+                    entries: vec![ElstEntry {
+                        segment_duration: 0,
+                        media_time,
+                        media_rate: 1,
+                        media_rate_fraction: 0,
+                    }],
+                }),
+            });
+        }
+        MediaConfig::FlacConfig(ref flac_config) => {
+            let smhd = SmhdBox::default();
+            trak.mdia.minf.smhd = Some(smhd);
+
+            let flac = FlacBox::new(flac_config)?;
+            trak.mdia.minf.stbl.stsd.flac = Some(flac);
+        }
+        MediaConfig::Ac3Config(ref ac3_config) => {
+            let smhd = SmhdBox::default();
+            trak.mdia.minf.smhd = Some(smhd);
+
+            let ac3 = Ac3Box::new(ac3_config)?;
+            trak.mdia.minf.stbl.stsd.ac3 = Some(ac3);
+        }
+        MediaConfig::Ec3Config(ref ec3_config) => {
+            let smhd = SmhdBox::default();
+            trak.mdia.minf.smhd = Some(smhd);
+
+            let ec3 = Ec3Box::new(ec3_config)?;
+            trak.mdia.minf.stbl.stsd.ec3 = Some(ec3);
+        }
+        MediaConfig::TtxtConfig(ref _ttxt_config) => {
+            let tx3g = Tx3gBox::default();
+            trak.mdia.minf.stbl.stsd.tx3g = Some(tx3g);
+        }
+    }
+    Ok(trak)
 }
 
 // TODO creation_time, modification_time
@@ -641,51 +1996,13 @@ pub(crate) struct Mp4TrackWriter {
 
 impl Mp4TrackWriter {
     pub(crate) fn new(track_id: u32, config: &TrackConfig) -> Result<Self> {
-        let mut trak = TrakBox::default();
-        trak.tkhd.track_id = track_id;
-        trak.mdia.mdhd.timescale = config.timescale;
-        trak.mdia.mdhd.language = config.language.to_owned();
-        trak.mdia.hdlr.handler_type = config.track_type.into();
-        trak.mdia.minf.stbl.co64 = Some(Co64Box::default());
-        match config.media_conf {
-            MediaConfig::AvcConfig(ref avc_config) => {
-                trak.tkhd.set_width(avc_config.width);
-                trak.tkhd.set_height(avc_config.height);
-
-                let vmhd = VmhdBox::default();
-                trak.mdia.minf.vmhd = Some(vmhd);
-
-                let avc1 = Avc1Box::new(avc_config);
-                trak.mdia.minf.stbl.stsd.avc1 = Some(avc1);
-            }
-            MediaConfig::HevcConfig(ref hevc_config) => {
-                trak.tkhd.set_width(hevc_config.width);
-                trak.tkhd.set_height(hevc_config.height);
-
-                let vmhd = VmhdBox::default();
-                trak.mdia.minf.vmhd = Some(vmhd);
-
-                let hev1 = Hev1Box::new(hevc_config);
-                trak.mdia.minf.stbl.stsd.hev1 = Some(hev1);
-            }
-            MediaConfig::Vp9Config(ref config) => {
-                trak.tkhd.set_width(config.width);
-                trak.tkhd.set_height(config.height);
-
-                trak.mdia.minf.stbl.stsd.vp09 = Some(Vp09Box::new(config));
-            }
-            MediaConfig::AacConfig(ref aac_config) => {
-                let smhd = SmhdBox::default();
-                trak.mdia.minf.smhd = Some(smhd);
-
-                let mp4a = Mp4aBox::new(aac_config);
-                trak.mdia.minf.stbl.stsd.mp4a = Some(mp4a);
-            }
-            MediaConfig::TtxtConfig(ref _ttxt_config) => {
-                let tx3g = Tx3gBox::default();
-                trak.mdia.minf.stbl.stsd.tx3g = Some(tx3g);
-            }
+        if config.encryption.is_some() {
+            return Err(Error::InvalidData(
+                "encryption is only supported for fragmented output",
+            ));
         }
+        let mut trak = new_trak(track_id, config)?;
+        trak.mdia.minf.stbl.co64 = Some(Co64Box::default());
         Ok(Mp4TrackWriter {
             trak,
             chunk_buffer: Vec::new(),
@@ -730,11 +2047,11 @@ impl Mp4TrackWriter {
     }
 
     fn update_sample_times(&mut self, dur: u32) {
-        if let Some(ref mut entry) = self.trak.mdia.minf.stbl.stts.entries.last_mut() {
-            if entry.sample_delta == dur {
-                entry.sample_count += 1;
-                return;
-            }
+        if let Some(ref mut entry) = self.trak.mdia.minf.stbl.stts.entries.last_mut()
+            && entry.sample_delta == dur
+        {
+            entry.sample_count += 1;
+            return;
         }
 
         let entry = SttsEntry {
@@ -763,11 +2080,11 @@ impl Mp4TrackWriter {
             self.trak.mdia.minf.stbl.ctts.as_mut().unwrap()
         };
 
-        if let Some(ref mut entry) = ctts.entries.last_mut() {
-            if entry.sample_offset == offset {
-                entry.sample_count += 1;
-                return;
-            }
+        if let Some(ref mut entry) = ctts.entries.last_mut()
+            && entry.sample_offset == offset
+        {
+            entry.sample_count += 1;
+            return;
         }
 
         let entry = CttsEntry {
@@ -845,10 +2162,10 @@ impl Mp4TrackWriter {
     }
 
     fn update_sample_to_chunk(&mut self, chunk_id: u32) {
-        if let Some(entry) = self.trak.mdia.minf.stbl.stsc.entries.last() {
-            if entry.samples_per_chunk == self.chunk_samples {
-                return;
-            }
+        if let Some(entry) = self.trak.mdia.minf.stbl.stsc.entries.last()
+            && entry.samples_per_chunk == self.chunk_samples
+        {
+            return;
         }
 
         let entry = StscEntry {
@@ -866,7 +2183,7 @@ impl Mp4TrackWriter {
     }
 
     fn write_chunk<W: Write + Seek>(&mut self, writer: &mut W) -> Result<()> {
-        if self.chunk_buffer.is_empty() {
+        if self.chunk_samples == 0 {
             return Ok(());
         }
         let chunk_offset = writer.stream_position()?;
@@ -895,18 +2212,25 @@ impl Mp4TrackWriter {
         }
     }
 
-    pub(crate) fn write_end<W: Write + Seek>(&mut self, writer: &mut W) -> Result<TrakBox> {
+    pub(crate) fn write_end<W: Write + Seek>(
+        &mut self,
+        writer: &mut W,
+        movie_timescale: u32,
+    ) -> Result<TrakBox> {
         self.write_chunk(writer)?;
 
         let max_sample_size = self.max_sample_size();
-        if let Some(ref mut mp4a) = self.trak.mdia.minf.stbl.stsd.mp4a {
-            if let Some(ref mut esds) = mp4a.esds {
-                esds.es_desc.dec_config.buffer_size_db = max_sample_size;
-            }
+        if let Some(ref mut mp4a) = self.trak.mdia.minf.stbl.stsd.mp4a
+            && let Some(ref mut esds) = mp4a.esds
+        {
+            esds.es_desc.dec_config.buffer_size_db = max_sample_size;
             // TODO
             // mp4a.esds.es_desc.dec_config.max_bitrate
             // mp4a.esds.es_desc.dec_config.avg_bitrate
         }
+        if self.trak.mdia.minf.stbl.stsd.opus.is_some() {
+            self.finish_opus(movie_timescale);
+        }
         if let Ok(stco) = StcoBox::try_from(self.trak.mdia.minf.stbl.co64.as_ref().unwrap()) {
             self.trak.mdia.minf.stbl.stco = Some(stco);
             self.trak.mdia.minf.stbl.co64 = None;
@@ -914,4 +2238,624 @@ impl Mp4TrackWriter {
 
         Ok(self.trak.clone())
     }
+
+    /// Add the `roll` group of every sample, and end the pre-skip edit at
+    /// the end of the media.
+    fn finish_opus(&mut self, movie_timescale: u32) {
+        let stbl = &mut self.trak.mdia.minf.stbl;
+        if let Some(stts) = stbl.stts.entries.first() {
+            stbl.sgpds.push(opus_roll_sgpd(
+                self.trak.mdia.mdhd.timescale,
+                stts.sample_delta,
+            ));
+            stbl.sbgps.push(SbgpBox {
+                grouping_type: SampleGroupEntry::ROLL,
+                entries: vec![SbgpEntry {
+                    sample_count: stbl.stsz.sample_count,
+                    group_description_index: 1,
+                }],
+                ..SbgpBox::default()
+            });
+        }
+
+        let mdhd = &self.trak.mdia.mdhd;
+        if let Some(ref mut edts) = self.trak.edts
+            && let Some(ref mut elst) = edts.elst
+            && let Some(entry) = elst.entries.first_mut()
+        {
+            entry.segment_duration = mdhd.duration.saturating_sub(entry.media_time)
+                * movie_timescale as u64
+                / mdhd.timescale as u64;
+            if entry.segment_duration > u32::MAX as u64 {
+                elst.version = 1;
+            }
+            self.trak.tkhd.duration = entry.segment_duration;
+        }
+    }
+}
+
+/// The `roll` sample group description of Opus samples of the given
+/// duration, covering the 80 ms of pre-roll needed to decode from any
+/// sample, see Encapsulation of Opus in ISO Base Media File Format 4.3.6.
+fn opus_roll_sgpd(timescale: u32, sample_duration: u32) -> SgpdBox {
+    let pre_roll = 3840 * timescale as u64 / 48000;
+    let roll_samples = pre_roll.div_ceil(sample_duration.max(1) as u64);
+    SgpdBox {
+        version: 1,
+        grouping_type: SampleGroupEntry::ROLL,
+        default_length: 2,
+        entries: vec![SampleGroupEntry::Roll {
+            roll_distance: -(roll_samples.min(i16::MAX as u64) as i16),
+        }],
+        ..SgpdBox::default()
+    }
+}
+
+/// `sample_flags` of a sync sample: does not depend on other samples.
+const SYNC_SAMPLE_FLAGS: u32 = 0x0200_0000;
+/// `sample_flags` of a non-sync sample: depends on other samples and is
+/// flagged as `sample_is_non_sync_sample`.
+const NON_SYNC_SAMPLE_FLAGS: u32 = 0x0101_0000;
+
+#[derive(Debug, Default)]
+pub(crate) struct Mp4FragmentTrackWriter {
+    trak: TrakBox,
+
+    base_media_decode_time: u64,
+    trun: TrunBox,
+    has_rendering_offsets: bool,
+    fragment_duration: u64,
+    fragment_buffer: Vec<u8>,
+    encryptor: Option<SampleEncryptor>,
+}
+
+impl Mp4FragmentTrackWriter {
+    pub(crate) fn new(track_id: u32, config: &TrackConfig) -> Result<Self> {
+        let mut trak = new_trak(track_id, config)?;
+        trak.mdia.minf.stbl.stco = Some(StcoBox::default());
+        let encryptor = match config.encryption {
+            Some(ref encryption) => Some(SampleEncryptor::new(&mut trak, encryption)?),
+            None => None,
+        };
+        Ok(Mp4FragmentTrackWriter {
+            trak,
+            encryptor,
+            ..Self::default()
+        })
+    }
+
+    /// The `pssh` boxes of an encrypted track.
+    pub(crate) fn psshs(&self) -> &[PsshBox] {
+        match self.encryptor {
+            Some(ref encryptor) => &encryptor.config.psshs,
+            None => &[],
+        }
+    }
+
+    pub(crate) fn track_id(&self) -> u32 {
+        self.trak.tkhd.track_id
+    }
+
+    pub(crate) fn timescale(&self) -> u32 {
+        self.trak.mdia.mdhd.timescale
+    }
+
+    pub(crate) fn track_type(&self) -> Result<TrackType> {
+        TrackType::try_from(&self.trak.mdia.hdlr.handler_type)
+    }
+
+    pub(crate) fn trak(&self) -> &TrakBox {
+        &self.trak
+    }
+
+    /// Duration of the samples buffered for the current fragment, in the
+    /// track's timescale.
+    pub(crate) fn fragment_duration(&self) -> u64 {
+        self.fragment_duration
+    }
+
+    pub(crate) fn is_empty(&self) -> bool {
+        self.trun.sample_count == 0
+    }
+
+    /// Whether the first buffered sample is a sync sample.
+    pub(crate) fn starts_with_sync(&self) -> bool {
+        self.trun
+            .sample_flags
+            .first()
+            .is_some_and(|flags| !SampleFlags::from(*flags).is_non_sync_sample)
+    }
+
+    /// Earliest composition time of the buffered samples, in the track's
+    /// timescale.
+    pub(crate) fn earliest_presentation_time(&self) -> u64 {
+        self.presentation_times()
+            .min()
+            .unwrap_or(self.base_media_decode_time as i64)
+            .max(0) as u64
+    }
+
+    /// Index and composition time of the first buffered sync sample.
+    pub(crate) fn first_sync_sample(&self) -> Option<(usize, u64)> {
+        self.presentation_times()
+            .enumerate()
+            .zip(self.trun.sample_flags.iter())
+            .find(|(_, flags)| !SampleFlags::from(**flags).is_non_sync_sample)
+            .map(|((idx, time), _)| (idx, time.max(0) as u64))
+    }
+
+    fn presentation_times(&self) -> impl Iterator<Item = i64> + '_ {
+        let mut decode_time = self.base_media_decode_time as i64;
+        self.trun
+            .sample_durations
+            .iter()
+            .zip(self.trun.sample_cts.iter())
+            .map(move |(duration, cts)| {
+                let time = decode_time + *cts as i32 as i64;
+                decode_time += *duration as i64;
+                time
+            })
+    }
+
+    pub(crate) fn write_sample(&mut self, sample: &Mp4Sample) -> Result<()> {
+        let sample_size = u32::try_from(sample.bytes.len())
+            .map_err(|_| Error::InvalidData("sample size exceeds u32::MAX"))?;
+        let sample_flags = match sample.flags {
+            Some(flags) => u32::from(SampleFlags {
+                is_non_sync_sample: !sample.is_sync,
+                ..flags
+            }),
+            None if sample.is_sync => SYNC_SAMPLE_FLAGS,
+            None => NON_SYNC_SAMPLE_FLAGS,
+        };
+
+        self.trun.sample_count += 1;
+        self.trun.sample_durations.push(sample.duration);
+        self.trun.sample_sizes.push(sample_size);
+        self.trun.sample_flags.push(sample_flags);
+        self.trun.sample_cts.push(sample.rendering_offset as u32);
+        if sample.rendering_offset != 0 {
+            self.has_rendering_offsets = true;
+        }
+        if sample.rendering_offset < 0 {
+            // Signed composition offsets require trun version 1.
+            self.trun.version = 1;
+        }
+
+        if let Some(ref mut encryptor) = self.encryptor {
+            let decode_time = self.base_media_decode_time + self.fragment_duration;
+            let bytes = encryptor.encrypt_sample(decode_time, &sample.bytes)?;
+            self.fragment_buffer.extend_from_slice(&bytes);
+        } else {
+            self.fragment_buffer.extend_from_slice(&sample.bytes);
+        }
+        self.fragment_duration += sample.duration as u64;
+        Ok(())
+    }
+
+    /// Build the `traf` for the buffered samples. `data_offset` is the offset
+    /// of the first sample of this track relative to the start of the `moof`.
+    ///
+    /// The `saio` of an encrypted track is left for the caller to point at
+    /// the `senc` entries, whose position depends on the rest of the `moof`.
+    pub(crate) fn traf(&self, data_offset: i32) -> Result<TrafBox> {
+        let mut trun = self.trun.clone();
+        trun.flags = TrunBox::FLAG_DATA_OFFSET
+            | TrunBox::FLAG_SAMPLE_DURATION
+            | TrunBox::FLAG_SAMPLE_SIZE
+            | TrunBox::FLAG_SAMPLE_FLAGS;
+        if self.has_rendering_offsets {
+            trun.flags |= TrunBox::FLAG_SAMPLE_CTS;
+        } else {
+            trun.sample_cts.clear();
+        }
+        trun.data_offset = Some(data_offset);
+
+        let mut traf = TrafBox {
+            tfhd: TfhdBox {
+                flags: TfhdBox::FLAG_DEFAULT_BASE_IS_MOOF,
+                track_id: self.track_id(),
+                ..TfhdBox::default()
+            },
+            tfdt: Some(TfdtBox {
+                version: 1,
+                flags: 0,
+                base_media_decode_time: self.base_media_decode_time,
+            }),
+            truns: vec![trun],
+            ..TrafBox::default()
+        };
+        if self.trak.mdia.minf.stbl.stsd.opus.is_some()
+            && let Some(sample_duration) = self.trun.sample_durations.first()
+        {
+            traf.sgpds.push(opus_roll_sgpd(
+                self.trak.mdia.mdhd.timescale,
+                *sample_duration,
+            ));
+            traf.sbgps.push(SbgpBox {
+                grouping_type: SampleGroupEntry::ROLL,
+                entries: vec![SbgpEntry {
+                    sample_count: self.trun.sample_count,
+                    group_description_index: SbgpBox::FRAGMENT_LOCAL_INDEX_BASE + 1,
+                }],
+                ..SbgpBox::default()
+            });
+        }
+        if let Some(ref encryptor) = self.encryptor {
+            encryptor.add_fragment_boxes(&mut traf)?;
+        }
+        Ok(traf)
+    }
+
+    /// Write the buffered sample data and start a new fragment.
+    pub(crate) fn write_fragment_data<W: Write>(&mut self, writer: &mut W) -> Result<()> {
+        writer.write_all(&self.fragment_buffer)?;
+
+        self.base_media_decode_time += self.fragment_duration;
+        self.trun = TrunBox::default();
+        self.has_rendering_offsets = false;
+        self.fragment_duration = 0;
+        self.fragment_buffer.clear();
+        if let Some(ref mut encryptor) = self.encryptor {
+            encryptor.entries.clear();
+        }
+        Ok(())
+    }
+
+    pub(crate) fn fragment_data_size(&self) -> u64 {
+        self.fragment_buffer.len() as u64
+    }
+}
+
+/// Encrypts the samples of a track written by [Mp4FragmentTrackWriter], and
+/// collects the per-sample information of the current fragment.
+#[derive(Debug)]
+struct SampleEncryptor {
+    config: EncryptionConfig,
+    nal_units: Option<NalUnits>,
+    next_iv: Vec<u8>,
+
+    // The senc entry of every sample of the fragment, with whether the
+    // sample is protected.
+    entries: Vec<(SencEntry, bool)>,
+}
+
+/// The NAL unit format of AVC and HEVC samples, with the size of the NAL
+/// unit length that precedes every NAL unit and the parameter sets needed
+/// to parse slice headers.
+#[derive(Debug)]
+enum NalUnits {
+    Avc(usize, AvcParameterSets),
+    Hevc(usize, HevcParameterSets),
+}
+
+impl SampleEncryptor {
+    /// Set up the encryption of a track, turning its sample entry into an
+    /// encrypted one.
+    fn new(trak: &mut TrakBox, config: &EncryptionConfig) -> Result<Self> {
+        if !cfg!(feature = "cenc") {
+            return Err(Error::InvalidData("encryption requires the cenc feature"));
+        }
+        let has_pattern = config.crypt_byte_block != 0 || config.skip_byte_block != 0;
+        match config.scheme_type {
+            SchmBox::CENC | SchmBox::CBC1 if has_pattern => {
+                return Err(Error::InvalidData(
+                    "encryption pattern requires the cens or cbcs scheme",
+                ));
+            }
+            SchmBox::CENC | SchmBox::CENS | SchmBox::CBC1 | SchmBox::CBCS => {}
+            _ => return Err(Error::InvalidData("unsupported protection scheme")),
+        }
+        let (per_sample_iv_size, constant_iv) = match config.iv {
+            IvStrategy::PerSample(ref iv) if iv.len() == 8 || iv.len() == 16 => {
+                (iv.len() as u8, Vec::new())
+            }
+            IvStrategy::Constant(ref iv) if iv.len() == 16 => {
+                if config.scheme_type != SchmBox::CBCS {
+                    return Err(Error::InvalidData("constant IV requires the cbcs scheme"));
+                }
+                (0, iv.clone())
+            }
+            _ => return Err(Error::InvalidData("IV has an invalid size")),
+        };
+
+        let tenc = TencBox {
+            version: if has_pattern { 1 } else { 0 },
+            default_crypt_byte_block: config.crypt_byte_block,
+            default_skip_byte_block: config.skip_byte_block,
+            default_is_protected: true,
+            default_per_sample_iv_size: per_sample_iv_size,
+            default_kid: config.kid,
+            default_constant_iv: constant_iv,
+            ..TencBox::default()
+        };
+        let sinf = |original_format: BoxType| SinfBox {
+            frma: FrmaBox {
+                original_format: original_format.into(),
+            },
+            schm: Some(SchmBox {
+                scheme_type: config.scheme_type,
+                scheme_version: 0x10000,
+                ..SchmBox::default()
+            }),
+            schi: Some(SchiBox {
+                tenc: Some(tenc.clone()),
+                ..SchiBox::default()
+            }),
+            ..SinfBox::default()
+        };
+
+        let stsd = &mut trak.mdia.minf.stbl.stsd;
+        let nal_units = if let Some(ref mut avc1) = stsd.avc1 {
+            let parameter_sets = AvcParameterSets::new(&avc1.avcc)?;
+            avc1.sinf = Some(sinf(BoxType::Avc1Box));
+            Some(NalUnits::Avc(
+                avc1.avcc.length_size_minus_one as usize % 4 + 1,
+                parameter_sets,
+            ))
+        } else if let Some(ref mut hev1) = stsd.hev1 {
+            let parameter_sets = HevcParameterSets::new(&hev1.hvcc)?;
+            hev1.sinf = Some(sinf(hev1.box_type()));
+            Some(NalUnits::Hevc(
+                hev1.hvcc.length_size_minus_one as usize % 4 + 1,
+                parameter_sets,
+            ))
+        } else if stsd.vp09.is_some() || stsd.av01.is_some() {
+            // The uncompressed frame headers, and the superframe index of
+            // VP9 or the OBU headers of AV1, must stay in the clear, which
+            // requires parsing the frame headers.
+            return Err(Error::InvalidData(
+                "encryption of VP9 and AV1 tracks is not supported",
+            ));
+        } else if let Some(ref mut mp4a) = stsd.mp4a {
+            mp4a.sinf = Some(sinf(BoxType::Mp4aBox));
+            None
+        } else if let Some(ref mut opus) = stsd.opus {
+            opus.sinf = Some(sinf(BoxType::OpusBox));
+            None
+        } else if let Some(ref mut flac) = stsd.flac {
+            flac.sinf = Some(sinf(BoxType::FlacBox));
+            None
+        } else if let Some(ref mut ac3) = stsd.ac3 {
+            ac3.sinf = Some(sinf(BoxType::Ac3Box));
+            None
+        } else if let Some(ref mut ec3) = stsd.ec3 {
+            ec3.sinf = Some(sinf(BoxType::Ec3Box));
+            None
+        } else {
+            return Err(Error::InvalidData(
+                "encryption is not supported for the media type",
+            ));
+        };
+
+        let next_iv = match config.iv {
+            IvStrategy::PerSample(ref iv) => iv.clone(),
+            IvStrategy::Constant(_) => Vec::new(),
+        };
+        Ok(SampleEncryptor {
+            config: config.clone(),
+            nal_units,
+            next_iv,
+            entries: Vec::new(),
+        })
+    }
+
+    /// Encrypt a sample with the given decode time, unless it is within the
+    /// clear lead.
+    fn encrypt_sample(&mut self, decode_time: u64, bytes: &[u8]) -> Result<Vec<u8>> {
+        if decode_time < self.config.clear_lead {
+            self.entries.push((SencEntry::default(), false));
+            return Ok(bytes.to_vec());
+        }
+
+        let subsamples = match self.nal_units {
+            Some(ref mut nal_units) => nal_unit_subsamples(bytes, nal_units)?,
+            None => Vec::new(),
+        };
+        let iv = match self.config.iv {
+            IvStrategy::PerSample(_) => self.next_iv.clone(),
+            IvStrategy::Constant(ref iv) => iv.clone(),
+        };
+        let encryption = SampleEncryption {
+            scheme_type: self.config.scheme_type,
+            kid: self.config.kid,
+            iv,
+            crypt_byte_block: self.config.crypt_byte_block,
+            skip_byte_block: self.config.skip_byte_block,
+            subsamples,
+        };
+        let mut bytes = bytes.to_vec();
+        encrypt_sample(&self.config.key, &encryption, &mut bytes)?;
+
+        let iv = match self.config.iv {
+            IvStrategy::PerSample(_) => {
+                self.advance_iv(&encryption, bytes.len());
+                encryption.iv
+            }
+            // The constant IV is in the tenc.
+            IvStrategy::Constant(_) => Vec::new(),
+        };
+        self.entries.push((
+            SencEntry {
+                iv,
+                subsamples: encryption.subsamples,
+            },
+            true,
+        ));
+        Ok(bytes)
+    }
+
+    /// Step the per-sample IV past the one of a sample of `size` bytes. With
+    /// a 16 byte IV in counter mode, the IV is the initial counter block, so
+    /// it skips the blocks the sample used up.
+    fn advance_iv(&mut self, encryption: &SampleEncryption, size: usize) {
+        if self.next_iv.len() == 8 {
+            let iv = u64::from_be_bytes(self.next_iv[..].try_into().unwrap());
+            self.next_iv = iv.wrapping_add(1).to_be_bytes().to_vec();
+            return;
+        }
+        let step = match self.config.scheme_type {
+            SchmBox::CENC | SchmBox::CENS => {
+                let protected_size = if encryption.subsamples.is_empty() {
+                    size as u64
+                } else {
+                    encryption
+                        .subsamples
+                        .iter()
+                        .map(|subsample| subsample.bytes_of_protected_data as u64)
+                        .sum()
+                };
+                protected_size.div_ceil(16).max(1)
+            }
+            _ => 1,
+        };
+        let iv = u128::from_be_bytes(self.next_iv[..].try_into().unwrap());
+        self.next_iv = iv.wrapping_add(step as u128).to_be_bytes().to_vec();
+    }
+
+    /// Add the `senc`, `saiz` and `saio` boxes of the buffered samples to
+    /// their `traf`, and a `seig` sample group for samples in the clear
+    /// lead.
+    fn add_fragment_boxes(&self, traf: &mut TrafBox) -> Result<()> {
+        let entries: Vec<SencEntry> = self
+            .entries
+            .iter()
+            .map(|(entry, _)| entry.clone())
+            .collect();
+        let senc = SencBox::new(&entries)?;
+        let has_subsamples = senc.has_subsamples();
+        let sizes = entries
+            .iter()
+            .map(|entry| u8::try_from(entry.size(has_subsamples)))
+            .collect::<std::result::Result<Vec<u8>, _>>()
+            .map_err(|_| Error::InvalidData("sample has too many subsamples"))?;
+        if sizes.iter().any(|size| *size > 0) {
+            let uniform = sizes.windows(2).all(|sizes| sizes[0] == sizes[1]);
+            traf.saizs.push(SaizBox {
+                default_sample_info_size: if uniform { sizes[0] } else { 0 },
+                sample_count: sizes.len() as u32,
+                sample_info_sizes: if uniform { Vec::new() } else { sizes },
+                ..SaizBox::default()
+            });
+            traf.saios.push(SaioBox {
+                offsets: vec![0],
+                ..SaioBox::default()
+            });
+            traf.senc = Some(senc);
+        }
+
+        if self.entries.iter().any(|(_, is_protected)| !is_protected) {
+            traf.sgpds.push(SgpdBox {
+                version: 1,
+                grouping_type: SampleGroupEntry::SEIG,
+                default_length: 20,
+                entries: vec![SampleGroupEntry::Seig {
+                    crypt_byte_block: 0,
+                    skip_byte_block: 0,
+                    is_protected: false,
+                    per_sample_iv_size: 0,
+                    kid: [0; 16],
+                    constant_iv: Vec::new(),
+                }],
+                ..SgpdBox::default()
+            });
+            let mut sbgp = SbgpBox {
+                grouping_type: SampleGroupEntry::SEIG,
+                ..SbgpBox::default()
+            };
+            for (_, is_protected) in self.entries.iter() {
+                let group_description_index = if *is_protected {
+                    0
+                } else {
+                    SbgpBox::FRAGMENT_LOCAL_INDEX_BASE + 1
+                };
+                match sbgp.entries.last_mut() {
+                    Some(entry) if entry.group_description_index == group_description_index => {
+                        entry.sample_count += 1;
+                    }
+                    _ => sbgp.entries.push(SbgpEntry {
+                        sample_count: 1,
+                        group_description_index,
+                    }),
+                }
+            }
+            traf.sbgps.push(sbgp);
+        }
+        Ok(())
+    }
+}
+
+#[cfg(feature = "cenc")]
+fn encrypt_sample(key: &[u8; 16], encryption: &SampleEncryption, data: &mut [u8]) -> Result<()> {
+    crate::cenc::encrypt_sample(key, encryption, data)
+}
+
+#[cfg(not(feature = "cenc"))]
+fn encrypt_sample(_key: &[u8; 16], _encryption: &SampleEncryption, _data: &mut [u8]) -> Result<()> {
+    Err(Error::InvalidData("encryption requires the cenc feature"))
+}
+
+/// The subsamples of a sample of length-prefixed NAL units. Only the end
+/// of every slice NAL unit is protected, in a whole number of blocks,
+/// leaving its length, its NAL unit and slice headers, as `cbcs` and `cens`
+/// require, and the rest of the NAL unit in the clear. Parameter sets in
+/// the sample replace those of the sample entry.
+fn nal_unit_subsamples(data: &[u8], nal_units: &mut NalUnits) -> Result<Vec<SencSubsample>> {
+    let length_size = match nal_units {
+        NalUnits::Avc(length_size, _) | NalUnits::Hevc(length_size, _) => *length_size,
+    };
+
+    let mut subsamples = Vec::new();
+    let mut clear_size = 0;
+    let mut pos = 0;
+    while pos < data.len() {
+        let Some(length) = data.get(pos..pos + length_size) else {
+            return Err(Error::InvalidData("NAL unit length is truncated"));
+        };
+        let nal_size = length
+            .iter()
+            .fold(0usize, |size, byte| size << 8 | *byte as usize);
+        let nal_start = pos + length_size;
+        let nal_end = nal_start + nal_size;
+        if nal_end > data.len() {
+            return Err(Error::InvalidData("NAL unit is larger than the sample"));
+        }
+
+        let nal_unit = &data[nal_start..nal_end];
+        let slice_header_size = match nal_units {
+            NalUnits::Avc(_, parameter_sets) => parameter_sets.read_nal_unit(nal_unit)?,
+            NalUnits::Hevc(_, parameter_sets) => parameter_sets.read_nal_unit(nal_unit)?,
+        };
+        let protected_size = match slice_header_size {
+            Some(header_size) => (nal_size - header_size) / 16 * 16,
+            None => 0,
+        };
+        clear_size += nal_end - pos - protected_size;
+        if protected_size > 0 {
+            push_subsample(&mut subsamples, clear_size, protected_size as u32);
+            clear_size = 0;
+        }
+        pos = nal_end;
+    }
+    if clear_size > 0 || subsamples.is_empty() {
+        push_subsample(&mut subsamples, clear_size, 0);
+    }
+    Ok(subsamples)
+}
+
+/// Add a subsample, splitting off clear-only subsamples while the clear
+/// size does not fit the 16 bits of `bytes_of_clear_data`.
+fn push_subsample(subsamples: &mut Vec<SencSubsample>, clear_size: usize, protected_size: u32) {
+    let mut clear_size = clear_size;
+    while clear_size > u16::MAX as usize {
+        subsamples.push(SencSubsample {
+            bytes_of_clear_data: u16::MAX,
+            bytes_of_protected_data: 0,
+        });
+        clear_size -= u16::MAX as usize;
+    }
+    subsamples.push(SencSubsample {
+        bytes_of_clear_data: clear_size as u16,
+        bytes_of_protected_data: protected_size,
+    });
 }
@@ -1,6 +1,7 @@
 use std::borrow::Cow;
 use std::convert::TryFrom;
 use std::fmt;
+use std::ops::Range;
 
 use crate::mp4box::*;
 use crate::*;
@@ -192,13 +193,13 @@ const DISPLAY_TYPE_AUDIO: &str = "Audio";
 const DISPLAY_TYPE_SUBTITLE: &str = "Subtitle";
 
 const HANDLER_TYPE_VIDEO: &str = "vide";
-const HANDLER_TYPE_VIDEO_FOURCC: [u8; 4] = [b'v', b'i', b'd', b'e'];
+const HANDLER_TYPE_VIDEO_FOURCC: [u8; 4] = *b"vide";
 
 const HANDLER_TYPE_AUDIO: &str = "soun";
-const HANDLER_TYPE_AUDIO_FOURCC: [u8; 4] = [b's', b'o', b'u', b'n'];
+const HANDLER_TYPE_AUDIO_FOURCC: [u8; 4] = *b"soun";
 
 const HANDLER_TYPE_SUBTITLE: &str = "sbtl";
-const HANDLER_TYPE_SUBTITLE_FOURCC: [u8; 4] = [b's', b'b', b't', b'l'];
+const HANDLER_TYPE_SUBTITLE_FOURCC: [u8; 4] = *b"sbtl";
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum TrackType {
@@ -255,7 +256,12 @@ impl From<TrackType> for FourCC {
 const MEDIA_TYPE_H264: &str = "h264";
 const MEDIA_TYPE_H265: &str = "h265";
 const MEDIA_TYPE_VP9: &str = "vp9";
+const MEDIA_TYPE_AV1: &str = "av1";
 const MEDIA_TYPE_AAC: &str = "aac";
+const MEDIA_TYPE_OPUS: &str = "opus";
+const MEDIA_TYPE_FLAC: &str = "flac";
+const MEDIA_TYPE_AC3: &str = "ac3";
+const MEDIA_TYPE_EAC3: &str = "eac3";
 const MEDIA_TYPE_TTXT: &str = "ttxt";
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -263,7 +269,12 @@ pub enum MediaType {
     H264,
     H265,
     VP9,
+    AV1,
     AAC,
+    OPUS,
+    FLAC,
+    AC3,
+    EAC3,
     TTXT,
 }
 
@@ -281,7 +292,12 @@ impl TryFrom<&str> for MediaType {
             MEDIA_TYPE_H264 => Ok(MediaType::H264),
             MEDIA_TYPE_H265 => Ok(MediaType::H265),
             MEDIA_TYPE_VP9 => Ok(MediaType::VP9),
+            MEDIA_TYPE_AV1 => Ok(MediaType::AV1),
             MEDIA_TYPE_AAC => Ok(MediaType::AAC),
+            MEDIA_TYPE_OPUS => Ok(MediaType::OPUS),
+            MEDIA_TYPE_FLAC => Ok(MediaType::FLAC),
+            MEDIA_TYPE_AC3 => Ok(MediaType::AC3),
+            MEDIA_TYPE_EAC3 => Ok(MediaType::EAC3),
             MEDIA_TYPE_TTXT => Ok(MediaType::TTXT),
             _ => Err(Error::InvalidData("unsupported media type")),
         }
@@ -294,7 +310,12 @@ impl From<MediaType> for &str {
             MediaType::H264 => MEDIA_TYPE_H264,
             MediaType::H265 => MEDIA_TYPE_H265,
             MediaType::VP9 => MEDIA_TYPE_VP9,
+            MediaType::AV1 => MEDIA_TYPE_AV1,
             MediaType::AAC => MEDIA_TYPE_AAC,
+            MediaType::OPUS => MEDIA_TYPE_OPUS,
+            MediaType::FLAC => MEDIA_TYPE_FLAC,
+            MediaType::AC3 => MEDIA_TYPE_AC3,
+            MediaType::EAC3 => MEDIA_TYPE_EAC3,
             MediaType::TTXT => MEDIA_TYPE_TTXT,
         }
     }
@@ -306,7 +327,12 @@ impl From<&MediaType> for &str {
             MediaType::H264 => MEDIA_TYPE_H264,
             MediaType::H265 => MEDIA_TYPE_H265,
             MediaType::VP9 => MEDIA_TYPE_VP9,
+            MediaType::AV1 => MEDIA_TYPE_AV1,
             MediaType::AAC => MEDIA_TYPE_AAC,
+            MediaType::OPUS => MEDIA_TYPE_OPUS,
+            MediaType::FLAC => MEDIA_TYPE_FLAC,
+            MediaType::AC3 => MEDIA_TYPE_AC3,
+            MediaType::EAC3 => MEDIA_TYPE_EAC3,
             MediaType::TTXT => MEDIA_TYPE_TTXT,
         }
     }
@@ -599,6 +625,52 @@ impl fmt::Display for ChannelConfig {
     }
 }
 
+/// The loudspeaker locations of a Dolby Digital or Dolby Digital Plus
+/// stream, as the 16 bit `chanmap` mask of ETSI TS 102 366 Table E.1.4 with
+/// the left channel in the most significant bit and LFE in the least. This
+/// is also the value of the Dolby `AudioChannelConfiguration` in DASH
+/// manifests.
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Default)]
+pub struct DolbyChannelLayout(pub u16);
+
+impl DolbyChannelLayout {
+    // chanmap positions which stand for a pair of channels.
+    const PAIRS: [u16; 6] = [5, 6, 9, 10, 11, 13];
+
+    /// The layout of an AC-3 audio coding mode.
+    pub(crate) fn from_acmod(acmod: u8, lfeon: bool) -> Self {
+        let mask = match acmod {
+            0 => 0xA000, // 1+1, two independent mono channels as L and R
+            1 => 0x4000,
+            2 => 0xA000,
+            3 => 0xE000,
+            4 => 0xA100,
+            5 => 0xE100,
+            6 => 0xB800,
+            _ => 0xF800,
+        };
+        DolbyChannelLayout(mask | lfeon as u16)
+    }
+
+    pub fn channel_count(&self) -> u16 {
+        let pairs = Self::PAIRS
+            .iter()
+            .filter(|position| self.0 & (0x8000 >> *position) != 0)
+            .count() as u16;
+        self.0.count_ones() as u16 + pairs
+    }
+
+    pub fn has_lfe(&self) -> bool {
+        self.0 & 0x0001 != 0
+    }
+}
+
+impl fmt::Display for DolbyChannelLayout {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{:04X}", self.0)
+    }
+}
+
 #[derive(Debug, PartialEq, Eq, Clone, Default)]
 pub struct AvcConfig {
     pub width: u16,
@@ -611,6 +683,26 @@ pub struct AvcConfig {
 pub struct HevcConfig {
     pub width: u16,
     pub height: u16,
+    pub sample_entry: HevcSampleEntry,
+
+    /// Parameter set NAL units, including their NAL unit headers. The
+    /// profile, tier, level, chroma format and bit depths of the `hvcC` are
+    /// taken from the SPS.
+    pub video_param_set: Vec<u8>,
+    pub seq_param_set: Vec<u8>,
+    pub pic_param_set: Vec<u8>,
+}
+
+/// The sample entry type of an HEVC track.
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Default)]
+pub enum HevcSampleEntry {
+    /// `hev1`, where parameter sets may also be sent in the samples.
+    #[default]
+    Hev1,
+
+    /// `hvc1`, where parameter sets are only sent in the `hvcC`. Required
+    /// by Apple players.
+    Hvc1,
 }
 
 #[derive(Debug, PartialEq, Eq, Clone, Default)]
@@ -619,6 +711,17 @@ pub struct Vp9Config {
     pub height: u16,
 }
 
+#[derive(Debug, PartialEq, Eq, Clone, Default)]
+pub struct Av1Config {
+    pub width: u16,
+    pub height: u16,
+
+    /// The sequence header OBU, including its OBU header. The profile,
+    /// level, tier, bit depth and chroma format of the `av1C` are taken from
+    /// it.
+    pub sequence_header: Vec<u8>,
+}
+
 #[derive(Debug, PartialEq, Eq, Clone)]
 pub struct AacConfig {
     pub bitrate: u32,
@@ -638,6 +741,100 @@ impl Default for AacConfig {
     }
 }
 
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct OpusConfig {
+    pub channel_count: u8,
+
+    /// Number of samples at 48 kHz to discard from the decoder output at
+    /// the start of the stream. The writer adds an edit list skipping them.
+    pub pre_skip: u16,
+
+    pub input_sample_rate: u32,
+
+    /// Gain to apply to the decoder output, in Q7.8 dB.
+    pub output_gain: i16,
+
+    /// 0 for mono or stereo without a mapping table, otherwise the family
+    /// of `channel_mapping`.
+    pub channel_mapping_family: u8,
+    pub channel_mapping: Option<OpusChannelMapping>,
+}
+
+impl Default for OpusConfig {
+    fn default() -> Self {
+        Self {
+            channel_count: 2,
+            pre_skip: 312,
+            input_sample_rate: 48000,
+            output_gain: 0,
+            channel_mapping_family: 0,
+            channel_mapping: None,
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Default)]
+pub struct FlacConfig {
+    /// The sample rate, channel count and bits per sample of the sample
+    /// entry are taken from STREAMINFO.
+    pub stream_info: FlacStreamInfo,
+
+    /// Other metadata blocks to keep in the `dfLa`, such as VORBIS_COMMENT.
+    pub metadata_blocks: Vec<FlacMetadataBlock>,
+}
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct Ac3Config {
+    /// Sample rate code: 0 for 48 kHz, 1 for 44.1 kHz, 2 for 32 kHz.
+    pub fscod: u8,
+    pub bsid: u8,
+    pub bsmod: u8,
+
+    /// Audio coding mode, e.g. 2 for stereo or 7 for 3/2.
+    pub acmod: u8,
+    pub lfeon: bool,
+
+    /// Index of the bit rate, from 0 for 32 kbit/s to 18 for 640 kbit/s.
+    pub bit_rate_code: u8,
+}
+
+impl Default for Ac3Config {
+    fn default() -> Self {
+        Self {
+            fscod: 0,
+            bsid: 8,
+            bsmod: 0,
+            acmod: 7,
+            lfeon: true,
+            bit_rate_code: 15,
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct Ec3Config {
+    /// The data rate in kbit/s.
+    pub data_rate: u16,
+
+    /// The independent substreams. The sample rate and channel count of the
+    /// sample entry are taken from the first one.
+    pub substreams: Vec<Ec3Substream>,
+
+    /// The object complexity of a Dolby Atmos stream using joint object
+    /// coding, or `None` for channel based audio.
+    pub joc_complexity_index: Option<u8>,
+}
+
+impl Default for Ec3Config {
+    fn default() -> Self {
+        Self {
+            data_rate: 640,
+            substreams: vec![Ec3Substream::default()],
+            joc_complexity_index: None,
+        }
+    }
+}
+
 #[derive(Debug, PartialEq, Eq, Clone, Default)]
 pub struct TtxtConfig {}
 
@@ -646,16 +843,223 @@ pub enum MediaConfig {
     AvcConfig(AvcConfig),
     HevcConfig(HevcConfig),
     Vp9Config(Vp9Config),
+    Av1Config(Av1Config),
     AacConfig(AacConfig),
+    OpusConfig(OpusConfig),
+    FlacConfig(FlacConfig),
+    Ac3Config(Ac3Config),
+    Ec3Config(Ec3Config),
     TtxtConfig(TtxtConfig),
 }
 
-#[derive(Debug)]
+/// How [Mp4Track::seek] picks a sample for a presentation time.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SeekMode {
+    /// The last sync sample at or before the requested time.
+    PreviousSync,
+    /// The first sync sample at or after the requested time.
+    NextSync,
+    /// The sample presented at the requested time, regardless of whether it
+    /// is a sync sample.
+    Exact,
+}
+
+/// The `sample_flags` of a sample in a track fragment, see ISO/IEC 14496-12
+/// 8.8.3.1.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct SampleFlags {
+    /// 1 if the sample is a leading sample with a dependency before the
+    /// referenced I-picture, 2 if it is not a leading sample, 3 if it is a
+    /// leading sample without such a dependency, 0 if unknown.
+    pub is_leading: u8,
+
+    /// 1 if the sample depends on others, 2 if it does not (an I-picture),
+    /// 0 if unknown.
+    pub depends_on: u8,
+
+    /// 1 if other samples depend on this one, 2 if none do (it is
+    /// disposable), 0 if unknown.
+    pub is_depended_on: u8,
+
+    /// 1 if the sample has redundant coding, 2 if it does not, 0 if unknown.
+    pub has_redundancy: u8,
+
+    pub padding_value: u8,
+    pub is_non_sync_sample: bool,
+    pub degradation_priority: u16,
+}
+
+impl From<u32> for SampleFlags {
+    fn from(flags: u32) -> Self {
+        SampleFlags {
+            is_leading: ((flags >> 26) & 0x3) as u8,
+            depends_on: ((flags >> 24) & 0x3) as u8,
+            is_depended_on: ((flags >> 22) & 0x3) as u8,
+            has_redundancy: ((flags >> 20) & 0x3) as u8,
+            padding_value: ((flags >> 17) & 0x7) as u8,
+            is_non_sync_sample: (flags >> 16) & 0x1 != 0,
+            degradation_priority: flags as u16,
+        }
+    }
+}
+
+impl From<SampleFlags> for u32 {
+    fn from(flags: SampleFlags) -> u32 {
+        ((flags.is_leading as u32 & 0x3) << 26)
+            | ((flags.depends_on as u32 & 0x3) << 24)
+            | ((flags.is_depended_on as u32 & 0x3) << 22)
+            | ((flags.has_redundancy as u32 & 0x3) << 20)
+            | ((flags.padding_value as u32 & 0x7) << 17)
+            | ((flags.is_non_sync_sample as u32) << 16)
+            | flags.degradation_priority as u32
+    }
+}
+
+/// How a sample depends on other samples, from the `sdtp` box or the sample
+/// flags. The values have the same meaning as in [SampleFlags].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct SampleDependency {
+    pub is_leading: u8,
+    pub depends_on: u8,
+    pub is_depended_on: u8,
+    pub has_redundancy: u8,
+}
+
+impl SampleDependency {
+    /// Whether no other sample depends on this one, so that it can be
+    /// dropped without affecting the decoding of other samples.
+    pub fn is_disposable(&self) -> bool {
+        self.is_depended_on == 2
+    }
+}
+
+impl From<u8> for SampleDependency {
+    fn from(byte: u8) -> Self {
+        SampleDependency {
+            is_leading: (byte >> 6) & 0x3,
+            depends_on: (byte >> 4) & 0x3,
+            is_depended_on: (byte >> 2) & 0x3,
+            has_redundancy: byte & 0x3,
+        }
+    }
+}
+
+impl From<SampleDependency> for u8 {
+    fn from(dependency: SampleDependency) -> u8 {
+        ((dependency.is_leading & 0x3) << 6)
+            | ((dependency.depends_on & 0x3) << 4)
+            | ((dependency.is_depended_on & 0x3) << 2)
+            | (dependency.has_redundancy & 0x3)
+    }
+}
+
+impl From<SampleFlags> for SampleDependency {
+    fn from(flags: SampleFlags) -> Self {
+        SampleDependency {
+            is_leading: flags.is_leading,
+            depends_on: flags.depends_on,
+            is_depended_on: flags.is_depended_on,
+            has_redundancy: flags.has_redundancy,
+        }
+    }
+}
+
+/// A part of a sample, such as a NAL unit, described by the `subs` box.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Subsample {
+    /// Position of the subsample in the sample data.
+    pub range: Range<usize>,
+
+    pub priority: u8,
+    pub discardable: bool,
+    pub codec_specific_parameters: u32,
+}
+
+/// How a protected sample is encrypted, combining the `tenc` defaults of the
+/// track, the `seig` sample group and the per-sample auxiliary information.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct SampleEncryption {
+    /// Protection scheme, such as `cenc` or `cbcs`.
+    pub scheme_type: FourCC,
+
+    pub kid: [u8; 16],
+
+    /// The per-sample IV, or the constant IV of the track or sample group.
+    pub iv: Vec<u8>,
+
+    /// Number of encrypted and skipped 16-byte blocks of the encryption
+    /// pattern, both 0 if every block is encrypted.
+    pub crypt_byte_block: u8,
+    pub skip_byte_block: u8,
+
+    /// Clear and protected ranges covering the sample, in order. Empty if
+    /// the whole sample is protected.
+    pub subsamples: Vec<SencSubsample>,
+}
+
+/// How the IVs of the samples of an encrypted track are chosen.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum IvStrategy {
+    /// A per-sample IV of 8 or 16 bytes, stored in the `senc` of each
+    /// fragment. The first sample uses the given IV, which is then
+    /// incremented from sample to sample so that no IV or counter block is
+    /// used twice.
+    PerSample(Vec<u8>),
+
+    /// The same IV of 16 bytes for every sample, stored in the `tenc`. Only
+    /// allowed with the `cbcs` scheme.
+    Constant(Vec<u8>),
+}
+
+/// Common encryption of a track, see [TrackConfig::encryption].
+///
+/// Samples of AVC and HEVC tracks are encrypted per NAL unit, leaving the
+/// NAL unit lengths, the slice headers and any NAL units other than slices
+/// in the clear. Samples of audio tracks are encrypted as a whole. VP9 and
+/// AV1 tracks cannot be encrypted yet, as their frame headers must be left
+/// in the clear.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EncryptionConfig {
+    /// Protection scheme: `cenc`, `cens`, `cbc1` or `cbcs`.
+    pub scheme_type: FourCC,
+
+    pub kid: [u8; 16],
+    pub key: [u8; 16],
+    pub iv: IvStrategy,
+
+    /// Number of encrypted and skipped 16-byte blocks of the encryption
+    /// pattern of `cens` and `cbcs`, both 0 to encrypt every block.
+    pub crypt_byte_block: u8,
+    pub skip_byte_block: u8,
+
+    /// Duration at the start of the track, in the track's timescale, whose
+    /// samples are left in the clear.
+    pub clear_lead: u64,
+
+    /// `pssh` boxes of the DRM systems that can play the track, written to
+    /// the `moov`.
+    pub psshs: Vec<PsshBox>,
+}
+
+#[derive(Debug, Default)]
 pub struct Mp4Sample {
     pub start_time: u64,
     pub duration: u32,
     pub rendering_offset: i32,
     pub is_sync: bool,
+
+    /// Flags of a sample in a track fragment. When writing fragments, these
+    /// are written with `sample_is_non_sync_sample` taken from `is_sync`.
+    pub flags: Option<SampleFlags>,
+
+    /// Dependency information, from the `sdtp` box or the sample flags.
+    /// Not used when writing.
+    pub dependency: Option<SampleDependency>,
+
+    /// The subsamples described by the `subs` box, if any. Not used when
+    /// writing.
+    pub subsamples: Vec<Subsample>,
+
     pub bytes: Vec<u8>,
 }
 
@@ -730,17 +1134,17 @@ pub enum MetadataKey {
 
 pub trait Metadata<'a> {
     /// The video's title
-    fn title(&self) -> Option<Cow<str>>;
+    fn title(&self) -> Option<Cow<'_, str>>;
     /// The video's release year
     fn year(&self) -> Option<u32>;
     /// The video's poster (cover art)
     fn poster(&self) -> Option<&[u8]>;
     /// The video's summary
-    fn summary(&self) -> Option<Cow<str>>;
+    fn summary(&self) -> Option<Cow<'_, str>>;
 }
 
 impl<'a, T: Metadata<'a>> Metadata<'a> for &'a T {
-    fn title(&self) -> Option<Cow<str>> {
+    fn title(&self) -> Option<Cow<'_, str>> {
         (**self).title()
     }
 
@@ -752,13 +1156,13 @@ impl<'a, T: Metadata<'a>> Metadata<'a> for &'a T {
         (**self).poster()
     }
 
-    fn summary(&self) -> Option<Cow<str>> {
+    fn summary(&self) -> Option<Cow<'_, str>> {
         (**self).summary()
     }
 }
 
 impl<'a, T: Metadata<'a>> Metadata<'a> for Option<T> {
-    fn title(&self) -> Option<Cow<str>> {
+    fn title(&self) -> Option<Cow<'_, str>> {
         self.as_ref().and_then(|t| t.title())
     }
 
@@ -770,7 +1174,7 @@ impl<'a, T: Metadata<'a>> Metadata<'a> for Option<T> {
         self.as_ref().and_then(|t| t.poster())
     }
 
-    fn summary(&self) -> Option<Cow<str>> {
+    fn summary(&self) -> Option<Cow<'_, str>> {
         self.as_ref().and_then(|t| t.summary())
     }
 }
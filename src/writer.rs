@@ -1,8 +1,10 @@
 use byteorder::{BigEndian, WriteBytesExt};
+use std::convert::TryFrom;
 use std::io::{Seek, SeekFrom, Write};
+use std::time::Duration;
 
-use crate::mp4box::*;
-use crate::track::Mp4TrackWriter;
+use crate::mp4box::{mfhd::MfhdBox, mvex::MvexBox, trex::TrexBox};
+use crate::track::{Mp4FragmentTrackWriter, Mp4TrackWriter};
 use crate::*;
 
 #[derive(Debug, Clone, PartialEq, Eq)]
@@ -134,10 +136,18 @@ impl<W: Write + Seek> Mp4Writer<W> {
         let mut moov = MoovBox::default();
 
         for track in self.tracks.iter_mut() {
-            moov.traks.push(track.write_end(&mut self.writer)?);
+            moov.traks
+                .push(track.write_end(&mut self.writer, self.timescale)?);
         }
         self.update_mdat_size()?;
 
+        // Edit lists may have shortened the tracks.
+        self.duration = moov
+            .traks
+            .iter()
+            .map(|trak| trak.tkhd.duration)
+            .max()
+            .unwrap_or(0);
         moov.mvhd.timescale = self.timescale;
         moov.mvhd.duration = self.duration;
         if moov.mvhd.duration > (u32::MAX as u64) {
@@ -147,3 +157,389 @@ impl<W: Write + Seek> Mp4Writer<W> {
         Ok(())
     }
 }
+
+/// Configuration of [Mp4FragmentWriter].
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct FragmentConfig {
+    /// Target duration of a fragment.
+    ///
+    /// A new fragment is started at the first sync sample of the leading track
+    /// (the first video track, or the first track if there is none) once the
+    /// current fragment is at least this long. With a zero duration, every
+    /// sync sample starts a new fragment.
+    pub fragment_duration: Duration,
+
+    /// Write a `sidx` box indexing every fragment after the init segment.
+    ///
+    /// The `sidx` has to precede the fragments it indexes, so fragments are
+    /// held in memory until [Mp4FragmentWriter::write_end].
+    pub segment_index: bool,
+
+    /// Write an `mfra` box at the end of the file, with the first sync
+    /// sample of every track in each fragment.
+    ///
+    /// This lets readers seek to a fragment without reading every `moof`,
+    /// see [Mp4Reader::read_header_from_mfra].
+    pub random_access_index: bool,
+}
+
+/// Writer for fragmented MP4 (fMP4/CMAF) files.
+///
+/// The init segment (`ftyp` and `moov` with `mvex`) is written as soon as the
+/// first sample arrives, followed by a `moof` and `mdat` pair per fragment.
+/// Unlike [Mp4Writer], output is produced incrementally and the inner writer
+/// does not need to be seekable.
+#[derive(Debug)]
+pub struct Mp4FragmentWriter<W> {
+    writer: W,
+    tracks: Vec<Mp4FragmentTrackWriter>,
+    ftyp: FtypBox,
+    timescale: u32,
+    fragment_duration: Duration,
+    sequence_number: u32,
+    init_written: bool,
+
+    // The segment index and the fragments it indexes, which are written
+    // together at the end.
+    sidx: Option<SidxBox>,
+    pending_fragments: Vec<u8>,
+
+    // The random access index, and the size of the file so far without the
+    // sidx, which gives the offset of the next moof.
+    mfra: Option<MfraBox>,
+    position: u64,
+}
+
+impl<W> Mp4FragmentWriter<W> {
+    /// Consume self, returning the inner writer.
+    pub fn into_writer(self) -> W {
+        self.writer
+    }
+}
+
+impl<W: Write> Mp4FragmentWriter<W> {
+    /// Create a fragmented writer.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use mp4::{AacConfig, FragmentConfig, Mp4Config, Mp4FragmentWriter, Mp4Sample, TrackConfig};
+    /// use std::time::Duration;
+    ///
+    /// # fn main() -> mp4::Result<()> {
+    /// let config = Mp4Config {
+    ///     major_brand: str::parse("iso6").unwrap(),
+    ///     minor_version: 0,
+    ///     compatible_brands: vec![str::parse("iso6").unwrap(), str::parse("cmfc").unwrap()],
+    ///     timescale: 1000,
+    /// };
+    /// let fragment_config = FragmentConfig {
+    ///     fragment_duration: Duration::from_secs(2),
+    ///     ..FragmentConfig::default()
+    /// };
+    ///
+    /// let mut writer = Mp4FragmentWriter::write_start(Vec::new(), &config, &fragment_config)?;
+    /// writer.add_track(&TrackConfig::from(AacConfig::default()))?;
+    /// writer.write_sample(
+    ///     1,
+    ///     &Mp4Sample {
+    ///         start_time: 0,
+    ///         duration: 1024,
+    ///         rendering_offset: 0,
+    ///         is_sync: true,
+    ///         flags: None,
+    ///         dependency: None,
+    ///         subsamples: Vec::new(),
+    ///         bytes: vec![0u8; 8],
+    ///     },
+    /// )?;
+    /// writer.write_end()?;
+    ///
+    /// let data: Vec<u8> = writer.into_writer();
+    /// # Ok(()) }
+    /// ```
+    pub fn write_start(
+        mut writer: W,
+        config: &Mp4Config,
+        fragment_config: &FragmentConfig,
+    ) -> Result<Self> {
+        let ftyp = FtypBox {
+            major_brand: config.major_brand,
+            minor_version: config.minor_version,
+            compatible_brands: config.compatible_brands.clone(),
+        };
+        let position = ftyp.write_box(&mut writer)?;
+
+        Ok(Self {
+            writer,
+            tracks: Vec::new(),
+            ftyp,
+            timescale: config.timescale,
+            fragment_duration: fragment_config.fragment_duration,
+            sequence_number: 1,
+            init_written: false,
+            sidx: fragment_config.segment_index.then(SidxBox::default),
+            pending_fragments: Vec::new(),
+            mfra: fragment_config.random_access_index.then(MfraBox::default),
+            position,
+        })
+    }
+
+    /// Add a track. Tracks must be added before the first sample is written.
+    pub fn add_track(&mut self, config: &TrackConfig) -> Result<()> {
+        if self.init_written {
+            return Err(Error::InvalidData(
+                "cannot add a track after the init segment was written",
+            ));
+        }
+        let track_id = self.tracks.len() as u32 + 1;
+        let track = Mp4FragmentTrackWriter::new(track_id, config)?;
+        self.tracks.push(track);
+        Ok(())
+    }
+
+    /// The `ftyp` and `moov` boxes that make up the init segment.
+    pub fn init_segment(&self) -> (FtypBox, MoovBox) {
+        let mut moov = MoovBox::default();
+        moov.mvhd.timescale = self.timescale;
+        moov.mvhd.next_track_id = self.tracks.len() as u32 + 1;
+        for track in self.tracks.iter() {
+            moov.traks.push(track.trak().clone());
+            for pssh in track.psshs() {
+                if !moov.psshs.contains(pssh) {
+                    moov.psshs.push(pssh.clone());
+                }
+            }
+        }
+        let trexs = self
+            .tracks
+            .iter()
+            .map(|track| TrexBox {
+                track_id: track.track_id(),
+                default_sample_description_index: 1,
+                ..TrexBox::default()
+            })
+            .collect();
+        moov.mvex = Some(MvexBox {
+            trexs,
+            ..MvexBox::default()
+        });
+        (self.ftyp.clone(), moov)
+    }
+
+    fn write_init(&mut self) -> Result<()> {
+        if self.init_written {
+            return Ok(());
+        }
+        let (_, moov) = self.init_segment();
+        moov.write_box(&mut self.writer)?;
+        self.position += moov.box_size();
+        if let Some(ref mut mfra) = self.mfra {
+            for track in self.tracks.iter() {
+                mfra.tfras.push(TfraBox {
+                    track_id: track.track_id(),
+                    ..TfraBox::default()
+                });
+            }
+        }
+        self.init_written = true;
+        Ok(())
+    }
+
+    /// The track that decides where fragments are cut.
+    fn leading_track(&self) -> Option<usize> {
+        self.tracks
+            .iter()
+            .position(|t| matches!(t.track_type(), Ok(TrackType::Video)))
+            .or(if self.tracks.is_empty() {
+                None
+            } else {
+                Some(0)
+            })
+    }
+
+    fn is_fragment_boundary(&self, track_idx: usize, sample: &Mp4Sample) -> bool {
+        if self.leading_track() != Some(track_idx) || !sample.is_sync {
+            return false;
+        }
+        let track = &self.tracks[track_idx];
+        if track.is_empty() {
+            return false;
+        }
+        let target = self.fragment_duration.as_micros() * track.timescale() as u128 / 1_000_000;
+        track.fragment_duration() as u128 >= target
+    }
+
+    pub fn write_sample(&mut self, track_id: u32, sample: &Mp4Sample) -> Result<()> {
+        if track_id == 0 || track_id as usize > self.tracks.len() {
+            return Err(Error::TrakNotFound(track_id));
+        }
+        let track_idx = track_id as usize - 1;
+
+        self.write_init()?;
+        if self.is_fragment_boundary(track_idx, sample) {
+            self.write_fragment()?;
+        }
+        self.tracks[track_idx].write_sample(sample)
+    }
+
+    /// Write all buffered samples as a fragment (`moof` and `mdat`).
+    ///
+    /// Fragments are cut automatically according to [FragmentConfig], but this
+    /// can be called to end a fragment early. Does nothing if no samples are
+    /// buffered.
+    pub fn write_fragment(&mut self) -> Result<()> {
+        self.write_init()?;
+        if self.tracks.iter().all(|t| t.is_empty()) {
+            return Ok(());
+        }
+
+        let mut moof = MoofBox {
+            mfhd: MfhdBox {
+                sequence_number: self.sequence_number,
+                ..MfhdBox::default()
+            },
+            ..MoofBox::default()
+        };
+
+        // The data offsets only influence the values, not the sizes, of the
+        // trafs, so the moof size can be computed up front.
+        for track in self.tracks.iter().filter(|t| !t.is_empty()) {
+            moof.trafs.push(track.traf(0)?);
+        }
+        let mdat_data_size: u64 = self.tracks.iter().map(|t| t.fragment_data_size()).sum();
+        let mdat_size = HEADER_SIZE + mdat_data_size;
+        let mdat_header_size = if mdat_size > u32::MAX as u64 {
+            HEADER_SIZE + 8
+        } else {
+            HEADER_SIZE
+        };
+
+        let mut data_offset = moof.box_size() + mdat_header_size;
+        moof.trafs.clear();
+        for track in self.tracks.iter().filter(|t| !t.is_empty()) {
+            let offset = i32::try_from(data_offset)
+                .map_err(|_| Error::InvalidData("fragment data offset exceeds i32::MAX"))?;
+            moof.trafs.push(track.traf(offset)?);
+            data_offset += track.fragment_data_size();
+        }
+
+        // Point the saio of every encrypted track at its senc entries,
+        // relative to the start of the moof.
+        let mut traf_offset = HEADER_SIZE + moof.mfhd.box_size();
+        traf_offset += moof.psshs.iter().map(|pssh| pssh.box_size()).sum::<u64>();
+        for traf in moof.trafs.iter_mut() {
+            if let Some(offset) = traf.senc_entries_offset() {
+                traf.saios[0].offsets = vec![traf_offset + offset];
+            }
+            traf_offset += traf.box_size();
+        }
+
+        let leading_track = self.leading_track();
+        if let Some(ref mut sidx) = self.sidx {
+            let referenced_size = u32::try_from(moof.box_size() + mdat_size)
+                .ok()
+                .filter(|size| *size <= 0x7FFF_FFFF)
+                .ok_or(Error::InvalidData("fragment is too large for sidx"))?;
+            let reference = match leading_track.map(|idx| &self.tracks[idx]) {
+                Some(track) if !track.is_empty() => {
+                    if sidx.references.is_empty() {
+                        sidx.reference_id = track.track_id();
+                        sidx.timescale = track.timescale();
+                        sidx.earliest_presentation_time = track.earliest_presentation_time();
+                    }
+                    let starts_with_sap = track.starts_with_sync();
+                    SidxReference {
+                        referenced_size,
+                        subsegment_duration: u32::try_from(track.fragment_duration())
+                            .map_err(|_| Error::InvalidData("fragment is too long for sidx"))?,
+                        starts_with_sap,
+                        sap_type: if starts_with_sap { 1 } else { 0 },
+                        ..SidxReference::default()
+                    }
+                }
+                _ => SidxReference {
+                    referenced_size,
+                    ..SidxReference::default()
+                },
+            };
+            sidx.references.push(reference);
+        }
+
+        if let Some(ref mut mfra) = self.mfra {
+            let tracks = self.tracks.iter().filter(|t| !t.is_empty());
+            for (traf_idx, track) in tracks.enumerate() {
+                let Some((sample_idx, time)) = track.first_sync_sample() else {
+                    continue;
+                };
+                if let Some(tfra) = mfra
+                    .tfras
+                    .iter_mut()
+                    .find(|tfra| tfra.track_id == track.track_id())
+                {
+                    tfra.entries.push(TfraEntry {
+                        time,
+                        moof_offset: self.position,
+                        traf_number: traf_idx as u32 + 1,
+                        trun_number: 1,
+                        sample_number: sample_idx as u32 + 1,
+                    });
+                }
+            }
+        }
+        self.position += moof.box_size() + mdat_size;
+
+        let mut writer: &mut dyn Write = if self.sidx.is_some() {
+            &mut self.pending_fragments
+        } else {
+            &mut self.writer
+        };
+        moof.write_box(&mut writer)?;
+        BoxHeader::new(BoxType::MdatBox, mdat_header_size + mdat_data_size).write(&mut writer)?;
+        for track in self.tracks.iter_mut().filter(|t| !t.is_empty()) {
+            track.write_fragment_data(&mut writer)?;
+        }
+
+        self.sequence_number += 1;
+        Ok(())
+    }
+
+    /// Write the last fragment, preceded by the `sidx` if
+    /// [FragmentConfig::segment_index] is set and followed by the `mfra` if
+    /// [FragmentConfig::random_access_index] is set.
+    pub fn write_end(&mut self) -> Result<()> {
+        self.write_fragment()?;
+        let mut sidx_size = 0;
+        if let Some(mut sidx) = self.sidx.take() {
+            if sidx.earliest_presentation_time > u32::MAX as u64 {
+                sidx.version = 1;
+            }
+            sidx_size = sidx.write_box(&mut self.writer)?;
+            self.writer.write_all(&self.pending_fragments)?;
+            self.pending_fragments = Vec::new();
+        }
+        if let Some(mut mfra) = self.mfra.take() {
+            for tfra in mfra.tfras.iter_mut() {
+                for entry in tfra.entries.iter_mut() {
+                    entry.moof_offset += sidx_size;
+                }
+                if tfra.entries.iter().any(|entry| {
+                    entry.time > u32::MAX as u64 || entry.moof_offset > u32::MAX as u64
+                }) {
+                    tfra.version = 1;
+                }
+                let length_size = |number: fn(&TfraEntry) -> u32| {
+                    let max = tfra.entries.iter().map(number).max().unwrap_or(0);
+                    (max.max(1).ilog2() / 8) as u8
+                };
+                tfra.length_size_of_traf_num = length_size(|entry| entry.traf_number);
+                tfra.length_size_of_trun_num = length_size(|entry| entry.trun_number);
+                tfra.length_size_of_sample_num = length_size(|entry| entry.sample_number);
+            }
+            mfra.mfro.size = u32::try_from(mfra.box_size())
+                .map_err(|_| Error::InvalidData("mfra size exceeds u32::MAX"))?;
+            mfra.write_box(&mut self.writer)?;
+        }
+        Ok(())
+    }
+}
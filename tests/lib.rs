@@ -1,7 +1,12 @@
 use mp4::{
-    AudioObjectType, AvcProfile, ChannelConfig, MediaType, Metadata, Mp4Reader, SampleFreqIndex,
-    TrackType,
+    AudioObjectType, AvcProfile, BoxContent, BoxHeader, BoxTree, BoxType, BoxValue, ChannelConfig,
+    EdtsBox, ElstBox, ElstEntry, EmsgBox, FrmaBox, MediaType, Metadata, MoovBox, Mp4Box, Mp4Reader,
+    PsshBox, ReadBox, SaioBox, SaizBox, SampleDependency, SampleFlags, SampleFreqIndex,
+    SampleGroupEntry, SbgpBox, SbgpEntry, SchiBox, SchmBox, SdtpBox, SeekMode, SencBox, SencEntry,
+    SencSubsample, SgpdBox, SinfBox, SubsBox, SubsEntry, SubsSubsample, TencBox, TfhdBox,
+    TrackType, TrafBox, TrunBox, WriteBox,
 };
+use std::collections::HashMap;
 use std::fs::{self, File};
 use std::io::BufReader;
 use std::time::Duration;
@@ -44,6 +49,7 @@ fn test_read_mp4() {
             rendering_offset: 0,
             is_sync: true,
             bytes: vec![0x0u8; 751],
+            ..Default::default()
         }
     );
     let eos = mp4.read_sample(1, 2).unwrap();
@@ -61,6 +67,7 @@ fn test_read_mp4() {
             rendering_offset: 0,
             is_sync: true,
             bytes: vec![0x0u8; 179],
+            ..Default::default()
         }
     );
 
@@ -73,6 +80,7 @@ fn test_read_mp4() {
             rendering_offset: 0,
             is_sync: true,
             bytes: vec![0x0u8; 180],
+            ..Default::default()
         }
     );
 
@@ -85,6 +93,7 @@ fn test_read_mp4() {
             rendering_offset: 0,
             is_sync: true,
             bytes: vec![0x0u8; 160],
+            ..Default::default()
         }
     );
 
@@ -177,6 +186,106 @@ fn test_read_metadata() {
     assert_eq!(poster, want_poster.as_slice());
 }
 
+#[test]
+fn test_unknown_boxes_round_trip() {
+    let path = "tests/samples/big_buck_bunny_metadata.m4v";
+    let mp4 = get_reader(path);
+
+    let trak = &mp4.moov.traks[0];
+    let avc1 = trak.mdia.minf.stbl.stsd.avc1.as_ref().unwrap();
+    assert_eq!(avc1.unknown_boxes[0].0, BoxType::UnknownBox(0x70617370)); // pasp
+    let stbl = &mp4.moov.traks[1].mdia.minf.stbl;
+    assert!(stbl.unknown_boxes.is_empty());
+    assert_eq!(stbl.sgpds.len(), 1);
+    assert_eq!(stbl.sbgps.len(), 1);
+    let track = mp4.tracks().get(&2).unwrap();
+    assert_eq!(
+        track.sample_group(1, SampleGroupEntry::ROLL),
+        Some(&SampleGroupEntry::Roll { roll_distance: -1 })
+    );
+
+    // Nothing is lost, so boxes are written back with their original size.
+    // The audio trak is left out, as esds descriptors are written compactly.
+    assert_eq!(mp4.moov.traks[0].box_size(), 763);
+    assert_eq!(mp4.moov.udta.as_ref().unwrap().box_size(), 136406);
+
+    let mut buf = Vec::new();
+    mp4.moov.write_box(&mut buf).unwrap();
+    assert_eq!(buf.len() as u64, mp4.moov.box_size());
+
+    let mut reader = std::io::Cursor::new(&buf);
+    let header = BoxHeader::read(&mut reader).unwrap();
+    let moov = MoovBox::read_box(&mut reader, header.size).unwrap();
+    assert_eq!(moov, mp4.moov);
+}
+
+#[test]
+fn test_box_tree() {
+    let path = "tests/samples/big_buck_bunny_metadata.m4v";
+    let f = File::open(path).unwrap();
+    let size = f.metadata().unwrap().len();
+    let tree = BoxTree::read(BufReader::new(f), size).unwrap();
+
+    let types: Vec<_> = tree.boxes.iter().map(|node| node.box_type).collect();
+    assert_eq!(
+        types,
+        [
+            BoxType::FtypBox,
+            BoxType::MoovBox,
+            BoxType::FreeBox,
+            BoxType::MdatBox
+        ]
+    );
+    assert_eq!(tree.boxes.iter().map(|node| node.size).sum::<u64>(), size);
+
+    let moov = &tree.boxes[1];
+    assert_eq!(moov.offset, 32);
+    assert_eq!(moov.payload_offset(), 40);
+    assert_eq!(moov.size, 138127);
+    match moov.value() {
+        Some(BoxValue::MoovBox(value)) => assert_eq!(value.traks.len(), 2),
+        value => panic!("unexpected moov value {value:?}"),
+    }
+
+    // Every box is found, known or not.
+    let mut pasp = None;
+    let mut count = 0;
+    tree.visit(|node, depth| {
+        count += 1;
+        if node.box_type == BoxType::UnknownBox(0x70617370) {
+            pasp = Some((node.clone(), depth));
+        }
+    });
+    assert_eq!(count, 67);
+    let (pasp, depth) = pasp.unwrap();
+    assert_eq!(depth, 7);
+    assert_eq!(pasp.offset, 591);
+    assert_eq!(pasp.raw_data().unwrap(), [0, 0, 0, 1, 0, 0, 0, 1]);
+    assert_eq!(tree.boxes[3].content, BoxContent::Skipped);
+}
+
+#[test]
+fn test_box_tree_malformed() {
+    // A top-level box smaller than its header.
+    let data = b"\x00\x00\x00\x04free\x00\x00\x00\x00";
+    assert!(BoxTree::read(std::io::Cursor::new(data), data.len() as u64).is_err());
+
+    // A moov with a valid child followed by one larger than the moov.
+    let mut data = Vec::new();
+    data.extend(b"\x00\x00\x00\x20moov");
+    data.extend(b"\x00\x00\x00\x08free");
+    data.extend(b"\x00\x00\x00\x40abcd\x01\x02\x03\x04\x05\x06\x07\x08");
+    let tree = BoxTree::read(std::io::Cursor::new(&data), data.len() as u64).unwrap();
+    let children = &tree.boxes[0].children;
+    assert_eq!(children.len(), 2);
+    assert_eq!(children[0].box_type, BoxType::FreeBox);
+    assert_eq!(children[1].box_type, BoxType::UnknownBox(0x61626364));
+    assert_eq!(children[1].offset, 16);
+    assert_eq!(children[1].size, 16);
+    assert!(matches!(children[1].content, BoxContent::Invalid { .. }));
+    assert_eq!(children[1].raw_data().unwrap(), [1, 2, 3, 4, 5, 6, 7, 8]);
+}
+
 #[test]
 fn test_read_fragments() {
     let mp4 = get_reader("tests/samples/minimal_init.mp4");
@@ -203,9 +312,1861 @@ fn test_read_fragments() {
             duration: 512,
             rendering_offset: 0,
             is_sync: true,
+            flags: None,
+            dependency: None,
+            subsamples: Vec::new(),
             bytes: vec![0x0u8; 751],
         }
     );
+    // first_sample_flags takes precedence over the tfhd default flags.
+    assert_eq!(
+        sample_1_1.flags,
+        Some(SampleFlags {
+            depends_on: 2,
+            ..SampleFlags::default()
+        })
+    );
     let eos = mp4_fragment.read_sample(1, 2);
     assert!(eos.is_err());
 }
+
+/// Two seconds of fragmented video and audio, in two fragments.
+fn write_fragmented() -> Vec<u8> {
+    write_fragmented_with(&mp4::FragmentConfig {
+        fragment_duration: Duration::from_secs(1),
+        ..mp4::FragmentConfig::default()
+    })
+}
+
+fn write_fragmented_with(fragment_config: &mp4::FragmentConfig) -> Vec<u8> {
+    let config = mp4::Mp4Config {
+        major_brand: str::parse("iso6").unwrap(),
+        minor_version: 0,
+        compatible_brands: vec![str::parse("iso6").unwrap(), str::parse("cmfc").unwrap()],
+        timescale: 1000,
+    };
+    let mut writer =
+        mp4::Mp4FragmentWriter::write_start(Vec::new(), &config, fragment_config).unwrap();
+    writer
+        .add_track(&mp4::TrackConfig::from(mp4::AvcConfig {
+            width: 320,
+            height: 240,
+            seq_param_set: vec![0x67, 0x64, 0x00, 0x0d],
+            pic_param_set: vec![0x68, 0xee, 0x3c, 0x80],
+        }))
+        .unwrap();
+    writer
+        .add_track(&mp4::TrackConfig::from(mp4::AacConfig::default()))
+        .unwrap();
+
+    // 2 seconds of 25 fps video with a 1 second GOP, and 50 Hz audio frames.
+    for i in 0..100u64 {
+        if i % 2 == 0 {
+            let video = mp4::Mp4Sample {
+                start_time: i * 20,
+                duration: 40,
+                rendering_offset: 0,
+                is_sync: i % 50 == 0,
+                flags: None,
+                dependency: None,
+                subsamples: Vec::new(),
+                bytes: vec![(i / 2) as u8; 100 + i as usize],
+            };
+            writer.write_sample(1, &video).unwrap();
+        }
+        let audio = mp4::Mp4Sample {
+            start_time: i * 20,
+            duration: 20,
+            rendering_offset: 0,
+            is_sync: true,
+            flags: None,
+            dependency: None,
+            subsamples: Vec::new(),
+            bytes: vec![i as u8; 10],
+        };
+        writer.write_sample(2, &audio).unwrap();
+    }
+    writer.write_end().unwrap();
+
+    writer.into_writer()
+}
+
+#[test]
+fn test_write_fragmented() {
+    let data = write_fragmented();
+    let size = data.len() as u64;
+    let mut mp4 = Mp4Reader::read_header(std::io::Cursor::new(data), size).unwrap();
+
+    assert!(mp4.is_fragmented());
+    assert!(mp4.moov.mvex.is_some());
+    assert_eq!(mp4.moofs.len(), 2);
+    assert_eq!(mp4.sample_count(1).unwrap(), 50);
+    assert_eq!(mp4.sample_count(2).unwrap(), 100);
+
+    let sample = mp4.read_sample(1, 26).unwrap().unwrap();
+    assert_eq!(sample.start_time, 1000);
+    assert_eq!(sample.duration, 40);
+    assert_eq!(sample.bytes, vec![25u8; 150]);
+    assert!(sample.is_sync);
+    assert_eq!(sample.flags.unwrap().depends_on, 2);
+
+    // Sync samples are taken from the sample flags, not guessed.
+    let sync_samples: Vec<u32> = (1..=50)
+        .filter(|sample_id| mp4.read_sample(1, *sample_id).unwrap().unwrap().is_sync)
+        .collect();
+    assert_eq!(sync_samples, [1, 26]);
+    let flags = mp4.read_sample(1, 27).unwrap().unwrap().flags.unwrap();
+    assert!(flags.is_non_sync_sample);
+    assert_eq!(flags.depends_on, 1);
+
+    let sample = mp4.read_sample(2, 100).unwrap().unwrap();
+    assert_eq!(sample.start_time, 1980);
+    assert_eq!(sample.bytes, vec![99u8; 10]);
+
+    let start_times: Vec<u64> = mp4.samples().map(|s| s.unwrap().1.start_time).collect();
+    assert_eq!(start_times.len(), 150);
+    assert!(start_times.windows(2).all(|w| w[0] <= w[1]));
+
+    assert_eq!(
+        mp4.seek(2, Duration::from_millis(1510), SeekMode::Exact)
+            .unwrap(),
+        (76, Duration::from_millis(1500))
+    );
+}
+
+/// Rewrite the init segment and the fragments of a file written by
+/// [write_fragmented], keeping the sample data in place.
+fn rewrite_fragmented(
+    data: &[u8],
+    mut edit_moov: impl FnMut(&mut MoovBox),
+    mut edit_traf: impl FnMut(&mut TrafBox),
+) -> Vec<u8> {
+    let size = data.len() as u64;
+    let mp4 = Mp4Reader::read_header(std::io::Cursor::new(data), size).unwrap();
+    let tree = BoxTree::read(std::io::Cursor::new(data), size).unwrap();
+
+    let mut rewritten = Vec::new();
+    let mut moofs = mp4.moofs.iter();
+    for node in tree.boxes.iter() {
+        match node.box_type {
+            BoxType::MoovBox => {
+                let mut moov = mp4.moov.clone();
+                edit_moov(&mut moov);
+                moov.write_box(&mut rewritten).unwrap();
+            }
+            BoxType::MoofBox => {
+                let mut moof = moofs.next().unwrap().clone();
+                let moof_size = moof.box_size();
+                moof.trafs.iter_mut().for_each(&mut edit_traf);
+
+                // Sample data moves with the end of the moof.
+                let shift = moof.box_size() as i32 - moof_size as i32;
+                for trun in moof.trafs.iter_mut().flat_map(|traf| traf.truns.iter_mut()) {
+                    if let Some(data_offset) = trun.data_offset.as_mut() {
+                        *data_offset += shift;
+                    }
+                }
+                moof.write_box(&mut rewritten).unwrap();
+            }
+            _ => rewritten.extend(&data[node.offset as usize..(node.offset + node.size) as usize]),
+        }
+    }
+    rewritten
+}
+
+/// Check that two files have the same samples.
+fn assert_same_samples(data: &[u8], want: &[u8]) {
+    let mut mp4 = Mp4Reader::read_header(std::io::Cursor::new(data), data.len() as u64).unwrap();
+    let mut want = Mp4Reader::read_header(std::io::Cursor::new(want), want.len() as u64).unwrap();
+    for track_id in [1, 2] {
+        let sample_count = want.sample_count(track_id).unwrap();
+        assert_eq!(mp4.sample_count(track_id).unwrap(), sample_count);
+        for sample_id in 1..=sample_count {
+            let want = want.read_sample(track_id, sample_id).unwrap().unwrap();
+            let sample = mp4.read_sample(track_id, sample_id).unwrap().unwrap();
+            assert_eq!(sample, want);
+            assert_eq!(sample.flags, want.flags);
+            assert_eq!(sample.bytes, want.bytes);
+        }
+    }
+}
+
+#[test]
+fn test_read_fragment_defaults() {
+    let data = write_fragmented();
+
+    // Split the audio samples of every fragment over two truns, the second
+    // without a data offset, and take sample sizes and durations from the
+    // tfhd defaults.
+    let rewritten = rewrite_fragmented(
+        &data,
+        |_| {},
+        |traf| {
+            if traf.tfhd.track_id != 2 {
+                return;
+            }
+            let mut trun = traf.truns.remove(0);
+            assert!(trun.sample_sizes.iter().all(|size| *size == 10));
+            assert!(trun.sample_durations.iter().all(|duration| *duration == 20));
+            traf.tfhd.flags |=
+                TfhdBox::FLAG_DEFAULT_SAMPLE_DURATION | TfhdBox::FLAG_DEFAULT_SAMPLE_SIZE;
+            traf.tfhd.default_sample_duration = Some(20);
+            traf.tfhd.default_sample_size = Some(10);
+            trun.flags &= !(TrunBox::FLAG_SAMPLE_DURATION | TrunBox::FLAG_SAMPLE_SIZE);
+            trun.sample_durations.clear();
+            trun.sample_sizes.clear();
+
+            let half = trun.sample_count / 2;
+            let second = TrunBox {
+                flags: trun.flags & !TrunBox::FLAG_DATA_OFFSET,
+                sample_count: trun.sample_count - half,
+                data_offset: None,
+                sample_flags: trun.sample_flags.split_off(half as usize),
+                ..trun.clone()
+            };
+            trun.sample_count = half;
+            traf.truns = vec![trun, second];
+        },
+    );
+
+    let mp4 =
+        Mp4Reader::read_header(std::io::Cursor::new(&rewritten), rewritten.len() as u64).unwrap();
+    assert_eq!(mp4.moofs[0].trafs[1].truns.len(), 2);
+    assert_same_samples(&rewritten, &data);
+}
+
+#[test]
+fn test_read_fragment_trex() {
+    let data = write_fragmented();
+    let mp4 = Mp4Reader::read_header(std::io::Cursor::new(&data), data.len() as u64).unwrap();
+    let mvex = mp4.moov.mvex.as_ref().unwrap();
+    assert_eq!(mvex.trexs.len(), 2);
+    assert_eq!(mvex.trex(2).unwrap().track_id, 2);
+
+    // Take sample durations and flags from a trex per track.
+    let rewritten = rewrite_fragmented(
+        &data,
+        |moov| {
+            let mvex = moov.mvex.as_mut().unwrap();
+            mvex.trexs[0].default_sample_duration = 40;
+            mvex.trexs[0].default_sample_flags = 0x0101_0000;
+            mvex.trexs[1].default_sample_duration = 20;
+            mvex.trexs[1].default_sample_flags = 0x0200_0000;
+        },
+        |traf| {
+            let trun = &mut traf.truns[0];
+            trun.flags &= !(TrunBox::FLAG_SAMPLE_DURATION | TrunBox::FLAG_SAMPLE_FLAGS);
+            trun.sample_durations.clear();
+            if traf.tfhd.track_id == 1 {
+                // Every video fragment starts with a sync sample.
+                trun.flags |= TrunBox::FLAG_FIRST_SAMPLE_FLAGS;
+                trun.first_sample_flags = Some(trun.sample_flags[0]);
+            }
+            trun.sample_flags.clear();
+        },
+    );
+    assert_same_samples(&rewritten, &data);
+}
+
+#[test]
+fn test_read_fragment_without_tfdt() {
+    // Without a tfdt, the decode times of a traf follow on from the
+    // preceding trafs, with or without a sample index.
+    let data = write_fragmented();
+    let rewritten = rewrite_fragmented(&data, |_| {}, |traf| traf.tfdt = None);
+    assert_same_samples(&rewritten, &data);
+
+    let mut mp4 =
+        Mp4Reader::read_header(std::io::Cursor::new(&rewritten), rewritten.len() as u64).unwrap();
+    let want: Vec<_> = mp4.samples().map(|s| s.unwrap().1.start_time).collect();
+    mp4.build_sample_index().unwrap();
+    let got: Vec<_> = mp4.samples().map(|s| s.unwrap().1.start_time).collect();
+    assert_eq!(got, want);
+}
+
+#[test]
+fn test_read_stream() {
+    let data = write_fragmented();
+    let size = data.len() as u64;
+    let mut mp4 = Mp4Reader::read_header(std::io::Cursor::new(data.clone()), size).unwrap();
+
+    // A byte slice reader can not seek.
+    let mut stream = mp4::Mp4StreamReader::read_init(&data[..]).unwrap();
+    assert_eq!(stream.moov, mp4.moov);
+    assert_eq!(stream.tracks().len(), 2);
+
+    let mut sample_ids = HashMap::new();
+    let mut fragment_track_ids = Vec::new();
+    while let Some((track_id, sample)) = stream.next_sample().unwrap() {
+        let sample_id = sample_ids.entry(track_id).or_insert(0);
+        *sample_id += 1;
+        let want = mp4.read_sample(track_id, *sample_id).unwrap().unwrap();
+        assert_eq!(sample, want);
+        assert_eq!(sample.flags, want.flags);
+        assert_eq!(sample.bytes, want.bytes);
+        if fragment_track_ids.last() != Some(&track_id) {
+            fragment_track_ids.push(track_id);
+        }
+    }
+    assert_eq!(sample_ids[&1], 50);
+    assert_eq!(sample_ids[&2], 100);
+    assert_eq!(fragment_track_ids, vec![1, 2, 1, 2]);
+}
+
+#[test]
+fn test_read_stream_segments() {
+    let mut data = fs::read("tests/samples/minimal_init.mp4").unwrap();
+    data.extend(fs::read("tests/samples/minimal_fragment.m4s").unwrap());
+
+    let mut stream = mp4::Mp4StreamReader::read_init(&data[..]).unwrap();
+    let (track_id, sample) = stream.next_sample().unwrap().unwrap();
+    assert_eq!(track_id, 1);
+    assert_eq!(sample.bytes.len(), 751);
+    assert!(stream.next_sample().unwrap().is_none());
+
+    // The stream ends in the middle of a box.
+    let mut stream = mp4::Mp4StreamReader::read_init(&data[..data.len() - 1]).unwrap();
+    assert!(stream.next_sample().is_err());
+
+    // A moof without an mdat is not silently dropped.
+    let fragment = fs::read("tests/samples/minimal_fragment.m4s").unwrap();
+    let tree = BoxTree::read(std::io::Cursor::new(&fragment), fragment.len() as u64).unwrap();
+    let moof = tree
+        .boxes
+        .iter()
+        .find(|node| node.box_type == BoxType::MoofBox)
+        .unwrap();
+    let moof = moof.offset as usize..(moof.offset + moof.size) as usize;
+    let mut data = fs::read("tests/samples/minimal_init.mp4").unwrap();
+    data.extend(&fragment[..moof.end]);
+    data.extend(&fragment[moof.start..]);
+    let mut stream = mp4::Mp4StreamReader::read_init(&data[..]).unwrap();
+    assert!(stream.next_sample().is_err());
+}
+
+/// Write a progressive file with a single track holding `samples`.
+fn write_track(config: &mp4::TrackConfig, samples: &[mp4::Mp4Sample]) -> mp4::Result<Vec<u8>> {
+    let mp4_config = mp4::Mp4Config {
+        major_brand: str::parse("isom").unwrap(),
+        minor_version: 512,
+        compatible_brands: vec![str::parse("isom").unwrap(), str::parse("mp41").unwrap()],
+        timescale: 1000,
+    };
+    let mut writer = mp4::Mp4Writer::write_start(std::io::Cursor::new(Vec::new()), &mp4_config)?;
+    writer.add_track(config)?;
+    for sample in samples {
+        writer.write_sample(1, sample)?;
+    }
+    writer.write_end()?;
+    Ok(writer.into_writer().into_inner())
+}
+
+#[test]
+fn test_write_empty_chunk() {
+    // Every chunk after the first holds only empty samples.
+    let samples: Vec<mp4::Mp4Sample> = (0..100u8)
+        .map(|i| mp4::Mp4Sample {
+            start_time: i as u64 * 1024,
+            duration: 1024,
+            rendering_offset: 0,
+            is_sync: true,
+            flags: None,
+            dependency: None,
+            subsamples: Vec::new(),
+            bytes: if i < 2 { vec![i; 50] } else { Vec::new() },
+        })
+        .collect();
+    let config = mp4::TrackConfig::from(mp4::AacConfig::default());
+    let data = write_track(&config, &samples).unwrap();
+
+    let size = data.len() as u64;
+    let mut mp4 = Mp4Reader::read_header(std::io::Cursor::new(data), size).unwrap();
+    assert_eq!(mp4.sample_count(1).unwrap(), 100);
+    for (sample_id, want) in (1..=100).zip(&samples) {
+        let got = mp4.read_sample(1, sample_id).unwrap().unwrap();
+        assert_eq!(got.start_time, want.start_time);
+        assert_eq!(got.bytes, want.bytes);
+    }
+}
+
+#[test]
+fn test_write_faststart() {
+    let samples: Vec<mp4::Mp4Sample> = (0..100u8)
+        .map(|i| mp4::Mp4Sample {
+            start_time: i as u64 * 1024,
+            duration: 1024,
+            rendering_offset: 0,
+            is_sync: true,
+            flags: None,
+            dependency: None,
+            subsamples: Vec::new(),
+            // The last chunk is empty, so its offset is the end of the mdat.
+            bytes: if i < 94 {
+                vec![i; 50 + i as usize]
+            } else {
+                Vec::new()
+            },
+        })
+        .collect();
+    let config = mp4::TrackConfig::from(mp4::AacConfig::default());
+    let mut data = write_track(&config, &samples).unwrap();
+    let emsg = EmsgBox {
+        version: 1,
+        timescale: 1000,
+        presentation_time: Some(500),
+        scheme_id_uri: "urn:example:event".to_string(),
+        message_data: b"event".to_vec(),
+        ..EmsgBox::default()
+    };
+    emsg.write_box(&mut data).unwrap();
+
+    let size = data.len() as u64;
+    let mut src = Mp4Reader::read_header(std::io::Cursor::new(data), size).unwrap();
+    let mut faststart = Vec::new();
+    src.write_faststart(&mut faststart).unwrap();
+    assert_eq!(faststart.len() as u64, size);
+
+    // moov directly follows ftyp.
+    let ftyp_size = u32::from_be_bytes(faststart[0..4].try_into().unwrap()) as usize;
+    assert_eq!(&faststart[ftyp_size + 4..ftyp_size + 8], b"moov");
+
+    let size = faststart.len() as u64;
+    let mut dst = Mp4Reader::read_header(std::io::Cursor::new(faststart), size).unwrap();
+    assert_eq!(dst.emsgs, [emsg]);
+    assert_eq!(dst.sample_count(1).unwrap(), 100);
+    for sample_id in 1..=100 {
+        let want = src.read_sample(1, sample_id).unwrap().unwrap();
+        let got = dst.read_sample(1, sample_id).unwrap().unwrap();
+        assert_eq!(got, want);
+        assert_eq!(got.bytes, want.bytes);
+    }
+}
+
+#[test]
+fn test_write_hevc() {
+    let vps = [0x40, 0x01, 0x0c, 0x01];
+    let sps = [
+        0x42, 0x01, 0x01, 0x01, 0x60, 0x00, 0x00, 0x03, 0x00, 0x90, 0x00, 0x00, 0x03, 0x00, 0x00,
+        0x03, 0x00, 0x5d, 0xa0, 0x02, 0x80, 0x80, 0x2d, 0x16, 0x59, 0x59, 0xa4, 0x93, 0x2b, 0xc0,
+        0x5a, 0x70, 0x80, 0x00, 0x01, 0xf4, 0x80, 0x00, 0x30, 0xd4, 0x20,
+    ];
+    let pps = [0x44, 0x01, 0xc1, 0x72, 0xb4, 0x62, 0x40];
+    for (sample_entry, box_type) in [
+        (mp4::HevcSampleEntry::Hev1, BoxType::Hev1Box),
+        (mp4::HevcSampleEntry::Hvc1, BoxType::Hvc1Box),
+    ] {
+        let config = mp4::TrackConfig::from(mp4::HevcConfig {
+            width: 1280,
+            height: 720,
+            sample_entry,
+            video_param_set: vps.to_vec(),
+            seq_param_set: sps.to_vec(),
+            pic_param_set: pps.to_vec(),
+        });
+        let data = write_track(&config, &[]).unwrap();
+
+        let mp4 = Mp4Reader::read_header(std::io::Cursor::new(&data), data.len() as u64).unwrap();
+        let track = mp4.tracks().get(&1).unwrap();
+        assert_eq!(track.media_type().unwrap(), mp4::MediaType::H265);
+        assert_eq!(track.box_type().unwrap(), box_type.into());
+        assert_eq!(track.video_parameter_set().unwrap(), vps);
+        assert_eq!(track.sequence_parameter_set().unwrap(), sps);
+        assert_eq!(track.picture_parameter_set().unwrap(), pps);
+
+        let hvcc = &track.trak.mdia.minf.stbl.stsd.hev1.as_ref().unwrap().hvcc;
+        assert_eq!(hvcc.general_profile_idc, 1);
+        assert_eq!(hvcc.general_level_idc, 93);
+        assert_eq!(hvcc.chroma_format_idc, 1);
+    }
+
+    // Parameter sets are required to build the hvcC.
+    let config = mp4::TrackConfig::from(mp4::HevcConfig::default());
+    assert!(write_track(&config, &[]).is_err());
+}
+
+#[test]
+fn test_write_av1() {
+    // Main profile, level 4.0, 1920x1080 4:2:0 8-bit.
+    let sequence_header = [
+        0x0a, 0x0e, 0x00, 0x00, 0x00, 0x42, 0xab, 0xbf, 0xc3, 0x73, 0xff, 0xe6, 0x40, 0x40, 0x40,
+        0x41,
+    ];
+    let config = mp4::TrackConfig::from(mp4::Av1Config {
+        width: 1920,
+        height: 1080,
+        sequence_header: sequence_header.to_vec(),
+    });
+    let data = write_track(&config, &[]).unwrap();
+
+    let mp4 = Mp4Reader::read_header(std::io::Cursor::new(&data), data.len() as u64).unwrap();
+    let track = mp4.tracks().get(&1).unwrap();
+    assert_eq!(track.media_type().unwrap(), mp4::MediaType::AV1);
+    assert_eq!(track.box_type().unwrap(), BoxType::Av01Box.into());
+    assert_eq!(track.width(), 1920);
+    assert_eq!(track.sequence_header().unwrap(), sequence_header);
+
+    let av1c = &track.trak.mdia.minf.stbl.stsd.av01.as_ref().unwrap().av1c;
+    assert_eq!(av1c.seq_level_idx_0, 8);
+    assert_eq!(av1c.bit_depth(), 8);
+}
+
+#[test]
+fn test_write_opus() {
+    let config = mp4::Mp4Config {
+        major_brand: str::parse("isom").unwrap(),
+        minor_version: 512,
+        compatible_brands: vec![str::parse("isom").unwrap(), str::parse("Opus").unwrap()],
+        timescale: 1000,
+    };
+    let opus_config = mp4::OpusConfig {
+        pre_skip: 312,
+        ..mp4::OpusConfig::default()
+    };
+    // 2 seconds of 20 ms frames.
+    let samples: Vec<mp4::Mp4Sample> = (0..100u64)
+        .map(|i| mp4::Mp4Sample {
+            start_time: i * 960,
+            duration: 960,
+            rendering_offset: 0,
+            is_sync: true,
+            flags: None,
+            dependency: None,
+            subsamples: Vec::new(),
+            bytes: vec![i as u8; 40],
+        })
+        .collect();
+
+    let data = write_track(&mp4::TrackConfig::from(opus_config.clone()), &samples).unwrap();
+
+    let mp4 = Mp4Reader::read_header(std::io::Cursor::new(&data), data.len() as u64).unwrap();
+    let track = mp4.tracks().get(&1).unwrap();
+    assert_eq!(track.media_type().unwrap(), MediaType::OPUS);
+    assert_eq!(track.box_type().unwrap(), BoxType::OpusBox.into());
+    assert_eq!(track.timescale(), 48000);
+    let dops = track.opus_header().unwrap();
+    assert_eq!(dops.output_channel_count, 2);
+    assert_eq!(dops.pre_skip, 312);
+    // 80 ms of pre-roll is 4 frames.
+    assert_eq!(
+        track.sample_group(50, SampleGroupEntry::ROLL),
+        Some(&SampleGroupEntry::Roll { roll_distance: -4 })
+    );
+    assert_eq!(track.presentation_time(1).unwrap(), Some(-312));
+    assert_eq!(track.presentation_time(2).unwrap(), Some(960 - 312));
+    assert_eq!(track.presentation_duration(), Duration::from_millis(1993));
+    assert_eq!(mp4.duration(), Duration::from_millis(1993));
+
+    let mut writer = mp4::Mp4FragmentWriter::write_start(
+        Vec::new(),
+        &config,
+        &mp4::FragmentConfig {
+            fragment_duration: Duration::from_secs(1),
+            ..mp4::FragmentConfig::default()
+        },
+    )
+    .unwrap();
+    writer
+        .add_track(&mp4::TrackConfig::from(opus_config))
+        .unwrap();
+    for sample in samples.iter() {
+        writer.write_sample(1, sample).unwrap();
+    }
+    writer.write_end().unwrap();
+    let data = writer.into_writer();
+
+    let mp4 = Mp4Reader::read_header(std::io::Cursor::new(&data), data.len() as u64).unwrap();
+    let track = mp4.tracks().get(&1).unwrap();
+    assert_eq!(track.media_type().unwrap(), MediaType::OPUS);
+    assert_eq!(track.sample_count(), 100);
+    for sample_id in [1, 60, 100] {
+        assert_eq!(
+            track.sample_group(sample_id, SampleGroupEntry::ROLL),
+            Some(&SampleGroupEntry::Roll { roll_distance: -4 })
+        );
+    }
+    assert_eq!(track.presentation_time(2).unwrap(), Some(960 - 312));
+}
+
+#[test]
+fn test_write_flac() {
+    let vorbis_comment = mp4::FlacMetadataBlock {
+        block_type: mp4::FlacMetadataBlock::VORBIS_COMMENT,
+        data: b"\x09\x00\x00\x00reference\x00\x00\x00\x00".to_vec(),
+    };
+    let flac_config = mp4::FlacConfig {
+        stream_info: mp4::FlacStreamInfo {
+            min_block_size: 4096,
+            max_block_size: 4096,
+            sample_rate: 96000,
+            channels: 2,
+            bits_per_sample: 24,
+            total_samples: 40960,
+            ..mp4::FlacStreamInfo::default()
+        },
+        metadata_blocks: vec![vorbis_comment.clone()],
+    };
+    let samples: Vec<mp4::Mp4Sample> = (0..10u64)
+        .map(|i| mp4::Mp4Sample {
+            start_time: i * 4096,
+            duration: 4096,
+            rendering_offset: 0,
+            is_sync: true,
+            flags: None,
+            dependency: None,
+            subsamples: Vec::new(),
+            bytes: vec![i as u8; 1000],
+        })
+        .collect();
+    let data = write_track(&mp4::TrackConfig::from(flac_config), &samples).unwrap();
+
+    let mp4 = Mp4Reader::read_header(std::io::Cursor::new(&data), data.len() as u64).unwrap();
+    let track = mp4.tracks().get(&1).unwrap();
+    assert_eq!(track.media_type().unwrap(), MediaType::FLAC);
+    assert_eq!(track.box_type().unwrap(), BoxType::FlacBox.into());
+    assert_eq!(track.timescale(), 96000);
+    assert_eq!(track.sample_rate().unwrap(), 96000);
+    assert_eq!(track.channel_count().unwrap(), 2);
+    assert_eq!(track.bits_per_sample().unwrap(), 24);
+    assert_eq!(
+        track.flac_metadata().unwrap().metadata_blocks,
+        [vorbis_comment]
+    );
+
+    // STREAMINFO is required.
+    let config = mp4::TrackConfig::from(mp4::FlacConfig::default());
+    assert!(write_track(&config, &[]).is_err());
+}
+
+#[test]
+fn test_write_ac3_ec3() {
+    let config = mp4::Mp4Config {
+        major_brand: str::parse("isom").unwrap(),
+        minor_version: 512,
+        compatible_brands: vec![str::parse("isom").unwrap()],
+        timescale: 1000,
+    };
+    // Stereo at 44.1 kHz and 192 kbit/s.
+    let ac3_config = mp4::Ac3Config {
+        fscod: 1,
+        acmod: 2,
+        lfeon: false,
+        bit_rate_code: 10,
+        ..mp4::Ac3Config::default()
+    };
+    // 7.1 with Atmos, as 5.1 plus a dependent substream with Lrs/Rrs.
+    let ec3_config = mp4::Ec3Config {
+        data_rate: 768,
+        substreams: vec![mp4::Ec3Substream {
+            num_dep_sub: 1,
+            chan_loc: 0x080,
+            ..mp4::Ec3Substream::default()
+        }],
+        joc_complexity_index: Some(16),
+    };
+    let mut writer =
+        mp4::Mp4Writer::write_start(std::io::Cursor::new(Vec::new()), &config).unwrap();
+    writer
+        .add_track(&mp4::TrackConfig::from(ac3_config))
+        .unwrap();
+    writer
+        .add_track(&mp4::TrackConfig::from(ec3_config.clone()))
+        .unwrap();
+    for track_id in 1..=2 {
+        for i in 0..10u64 {
+            let sample = mp4::Mp4Sample {
+                start_time: i * 1536,
+                duration: 1536,
+                rendering_offset: 0,
+                is_sync: true,
+                flags: None,
+                dependency: None,
+                subsamples: Vec::new(),
+                bytes: vec![i as u8; 768],
+            };
+            writer.write_sample(track_id, &sample).unwrap();
+        }
+    }
+    writer.write_end().unwrap();
+    let data = writer.into_writer().into_inner();
+
+    let mp4 = Mp4Reader::read_header(std::io::Cursor::new(&data), data.len() as u64).unwrap();
+    let track = mp4.tracks().get(&1).unwrap();
+    assert_eq!(track.media_type().unwrap(), MediaType::AC3);
+    assert_eq!(track.box_type().unwrap(), BoxType::Ac3Box.into());
+    assert_eq!(track.timescale(), 44100);
+    assert_eq!(track.sample_rate().unwrap(), 44100);
+    assert_eq!(track.channel_count().unwrap(), 2);
+    assert_eq!(track.channel_layout().unwrap().to_string(), "A000");
+    assert_eq!(track.bitrate(), 192000);
+
+    let track = mp4.tracks().get(&2).unwrap();
+    assert_eq!(track.media_type().unwrap(), MediaType::EAC3);
+    assert_eq!(track.box_type().unwrap(), BoxType::Ec3Box.into());
+    assert_eq!(track.sample_rate().unwrap(), 48000);
+    assert_eq!(track.channel_count().unwrap(), 8);
+    let layout = track.channel_layout().unwrap();
+    assert_eq!(layout.to_string(), "FA01");
+    assert!(layout.has_lfe());
+    assert_eq!(track.bitrate(), 768000);
+    let dec3 = track.ec3_config().unwrap();
+    assert_eq!(dec3.substreams, ec3_config.substreams);
+    assert!(dec3.is_atmos());
+    assert!(track.ac3_config().is_err());
+}
+
+#[test]
+fn test_read_samples_interleaved() {
+    let mut mp4 = get_reader("tests/samples/minimal.mp4");
+    let timescales: Vec<(u32, u32)> = mp4
+        .tracks()
+        .values()
+        .map(|t| (t.track_id(), t.timescale()))
+        .collect();
+
+    let samples: Vec<(u32, mp4::Mp4Sample)> = mp4.samples().map(|s| s.unwrap()).collect();
+    let track_ids: Vec<u32> = samples.iter().map(|(track_id, _)| *track_id).collect();
+    assert_eq!(track_ids, vec![1, 2, 2, 2]);
+
+    let times: Vec<f64> = samples
+        .iter()
+        .map(|(track_id, sample)| {
+            let timescale = timescales.iter().find(|t| t.0 == *track_id).unwrap().1;
+            sample.start_time as f64 / timescale as f64
+        })
+        .collect();
+    assert!(times.windows(2).all(|w| w[0] <= w[1]));
+
+    let audio: Vec<u64> = mp4
+        .track_samples(&[2])
+        .unwrap()
+        .map(|s| s.unwrap().1.start_time)
+        .collect();
+    assert_eq!(audio, vec![0, 1024, 2048]);
+
+    assert!(mp4.track_samples(&[3]).is_err());
+}
+
+#[test]
+fn test_segment_index() {
+    let data = write_fragmented_with(&mp4::FragmentConfig {
+        fragment_duration: Duration::from_secs(1),
+        segment_index: true,
+        ..mp4::FragmentConfig::default()
+    });
+    let size = data.len() as u64;
+
+    // The sidx follows the init segment and indexes each moof and mdat pair.
+    let tree = BoxTree::read(std::io::Cursor::new(&data), size).unwrap();
+    let box_types: Vec<BoxType> = tree.boxes.iter().map(|node| node.box_type).collect();
+    assert_eq!(
+        box_types,
+        [
+            BoxType::FtypBox,
+            BoxType::MoovBox,
+            BoxType::SidxBox,
+            BoxType::MoofBox,
+            BoxType::MdatBox,
+            BoxType::MoofBox,
+            BoxType::MdatBox,
+        ]
+    );
+
+    let mp4 = Mp4Reader::read_header(std::io::Cursor::new(&data), size).unwrap();
+    assert_eq!(mp4.sidxs.len(), 1);
+    assert_eq!(mp4.sidxs[0].reference_id, 1);
+    assert_eq!(mp4.sidxs[0].timescale, 1000);
+
+    let subsegments = mp4.subsegments();
+    assert_eq!(subsegments.len(), 2);
+    for (i, subsegment) in subsegments.iter().enumerate() {
+        let moof = &tree.boxes[3 + i * 2];
+        let mdat = &tree.boxes[4 + i * 2];
+        assert_eq!(subsegment.byte_range, moof.offset..mdat.offset + mdat.size);
+        assert_eq!(subsegment.start_time, i as u64 * 1000);
+        assert_eq!(subsegment.duration, 1000);
+        assert!(subsegment.starts_with_sap);
+        assert!(!subsegment.is_index);
+    }
+
+    // The samples are unaffected by the index.
+    assert_same_samples(&data, &write_fragmented());
+
+    // Offsets are relative to the end of the sidx as stored, which may be
+    // larger than the box as written back.
+    let sidx = &tree.boxes[2];
+    let sidx_end = (sidx.offset + sidx.size) as usize;
+    let mut padded = data[..sidx_end].to_vec();
+    padded.extend([0; 4]);
+    padded.extend(&data[sidx_end..]);
+    let sidx_size = u32::from_be_bytes(padded[sidx.offset as usize..][..4].try_into().unwrap());
+    padded[sidx.offset as usize..][..4].copy_from_slice(&(sidx_size + 4).to_be_bytes());
+    let size = padded.len() as u64;
+    let padded_mp4 = Mp4Reader::read_header(std::io::Cursor::new(&padded), size).unwrap();
+    let padded_subsegments = padded_mp4.subsegments();
+    assert_eq!(padded_subsegments.len(), 2);
+    for (padded, subsegment) in padded_subsegments.iter().zip(subsegments.iter()) {
+        assert_eq!(
+            padded.byte_range,
+            subsegment.byte_range.start + 4..subsegment.byte_range.end + 4
+        );
+    }
+}
+
+#[test]
+fn test_random_access_index() {
+    let data = write_fragmented_with(&mp4::FragmentConfig {
+        fragment_duration: Duration::from_secs(1),
+        segment_index: true,
+        random_access_index: true,
+    });
+    let size = data.len() as u64;
+
+    let tree = BoxTree::read(std::io::Cursor::new(&data), size).unwrap();
+    let moof_offsets: Vec<u64> = tree
+        .boxes
+        .iter()
+        .filter(|node| node.box_type == BoxType::MoofBox)
+        .map(|node| node.offset)
+        .collect();
+    assert_eq!(moof_offsets.len(), 2);
+    assert_eq!(tree.boxes.last().unwrap().box_type, BoxType::MfraBox);
+
+    // Only the init segment and the mfra are read.
+    let mut mp4 = Mp4Reader::read_header_from_mfra(std::io::Cursor::new(&data), size).unwrap();
+    assert!(mp4.moofs.is_empty());
+    let mfra = mp4.mfra.as_ref().unwrap();
+    assert_eq!(mfra.mfro.size as u64, tree.boxes.last().unwrap().size);
+    for track_id in [1, 2] {
+        let tfra = mfra.tfra(track_id).unwrap();
+        let offsets: Vec<u64> = tfra.entries.iter().map(|e| e.moof_offset).collect();
+        assert_eq!(offsets, moof_offsets);
+    }
+
+    let mut want = Mp4Reader::read_header(std::io::Cursor::new(&data), size).unwrap();
+    assert!(want.mfra.is_some());
+
+    let (sample_id, time) = mp4
+        .read_fragment_at(1, Duration::from_millis(1500))
+        .unwrap();
+    assert_eq!((sample_id, time), (1, Duration::from_secs(1)));
+    assert_eq!(mp4.moofs.len(), 1);
+    assert_eq!(mp4.sample_count(1).unwrap(), 25);
+    let sample = mp4.read_sample(1, sample_id).unwrap().unwrap();
+    assert!(sample.is_sync);
+    assert_eq!(sample, want.read_sample(1, 26).unwrap().unwrap());
+
+    let (sample_id, time) = mp4.read_fragment_at(2, Duration::from_millis(500)).unwrap();
+    assert_eq!((sample_id, time), (1, Duration::ZERO));
+    let sample = mp4.read_sample(2, sample_id).unwrap().unwrap();
+    assert_eq!(sample, want.read_sample(2, 1).unwrap().unwrap());
+
+    assert!(matches!(
+        mp4.read_fragment_at(3, Duration::ZERO),
+        Err(mp4::Error::TrakNotFound(3))
+    ));
+    let plain = write_fragmented();
+    let plain_size = plain.len() as u64;
+    assert!(matches!(
+        Mp4Reader::read_header_from_mfra(std::io::Cursor::new(&plain), plain_size),
+        Err(mp4::Error::BoxNotFound(BoxType::MfraBox))
+    ));
+}
+
+fn write_video(gop_size: u64, sample_count: u64) -> Vec<u8> {
+    let config = mp4::TrackConfig::from(mp4::AvcConfig {
+        width: 320,
+        height: 240,
+        seq_param_set: vec![0x67, 0x64, 0x00, 0x0d],
+        pic_param_set: vec![0x68, 0xee, 0x3c, 0x80],
+    });
+    let samples: Vec<mp4::Mp4Sample> = (0..sample_count)
+        .map(|i| mp4::Mp4Sample {
+            start_time: i * 40,
+            duration: 40,
+            rendering_offset: 0,
+            is_sync: i % gop_size == 0,
+            flags: None,
+            dependency: None,
+            subsamples: Vec::new(),
+            bytes: vec![i as u8; 100],
+        })
+        .collect();
+    write_track(&config, &samples).unwrap()
+}
+
+#[test]
+fn test_sample_groups() {
+    let data = write_video(5, 10);
+    let mp4 = Mp4Reader::read_header(std::io::Cursor::new(&data), data.len() as u64).unwrap();
+
+    let sbgp_entry = |sample_count, group_description_index| SbgpEntry {
+        sample_count,
+        group_description_index,
+    };
+    let mut moov = mp4.moov.clone();
+    let stbl = &mut moov.traks[0].mdia.minf.stbl;
+    stbl.sgpds = vec![
+        SgpdBox {
+            version: 1,
+            grouping_type: SampleGroupEntry::ROLL,
+            default_length: 2,
+            entries: vec![
+                SampleGroupEntry::Roll { roll_distance: -1 },
+                SampleGroupEntry::Roll { roll_distance: 2 },
+            ],
+            ..SgpdBox::default()
+        },
+        SgpdBox {
+            version: 2,
+            grouping_type: SampleGroupEntry::RAP,
+            default_length: 1,
+            default_sample_description_index: 1,
+            entries: vec![SampleGroupEntry::Rap {
+                num_leading_samples_known: true,
+                num_leading_samples: 2,
+            }],
+            ..SgpdBox::default()
+        },
+    ];
+    stbl.sbgps = vec![SbgpBox {
+        grouping_type: SampleGroupEntry::ROLL,
+        entries: vec![sbgp_entry(2, 1), sbgp_entry(3, 0), sbgp_entry(5, 2)],
+        ..SbgpBox::default()
+    }];
+
+    let data = replace_moov(&data, &moov);
+    let mp4 = Mp4Reader::read_header(std::io::Cursor::new(&data), data.len() as u64).unwrap();
+    assert_eq!(mp4.moov, moov);
+
+    let track = mp4.tracks().get(&1).unwrap();
+    let roll = |sample_id| track.sample_group(sample_id, SampleGroupEntry::ROLL);
+    assert_eq!(roll(1), Some(&SampleGroupEntry::Roll { roll_distance: -1 }));
+    assert_eq!(roll(2), Some(&SampleGroupEntry::Roll { roll_distance: -1 }));
+    assert_eq!(roll(3), None);
+    assert_eq!(roll(10), Some(&SampleGroupEntry::Roll { roll_distance: 2 }));
+    assert_eq!(roll(11), None);
+
+    // Without an sbgp, samples map to the default description.
+    assert!(matches!(
+        track.sample_group(4, SampleGroupEntry::RAP),
+        Some(SampleGroupEntry::Rap {
+            num_leading_samples: 2,
+            ..
+        })
+    ));
+    assert_eq!(track.sample_group(4, SampleGroupEntry::SYNC), None);
+
+    // Fragments map samples to descriptions in the stbl and in the traf.
+    let data = write_fragmented();
+    let rewritten = rewrite_fragmented(
+        &data,
+        |moov| {
+            moov.traks[1].mdia.minf.stbl.sgpds = vec![SgpdBox {
+                version: 1,
+                grouping_type: SampleGroupEntry::ROLL,
+                default_length: 2,
+                entries: vec![SampleGroupEntry::Roll { roll_distance: -1 }],
+                ..SgpdBox::default()
+            }];
+        },
+        |traf| {
+            if traf.tfhd.track_id != 2 {
+                return;
+            }
+            traf.sgpds = vec![SgpdBox {
+                version: 1,
+                grouping_type: SampleGroupEntry::ROLL,
+                default_length: 2,
+                entries: vec![SampleGroupEntry::Roll { roll_distance: -2 }],
+                ..SgpdBox::default()
+            }];
+            traf.sbgps = vec![SbgpBox {
+                grouping_type: SampleGroupEntry::ROLL,
+                entries: vec![
+                    sbgp_entry(1, 1),
+                    sbgp_entry(1, SbgpBox::FRAGMENT_LOCAL_INDEX_BASE + 1),
+                ],
+                ..SbgpBox::default()
+            }];
+        },
+    );
+    assert_same_samples(&rewritten, &data);
+
+    let mp4 =
+        Mp4Reader::read_header(std::io::Cursor::new(&rewritten), rewritten.len() as u64).unwrap();
+    assert_eq!(mp4.moofs[0].trafs[1].sbgps.len(), 1);
+    let track = mp4.tracks().get(&2).unwrap();
+    let roll = |sample_id| track.sample_group(sample_id, SampleGroupEntry::ROLL);
+    assert_eq!(roll(1), Some(&SampleGroupEntry::Roll { roll_distance: -1 }));
+    assert_eq!(roll(2), Some(&SampleGroupEntry::Roll { roll_distance: -2 }));
+    assert_eq!(roll(3), None);
+    assert_eq!(
+        roll(51),
+        Some(&SampleGroupEntry::Roll { roll_distance: -1 })
+    );
+    assert_eq!(
+        roll(52),
+        Some(&SampleGroupEntry::Roll { roll_distance: -2 })
+    );
+    assert_eq!(roll(101), None);
+}
+
+#[test]
+fn test_sample_dependency_and_subsamples() {
+    let data = write_video(5, 10);
+    let mp4 = Mp4Reader::read_header(std::io::Cursor::new(&data), data.len() as u64).unwrap();
+
+    // I-frames, P-frames and disposable B-frames, with the first and third
+    // samples split into a 4 byte and a 96 byte NAL unit.
+    let dependency = |depends_on, is_depended_on| SampleDependency {
+        depends_on,
+        is_depended_on,
+        ..SampleDependency::default()
+    };
+    let subsample = |subsample_size, discardable| SubsSubsample {
+        subsample_size,
+        subsample_priority: 0,
+        discardable,
+        codec_specific_parameters: 0,
+    };
+    let subs = SubsBox {
+        entries: vec![
+            SubsEntry {
+                sample_delta: 1,
+                subsamples: vec![subsample(4, false), subsample(96, false)],
+            },
+            SubsEntry {
+                sample_delta: 2,
+                subsamples: vec![subsample(4, true), subsample(96, false)],
+            },
+        ],
+        ..SubsBox::default()
+    };
+    let mut moov = mp4.moov.clone();
+    let stbl = &mut moov.traks[0].mdia.minf.stbl;
+    stbl.sdtp = Some(SdtpBox {
+        entries: (0..10)
+            .map(|i| match i % 5 {
+                0 => dependency(2, 1),
+                2 | 4 => dependency(1, 1),
+                _ => dependency(1, 2),
+            })
+            .collect(),
+        ..SdtpBox::default()
+    });
+    stbl.subs = vec![subs.clone()];
+
+    let data = replace_moov(&data, &moov);
+    let mut mp4 = Mp4Reader::read_header(std::io::Cursor::new(&data), data.len() as u64).unwrap();
+    assert_eq!(mp4.moov, moov);
+
+    for indexed in [false, true] {
+        if indexed {
+            mp4.build_sample_index().unwrap();
+        }
+        let disposable: Vec<u32> = (1..=10)
+            .filter(|sample_id| {
+                let sample = mp4.read_sample(1, *sample_id).unwrap().unwrap();
+                sample.dependency.unwrap().is_disposable()
+            })
+            .collect();
+        assert_eq!(disposable, [2, 4, 7, 9]);
+
+        let sample = mp4.read_sample(1, 1).unwrap().unwrap();
+        assert_eq!(sample.dependency.unwrap().depends_on, 2);
+        let ranges: Vec<_> = sample.subsamples.iter().map(|s| s.range.clone()).collect();
+        assert_eq!(ranges, [0..4, 4..100]);
+        let sample = mp4.read_sample(1, 3).unwrap().unwrap();
+        assert!(sample.subsamples[0].discardable);
+        assert!(
+            mp4.read_sample(1, 2)
+                .unwrap()
+                .unwrap()
+                .subsamples
+                .is_empty()
+        );
+    }
+
+    // Fragments take the dependency from the sample flags unless the traf
+    // has an sdtp, and subsamples are numbered from the start of the traf.
+    let data = write_fragmented();
+    let rewritten = rewrite_fragmented(
+        &data,
+        |_| {},
+        |traf| match traf.tfhd.track_id {
+            1 => traf.subs = vec![subs.clone()],
+            _ => {
+                traf.sdtp = Some(SdtpBox {
+                    entries: vec![dependency(2, 2); traf.truns[0].sample_count as usize],
+                    ..SdtpBox::default()
+                })
+            }
+        },
+    );
+    assert_same_samples(&rewritten, &data);
+
+    let mut mp4 =
+        Mp4Reader::read_header(std::io::Cursor::new(&rewritten), rewritten.len() as u64).unwrap();
+    let sample = mp4.read_sample(1, 1).unwrap().unwrap();
+    assert_eq!(sample.dependency, Some(dependency(2, 0)));
+    assert_eq!(sample.subsamples.len(), 2);
+    let sample = mp4.read_sample(1, 2).unwrap().unwrap();
+    assert_eq!(sample.dependency, Some(dependency(1, 0)));
+    assert!(sample.subsamples.is_empty());
+    let sample = mp4.read_sample(1, 28).unwrap().unwrap();
+    assert_eq!(sample.subsamples[1].range, 4..100);
+    let sample = mp4.read_sample(2, 60).unwrap().unwrap();
+    assert!(sample.dependency.unwrap().is_disposable());
+}
+
+const KID: [u8; 16] = [0x11; 16];
+
+fn sinf(original_format: &str, scheme_type: mp4::FourCC, tenc: TencBox) -> SinfBox {
+    SinfBox {
+        frma: FrmaBox {
+            original_format: str::parse(original_format).unwrap(),
+        },
+        schm: Some(SchmBox {
+            scheme_type,
+            scheme_version: 0x10000,
+            ..SchmBox::default()
+        }),
+        schi: Some(SchiBox {
+            tenc: Some(tenc),
+            ..SchiBox::default()
+        }),
+        ..SinfBox::default()
+    }
+}
+
+fn cenc_tenc() -> TencBox {
+    TencBox {
+        default_is_protected: true,
+        default_per_sample_iv_size: 8,
+        default_kid: KID,
+        ..TencBox::default()
+    }
+}
+
+fn cenc_subsamples() -> Vec<SencSubsample> {
+    vec![SencSubsample {
+        bytes_of_clear_data: 4,
+        bytes_of_protected_data: 96,
+    }]
+}
+
+/// Mark the tracks of a file written by [write_fragmented] as encrypted,
+/// leaving the sample data as it is.
+///
+/// Video is encrypted with cenc and per-sample IVs in senc, leaving the
+/// first sample of each fragment in the clear with a seig group, and audio
+/// is encrypted with cbcs and a constant IV.
+fn protect_fragmented(data: &[u8]) -> Vec<u8> {
+    rewrite_fragmented(
+        data,
+        |moov| {
+            let stsd = &mut moov.traks[0].mdia.minf.stbl.stsd;
+            stsd.avc1.as_mut().unwrap().sinf = Some(sinf("avc1", SchmBox::CENC, cenc_tenc()));
+            let stsd = &mut moov.traks[1].mdia.minf.stbl.stsd;
+            stsd.mp4a.as_mut().unwrap().sinf = Some(sinf(
+                "mp4a",
+                SchmBox::CBCS,
+                TencBox {
+                    version: 1,
+                    default_crypt_byte_block: 1,
+                    default_skip_byte_block: 9,
+                    default_is_protected: true,
+                    default_kid: KID,
+                    default_constant_iv: vec![7; 16],
+                    ..TencBox::default()
+                },
+            ));
+            moov.psshs.push(PsshBox {
+                version: 1,
+                system_id: PsshBox::COMMON_SYSTEM_ID,
+                kids: vec![KID],
+                ..PsshBox::default()
+            });
+        },
+        |traf| {
+            if traf.tfhd.track_id != 1 {
+                return;
+            }
+            traf.sgpds.push(SgpdBox {
+                version: 1,
+                grouping_type: SampleGroupEntry::SEIG,
+                default_length: 20,
+                entries: vec![SampleGroupEntry::Seig {
+                    crypt_byte_block: 0,
+                    skip_byte_block: 0,
+                    is_protected: false,
+                    per_sample_iv_size: 0,
+                    kid: [0; 16],
+                    constant_iv: vec![],
+                }],
+                ..SgpdBox::default()
+            });
+            traf.sbgps.push(SbgpBox {
+                grouping_type: SampleGroupEntry::SEIG,
+                entries: vec![SbgpEntry {
+                    sample_count: 1,
+                    group_description_index: SbgpBox::FRAGMENT_LOCAL_INDEX_BASE + 1,
+                }],
+                ..SbgpBox::default()
+            });
+            let sample_count: u32 = traf.truns.iter().map(|trun| trun.sample_count).sum();
+            let entries: Vec<SencEntry> = (0..sample_count)
+                .map(|i| match i {
+                    0 => SencEntry::default(),
+                    _ => SencEntry {
+                        iv: vec![i as u8; 8],
+                        subsamples: cenc_subsamples(),
+                    },
+                })
+                .collect();
+            traf.senc = Some(SencBox::new(&entries).unwrap());
+        },
+    )
+}
+
+#[test]
+fn test_encryption() {
+    let kid = KID;
+    let subsamples = cenc_subsamples();
+    let data = write_fragmented();
+    let rewritten = protect_fragmented(&data);
+    assert_same_samples(&rewritten, &data);
+
+    let mut mp4 =
+        Mp4Reader::read_header(std::io::Cursor::new(&rewritten), rewritten.len() as u64).unwrap();
+    assert_eq!(mp4.moov.psshs[0].kids, [kid]);
+
+    let video = mp4.tracks().get(&1).unwrap();
+    assert_eq!(video.media_type().unwrap(), MediaType::H264);
+    assert!(video.is_encrypted());
+    assert_eq!(video.original_format(), Some(str::parse("avc1").unwrap()));
+    assert_eq!(video.protection_scheme(), Some(SchmBox::CENC));
+    assert_eq!(video.default_kid(), Some(kid));
+    assert_eq!(video.sample_encryption(1).unwrap(), None);
+    assert_eq!(video.sample_encryption(26).unwrap(), None);
+    let encryption = video.sample_encryption(28).unwrap().unwrap();
+    assert_eq!(encryption.scheme_type, SchmBox::CENC);
+    assert_eq!(encryption.kid, kid);
+    assert_eq!(encryption.iv, [2; 8]);
+    assert_eq!(encryption.subsamples, subsamples);
+
+    let audio = mp4.tracks().get(&2).unwrap();
+    assert_eq!(audio.media_type().unwrap(), MediaType::AAC);
+    assert_eq!(audio.protection_scheme(), Some(SchmBox::CBCS));
+    let audio_encryption = audio.sample_encryption(60).unwrap().unwrap();
+    assert_eq!(audio_encryption.iv, [7; 16]);
+    assert_eq!(
+        (
+            audio_encryption.crypt_byte_block,
+            audio_encryption.skip_byte_block
+        ),
+        (1, 9)
+    );
+    assert!(audio_encryption.subsamples.is_empty());
+
+    assert_eq!(mp4.sample_encryption(1, 28).unwrap(), Some(encryption));
+    assert!(mp4.sample_encryption(3, 1).is_err());
+
+    // Without senc, the sample information is located by saiz and saio.
+    // Point it at the sample data, so that the IVs are 8 byte slices of the
+    // 100 byte samples.
+    let data = write_video(5, 20);
+    let mut mp4 = Mp4Reader::read_header(std::io::Cursor::new(&data), data.len() as u64).unwrap();
+    let sample_offset = mp4.sample_offset(1, 1).unwrap();
+    let mut moov = mp4.moov.clone();
+    let stbl = &mut moov.traks[0].mdia.minf.stbl;
+    stbl.stsd.avc1.as_mut().unwrap().sinf = Some(sinf("avc1", SchmBox::CENC, cenc_tenc()));
+    stbl.saizs.push(SaizBox {
+        default_sample_info_size: 8,
+        sample_count: 20,
+        ..SaizBox::default()
+    });
+    stbl.saios.push(SaioBox {
+        offsets: vec![sample_offset],
+        ..SaioBox::default()
+    });
+
+    let data = replace_moov(&data, &moov);
+    let mut mp4 = Mp4Reader::read_header(std::io::Cursor::new(&data), data.len() as u64).unwrap();
+    assert!(mp4.tracks().get(&1).unwrap().sample_encryption(1).is_err());
+    let encryption = mp4.sample_encryption(1, 1).unwrap().unwrap();
+    assert_eq!(encryption.iv, [0; 8]);
+    assert!(encryption.subsamples.is_empty());
+    let encryption = mp4.sample_encryption(1, 14).unwrap().unwrap();
+    assert_eq!(encryption.iv, [1; 8]);
+}
+
+#[test]
+fn test_write_faststart_aux_info() {
+    // One saio offset per chunk, pointing at the start of the chunk.
+    let data = write_video(5, 50);
+    let mp4 = Mp4Reader::read_header(std::io::Cursor::new(&data), data.len() as u64).unwrap();
+    let mut moov = mp4.moov.clone();
+    let stbl = &mut moov.traks[0].mdia.minf.stbl;
+    let chunk_offsets: Vec<u64> = stbl
+        .stco
+        .as_ref()
+        .unwrap()
+        .entries
+        .iter()
+        .map(|offset| *offset as u64)
+        .collect();
+    assert!(chunk_offsets.len() > 1);
+    stbl.stsd.avc1.as_mut().unwrap().sinf = Some(sinf("avc1", SchmBox::CENC, cenc_tenc()));
+    stbl.saizs.push(SaizBox {
+        default_sample_info_size: 8,
+        sample_count: 50,
+        ..SaizBox::default()
+    });
+    stbl.saios.push(SaioBox {
+        offsets: chunk_offsets,
+        ..SaioBox::default()
+    });
+
+    let data = replace_moov(&data, &moov);
+    let mut src = Mp4Reader::read_header(std::io::Cursor::new(&data), data.len() as u64).unwrap();
+    let mut faststart = Vec::new();
+    src.write_faststart(&mut faststart).unwrap();
+
+    let size = faststart.len() as u64;
+    let mut dst = Mp4Reader::read_header(std::io::Cursor::new(faststart), size).unwrap();
+    let dst_stbl = &dst.moov.traks[0].mdia.minf.stbl;
+    assert_eq!(
+        dst_stbl.saios[0].offsets,
+        dst_stbl
+            .stco
+            .as_ref()
+            .unwrap()
+            .entries
+            .iter()
+            .map(|offset| *offset as u64)
+            .collect::<Vec<u64>>()
+    );
+    for sample_id in 1..=50 {
+        assert_eq!(
+            dst.sample_encryption(1, sample_id).unwrap(),
+            src.sample_encryption(1, sample_id).unwrap()
+        );
+    }
+}
+
+#[cfg(feature = "cenc")]
+#[test]
+fn test_decryption() {
+    let key = [0x22; 16];
+    let keys = HashMap::from([(KID, key)]);
+
+    // Encrypt the samples of the protected file in place. CTR decryption is
+    // its own inverse, and the 10 byte audio samples are too short for any
+    // cbcs block to be encrypted.
+    let data = write_fragmented();
+    let mut protected = protect_fragmented(&data);
+    let mut mp4 =
+        Mp4Reader::read_header(std::io::Cursor::new(&protected), protected.len() as u64).unwrap();
+    let mut encrypted = Vec::new();
+    for sample_id in 1..=mp4.sample_count(1).unwrap() {
+        if let Some(encryption) = mp4.sample_encryption(1, sample_id).unwrap() {
+            let offset = mp4.sample_offset(1, sample_id).unwrap() as usize;
+            let mut bytes = mp4
+                .read_sample(1, sample_id)
+                .unwrap()
+                .unwrap()
+                .bytes
+                .to_vec();
+            mp4::decrypt_sample(&key, &encryption, &mut bytes).unwrap();
+            encrypted.push((offset, bytes));
+        }
+    }
+    assert_eq!(encrypted.len(), 48);
+    for (offset, bytes) in encrypted {
+        protected[offset..offset + bytes.len()].copy_from_slice(&bytes);
+    }
+
+    let mut mp4 =
+        Mp4Reader::read_header(std::io::Cursor::new(&protected), protected.len() as u64).unwrap();
+    let sample = mp4.read_sample(1, 2).unwrap().unwrap();
+    assert_eq!(sample.bytes[..4], [1; 4]);
+    assert_ne!(sample.bytes[4..], [1; 98]);
+    let sample = mp4.read_decrypted_sample(1, 2, &keys).unwrap().unwrap();
+    assert_eq!(sample.bytes, vec![1; 102]);
+    let sample = mp4.read_decrypted_sample(2, 3, &keys).unwrap().unwrap();
+    assert_eq!(sample.bytes, vec![2; 10]);
+    assert!(matches!(
+        mp4.read_decrypted_sample(1, 2, &HashMap::new()),
+        Err(mp4::Error::KeyNotFound(KID))
+    ));
+
+    let mut decrypted = Vec::new();
+    mp4.write_decrypted(&mut decrypted, &keys).unwrap();
+    assert_eq!(decrypted.len(), protected.len());
+    assert_same_samples(&decrypted, &data);
+
+    let mp4 =
+        Mp4Reader::read_header(std::io::Cursor::new(&decrypted), decrypted.len() as u64).unwrap();
+    assert!(mp4.moov.psshs.is_empty());
+    for track in mp4.tracks().values() {
+        assert!(!track.is_encrypted());
+    }
+    assert_eq!(
+        mp4.tracks()[&1].box_type().unwrap(),
+        BoxType::Avc1Box.into()
+    );
+    assert_eq!(
+        mp4.tracks()[&2].box_type().unwrap(),
+        BoxType::Mp4aBox.into()
+    );
+    for traf in mp4.moofs.iter().flat_map(|moof| moof.trafs.iter()) {
+        assert!(traf.senc.is_none());
+        assert!(traf.sgpds.is_empty());
+        assert!(traf.sbgps.is_empty());
+    }
+}
+
+/// An AVC sample of an SEI NAL unit and a slice NAL unit, with a 5 byte
+/// IDR or P slice header for [AVC_SPS] and [AVC_PPS].
+#[cfg(feature = "cenc")]
+fn avc_sample(i: u8, is_sync: bool) -> Vec<u8> {
+    let mut bytes = vec![0, 0, 0, 10, 0x06];
+    bytes.extend_from_slice(&[i; 9]);
+    bytes.extend_from_slice(&[0, 0, 1, 45]);
+    if is_sync {
+        bytes.extend_from_slice(&[0x65, 0x88, 0x84, 0x03, 0xff]);
+    } else {
+        bytes.extend_from_slice(&[0x41, 0x9a, 0x21, 0x0f, 0xff]);
+    }
+    bytes.extend_from_slice(&[i; 296]);
+    bytes
+}
+
+/// A High profile SPS of 320x240 frames and a PPS with CABAC.
+#[cfg(feature = "cenc")]
+const AVC_SPS: [u8; 9] = [0x67, 0x64, 0x00, 0x0d, 0xac, 0xda, 0x05, 0x07, 0xe4];
+#[cfg(feature = "cenc")]
+const AVC_PPS: [u8; 4] = [0x68, 0xee, 0x3c, 0x80];
+
+#[cfg(feature = "cenc")]
+fn write_encrypted(video: mp4::EncryptionConfig, audio: mp4::EncryptionConfig) -> Vec<u8> {
+    let config = mp4::Mp4Config {
+        major_brand: str::parse("iso6").unwrap(),
+        minor_version: 0,
+        compatible_brands: vec![str::parse("iso6").unwrap()],
+        timescale: 1000,
+    };
+    let fragment_config = mp4::FragmentConfig {
+        fragment_duration: Duration::from_secs(1),
+        ..mp4::FragmentConfig::default()
+    };
+    let mut writer =
+        mp4::Mp4FragmentWriter::write_start(Vec::new(), &config, &fragment_config).unwrap();
+    let mut video_config = mp4::TrackConfig::from(mp4::AvcConfig {
+        width: 320,
+        height: 240,
+        seq_param_set: AVC_SPS.to_vec(),
+        pic_param_set: AVC_PPS.to_vec(),
+    });
+    video_config.encryption = Some(video);
+    writer.add_track(&video_config).unwrap();
+    let mut audio_config = mp4::TrackConfig::from(mp4::AacConfig::default());
+    audio_config.encryption = Some(audio);
+    writer.add_track(&audio_config).unwrap();
+
+    for i in 0..50u64 {
+        let video = mp4::Mp4Sample {
+            start_time: i * 40,
+            duration: 40,
+            rendering_offset: 0,
+            is_sync: i % 25 == 0,
+            flags: None,
+            dependency: None,
+            subsamples: Vec::new(),
+            bytes: avc_sample(i as u8, i % 25 == 0),
+        };
+        writer.write_sample(1, &video).unwrap();
+        let audio = mp4::Mp4Sample {
+            start_time: i * 40,
+            duration: 40,
+            rendering_offset: 0,
+            is_sync: true,
+            flags: None,
+            dependency: None,
+            subsamples: Vec::new(),
+            bytes: vec![i as u8; 40],
+        };
+        writer.write_sample(2, &audio).unwrap();
+    }
+    writer.write_end().unwrap();
+    writer.into_writer()
+}
+
+#[cfg(feature = "cenc")]
+#[test]
+fn test_write_encrypted() {
+    let key = [0x22; 16];
+    let pssh = PsshBox {
+        version: 1,
+        system_id: PsshBox::COMMON_SYSTEM_ID,
+        kids: vec![KID],
+        ..PsshBox::default()
+    };
+    let video = mp4::EncryptionConfig {
+        scheme_type: SchmBox::CENC,
+        kid: KID,
+        key,
+        iv: mp4::IvStrategy::PerSample(vec![0; 8]),
+        crypt_byte_block: 0,
+        skip_byte_block: 0,
+        clear_lead: 1000,
+        psshs: vec![pssh.clone()],
+    };
+    let audio = mp4::EncryptionConfig {
+        scheme_type: SchmBox::CBCS,
+        iv: mp4::IvStrategy::Constant(vec![7; 16]),
+        crypt_byte_block: 1,
+        skip_byte_block: 9,
+        clear_lead: 0,
+        ..video.clone()
+    };
+    let data = write_encrypted(video.clone(), audio);
+    let mut mp4 = Mp4Reader::read_header(std::io::Cursor::new(&data), data.len() as u64).unwrap();
+    assert_eq!(mp4.moov.psshs, [pssh]);
+
+    let stsd = &mp4.moov.traks[0].mdia.minf.stbl.stsd;
+    assert_eq!(stsd.avc1.as_ref().unwrap().box_type(), BoxType::EncvBox);
+    let stsd = &mp4.moov.traks[1].mdia.minf.stbl.stsd;
+    assert_eq!(stsd.mp4a.as_ref().unwrap().box_type(), BoxType::EncaBox);
+
+    let track = mp4.tracks().get(&1).unwrap();
+    assert_eq!(track.original_format(), Some(BoxType::Avc1Box.into()));
+    assert_eq!(track.protection_scheme(), Some(SchmBox::CENC));
+    assert_eq!(track.default_kid(), Some(KID));
+    let track = mp4.tracks().get(&2).unwrap();
+    assert_eq!(track.protection_scheme(), Some(SchmBox::CBCS));
+
+    // The first second is in the clear, then the end of the slices, after
+    // their headers, is encrypted with a new IV for every sample.
+    assert_eq!(mp4.sample_encryption(1, 25).unwrap(), None);
+    assert_eq!(
+        mp4.read_sample(1, 25).unwrap().unwrap().bytes,
+        avc_sample(24, false)
+    );
+    let encryption = mp4.sample_encryption(1, 26).unwrap().unwrap();
+    assert_eq!(encryption.iv, [0; 8]);
+    assert_eq!(
+        encryption.subsamples,
+        [SencSubsample {
+            bytes_of_clear_data: 31,
+            bytes_of_protected_data: 288,
+        }]
+    );
+    let encryption = mp4.sample_encryption(1, 27).unwrap().unwrap();
+    assert_eq!(encryption.iv, [0, 0, 0, 0, 0, 0, 0, 1]);
+    let sample = mp4.read_sample(1, 27).unwrap().unwrap();
+    assert_eq!(sample.bytes[..31], avc_sample(26, false)[..31]);
+    assert_ne!(sample.bytes, avc_sample(26, false));
+
+    let encryption = mp4.sample_encryption(2, 1).unwrap().unwrap();
+    assert_eq!(encryption.iv, [7; 16]);
+    assert!(encryption.subsamples.is_empty());
+    let sample = mp4.read_sample(2, 1).unwrap().unwrap();
+    assert_ne!(sample.bytes[..16], [0; 16]);
+    assert_eq!(sample.bytes[16..], [0; 24]);
+    assert!(mp4.moofs.iter().all(|moof| moof.trafs[1].senc.is_none()));
+
+    let keys = HashMap::from([(KID, key)]);
+    for sample_id in 1..=50 {
+        let sample = mp4.read_decrypted_sample(1, sample_id, &keys).unwrap();
+        let i = sample_id as u8 - 1;
+        assert_eq!(sample.unwrap().bytes, avc_sample(i, i.is_multiple_of(25)));
+        let sample = mp4.read_decrypted_sample(2, sample_id, &keys).unwrap();
+        assert_eq!(sample.unwrap().bytes, vec![i; 40]);
+    }
+
+    let mut decrypted = Vec::new();
+    mp4.write_decrypted(&mut decrypted, &keys).unwrap();
+    let mut mp4 =
+        Mp4Reader::read_header(std::io::Cursor::new(&decrypted), decrypted.len() as u64).unwrap();
+    let stsd = &mp4.moov.traks[0].mdia.minf.stbl.stsd;
+    assert_eq!(stsd.avc1.as_ref().unwrap().box_type(), BoxType::Avc1Box);
+    let sample = mp4.read_sample(1, 27).unwrap().unwrap();
+    assert_eq!(sample.bytes, avc_sample(26, false));
+
+    // A per-sample IV requires 8 or 16 bytes, and cenc has no pattern.
+    let config = mp4::Mp4Config {
+        major_brand: str::parse("iso6").unwrap(),
+        minor_version: 0,
+        compatible_brands: Vec::new(),
+        timescale: 1000,
+    };
+    let mut writer =
+        mp4::Mp4FragmentWriter::write_start(Vec::new(), &config, &mp4::FragmentConfig::default())
+            .unwrap();
+    let mut config = mp4::TrackConfig::from(mp4::AacConfig::default());
+    config.encryption = Some(mp4::EncryptionConfig {
+        iv: mp4::IvStrategy::PerSample(vec![0; 4]),
+        ..video.clone()
+    });
+    assert!(writer.add_track(&config).is_err());
+    config.encryption = Some(mp4::EncryptionConfig {
+        crypt_byte_block: 1,
+        skip_byte_block: 9,
+        ..video.clone()
+    });
+    assert!(writer.add_track(&config).is_err());
+
+    // VP9 and AV1 frame headers are not parsed to leave them in the clear.
+    let mut config = mp4::TrackConfig::from(mp4::Vp9Config::default());
+    config.encryption = Some(video.clone());
+    assert!(writer.add_track(&config).is_err());
+    let mut config = mp4::TrackConfig::from(mp4::Av1Config {
+        width: 1920,
+        height: 1080,
+        sequence_header: vec![
+            0x0a, 0x0e, 0x00, 0x00, 0x00, 0x42, 0xab, 0xbf, 0xc3, 0x73, 0xff, 0xe6, 0x40, 0x40,
+            0x40, 0x41,
+        ],
+    });
+    assert!(writer.add_track(&config).is_ok());
+    config.encryption = Some(video);
+    assert!(writer.add_track(&config).is_err());
+}
+
+/// Replace the moov box of a file written by [write_video], which comes
+/// last, after the media data.
+fn replace_moov(data: &[u8], moov: &MoovBox) -> Vec<u8> {
+    let tree = BoxTree::read(std::io::Cursor::new(data), data.len() as u64).unwrap();
+    let moov_node = tree.boxes.last().unwrap();
+    assert_eq!(moov_node.box_type, BoxType::MoovBox);
+
+    let mut buf = data[..moov_node.offset as usize].to_vec();
+    moov.write_box(&mut buf).unwrap();
+    buf
+}
+
+#[test]
+fn test_edit_list() {
+    let data = write_video(5, 10);
+    let mp4 = Mp4Reader::read_header(std::io::Cursor::new(&data), data.len() as u64).unwrap();
+
+    // Without an edit list the presentation timeline is the media timeline.
+    let track = mp4.tracks().get(&1).unwrap();
+    assert_eq!(track.presentation_time(3).unwrap(), Some(80));
+    assert_eq!(track.presentation_duration(), track.duration());
+
+    let edit = |segment_duration, media_time: i64, media_rate| ElstEntry {
+        segment_duration,
+        media_time: media_time as u64,
+        media_rate,
+        media_rate_fraction: 0,
+    };
+    let mut moov = mp4.moov.clone();
+    moov.traks[0].edts = Some(EdtsBox {
+        elst: Some(ElstBox {
+            version: 0,
+            flags: 0,
+            entries: vec![
+                // 100ms of nothing, then 200ms of media from 20ms, then
+                // 50ms showing the frame at 120ms.
+                edit(100, -1, 1),
+                edit(200, 20, 1),
+                edit(50, 120, 0),
+            ],
+        }),
+    });
+    let data = replace_moov(&data, &moov);
+    let mp4 = Mp4Reader::read_header(std::io::Cursor::new(&data), data.len() as u64).unwrap();
+    let track = mp4.tracks().get(&1).unwrap();
+
+    let times: Vec<_> = (1..=10)
+        .map(|sample_id| track.presentation_time(sample_id).unwrap())
+        .collect();
+    assert_eq!(
+        times,
+        [
+            Some(80),
+            Some(120),
+            Some(160),
+            Some(200),
+            Some(240),
+            Some(280),
+            None,
+            None,
+            None,
+            None
+        ]
+    );
+    assert_eq!(track.presentation_duration(), Duration::from_millis(350));
+
+    // Seeking maps the samples through the edit list.
+    assert_eq!(
+        track
+            .seek(Duration::from_millis(130), SeekMode::Exact)
+            .unwrap(),
+        (2, Duration::from_millis(120))
+    );
+    assert_eq!(
+        track
+            .seek(Duration::from_millis(300), SeekMode::PreviousSync)
+            .unwrap(),
+        (6, Duration::from_millis(280))
+    );
+    assert_eq!(
+        track.seek(Duration::ZERO, SeekMode::Exact).unwrap(),
+        (1, Duration::from_millis(80))
+    );
+    assert!(
+        track
+            .seek(Duration::from_millis(290), SeekMode::NextSync)
+            .is_err()
+    );
+
+    // An edit without a duration extends to the end of the media.
+    moov.traks[0].edts.as_mut().unwrap().elst = Some(ElstBox {
+        version: 1,
+        flags: 0,
+        entries: vec![edit(0, 20, 1)],
+    });
+    let data = replace_moov(&data, &moov);
+    let mp4 = Mp4Reader::read_header(std::io::Cursor::new(&data), data.len() as u64).unwrap();
+    let track = mp4.tracks().get(&1).unwrap();
+    assert_eq!(track.presentation_time(1).unwrap(), Some(-20));
+    assert_eq!(track.presentation_time(10).unwrap(), Some(340));
+    assert_eq!(track.presentation_duration(), Duration::from_millis(380));
+}
+
+#[test]
+fn test_seek() {
+    let data = write_video(25, 100);
+    let size = data.len() as u64;
+    let mp4 = Mp4Reader::read_header(std::io::Cursor::new(data), size).unwrap();
+
+    let time = Duration::from_millis(1500);
+    assert_eq!(
+        mp4.seek(1, time, SeekMode::PreviousSync).unwrap(),
+        (26, Duration::from_secs(1))
+    );
+    assert_eq!(
+        mp4.seek(1, time, SeekMode::NextSync).unwrap(),
+        (51, Duration::from_secs(2))
+    );
+    assert_eq!(
+        mp4.seek(1, time, SeekMode::Exact).unwrap(),
+        (38, Duration::from_millis(1480))
+    );
+
+    // Seeking past the end.
+    let time = Duration::from_secs(10);
+    assert_eq!(
+        mp4.seek(1, time, SeekMode::PreviousSync).unwrap(),
+        (76, Duration::from_secs(3))
+    );
+    assert!(mp4.seek(1, time, SeekMode::NextSync).is_err());
+    assert!(mp4.seek(2, time, SeekMode::Exact).is_err());
+}
+
+#[test]
+fn test_sample_index() {
+    fn read_all<R: std::io::Read + std::io::Seek>(
+        mp4: &mut Mp4Reader<R>,
+    ) -> Vec<(u32, mp4::Mp4Sample)> {
+        mp4.samples().map(|s| s.unwrap()).collect()
+    }
+
+    let mut readers = vec![
+        get_reader("tests/samples/minimal.mp4"),
+        get_reader("tests/samples/big_buck_bunny_metadata.m4v"),
+        get_reader("tests/samples/extended_audio_object_type.mp4"),
+    ];
+    let init = get_reader("tests/samples/minimal_init.mp4");
+    let f = File::open("tests/samples/minimal_fragment.m4s").unwrap();
+    let f_size = f.metadata().unwrap().len();
+    readers.push(
+        init.read_fragment_header(BufReader::new(f), f_size)
+            .unwrap(),
+    );
+
+    for mut mp4 in readers {
+        let want = read_all(&mut mp4);
+        assert!(!mp4.tracks().values().any(|t| t.has_sample_index()));
+
+        mp4.build_sample_index().unwrap();
+        assert!(mp4.tracks().values().all(|t| t.has_sample_index()));
+        let got = read_all(&mut mp4);
+
+        assert_eq!(got.len(), want.len());
+        for ((got_id, got), (want_id, want)) in got.iter().zip(want.iter()) {
+            assert_eq!(got_id, want_id);
+            assert_eq!(got, want);
+            assert_eq!(got.bytes, want.bytes);
+        }
+    }
+
+    // The stts has fewer samples than the stsz.
+    let data = write_video(5, 10);
+    let mp4 = Mp4Reader::read_header(std::io::Cursor::new(&data), data.len() as u64).unwrap();
+    let mut moov = mp4.moov.clone();
+    moov.traks[0].mdia.minf.stbl.stts.entries[0].sample_count -= 1;
+    let data = replace_moov(&data, &moov);
+    let mut mp4 = Mp4Reader::read_header(std::io::Cursor::new(&data), data.len() as u64).unwrap();
+    assert!(mp4.build_sample_index().is_err());
+}
+
+#[cfg(feature = "tokio")]
+#[tokio::test]
+async fn test_read_async() {
+    let f = tokio::fs::File::open("tests/samples/minimal.mp4")
+        .await
+        .unwrap();
+    let size = f.metadata().await.unwrap().len();
+    let mut async_mp4 = Mp4Reader::read_header_async(tokio::io::BufReader::new(f), size)
+        .await
+        .unwrap();
+    let mut mp4 = get_reader("tests/samples/minimal.mp4");
+
+    assert_eq!(async_mp4.size(), mp4.size());
+    assert_eq!(async_mp4.ftyp, mp4.ftyp);
+    assert_eq!(async_mp4.moov, mp4.moov);
+
+    let mut track_ids: Vec<u32> = mp4.tracks().keys().copied().collect();
+    track_ids.sort_unstable();
+    for track_id in track_ids {
+        let sample_count = mp4.sample_count(track_id).unwrap();
+        for sample_id in 1..=sample_count + 1 {
+            let want = mp4.read_sample(track_id, sample_id).unwrap();
+            let got = async_mp4
+                .read_sample_async(track_id, sample_id)
+                .await
+                .unwrap();
+            assert_eq!(got, want);
+        }
+    }
+}
+
+#[cfg(feature = "tokio")]
+#[tokio::test]
+async fn test_read_async_fragmented() {
+    let data = fs::read("tests/samples/minimal_fragment.m4s").unwrap();
+    let init = fs::read("tests/samples/minimal_init.mp4").unwrap();
+    let mut full = init.clone();
+    full.extend_from_slice(&data);
+
+    let size = full.len() as u64;
+    let mut async_mp4 = Mp4Reader::read_header_async(std::io::Cursor::new(full.clone()), size)
+        .await
+        .unwrap();
+    let mut mp4 = Mp4Reader::read_header(std::io::Cursor::new(full), size).unwrap();
+    assert!(async_mp4.is_fragmented());
+    assert_eq!(async_mp4.moofs, mp4.moofs);
+
+    let sample_count = mp4.sample_count(1).unwrap();
+    assert!(sample_count > 0);
+    for sample_id in 1..=sample_count {
+        let want = mp4.read_sample(1, sample_id).unwrap();
+        let got = async_mp4.read_sample_async(1, sample_id).await.unwrap();
+        assert_eq!(got, want);
+    }
+}